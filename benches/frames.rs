@@ -0,0 +1,90 @@
+//! Events per second through a whole [`Frame`], the one the daemon runs each
+//! device's events through, from events as a device reports them to the
+//! single batch each frame is emitted as. The sink only counts the batches,
+//! so this measures the frame's own work, the buffer it reuses from frame to
+//! frame included, rather than uinput.
+//!
+//! Comparing a change against the tree before it takes criterion's saved
+//! baselines: `cargo bench --bench frames -- --save-baseline before` on the
+//! old tree, then `--baseline before` on the new one.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use evdev::{EventType, InputEvent, KeyCode, RelativeAxisCode, SynchronizationCode};
+use sensor_alignment::frame::{Frame, FrameSink, Hotkeys, ScrollOptions};
+use sensor_alignment::state::LiveState;
+use sensor_alignment::{Pipeline, PointerOptions};
+
+const FRAMES: usize = 10_000;
+
+/// Counts the batches emitted, one per frame, and drops them.
+#[derive(Default)]
+struct Batches(usize);
+
+impl FrameSink for Batches {
+    fn emit(&mut self, events: &[InputEvent]) -> std::io::Result<()> {
+        black_box(events);
+        self.0 += 1;
+        Ok(())
+    }
+}
+
+/// A stroke that keeps changing direction, as REL_X, REL_Y and SYN_REPORT
+/// events, with a press or release of the left button in every `click`th
+/// frame if that's given.
+fn stream(click: Option<usize>) -> Vec<InputEvent> {
+    (0..FRAMES)
+        .flat_map(|i| {
+            let phase = i as f64 / 50.0;
+            let dx = (phase.cos() * 20.0) as i32;
+            let dy = (phase.sin() * 20.0) as i32;
+            let button = click.filter(|every| i % every == 0).map(|every| {
+                let pressed = (i / every) % 2 == 0;
+                InputEvent::new(EventType::KEY.0, KeyCode::BTN_LEFT.0, pressed as i32)
+            });
+            [
+                InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_X.0, dx),
+                InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_Y.0, dy),
+            ]
+            .into_iter()
+            .chain(button)
+            .chain([InputEvent::new(
+                EventType::SYNCHRONIZATION.0,
+                SynchronizationCode::SYN_REPORT.0,
+                0,
+            )])
+        })
+        .collect()
+}
+
+fn frames(c: &mut Criterion) {
+    let state = LiveState::new(3.5);
+    let mut group = c.benchmark_group("frame");
+    for (name, click) in [("motion", None), ("clicks", Some(4))] {
+        let events = stream(click);
+        group.throughput(Throughput::Elements(events.len() as u64));
+        group.bench_with_input(BenchmarkId::new("events", name), &events, |b, events| {
+            let mut frame = Frame::new(
+                None,
+                ScrollOptions::default(),
+                Pipeline::default(),
+                PointerOptions::default(),
+                Hotkeys::default(),
+                Vec::new(),
+                0.0,
+            );
+            let mut batches = Batches::default();
+            b.iter(|| {
+                for &event in black_box(events) {
+                    frame.handle(event, &state, &mut batches).unwrap();
+                }
+            });
+            assert!(batches.0 > 0);
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, frames);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+//! Events per second through the pointer transform, from relative events as
+//! a device reports them to the batch of events a frame is emitted as. The
+//! batch goes nowhere, so this measures the math and the bookkeeping around
+//! it rather than uinput.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use evdev::{EventType, InputEvent, RelativeAxisCode, SynchronizationCode};
+use sensor_alignment::{Matrix, PointerOptions, Transform};
+
+const FRAMES: usize = 10_000;
+
+/// A stroke that keeps changing direction, as REL_X, REL_Y and SYN_REPORT
+/// events.
+fn stream() -> Vec<InputEvent> {
+    (0..FRAMES)
+        .flat_map(|i| {
+            let phase = i as f64 / 50.0;
+            let dx = (phase.cos() * 20.0) as i32;
+            let dy = (phase.sin() * 20.0) as i32;
+            [
+                InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_X.0, dx),
+                InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_Y.0, dy),
+                InputEvent::new(
+                    EventType::SYNCHRONIZATION.0,
+                    SynchronizationCode::SYN_REPORT.0,
+                    0,
+                ),
+            ]
+        })
+        .collect()
+}
+
+/// Accumulates each frame, transforms it at its SYN_REPORT and hands the
+/// result to a sink that drops it, like the event loop does with a real one.
+fn run(
+    events: &[InputEvent],
+    transform: &mut Transform,
+    matrix: &Matrix,
+    batch: &mut Vec<InputEvent>,
+) {
+    let (mut dx, mut dy) = (0, 0);
+    for event in events {
+        match (event.event_type(), event.code()) {
+            (EventType::RELATIVE, code) if code == RelativeAxisCode::REL_X.0 => dx += event.value(),
+            (EventType::RELATIVE, code) if code == RelativeAxisCode::REL_Y.0 => dy += event.value(),
+            (EventType::SYNCHRONIZATION, _) => {
+                if let Some((x, y)) = transform.apply(dx, dy, matrix) {
+                    batch.push(InputEvent::new(
+                        EventType::RELATIVE.0,
+                        RelativeAxisCode::REL_X.0,
+                        x,
+                    ));
+                    batch.push(InputEvent::new(
+                        EventType::RELATIVE.0,
+                        RelativeAxisCode::REL_Y.0,
+                        y,
+                    ));
+                }
+                batch.push(*event);
+                black_box(&batch);
+                batch.clear();
+                (dx, dy) = (0, 0);
+            }
+            _ => batch.push(*event),
+        }
+    }
+}
+
+fn throughput(c: &mut Criterion) {
+    let events = stream();
+    let mut group = c.benchmark_group("transform");
+    group.throughput(Throughput::Elements(events.len() as u64));
+    for angle_deg in [0.0, 3.5, 45.0, 90.0] {
+        let matrix = Matrix::rotation(angle_deg);
+        group.bench_with_input(
+            BenchmarkId::new("angle", angle_deg),
+            &matrix,
+            |b, matrix| {
+                let mut transform = Transform::new(PointerOptions::default());
+                let mut batch = Vec::with_capacity(3);
+                b.iter(|| run(black_box(&events), &mut transform, matrix, &mut batch));
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,87 @@
+//! Records where a build came from for `sensor_alignment version`: the git
+//! commit, the date, the compiler and the features compiled in, which decide
+//! what events the program can handle at all.
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    // Whether the tree is dirty: staging or committing rewrites the index,
+    // and editing the sources dirties it without touching the index.
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = output("git", &["rev-parse", "--short=12", "HEAD"])
+        .map(|hash| {
+            let dirty = output("git", &["status", "--porcelain", "--untracked-files=no"])
+                .is_some_and(|status| !status.is_empty());
+            if dirty { format!("{hash}-dirty") } else { hash }
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc = output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+        .map(|feature| feature.to_lowercase().replace('_', "-"))
+        .collect();
+    features.sort();
+    let features = if features.is_empty() {
+        "none".to_string()
+    } else {
+        features.join(", ")
+    };
+
+    println!("cargo:rustc-env=BUILD_COMMIT={commit}");
+    println!("cargo:rustc-env=BUILD_DATE={}", build_date());
+    println!("cargo:rustc-env=BUILD_RUSTC={rustc}");
+    println!("cargo:rustc-env=BUILD_FEATURES={features}");
+    println!(
+        "cargo:rustc-env=BUILD_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+    println!(
+        "cargo:rustc-env=BUILD_PROFILE={}",
+        std::env::var("PROFILE").unwrap_or_default()
+    );
+}
+
+/// The trimmed standard output of `program`, if it ran and succeeded.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let result = Command::new(program).args(args).output().ok()?;
+    if !result.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(result.stdout).ok()?.trim().to_string())
+}
+
+/// Today as YYYY-MM-DD in UTC, or the day `SOURCE_DATE_EPOCH` falls on so
+/// that reproducible builds stay reproducible.
+fn build_date() -> String {
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs())
+        });
+    // Howard Hinnant's civil_from_days, for days since 1970-01-01.
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
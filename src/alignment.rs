@@ -0,0 +1,172 @@
+use evdev::{EventType, InputEvent, SynchronizationCode};
+
+use crate::frame::{Frame, FrameSink, Hotkeys, ScrollOptions};
+use crate::state::LiveState;
+use crate::{Pipeline, PointerOptions};
+
+/// The transform of whole event frames, for embedding it in another input
+/// daemon: events read from a device go in, the events to write to a
+/// virtual device come out, and reading and writing them is up to the
+/// caller.
+///
+/// Pointer motion, and the wheels with [`with_rotate_scroll`], is summed
+/// until the `SYN_REPORT` that ends its frame and transformed as one motion.
+/// Everything else is passed through in the order it came, with the
+/// transformed motion taking the place of the first event it was summed
+/// from. It's the daemon's own [`Frame`], without a device to take axis
+/// ranges from or any of the options set from the command line.
+///
+/// [`with_rotate_scroll`]: SensorAlignment::with_rotate_scroll
+pub struct SensorAlignment {
+    options: PointerOptions,
+    pipeline: Pipeline,
+    rotate_above: f64,
+    scroll_options: ScrollOptions,
+    state: LiveState,
+    frame: Frame,
+}
+
+/// Appends each frame to the events handed back to the caller, ended by
+/// its `SYN_REPORT`.
+struct Appended<'a>(&'a mut Vec<InputEvent>);
+
+impl FrameSink for Appended<'_> {
+    fn emit(&mut self, events: &[InputEvent]) -> std::io::Result<()> {
+        self.0.extend_from_slice(events);
+        self.0.push(InputEvent::new(
+            EventType::SYNCHRONIZATION.0,
+            SynchronizationCode::SYN_REPORT.0,
+            0,
+        ));
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SensorAlignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SensorAlignment")
+            .field("angle_deg", &self.state.angle_deg())
+            .field("enabled", &self.state.enabled())
+            .field("options", &self.options)
+            .field("pipeline", &self.pipeline)
+            .field("rotate_above", &self.rotate_above)
+            .field("scroll_options", &self.scroll_options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SensorAlignment {
+    /// Rotates pointer motion by `angle_deg`, with the default options.
+    pub fn new(angle_deg: f64) -> Self {
+        SensorAlignment {
+            options: PointerOptions::default(),
+            pipeline: Pipeline::default(),
+            rotate_above: 0.0,
+            scroll_options: ScrollOptions::default(),
+            state: LiveState::new(angle_deg),
+            frame: Self::frame(
+                PointerOptions::default(),
+                Pipeline::default(),
+                0.0,
+                ScrollOptions::default(),
+            ),
+        }
+    }
+
+    /// Tunes pointer motion around the rotation, see [`PointerOptions`].
+    pub fn with_options(mut self, options: PointerOptions) -> Self {
+        self.options = options;
+        self.rebuilt()
+    }
+
+    /// Runs `pipeline` ahead of the rotation.
+    pub fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self.rebuilt()
+    }
+
+    /// Only rotates pointer motion faster than this many counts per frame,
+    /// see [`rotation_weight`](crate::rotation_weight).
+    pub fn with_rotate_above(mut self, counts: f64) -> Self {
+        self.rotate_above = counts;
+        self.rebuilt()
+    }
+
+    /// Rotates the wheels together with pointer motion.
+    pub fn with_rotate_scroll(mut self, rotate: bool) -> Self {
+        self.scroll_options.rotate = rotate;
+        self.rebuilt()
+    }
+
+    pub fn angle_deg(&self) -> f64 {
+        self.state.angle_deg()
+    }
+
+    /// Changes the angle from the next frame that ends on.
+    pub fn set_angle_deg(&mut self, angle_deg: f64) {
+        self.state.set_angle_deg(angle_deg);
+    }
+
+    /// Whether motion is transformed at all.
+    pub fn enabled(&self) -> bool {
+        self.state.enabled()
+    }
+
+    /// Switches transforming motion on or off. Either way the remainder is
+    /// forgotten, so the transform starts afresh.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled != self.state.enabled() {
+            self.state.toggle_enabled();
+        }
+    }
+
+    /// Handles `events` in order, appending every frame they complete to
+    /// `out`, each ended by its `SYN_REPORT`. Events of a frame that isn't
+    /// complete yet are kept for the next call.
+    pub fn process(&mut self, events: &[InputEvent], out: &mut Vec<InputEvent>) {
+        for &event in events {
+            // Appending to `out` can't fail.
+            let _ = self.frame.handle(event, &self.state, &mut Appended(out));
+        }
+    }
+
+    /// Whether smoothing still owes motion that should be emitted even if
+    /// the device stays quiet, by calling [`tick`](Self::tick).
+    pub fn smoothing_pending(&self) -> bool {
+        self.frame.smoothing_pending()
+    }
+
+    /// Appends the next step of owed smoothed motion to `out` as a frame of
+    /// its own, if there is any.
+    pub fn tick(&mut self, out: &mut Vec<InputEvent>) {
+        let _ = self.frame.tick(&mut Appended(out));
+    }
+
+    fn frame(
+        options: PointerOptions,
+        pipeline: Pipeline,
+        rotate_above: f64,
+        scroll_options: ScrollOptions,
+    ) -> Frame {
+        Frame::new(
+            None,
+            scroll_options,
+            pipeline,
+            options,
+            Hotkeys::default(),
+            Vec::new(),
+            rotate_above,
+        )
+    }
+
+    /// Starts the frame over with the options as they are now.
+    fn rebuilt(mut self) -> Self {
+        self.frame = Self::frame(
+            self.options,
+            self.pipeline.clone(),
+            self.rotate_above,
+            self.scroll_options,
+        );
+        self
+    }
+}
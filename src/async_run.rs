@@ -0,0 +1,46 @@
+use evdev::uinput::VirtualDevice;
+use evdev::{Device, EventType, SynchronizationCode};
+use tokio_util::sync::CancellationToken;
+
+use crate::{Matrix, Pipeline, PointerOptions, SensorAlignment};
+
+/// Transforms pointer motion from `source` and emits it on `sink` until
+/// `cancel` is cancelled or reading fails.
+///
+/// This is the pointer pipeline of [`SensorAlignment`] only: everything
+/// other than `REL_X`/`REL_Y` is forwarded within its frame, and smoothing
+/// only advances while the device sends frames. Grabbing `source` beforehand
+/// is up to the caller.
+///
+/// Reading is async, but each frame is emitted on `sink` with a plain write
+/// from the task. uinput takes the write without waiting on whoever reads
+/// the virtual device, so that doesn't hold up the runtime for long, but it
+/// does block the thread while it lasts.
+pub async fn run(
+    source: Device,
+    mut sink: VirtualDevice,
+    matrix: Matrix,
+    options: PointerOptions,
+    cancel: CancellationToken,
+) -> std::io::Result<()> {
+    let mut events = source.into_event_stream()?;
+    let mut alignment = SensorAlignment::new(0.0)
+        .with_options(options)
+        .with_pipeline(Pipeline::from(matrix));
+    let mut frame = Vec::new();
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            event = events.next_event() => event?,
+        };
+        alignment.process(&[event], &mut frame);
+        if let Some((syn, events)) = frame.split_last()
+            && syn.event_type() == EventType::SYNCHRONIZATION
+            && syn.code() == SynchronizationCode::SYN_REPORT.0
+        {
+            // emit() ends the frame with its own SYN_REPORT.
+            sink.emit(events)?;
+            frame.clear();
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::io::{BufRead, Write};
+use std::os::fd::AsFd;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use evdev::{Device, EventSummary, RelativeAxisCode, SynchronizationCode};
+use nix::errno::Errno;
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use sensor_alignment::{Degrees, Ellipse, Matrix};
+
+use crate::config::Config;
+
+/// How long each attempt records motion for.
+const RECORD_TIME: Duration = Duration::from_secs(3);
+/// Less total motion than this, in counts, is too little to trust the
+/// direction of.
+const MIN_MOTION: f64 = 200.0;
+/// How long a circle is recorded for, long enough to go round a few times.
+const CIRCLE_TIME: Duration = Duration::from_secs(5);
+/// Fewer frames than this around a circle are too few to fit an ellipse to.
+const MIN_CIRCLE_FRAMES: usize = 50;
+/// A circle at least this wide, in counts, is big enough that rounding to
+/// whole counts doesn't make it look skewed.
+const MIN_CIRCLE_SIZE: f64 = 400.0;
+/// Circles coming out less stretched than this are taken to be round: a
+/// hand doesn't draw any rounder.
+const CIRCLE_ASPECT: f64 = 1.02;
+/// How far from X or Y, in degrees, the long axis may lie for the scales
+/// to still even it out well enough.
+const MAX_SCALE_SLANT_DEG: f64 = 5.0;
+
+/// Asks the user to push the sensor straight up and returns the angle that
+/// turns the recorded motion into screen-up. Keeps asking until there's
+/// enough motion to go by.
+pub fn measure(device: &mut Device) -> std::io::Result<f64> {
+    let stdin = std::io::stdin();
+    loop {
+        print!("Press Enter, then move the mouse straight up a few times... ");
+        std::io::stdout().flush()?;
+        stdin.lock().read_line(&mut String::new())?;
+        // Motion from before the user was ready doesn't count.
+        discard_pending(device)?;
+
+        let (dx, dy) = record(device, RECORD_TIME)?;
+        println!("recorded ({dx}, {dy})");
+        let motion = (dx as f64).hypot(dy as f64);
+        if motion < MIN_MOTION {
+            println!("That's too little motion to tell the direction, please try again.");
+            continue;
+        }
+        // Screen-up is negative Y.
+        return Ok(
+            sensor_alignment::angle_between((dx as f64, dy as f64), (0.0, -1.0))
+                .expect("the motion isn't zero"),
+        );
+    }
+}
+
+/// Asks the user to trace circles and returns the ellipse the sensor saw
+/// them as. Keeps asking until there's a circle big and smooth enough to
+/// fit one to.
+pub fn measure_circle(device: &mut Device) -> std::io::Result<Ellipse> {
+    let stdin = std::io::stdin();
+    loop {
+        print!("Press Enter, then move the mouse around in a circle a few times... ");
+        std::io::stdout().flush()?;
+        stdin.lock().read_line(&mut String::new())?;
+        discard_pending(device)?;
+
+        let path = record_path(device, CIRCLE_TIME)?;
+        println!("recorded {} frames", path.len());
+        if path.len() < MIN_CIRCLE_FRAMES {
+            println!("That's too few frames to fit a circle to, please try again.");
+            continue;
+        }
+        match Ellipse::fit(&path) {
+            Some(ellipse) if 2.0 * ellipse.minor >= MIN_CIRCLE_SIZE => return Ok(ellipse),
+            Some(_) => println!("That circle is too small to go by, please draw a bigger one."),
+            None => println!("That doesn't look like a circle, please try again."),
+        }
+    }
+}
+
+/// Prints what evens out the sensor that saw a circle as `ellipse`, saving
+/// it to the config file at `write` if that's given and the flags can do it.
+///
+/// A circle has no up, so it says nothing about the angle. What it shows is
+/// the direction the sensor counts more along, and how much more. Along X or
+/// Y, --scale-x and --scale-y even that out; at a slant only a matrix can,
+/// as the scales work along the sensor's own axes.
+pub fn recommend_scales(ellipse: &Ellipse, write: Option<&Path>) -> std::io::Result<()> {
+    if ellipse.aspect() < CIRCLE_ASPECT {
+        println!(
+            "The circles came out round to within {:.0}%: there's nothing to correct, and no \
+             direction to tell an angle from",
+            (CIRCLE_ASPECT - 1.0) * 100.0
+        );
+        return Ok(());
+    }
+    // How far the long axis is from the nearer of X and Y.
+    let slant_deg = ellipse.angle_deg - 90.0 * (ellipse.angle_deg / 90.0).round();
+    println!(
+        "The circles came out {:.1}% longer along {} than across",
+        (ellipse.aspect() - 1.0) * 100.0,
+        Degrees(ellipse.angle_deg)
+    );
+    if slant_deg.abs() > MAX_SCALE_SLANT_DEG {
+        let Matrix { a, b, c, d } = ellipse.rounding_matrix();
+        println!("That's a slant --scale-x and --scale-y can't even out. This step can:");
+        println!("[[transform]]\nop = \"matrix\"\nmatrix = [{a:.4}, {b:.4}, {c:.4}, {d:.4}]");
+        if write.is_some() {
+            return Err(std::io::Error::other(
+                "only scales are saved, add the step to the config file by hand",
+            ));
+        }
+        return Ok(());
+    }
+    // Three decimals are finer than a circle drawn by hand.
+    let (scale_x, scale_y) = ellipse.axis_scales();
+    let scales = (
+        (scale_x * 1000.0).round() / 1000.0,
+        (scale_y * 1000.0).round() / 1000.0,
+    );
+    println!("Recommended: --scale-x {} --scale-y {}", scales.0, scales.1);
+    if let Some(path) = write {
+        Config::save_scales(path, scales)?;
+        println!("Saved to {}", path.display());
+    }
+    Ok(())
+}
+
+fn discard_pending(device: &mut Device) -> std::io::Result<()> {
+    match device.fetch_events() {
+        Ok(events) => events.for_each(drop),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Sums up `REL_X`/`REL_Y` for `duration`.
+fn record(device: &mut Device, duration: Duration) -> std::io::Result<(i64, i64)> {
+    let path = record_path(device, duration)?;
+    let (x, y) = path.last().copied().unwrap_or_default();
+    Ok((x as i64, y as i64))
+}
+
+/// Where `REL_X`/`REL_Y` took the pointer after each frame that moved it
+/// during `duration`, from where it started.
+fn record_path(device: &mut Device, duration: Duration) -> std::io::Result<Vec<(f64, f64)>> {
+    let deadline = Instant::now() + duration;
+    let (mut dx, mut dy) = (0i64, 0i64);
+    let mut path = Vec::new();
+    let mut moved = false;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(path);
+        }
+        let mut fds = [PollFd::new(device.as_fd(), PollFlags::POLLIN)];
+        match poll(
+            &mut fds,
+            PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX),
+        ) {
+            Ok(0) | Err(Errno::EINTR) => continue,
+            Ok(_) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+            Err(e) => return Err(e),
+        };
+        for event in events {
+            match event.destructure() {
+                EventSummary::RelativeAxis(_, RelativeAxisCode::REL_X, value) => {
+                    dx += value as i64;
+                    moved = true;
+                }
+                EventSummary::RelativeAxis(_, RelativeAxisCode::REL_Y, value) => {
+                    dy += value as i64;
+                    moved = true;
+                }
+                EventSummary::Synchronization(_, SynchronizationCode::SYN_REPORT, _) if moved => {
+                    path.push((dx as f64, dy as f64));
+                    moved = false;
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::sync::OnceLock;
+
+use evdev::{
+    AttributeSet, Device, EventSummary, EventType, FFEffectCode, InputEvent, KeyCode, MiscCode,
+    PropType, RelativeAxisCode, SwitchCode, UinputAbsSetup,
+};
+use log::debug;
+use sensor_alignment::frame::{DialMode, can_forward};
+
+use crate::devices::{self, AxisMode, WheelMode};
+
+/// Everything the virtual device has to declare so that any event read from
+/// the source device can be emitted again.
+pub struct Capabilities {
+    pub relative_axes: AttributeSet<RelativeAxisCode>,
+    pub keys: AttributeSet<KeyCode>,
+    pub absolute_axes: Vec<UinputAbsSetup>,
+    pub misc: Option<AttributeSet<MiscCode>>,
+    pub switches: Option<AttributeSet<SwitchCode>>,
+    pub properties: AttributeSet<PropType>,
+    /// The force feedback effects, which a [`Relay`] passes on to the device
+    /// they came from, and how many it holds at once.
+    ///
+    /// [`Relay`]: crate::force_feedback::Relay
+    pub force_feedback: Option<AttributeSet<FFEffectCode>>,
+    pub ff_effects_max: u32,
+}
+
+impl Capabilities {
+    /// Copies the full capability set of `device`, falling back to a basic
+    /// three-button mouse for the axes and keys it doesn't report.
+    ///
+    /// LEDs, sounds and autorepeat are not copied: the uinput builder has no
+    /// way to declare them.
+    pub fn from_device(device: &Device) -> Self {
+        for event_type in device.supported_events().iter() {
+            if !can_forward(event_type) && event_type != EventType::FORCEFEEDBACK {
+                debug!(
+                    "{} reports {event_type:?} events, which aren't passed through",
+                    device.name().unwrap_or("Unknown"),
+                );
+            }
+        }
+        Capabilities {
+            relative_axes: device
+                .supported_relative_axes()
+                .unwrap_or(default_rel_axes())
+                .iter()
+                .collect(),
+            keys: device
+                .supported_keys()
+                .unwrap_or(default_keys())
+                .iter()
+                .collect(),
+            absolute_axes: device
+                .get_absinfo()
+                .map(|axes| {
+                    axes.map(|(code, info)| UinputAbsSetup::new(code, info))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            misc: device.misc_properties().map(|misc| misc.iter().collect()),
+            switches: device
+                .supported_switches()
+                .map(|switches| switches.iter().collect()),
+            properties: device.properties().iter().collect(),
+            force_feedback: device.supported_ff().map(|ff| ff.iter().collect()),
+            ff_effects_max: device.max_ff_effects().try_into().unwrap_or(u32::MAX),
+        }
+    }
+
+    /// What a device that produced `events` must at least have had, for
+    /// replaying them without the device. Absolute axes are left out, as
+    /// their ranges aren't known.
+    pub fn from_events(events: &[InputEvent]) -> Self {
+        let mut relative_axes: AttributeSet<RelativeAxisCode> = default_rel_axes().iter().collect();
+        let mut keys: AttributeSet<KeyCode> = default_keys().iter().collect();
+        let mut misc = AttributeSet::new();
+        let mut switches = AttributeSet::new();
+        for event in events {
+            match event.event_type() {
+                EventType::RELATIVE => relative_axes.insert(RelativeAxisCode(event.code())),
+                EventType::KEY => keys.insert(KeyCode(event.code())),
+                EventType::MISC => misc.insert(MiscCode(event.code())),
+                EventType::SWITCH => switches.insert(SwitchCode(event.code())),
+                _ => {}
+            }
+        }
+        Capabilities {
+            relative_axes,
+            keys,
+            absolute_axes: Vec::new(),
+            misc: misc.iter().next().is_some().then_some(misc),
+            switches: switches.iter().next().is_some().then_some(switches),
+            properties: AttributeSet::new(),
+            force_feedback: None,
+            ff_effects_max: 0,
+        }
+    }
+
+    /// Leaves out the motion axes `mode` drops. Absolute mode keeps the
+    /// wheels; relative mode drops every absolute axis, multitouch included,
+    /// since they'd make no sense without the position.
+    pub fn keep_axes(&mut self, mode: AxisMode) {
+        match mode {
+            AxisMode::Rel => self.absolute_axes.clear(),
+            AxisMode::Abs => {
+                self.relative_axes.remove(RelativeAxisCode::REL_X);
+                self.relative_axes.remove(RelativeAxisCode::REL_Y);
+            }
+            AxisMode::Auto => {}
+        }
+    }
+
+    /// Leaves out the wheel axes `mode` drops.
+    pub fn keep_wheels(&mut self, mode: WheelMode) {
+        for &code in mode.dropped() {
+            self.relative_axes.remove(code);
+        }
+    }
+
+    /// Union of two capability sets, for a virtual device that merges
+    /// several sources. Absolute axes declared by both keep `self`'s range.
+    pub fn merge(mut self, other: Capabilities) -> Self {
+        self.relative_axes = self
+            .relative_axes
+            .iter()
+            .chain(other.relative_axes.iter())
+            .collect();
+        self.keys = self.keys.iter().chain(other.keys.iter()).collect();
+        for axis in other.absolute_axes {
+            if !self
+                .absolute_axes
+                .iter()
+                .any(|known| known.code() == axis.code())
+            {
+                self.absolute_axes.push(axis);
+            }
+        }
+        self.misc = match (self.misc, other.misc) {
+            (Some(a), Some(b)) => Some(a.iter().chain(b.iter()).collect()),
+            (a, b) => a.or(b),
+        };
+        self.switches = match (self.switches, other.switches) {
+            (Some(a), Some(b)) => Some(a.iter().chain(b.iter()).collect()),
+            (a, b) => a.or(b),
+        };
+        self.properties = self
+            .properties
+            .iter()
+            .chain(other.properties.iter())
+            .collect();
+        // Effects are only passed on to one device, the first that has any.
+        if self.force_feedback.is_none() {
+            self.force_feedback = other.force_feedback;
+            self.ff_effects_max = other.ff_effects_max;
+        }
+        self
+    }
+
+    /// Declares both axes of every wheel the device has, since a rotated
+    /// vertical wheel may come out partly horizontal (and vice versa).
+    pub fn declare_rotated_wheels(&mut self) {
+        for (hwheel, wheel) in [
+            (RelativeAxisCode::REL_HWHEEL, RelativeAxisCode::REL_WHEEL),
+            (
+                RelativeAxisCode::REL_HWHEEL_HI_RES,
+                RelativeAxisCode::REL_WHEEL_HI_RES,
+            ),
+        ] {
+            if self.relative_axes.contains(hwheel) || self.relative_axes.contains(wheel) {
+                self.relative_axes.insert(hwheel);
+                self.relative_axes.insert(wheel);
+            }
+        }
+    }
+
+    /// Declares the wheel that `REL_DIAL` is taken as, if the device has a
+    /// dial. `REL_DIAL` itself stays declared.
+    pub fn declare_dial_wheel(&mut self, mode: DialMode) {
+        if let Some(wheel) = mode.wheel()
+            && self.relative_axes.contains(RelativeAxisCode::REL_DIAL)
+        {
+            self.relative_axes.insert(wheel);
+        }
+    }
+
+    /// Declares the keys that others are remapped to, which the device may
+    /// not have itself.
+    pub fn declare_remapped_keys(&mut self, remap: &[(KeyCode, KeyCode)]) {
+        for &(_, to) in remap {
+            self.keys.insert(to);
+        }
+    }
+
+    /// Moves the wheels into a capability set of their own, for the scroll
+    /// device of --split-scroll. `None` if there are none to move.
+    ///
+    /// udev only tags a device `ID_INPUT_MOUSE` if it has `REL_X`, `REL_Y`
+    /// and `BTN_LEFT`, and libinput ignores one that isn't tagged, so the
+    /// scroll device declares them too. Nothing is ever emitted on them.
+    pub fn split_wheels(&mut self) -> Option<Capabilities> {
+        let wheels: AttributeSet<RelativeAxisCode> = self
+            .relative_axes
+            .iter()
+            .filter(|&code| devices::is_wheel(code))
+            .collect();
+        wheels.iter().next()?;
+        for code in wheels.iter() {
+            self.relative_axes.remove(code);
+        }
+        let mut relative_axes = wheels;
+        relative_axes.insert(RelativeAxisCode::REL_X);
+        relative_axes.insert(RelativeAxisCode::REL_Y);
+        let mut keys = AttributeSet::new();
+        keys.insert(KeyCode::BTN_LEFT);
+        Some(Capabilities {
+            relative_axes,
+            keys,
+            absolute_axes: Vec::new(),
+            misc: None,
+            switches: None,
+            properties: AttributeSet::new(),
+            force_feedback: None,
+            ff_effects_max: 0,
+        })
+    }
+
+    /// Declares a key that's sent without the device having pressed it.
+    pub fn declare_key(&mut self, code: KeyCode) {
+        self.keys.insert(code);
+    }
+
+    /// Whether the virtual device can emit `event`. The kernel drops events
+    /// it wasn't declared for without an error.
+    pub fn declares(&self, event: &InputEvent) -> bool {
+        match event.destructure() {
+            EventSummary::Synchronization(..) => true,
+            EventSummary::Key(_, code, _) => self.keys.contains(code),
+            EventSummary::RelativeAxis(_, code, _) => self.relative_axes.contains(code),
+            EventSummary::AbsoluteAxis(_, code, _) => {
+                self.absolute_axes.iter().any(|axis| axis.code() == code.0)
+            }
+            EventSummary::Misc(_, code, _) => {
+                self.misc.as_ref().is_some_and(|misc| misc.contains(code))
+            }
+            EventSummary::Switch(_, code, _) => self
+                .switches
+                .as_ref()
+                .is_some_and(|switches| switches.contains(code)),
+            _ => false,
+        }
+    }
+}
+
+fn default_rel_axes() -> &'static AttributeSet<RelativeAxisCode> {
+    static DEFAULT: OnceLock<AttributeSet<RelativeAxisCode>> = OnceLock::new();
+    DEFAULT.get_or_init(|| {
+        let mut set = AttributeSet::new();
+        set.insert(RelativeAxisCode::REL_X);
+        set.insert(RelativeAxisCode::REL_Y);
+        set.insert(RelativeAxisCode::REL_WHEEL);
+        // Modern mice report high-resolution scrolling next to the coarse
+        // wheel, which would fail to emit if the axes weren't declared.
+        set.insert(RelativeAxisCode::REL_WHEEL_HI_RES);
+        set.insert(RelativeAxisCode::REL_HWHEEL_HI_RES);
+        set
+    })
+}
+
+fn default_keys() -> &'static AttributeSet<KeyCode> {
+    static DEFAULT: OnceLock<AttributeSet<KeyCode>> = OnceLock::new();
+    DEFAULT.get_or_init(|| {
+        let mut set = AttributeSet::new();
+        set.insert(KeyCode::BTN_LEFT);
+        set.insert(KeyCode::BTN_RIGHT);
+        set.insert(KeyCode::BTN_MIDDLE);
+        set
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_scroll_device_looks_like_a_mouse_to_udev() {
+        let mut capabilities = Capabilities::from_events(&[]);
+        let wheels = capabilities.split_wheels().unwrap();
+
+        assert!(wheels.relative_axes.contains(RelativeAxisCode::REL_WHEEL));
+        assert!(wheels.relative_axes.contains(RelativeAxisCode::REL_X));
+        assert!(wheels.relative_axes.contains(RelativeAxisCode::REL_Y));
+        assert!(wheels.keys.contains(KeyCode::BTN_LEFT));
+        // The pointer keeps its motion and buttons, just not the wheels.
+        assert!(capabilities.relative_axes.contains(RelativeAxisCode::REL_X));
+        assert!(capabilities.keys.contains(KeyCode::BTN_LEFT));
+        assert!(
+            !capabilities
+                .relative_axes
+                .contains(RelativeAxisCode::REL_WHEEL)
+        );
+    }
+}
@@ -0,0 +1,726 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use sensor_alignment::{Matrix, Step};
+use serde::{Deserialize, Deserializer};
+
+/// Settings read from the TOML configuration file. Every field is optional,
+/// values given on the command line or in the environment take precedence.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The keys the file sets, with the profile applied, for logging where
+    /// each option came from.
+    #[serde(skip)]
+    pub keys: Vec<String>,
+    /// The profiles defined here and in the included files.
+    #[serde(skip)]
+    pub profiles: Vec<String>,
+    /// The files read, the config file itself first and then the ones it
+    /// includes, for --watch-config.
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+    /// A single device or a list of devices to merge.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub device_path: Vec<String>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub device_name: Vec<String>,
+    /// Shell-style patterns for the device name or physical path.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub device_glob: Vec<String>,
+    /// `rel`, `abs` or `auto`.
+    pub axis_mode: Option<String>,
+    /// `[x, y]` shares of the width and height.
+    pub abs_offset: Option<[f64; 2]>,
+    pub angle_deg: Option<f64>,
+    pub angle_rad: Option<f64>,
+    pub angle_grad: Option<f64>,
+    pub angle_turns: Option<f64>,
+    /// `[x, y]` directions, the angle being the one that turns `from` into
+    /// `to`.
+    pub from: Option<[f64; 2]>,
+    pub to: Option<[f64; 2]>,
+    pub gyro_device: Option<PathBuf>,
+    /// A display output whose rotation motion is turned on by, after the
+    /// rest of the transform.
+    pub match_display: Option<String>,
+    /// `[a, b, c, d]`, used instead of `angle_deg`.
+    pub matrix: Option<[f64; 4]>,
+    /// Also used instead of `angle_deg`.
+    pub mirror_axis_deg: Option<f64>,
+    /// Two angles blended into the one used, `blend` of the way from
+    /// `angle_a` to `angle_b`.
+    pub angle_a: Option<f64>,
+    pub angle_b: Option<f64>,
+    pub blend: Option<f64>,
+    /// `[[transform]]` steps applied in order, ahead of `angle_deg`.
+    #[serde(default)]
+    pub transform: Vec<TransformStep>,
+    pub rotate_scroll: Option<bool>,
+    /// Names out of `x`, `y`, `wheel` and `hwheel`, replacing
+    /// `rotate_scroll`.
+    pub rotate_axes: Option<Vec<String>>,
+    /// Only used with `rotate_scroll`, which otherwise uses `angle_deg`.
+    pub scroll_angle_deg: Option<f64>,
+    pub invert_scroll: Option<bool>,
+    /// `lowres`, `hires` or `both`.
+    pub wheel_mode: Option<String>,
+    pub no_wheel: Option<bool>,
+    pub dial_mode: Option<String>,
+    pub scroll_accel: Option<f64>,
+    pub rotate_above: Option<f64>,
+    /// `[speed, offset]` points of the angle offset by speed, in counts per
+    /// frame and degrees.
+    pub angle_curve: Option<Vec<[f64; 2]>>,
+    /// `[[response]]` points of the gain by speed.
+    #[serde(default)]
+    pub response: Vec<ResponsePoint>,
+    pub deadzone: Option<f64>,
+    /// Counts per inch of the sensor, by default what the device reports.
+    pub input_dpi: Option<f64>,
+    /// Counts per inch the virtual device should move by instead.
+    pub output_dpi: Option<f64>,
+    pub scale_x: Option<f64>,
+    pub scale_y: Option<f64>,
+    pub accel: Option<f64>,
+    pub sensitivity: Option<f64>,
+    pub smooth: Option<f64>,
+    pub tremor_filter: Option<bool>,
+    /// Only used with `tremor_filter`.
+    pub tremor_window: Option<usize>,
+    pub tremor_strength: Option<f64>,
+    pub flip_x: Option<bool>,
+    pub flip_y: Option<bool>,
+    pub snap_axis: Option<bool>,
+    pub snap_hysteresis: Option<f64>,
+    pub jitter_suppress: Option<f64>,
+    pub max_delta: Option<f64>,
+    pub max_speed: Option<f64>,
+    pub max_speed_x: Option<f64>,
+    pub max_speed_y: Option<f64>,
+    /// `nearest`, `floor`, `ceil`, `bankers`, `away_from_zero` or
+    /// `toward_zero`.
+    pub rounding: Option<String>,
+    /// Per output axis, taking precedence over `rounding` in the file but
+    /// not over `--rounding`.
+    pub rounding_x: Option<String>,
+    pub rounding_y: Option<String>,
+    pub control_socket: Option<PathBuf>,
+    pub angle_step: Option<f64>,
+    pub virtual_name: Option<String>,
+    pub phys: Option<String>,
+    /// IDs of the virtual device, which TOML lets you write in hex.
+    pub vendor: Option<u16>,
+    pub product: Option<u16>,
+    pub product_version: Option<u16>,
+    /// Takes precedence over the IDs above.
+    pub clone_id: Option<bool>,
+    /// One virtual device per source device.
+    pub separate_outputs: Option<bool>,
+    /// Wheel events from a virtual device of their own.
+    pub split_scroll: Option<bool>,
+    /// `per-sync` or `coalesce:MS`.
+    pub frame_policy: Option<String>,
+    /// `retry` or `skip`.
+    pub emit_errors: Option<String>,
+    #[serde(default)]
+    pub preset: Vec<f64>,
+    /// Key name such as `BTN_SIDE`.
+    pub cycle_button: Option<String>,
+    pub toggle_key: Option<String>,
+    pub reset_key: Option<String>,
+    pub pause_key: Option<String>,
+    pub forward_pause_key: Option<bool>,
+    pub rotate_while: Option<String>,
+    pub forward_hold_button: Option<bool>,
+    /// `[[chord]]` tables of keys that switch to an angle together.
+    #[serde(default)]
+    pub chord: Vec<ChordBinding>,
+    pub swap_buttons: Option<bool>,
+    /// A `[remap]` table of key names, e.g. `BTN_SIDE = "BTN_MIDDLE"`.
+    #[serde(default)]
+    pub remap: BTreeMap<String, String>,
+    /// Seconds the pointer has to rest before it clicks.
+    pub dwell_click: Option<f64>,
+    /// Only used with `dwell_click`.
+    pub dwell_tolerance: Option<f64>,
+    pub dwell_button: Option<String>,
+    /// Key tapped when the mouse is shaken.
+    pub shake_key: Option<String>,
+    /// Only used with `shake_key`.
+    pub shake_reversals: Option<usize>,
+    /// Seconds.
+    pub shake_window: Option<f64>,
+    pub shake_speed: Option<f64>,
+    /// Milliseconds.
+    pub debounce: Option<u64>,
+    pub palm_reject: Option<bool>,
+    /// Only used with `palm_reject`, as shares of the touchpad's ranges.
+    pub palm_size: Option<f64>,
+    pub palm_pressure: Option<f64>,
+    pub palm_edge: Option<f64>,
+    /// `[[app_profile]]` rules picking a profile by the focused window.
+    #[serde(default)]
+    pub app_profile: Vec<AppProfile>,
+    /// Prints the focused window's class and title on two lines, in place
+    /// of asking the desktop.
+    pub focus_command: Option<String>,
+}
+
+/// One `[[chord]]` table, e.g. `keys = ["KEY_LEFTCTRL", "KEY_LEFTALT",
+/// "KEY_1"]` with `angle_deg = 0.0`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChordBinding {
+    pub keys: Vec<String>,
+    pub angle_deg: f64,
+    /// Whether the keys still reach the virtual device as they go down.
+    #[serde(default)]
+    pub passthrough: bool,
+}
+
+/// One `[[app_profile]]` table: the profile to switch to while a window whose
+/// class and title match these shell-style patterns has the focus. A pattern
+/// left out matches anything.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AppProfile {
+    pub class: Option<String>,
+    pub title: Option<String>,
+    pub profile: String,
+}
+
+/// One `[[response]]` table, the gain at a speed in counts per frame.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ResponsePoint {
+    pub speed: f64,
+    pub gain: f64,
+}
+
+/// One `[[transform]]` table, e.g. `op = "scale"` with `y = 1.1`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformStep {
+    Scale {
+        #[serde(default = "one")]
+        x: f64,
+        #[serde(default = "one")]
+        y: f64,
+    },
+    Rotate {
+        angle_deg: f64,
+    },
+    /// `[a, b, c, d]` like the top-level `matrix`.
+    Matrix {
+        matrix: [f64; 4],
+    },
+    Flip {
+        #[serde(default)]
+        x: bool,
+        #[serde(default)]
+        y: bool,
+    },
+    Deadzone {
+        counts: f64,
+    },
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+impl TransformStep {
+    pub fn to_step(self) -> Step {
+        match self {
+            TransformStep::Scale { x, y } => Step::Scale { x, y },
+            TransformStep::Rotate { angle_deg } => Step::Rotate { angle_deg },
+            TransformStep::Matrix {
+                matrix: [a, b, c, d],
+            } => Step::Matrix(Matrix { a, b, c, d }),
+            TransformStep::Flip { x, y } => Step::Flip { x, y },
+            TransformStep::Deadzone { counts } => Step::Deadzone(counts),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file with the keys of `[profiles.<profile>]` laid
+    /// over the top-level ones. Without a profile, `[profiles.default]` is
+    /// used if there is one. The files named by `include` are read first,
+    /// see [`Config::read`].
+    ///
+    /// Unknown keys and values out of range are errors naming the line they
+    /// are on, in every profile and every included file, and not only the
+    /// ones used.
+    pub fn load(path: impl AsRef<Path>, profile: Option<&str>) -> std::io::Result<Config> {
+        let path = path.as_ref();
+        let invalid = |e: &dyn std::fmt::Display| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        };
+        let mut files = Vec::new();
+        let (mut table, mut profiles) = Config::read(path, &mut Vec::new(), &mut files)?;
+        let names: Vec<String> = profiles.keys().cloned().collect();
+        let selected = match profile {
+            Some(name) => Some(
+                profiles
+                    .remove(name)
+                    .ok_or_else(|| invalid(&format!("no profile named {name:?}")))?,
+            ),
+            None => profiles.remove("default"),
+        };
+        if let Some(toml::Value::Table(overrides)) = selected {
+            table.extend(overrides);
+        }
+        let keys = table.keys().cloned().collect();
+        // Every key was checked where it was written, so this only catches
+        // what a mistake in the merging would let through.
+        let config: Config = table.try_into().map_err(|e| invalid(&e))?;
+        config
+            .validate()
+            .map_err(|(key, message)| invalid(&format!("{key} {message}")))?;
+        if let Some(rule) = config
+            .app_profile
+            .iter()
+            .find(|rule| !names.contains(&rule.profile))
+        {
+            return Err(invalid(&format!(
+                "app_profile names profile {:?}, which isn't there",
+                rule.profile
+            )));
+        }
+        Ok(Config {
+            keys,
+            profiles: names,
+            files,
+            ..config
+        })
+    }
+
+    /// Reads the file at `path` together with the files its `include` names,
+    /// returning the top-level keys and the `[profiles]` table. The included
+    /// files come first, in the order given and each replacing keys of the
+    /// ones before, and the including file last, so that it wins. Profiles
+    /// of the same name are merged key by key the same way.
+    ///
+    /// Relative names are taken from the directory of the file naming them.
+    /// Each file is checked by itself, all of its profiles included.
+    /// `including` holds the files on the way here, which makes a file that
+    /// ends up including itself an error, and every file read is added to
+    /// `files`.
+    fn read(
+        path: &Path,
+        including: &mut Vec<PathBuf>,
+        files: &mut Vec<PathBuf>,
+    ) -> std::io::Result<(toml::Table, toml::Table)> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        };
+        let text = std::fs::read_to_string(path)?;
+        let mut table: toml::Table = text.parse().map_err(|e| invalid(&e))?;
+        let includes = match table.remove("include") {
+            None => Vec::new(),
+            Some(toml::Value::String(name)) => vec![name],
+            Some(toml::Value::Array(names)) => names
+                .into_iter()
+                .map(|name| match name {
+                    toml::Value::String(name) => Ok(name),
+                    _ => Err(invalid(&"include must be a list of file names")),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(invalid(&"include must be a list of file names")),
+        };
+        let profiles = match table.remove("profiles") {
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(_) => return Err(invalid(&"profiles must be a table")),
+            None => toml::Table::new(),
+        };
+        // The top-level keys are checked by themselves here, and again
+        // together with the profile once everything is merged.
+        let check = |table: toml::Table, profile: Option<&str>| -> std::io::Result<()> {
+            let config: Config = table
+                .try_into()
+                .map_err(|e| invalid(&describe(&text, profile, &e)))?;
+            config.validate().map_err(|(key, message)| {
+                invalid(&match line_of(&text, profile, key) {
+                    Some(line) => format!("line {line}: {key} {message}"),
+                    None => format!("{key} {message}"),
+                })
+            })
+        };
+        for (name, overrides) in &profiles {
+            match overrides {
+                toml::Value::Table(overrides) => check(overrides.clone(), Some(name))?,
+                _ => return Err(invalid(&format!("profile {name:?} must be a table"))),
+            }
+        }
+        check(table.clone(), None)?;
+
+        let canonical = path.canonicalize()?;
+        files.push(canonical.clone());
+        including.push(canonical);
+        let (mut merged, mut merged_profiles) = (toml::Table::new(), toml::Table::new());
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for name in includes {
+            let included = dir.join(&name);
+            let canonical = included
+                .canonicalize()
+                .map_err(|e| invalid(&format!("include {name:?}: {e}")))?;
+            if let Some(start) = including.iter().position(|path| *path == canonical) {
+                let cycle: Vec<String> = including[start..]
+                    .iter()
+                    .chain([&canonical])
+                    .map(|path| path.display().to_string())
+                    .collect();
+                return Err(invalid(&format!(
+                    "include {name:?} is a cycle: {}",
+                    cycle.join(" -> ")
+                )));
+            }
+            let (table, profiles) = Config::read(&included, including, files)?;
+            merged.extend(table);
+            merge_profiles(&mut merged_profiles, profiles);
+        }
+        including.pop();
+        merged.extend(table);
+        merge_profiles(&mut merged_profiles, profiles);
+        Ok((merged, merged_profiles))
+    }
+
+    /// Checks the values that have a type serde can't narrow down enough,
+    /// returning the key at fault and what's wrong with it.
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        let finite = [
+            ("angle_deg", self.angle_deg),
+            ("angle_rad", self.angle_rad),
+            ("angle_grad", self.angle_grad),
+            ("angle_turns", self.angle_turns),
+            ("mirror_axis_deg", self.mirror_axis_deg),
+            ("angle_a", self.angle_a),
+            ("angle_b", self.angle_b),
+            ("scroll_angle_deg", self.scroll_angle_deg),
+            ("scale_x", self.scale_x),
+            ("scale_y", self.scale_y),
+            ("angle_step", self.angle_step),
+        ];
+        for (key, value) in finite {
+            if let Some(value) = value
+                && !value.is_finite()
+            {
+                return Err((key, format!("must be a finite number, not {value}")));
+            }
+        }
+        for (key, scale) in [("scale_x", self.scale_x), ("scale_y", self.scale_y)] {
+            if scale == Some(0.0) {
+                return Err((
+                    key,
+                    "must not be 0, which stops the pointer along that axis".to_string(),
+                ));
+            }
+        }
+        let positive = [
+            ("sensitivity", self.sensitivity),
+            ("accel", self.accel),
+            ("input_dpi", self.input_dpi),
+            ("output_dpi", self.output_dpi),
+            ("dwell_click", self.dwell_click),
+            ("shake_window", self.shake_window),
+            ("shake_speed", self.shake_speed),
+            ("max_speed", self.max_speed),
+            ("max_speed_x", self.max_speed_x),
+            ("max_speed_y", self.max_speed_y),
+        ];
+        for (key, value) in positive {
+            if let Some(value) = value
+                && !(value > 0.0 && value.is_finite())
+            {
+                return Err((key, format!("must be above 0, not {value}")));
+            }
+        }
+        let not_negative = [
+            ("rotate_above", self.rotate_above),
+            ("deadzone", self.deadzone),
+            ("snap_hysteresis", self.snap_hysteresis),
+            ("jitter_suppress", self.jitter_suppress),
+            ("dwell_tolerance", self.dwell_tolerance),
+        ];
+        for (key, value) in not_negative {
+            if let Some(value) = value
+                && !(value >= 0.0 && value.is_finite())
+            {
+                return Err((key, format!("must be 0 or more, not {value}")));
+            }
+        }
+        if let Some(smooth) = self.smooth
+            && !(smooth > 0.0 && smooth <= 1.0)
+        {
+            return Err((
+                "smooth",
+                format!("must be above 0 and at most 1, not {smooth}"),
+            ));
+        }
+        if let Some(strength) = self.tremor_strength
+            && !(0.0..=1.0).contains(&strength)
+        {
+            return Err((
+                "tremor_strength",
+                format!("must be between 0 and 1, not {strength}"),
+            ));
+        }
+        for (key, share) in [
+            ("palm_size", self.palm_size),
+            ("palm_pressure", self.palm_pressure),
+        ] {
+            if let Some(share) = share
+                && !(0.0..=1.0).contains(&share)
+            {
+                return Err((key, format!("must be between 0 and 1, not {share}")));
+            }
+        }
+        if let Some(edge) = self.palm_edge
+            && !(0.0..0.5).contains(&edge)
+        {
+            return Err((
+                "palm_edge",
+                format!("must be at least 0 and below 0.5, not {edge}"),
+            ));
+        }
+        if let Some(accel) = self.scroll_accel
+            && !(accel >= 1.0 && accel.is_finite())
+        {
+            return Err(("scroll_accel", format!("must be at least 1, not {accel}")));
+        }
+        if let Some(reversals) = self.shake_reversals
+            && reversals < 2
+        {
+            return Err((
+                "shake_reversals",
+                format!("must be at least 2, not {reversals}"),
+            ));
+        }
+        if let Some(max_delta) = self.max_delta
+            && (max_delta.is_nan() || max_delta < 1.0)
+        {
+            return Err(("max_delta", format!("must be at least 1, not {max_delta}")));
+        }
+        for (key, value) in self
+            .from
+            .iter()
+            .map(|v| ("from", v))
+            .chain(self.to.iter().map(|v| ("to", v)))
+        {
+            if value.iter().any(|v| !v.is_finite()) {
+                return Err((key, format!("must be finite numbers, not {value:?}")));
+            }
+        }
+        if let Some(matrix) = self.matrix
+            && matrix.iter().any(|v| !v.is_finite())
+        {
+            return Err(("matrix", format!("must be finite numbers, not {matrix:?}")));
+        }
+        for chord in &self.chord {
+            if chord.keys.len() < 2 {
+                return Err((
+                    "keys",
+                    format!(
+                        "of a [[chord]] must be two or more, not {}",
+                        chord.keys.len()
+                    ),
+                ));
+            }
+            if !chord.angle_deg.is_finite() {
+                return Err((
+                    "angle_deg",
+                    format!("must be a finite number, not {}", chord.angle_deg),
+                ));
+            }
+        }
+        if let Some(rule) = self
+            .app_profile
+            .iter()
+            .find(|rule| rule.class.is_none() && rule.title.is_none())
+        {
+            return Err((
+                "profile",
+                format!(
+                    "{:?} needs a class or title to match in its [[app_profile]]",
+                    rule.profile
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sets `angle_deg` in the config file at `path`, creating it if needed.
+    /// Other ways of giving the angle are removed, and everything else is
+    /// kept, although comments are lost.
+    pub fn save_angle_deg(path: impl AsRef<Path>, angle_deg: f64) -> std::io::Result<()> {
+        Config::save(
+            path.as_ref(),
+            &[
+                "angle_rad",
+                "angle_grad",
+                "angle_turns",
+                "from",
+                "to",
+                "matrix",
+                "mirror_axis_deg",
+            ],
+            &[("angle_deg", angle_deg)],
+        )
+    }
+
+    /// Sets `scale_x` and `scale_y` in the config file at `path`, like
+    /// [`Config::save_angle_deg`] does the angle.
+    pub fn save_scales(path: impl AsRef<Path>, (x, y): (f64, f64)) -> std::io::Result<()> {
+        Config::save(path.as_ref(), &[], &[("scale_x", x), ("scale_y", y)])
+    }
+
+    /// Removes the keys `removed` from the config file at `path` and sets
+    /// `values`, creating the file if needed.
+    fn save(path: &Path, removed: &[&str], values: &[(&str, f64)]) -> std::io::Result<()> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        };
+        let mut table: toml::Table = match std::fs::read_to_string(path) {
+            Ok(text) => text.parse().map_err(|e| invalid(&e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => return Err(e),
+        };
+        for key in removed {
+            table.remove(*key);
+        }
+        for &(key, value) in values {
+            table.insert(key.to_string(), value.into());
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string(&table).map_err(|e| invalid(&e))?)
+    }
+
+    /// `$XDG_CONFIG_HOME/sensor_alignment/config.toml`, falling back to
+    /// `~/.config` when `XDG_CONFIG_HOME` isn't set.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_dir.join("sensor_alignment").join("config.toml"))
+    }
+}
+
+/// Rewrites a deserialization error to start with the line of the key it's
+/// about, and for an unknown key to suggest the known one it's closest to.
+fn describe(text: &str, profile: Option<&str>, e: &toml::de::Error) -> String {
+    let message = e.message();
+    if let Some(rest) = message.strip_prefix("unknown field `")
+        && let Some((key, expected)) = rest.split_once('`')
+    {
+        let suggestion = expected
+            .split('`')
+            .skip(1)
+            .step_by(2)
+            .map(|known| (edit_distance(key, known), known))
+            .filter(|&(distance, _)| distance <= 2)
+            .min();
+        let line =
+            line_of(text, profile, key).map_or(String::new(), |line| format!("line {line}: "));
+        return match suggestion {
+            Some((_, known)) => format!("{line}unknown key `{key}`, did you mean `{known}`?"),
+            None => format!("{line}unknown key `{key}`"),
+        };
+    }
+    // Other errors say which key they're about next to the message.
+    let key = e.to_string().lines().find_map(|line| {
+        let path = line.strip_prefix("in `")?.strip_suffix('`')?;
+        Some(path.rsplit('.').next().unwrap_or(path).to_string())
+    });
+    match key
+        .as_deref()
+        .and_then(|key| Some((key, line_of(text, profile, key)?)))
+    {
+        Some((key, line)) => format!("line {line}: {key}: {message}"),
+        None => e.to_string().trim_end().to_string(),
+    }
+}
+
+/// The 1-based number of the first line that sets `key`, either in the
+/// tables of `profile` or outside of any profile.
+fn line_of(text: &str, profile: Option<&str>, key: &str) -> Option<usize> {
+    let profile = profile.map(|name| format!("profiles.{name}"));
+    let mut in_scope = profile.is_none();
+    text.lines()
+        .position(|line| {
+            let line = line.trim_start();
+            if line.starts_with('[') {
+                let header = line.trim_matches(|c: char| c == '[' || c == ']' || c.is_whitespace());
+                in_scope = match &profile {
+                    Some(profile) => {
+                        header == profile || header.starts_with(&format!("{profile}."))
+                    }
+                    None => !header.starts_with("profiles."),
+                };
+                return false;
+            }
+            let rest = line
+                .strip_prefix(key)
+                .or_else(|| line.strip_prefix(&format!("\"{key}\"")));
+            in_scope && rest.is_some_and(|rest| rest.trim_start().starts_with('='))
+        })
+        .map(|index| index + 1)
+}
+
+/// Lays the profiles of a file over those of the files it includes, the
+/// keys of a profile both have replacing those `into` has already.
+fn merge_profiles(into: &mut toml::Table, profiles: toml::Table) {
+    for (name, profile) in profiles {
+        match (into.get_mut(&name), profile) {
+            (Some(toml::Value::Table(keys)), toml::Value::Table(overrides)) => {
+                keys.extend(overrides)
+            }
+            (_, profile) => {
+                into.insert(name, profile);
+            }
+        }
+    }
+}
+
+/// How many characters have to be inserted, removed or replaced to turn `a`
+/// into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(ca != cb);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
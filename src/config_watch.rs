@@ -0,0 +1,144 @@
+use std::ffi::OsString;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+
+/// How long the config file has to stay unchanged before it's read again.
+/// Editors often write a file more than once when saving it, e.g. truncating
+/// it first.
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watches the config file and the files it includes for --watch-config, so
+/// that saving any of them reloads the config like SIGHUP does.
+///
+/// The directories they're in are watched rather than the files themselves,
+/// since many editors save by writing a new file and renaming it over the
+/// old one, which a watch on the file wouldn't survive.
+pub struct ConfigWatcher {
+    inotify: Inotify,
+    // Each directory watched, with the names of the files in it.
+    dirs: Vec<(WatchDescriptor, PathBuf, Vec<OsString>)>,
+    // When a file was last written, until it has settled.
+    changed_at: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    /// Watches `files`, or what they link to if they're symlinks, as dotfile
+    /// managers like to make them.
+    pub fn new(files: &[PathBuf]) -> std::io::Result<ConfigWatcher> {
+        let mut watcher = ConfigWatcher {
+            inotify: Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?,
+            dirs: Vec::new(),
+            changed_at: None,
+        };
+        watcher.watch(files)?;
+        Ok(watcher)
+    }
+
+    /// Watches `files` from now on instead, for after a reload that changed
+    /// what's included. Directories no longer needed stay watched, with no
+    /// file in them to wait for.
+    pub fn watch(&mut self, files: &[PathBuf]) -> std::io::Result<()> {
+        for (_, _, names) in &mut self.dirs {
+            names.clear();
+        }
+        for path in files {
+            let path = path.canonicalize()?;
+            let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("{} is not a file", path.display()),
+                ));
+            };
+            let names = match self.dirs.iter().position(|(_, watched, _)| watched == dir) {
+                Some(index) => &mut self.dirs[index].2,
+                None => {
+                    let wd = self.inotify.add_watch(
+                        dir,
+                        AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO,
+                    )?;
+                    self.dirs.push((wd, dir.to_owned(), Vec::new()));
+                    &mut self.dirs.last_mut().unwrap().2
+                }
+            };
+            names.push(name.to_owned());
+        }
+        Ok(())
+    }
+
+    /// Takes in pending notifications, starting the wait for the file to
+    /// settle over if it was written to again.
+    pub fn read(&mut self) -> std::io::Result<()> {
+        let events = match self.inotify.read_events() {
+            Ok(events) => events,
+            Err(Errno::EAGAIN) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if events.iter().any(|event| {
+            self.dirs.iter().any(|(wd, _, names)| {
+                *wd == event.wd && event.name.as_ref().is_some_and(|name| names.contains(name))
+            })
+        }) {
+            self.changed_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// How soon the files will have settled, if one was written to.
+    pub fn due(&self) -> Option<Duration> {
+        self.changed_at
+            .map(|at| (at + DEBOUNCE).saturating_duration_since(Instant::now()))
+    }
+
+    /// Returns whether a file was written to and has settled since, once.
+    pub fn take_settled(&mut self) -> bool {
+        if self.due().is_some_and(|left| left.is_zero()) {
+            self.changed_at = None;
+            return true;
+        }
+        false
+    }
+}
+
+impl AsFd for ConfigWatcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inotify.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_an_included_file_is_noticed() {
+        let dir = std::env::temp_dir().join(format!(
+            "sensor_alignment-config-watch-{}",
+            std::process::id()
+        ));
+        let shared = dir.join("shared");
+        std::fs::create_dir_all(&shared).unwrap();
+        let (config, base) = (dir.join("config.toml"), shared.join("base.toml"));
+        std::fs::write(&config, "include = [\"shared/base.toml\"]\n").unwrap();
+        std::fs::write(&base, "sensitivity = 1.0\n").unwrap();
+        let mut watcher = ConfigWatcher::new(&[config.clone(), base.clone()]).unwrap();
+
+        std::fs::write(dir.join("unrelated.toml"), "").unwrap();
+        watcher.read().unwrap();
+        assert_eq!(watcher.due(), None);
+        std::fs::write(&base, "sensitivity = 2.0\n").unwrap();
+        watcher.read().unwrap();
+        assert!(watcher.due().is_some());
+
+        // No longer included after a reload.
+        watcher.changed_at = None;
+        watcher.watch(std::slice::from_ref(&config)).unwrap();
+        std::fs::write(&base, "sensitivity = 3.0\n").unwrap();
+        watcher.read().unwrap();
+        assert_eq!(watcher.due(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
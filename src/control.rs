@@ -0,0 +1,185 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::{info, warn};
+use sensor_alignment::Degrees;
+use sensor_alignment::state::LiveState;
+
+use crate::signals;
+use crate::stats;
+
+pub const DEFAULT_CONTROL_SOCKET: &str = "/run/sensor_alignment.sock";
+
+/// Unix socket accepting line-based commands to adjust the running instance:
+///
+/// - `get-angle` prints the current angle
+/// - `set-angle <degrees>` changes the angle
+/// - `reset` restores the configured angle
+/// - `switch-profile <name>` reloads the config with another profile
+/// - `status` prints the state of the running instance as one line of JSON
+///
+/// The socket file is removed when this is dropped.
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    pub fn bind(path: impl AsRef<Path>, state: Arc<LiveState>) -> std::io::Result<ControlSocket> {
+        let path = path.as_ref().to_path_buf();
+        // A socket nobody is listening on is left over from an earlier run.
+        if path.exists() && UnixStream::connect(&path).is_err() {
+            std::fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+
+        std::thread::spawn(move || {
+            signals::block_on_current_thread();
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = serve(stream, &state) {
+                            warn!("Control socket client failed: {e}");
+                        }
+                    }
+                    Err(e) => warn!("Control socket accept failed: {e}"),
+                }
+            }
+        });
+
+        Ok(ControlSocket { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn serve(stream: UnixStream, state: &LiveState) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let reply = execute(line?.trim(), state);
+        writeln!(writer, "{reply}")?;
+    }
+    Ok(())
+}
+
+fn execute(command: &str, state: &LiveState) -> String {
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("get-angle"), None, None) => format!("{}", state.angle_deg()),
+        (Some("set-angle"), Some(angle), None) => match angle.parse::<f64>() {
+            Ok(angle) if angle.is_finite() => {
+                state.set_angle_deg(angle);
+                info!("Angle set to {}", Degrees(angle));
+                format!("ok {angle}")
+            }
+            _ => format!("error: invalid angle '{angle}'"),
+        },
+        (Some("reset"), None, None) => {
+            state.reset();
+            info!("Angle reset to {}", Degrees(state.angle_deg()));
+            format!("ok {}", state.angle_deg())
+        }
+        (Some("switch-profile"), Some(name), None) => {
+            // Against the profiles of the last read, which the reload checks
+            // again in case the file has changed since.
+            if !state.profiles().iter().any(|profile| profile == name) {
+                return format!("error: no profile named '{name}'");
+            }
+            state.set_profile(Some(name.to_string()));
+            signals::request_reload();
+            format!("ok {name}")
+        }
+        (Some("status"), None, None) => {
+            let totals = stats::totals();
+            serde_json::json!({
+                "active": state.enabled() && !state.paused(),
+                "enabled": state.enabled(),
+                "paused": state.paused(),
+                "angle_deg": state.angle_deg(),
+                "devices": state.devices(),
+                "uptime_s": state.started().elapsed().as_secs_f64(),
+                "received": totals.received,
+                "emitted": totals.emitted,
+                "emit_failures": totals.emit_failures,
+                "idle_s": totals.idle.map(|idle| idle.as_secs_f64()),
+            })
+            .to_string()
+        }
+        _ => format!("error: unknown command '{command}'"),
+    }
+}
+
+/// Asks the instance listening on `path` for its status and prints it, for
+/// the `status` subcommand.
+pub fn print_status(path: &Path) -> std::io::Result<()> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unexpected reply: {e}"),
+        )
+    };
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+    writeln!(stream, "status")?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    let status: serde_json::Value = serde_json::from_str(&reply).map_err(|e| invalid(&e))?;
+    let number = |key: &str| status[key].as_f64().ok_or_else(|| invalid(&key));
+    let count = |key: &str| status[key].as_u64().ok_or_else(|| invalid(&key));
+
+    let state = match (status["enabled"].as_bool(), status["paused"].as_bool()) {
+        (Some(true), Some(false)) => "active",
+        (Some(true), Some(true)) => "paused",
+        (Some(false), _) => "off",
+        _ => return Err(invalid(&"enabled")),
+    };
+    println!("state:     {state}");
+    println!("angle:     {}", Degrees(number("angle_deg")?));
+    for device in status["devices"].as_array().into_iter().flatten() {
+        println!("device:    {}", device.as_str().unwrap_or_default());
+    }
+    let uptime = number("uptime_s")? as u64;
+    println!(
+        "uptime:    {}h {:02}m {:02}s",
+        uptime / 3600,
+        uptime / 60 % 60,
+        uptime % 60
+    );
+    println!(
+        "events:    {} received, {} emitted, {} emits failed",
+        count("received")?,
+        count("emitted")?,
+        count("emit_failures")?
+    );
+    match status["idle_s"].as_f64() {
+        Some(idle) => println!("last read: {idle:.1} s ago"),
+        None => println!("last read: never"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switching_to_a_profile_that_isnt_there_is_refused() {
+        let state = LiveState::new(0.0);
+        state.set_profiles(vec!["game".to_string()]);
+
+        assert_eq!(
+            execute("switch-profile gmae", &state),
+            "error: no profile named 'gmae'"
+        );
+        assert_eq!(state.profile(), None);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use nix::sys::signal::kill;
+use nix::unistd::{ForkResult, Pid, dup2, fork, setsid};
+
+pub const DEFAULT_PID_FILE: &str = "/run/sensor_alignment.pid";
+pub const DEFAULT_LOG_FILE: &str = "/var/log/sensor_alignment.log";
+
+/// PID file of the running daemon, removed when dropped.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Detaches from the terminal: forks, starts a new session and sends stdout
+/// and stderr to `log_file`. Only the child returns.
+///
+/// Must be called before any threads are started. Refuses to start if the
+/// PID file names a process that is still alive; a stale one is replaced.
+pub fn daemonize(pid_file: &Path, log_file: &Path) -> std::io::Result<PidFile> {
+    if let Some(pid) = running_pid(pid_file) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!(
+                "already running with pid {pid} according to {}",
+                pid_file.display()
+            ),
+        ));
+    }
+
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+    let null = File::open("/dev/null")?;
+
+    // SAFETY: no other threads exist yet, see above.
+    match unsafe { fork() }? {
+        ForkResult::Parent { .. } => std::process::exit(0),
+        ForkResult::Child => {}
+    }
+    setsid()?;
+    dup2(null.as_raw_fd(), 0)?;
+    dup2(log.as_raw_fd(), 1)?;
+    dup2(log.as_raw_fd(), 2)?;
+
+    let mut file = File::create(pid_file)?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(PidFile {
+        path: pid_file.to_path_buf(),
+    })
+}
+
+fn running_pid(pid_file: &Path) -> Option<Pid> {
+    let pid: i32 = std::fs::read_to_string(pid_file)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    let pid = Pid::from_raw(pid);
+    // Signal 0 only checks that the process exists.
+    kill(pid, None).is_ok().then_some(pid)
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use log::{info, warn};
+use sensor_alignment::Degrees;
+use sensor_alignment::state::LiveState;
+use zbus::object_server::SignalEmitter;
+
+use crate::signals;
+
+pub const BUS_NAME: &str = "org.sensor_alignment";
+pub const OBJECT_PATH: &str = "/org/sensor_alignment";
+
+// How often the angle is checked for changes made some other way, e.g. by a
+// hotkey or a signal, to announce them with `AngleChanged`.
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Which message bus to take the name on.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Bus {
+    Session,
+    System,
+}
+
+/// The `org.sensor_alignment.Control1` object, a D-Bus counterpart of the
+/// control socket.
+struct Control {
+    state: Arc<LiveState>,
+}
+
+#[zbus::interface(name = "org.sensor_alignment.Control1")]
+impl Control {
+    fn get_angle(&self) -> f64 {
+        self.state.angle_deg()
+    }
+
+    fn set_angle(&self, angle: f64) -> zbus::fdo::Result<()> {
+        if !angle.is_finite() {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "invalid angle {angle}"
+            )));
+        }
+        self.state.set_angle_deg(angle);
+        info!("Angle set to {} over D-Bus", Degrees(angle));
+        Ok(())
+    }
+
+    /// Switches rotation off or back on and returns whether it's on now.
+    fn toggle(&self) -> bool {
+        let enabled = self.state.toggle_enabled();
+        info!("Rotation {}", if enabled { "on" } else { "off" });
+        enabled
+    }
+
+    #[zbus(signal)]
+    async fn angle_changed(emitter: &SignalEmitter<'_>, angle: f64) -> zbus::Result<()>;
+}
+
+/// Takes the name on `bus` and serves the control object from a thread of its
+/// own for as long as the process runs.
+pub fn serve(bus: Bus, state: Arc<LiveState>) -> std::io::Result<()> {
+    let (ready, started) = mpsc::channel();
+    std::thread::spawn(move || {
+        // zbus starts threads of its own, which inherit this.
+        signals::block_on_current_thread();
+        let builder = match bus {
+            Bus::Session => zbus::blocking::connection::Builder::session(),
+            Bus::System => zbus::blocking::connection::Builder::system(),
+        };
+        let connection = builder
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| {
+                builder.serve_at(
+                    OBJECT_PATH,
+                    Control {
+                        state: Arc::clone(&state),
+                    },
+                )
+            })
+            .and_then(|builder| builder.build());
+        let connection = match connection {
+            Ok(connection) => {
+                let _ = ready.send(Ok(()));
+                connection
+            }
+            Err(e) => {
+                let _ = ready.send(Err(e));
+                return;
+            }
+        };
+        let control = match connection
+            .object_server()
+            .interface::<_, Control>(OBJECT_PATH)
+        {
+            Ok(control) => control,
+            Err(e) => {
+                warn!("D-Bus object went missing: {e}");
+                return;
+            }
+        };
+
+        let mut announced = state.angle_deg();
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let angle = state.angle_deg();
+            if angle != announced {
+                announced = angle;
+                let emitter = control.signal_emitter();
+                if let Err(e) = zbus::block_on(Control::angle_changed(emitter, angle)) {
+                    warn!("Sending AngleChanged failed: {e}");
+                }
+            }
+        }
+    });
+    match started.recv() {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(std::io::Error::other(format!("D-Bus: {e}"))),
+        Err(_) => Err(std::io::Error::other("D-Bus thread exited")),
+    }
+}
@@ -0,0 +1,503 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use evdev::{AbsoluteAxisCode, Device, KeyCode, RelativeAxisCode};
+pub use sensor_alignment::frame::{AxisMode, WheelMode};
+
+/// How the source device is picked. It's resolved to a device node again on
+/// every reconnect, since `/dev/input/eventN` numbers aren't stable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceSelector {
+    Path(PathBuf),
+    /// Case-insensitive substring of the device name.
+    Name(String),
+    /// Shell-style pattern matched against the whole device name or physical
+    /// path.
+    Glob(String),
+    /// The only connected device that looks like a mouse.
+    Auto,
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceSelector::Path(path) => write!(f, "{}", path.display()),
+            DeviceSelector::Name(name) => write!(f, "name matching \"{name}\""),
+            DeviceSelector::Glob(pattern) => write!(f, "device matching {pattern:?}"),
+            DeviceSelector::Auto => write!(f, "auto-detected mouse"),
+        }
+    }
+}
+
+impl DeviceSelector {
+    /// A device path as given, where `event7` and plain `7` are short for
+    /// `/dev/input/event7`.
+    pub fn path(text: &str) -> Self {
+        let number = text.strip_prefix("event").unwrap_or(text);
+        if !number.is_empty() && number.bytes().all(|byte| byte.is_ascii_digit()) {
+            DeviceSelector::Path(PathBuf::from(format!("/dev/input/event{number}")))
+        } else {
+            DeviceSelector::Path(text.into())
+        }
+    }
+
+    /// Finds the device node currently matching the selector. Devices named
+    /// `exclude_name` (our own virtual device) are never matched.
+    pub fn resolve(&self, exclude_name: &str) -> std::io::Result<PathBuf> {
+        match self {
+            DeviceSelector::Path(path) if !path.exists() => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no such device node",
+            )),
+            DeviceSelector::Path(path) => Ok(path.clone()),
+            DeviceSelector::Name(pattern) => find_by_name(pattern, exclude_name),
+            DeviceSelector::Glob(pattern) => find_by_glob(pattern, exclude_name),
+            DeviceSelector::Auto => find_mouse(exclude_name),
+        }
+    }
+}
+
+/// A source device together with the settings given for it alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceSpec {
+    pub selector: DeviceSelector,
+    /// Used instead of the global angle.
+    pub angle_deg: Option<f64>,
+}
+
+impl From<DeviceSelector> for DeviceSpec {
+    fn from(selector: DeviceSelector) -> Self {
+        DeviceSpec {
+            selector,
+            angle_deg: None,
+        }
+    }
+}
+
+impl std::str::FromStr for DeviceSpec {
+    type Err = String;
+
+    /// Parses a device path as taken by [`DeviceSelector::path`], optionally
+    /// followed by options such as `event7,angle=3.5`. The path may be
+    /// written as `path=event7` too.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts = text.split(',');
+        let first = parts.next().unwrap_or_default();
+        let path = first.strip_prefix("path=").unwrap_or(first);
+        if path.is_empty() {
+            return Err(format!("no device path in {text:?}"));
+        }
+        let mut spec = DeviceSpec::from(DeviceSelector::path(path));
+        for part in parts {
+            match part.split_once('=') {
+                Some(("angle", value)) => {
+                    let angle_deg = value
+                        .trim()
+                        .parse::<f64>()
+                        .map_err(|e| format!("angle {value:?}: {e}"))?;
+                    if !angle_deg.is_finite() {
+                        return Err(format!("angle must be a finite number, not {value}"));
+                    }
+                    spec.angle_deg = Some(angle_deg);
+                }
+                _ => {
+                    return Err(format!(
+                        "expected angle=DEGREES after the path, got {part:?}"
+                    ));
+                }
+            }
+        }
+        Ok(spec)
+    }
+}
+
+/// All input devices that can be opened, ordered by device node.
+pub fn enumerate() -> Vec<(PathBuf, Device)> {
+    let mut devices: Vec<_> = evdev::enumerate().collect();
+    devices.sort_by_key(|(path, _)| event_number(path));
+    devices
+}
+
+fn event_number(path: &std::path::Path) -> Option<u32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("event")?
+        .parse()
+        .ok()
+}
+
+fn find_by_name(pattern: &str, exclude_name: &str) -> std::io::Result<PathBuf> {
+    let pattern = pattern.to_lowercase();
+    pick_one(
+        &format!("input device name matches \"{pattern}\""),
+        find(exclude_name, |name, _| {
+            name.to_lowercase().contains(&pattern)
+        }),
+    )
+}
+
+fn find_by_glob(pattern: &str, exclude_name: &str) -> std::io::Result<PathBuf> {
+    let matches: Vec<(PathBuf, String)> = enumerate()
+        .into_iter()
+        .filter_map(|(path, device)| {
+            let name = device.name()?.to_string();
+            let phys = device.physical_path().unwrap_or_default();
+            (name != exclude_name && (glob_match(pattern, &name) || glob_match(pattern, phys)))
+                .then(|| (path, format!("{name}, {phys}")))
+        })
+        .collect();
+    pick_one(
+        &format!("input device name or physical path matches {pattern:?}"),
+        matches,
+    )
+}
+
+/// Whether `text` matches the shell-style `pattern` as a whole: `*` stands
+/// for any run of characters, `?` for any one, and `[...]` for one of those
+/// listed, with ranges like `a-z` and `!` or `^` in front to negate it.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Where to go on from after the last `*`, should what follows it fail to
+    // match: one character further into the text.
+    let mut backtrack = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+                continue;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some('[') => {
+                if let Some((matched, end)) = match_class(&pattern[p + 1..], text[t]) {
+                    if matched {
+                        p += end + 2;
+                        t += 1;
+                        continue;
+                    }
+                } else if text[t] == '[' {
+                    // An unclosed bracket is just a bracket.
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+            }
+            Some(&c) if c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match backtrack {
+            Some((star_p, star_t)) => {
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, star_t + 1));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `c` against the class starting right after a `[`, returning
+/// whether it matched and the index of the closing `]`, or `None` if there
+/// isn't one.
+fn match_class(class: &[char], c: char) -> Option<(bool, usize)> {
+    let negated = matches!(class.first(), Some('!' | '^'));
+    let start = usize::from(negated);
+    // A `]` right at the start is part of the class rather than its end.
+    let end = start + 1 + class.get(start + 1..)?.iter().position(|&c| c == ']')?;
+    let members = &class[start..end];
+    let mut matched = false;
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == '-' {
+            matched |= (members[i]..=members[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= members[i] == c;
+            i += 1;
+        }
+    }
+    Some((matched != negated, end))
+}
+
+fn find_mouse(exclude_name: &str) -> std::io::Result<PathBuf> {
+    pick_one(
+        "input device looks like a mouse",
+        find(exclude_name, |_, device| is_mouse(device)),
+    )
+}
+
+/// Relative X/Y motion and a left button.
+fn is_mouse(device: &Device) -> bool {
+    let has_motion = device.supported_relative_axes().is_some_and(|axes| {
+        axes.contains(RelativeAxisCode::REL_X) && axes.contains(RelativeAxisCode::REL_Y)
+    });
+    let has_button = device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(KeyCode::BTN_LEFT));
+    has_motion && has_button
+}
+
+fn find(
+    exclude_name: &str,
+    mut predicate: impl FnMut(&str, &Device) -> bool,
+) -> Vec<(PathBuf, String)> {
+    enumerate()
+        .into_iter()
+        .filter_map(|(path, device)| {
+            let name = device.name()?.to_string();
+            (name != exclude_name && predicate(&name, &device)).then_some((path, name))
+        })
+        .collect()
+}
+
+/// Returns the only match, or an `InvalidInput` error listing the candidates
+/// if there are several.
+fn pick_one(what: &str, mut matches: Vec<(PathBuf, String)>) -> std::io::Result<PathBuf> {
+    match matches.len() {
+        0 => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no {what}"),
+        )),
+        1 => Ok(matches.remove(0).0),
+        _ => {
+            let candidates: Vec<String> = matches
+                .iter()
+                .map(|(path, name)| format!("  {} ({name})", path.display()))
+                .collect();
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "more than one {what}, please pick one with --device-path, --device-glob or --device-name:\n{}",
+                    candidates.join("\n")
+                ),
+            ))
+        }
+    }
+}
+
+/// Whether the device reports pointer motion, relative or absolute.
+pub fn is_pointer(device: &Device) -> bool {
+    let has_rel = device.supported_relative_axes().is_some_and(|axes| {
+        axes.contains(RelativeAxisCode::REL_X) && axes.contains(RelativeAxisCode::REL_Y)
+    });
+    let has_abs = device.supported_absolute_axes().is_some_and(|axes| {
+        axes.contains(AbsoluteAxisCode::ABS_X) && axes.contains(AbsoluteAxisCode::ABS_Y)
+    });
+    has_rel || has_abs
+}
+
+/// Whether the device has the axes `mode` transforms, checked before it's
+/// grabbed: X and Y of the kind the mode keeps, or the wheels if only
+/// scrolling is rotated.
+pub fn has_transformed_axes(device: &Device, mode: AxisMode, wheels_only: bool) -> bool {
+    if wheels_only {
+        return device.supported_relative_axes().is_some_and(|axes| {
+            axes.contains(RelativeAxisCode::REL_WHEEL)
+                || axes.contains(RelativeAxisCode::REL_HWHEEL)
+                || axes.contains(RelativeAxisCode::REL_DIAL)
+        });
+    }
+    let has_rel = device.supported_relative_axes().is_some_and(|axes| {
+        axes.contains(RelativeAxisCode::REL_X) && axes.contains(RelativeAxisCode::REL_Y)
+    });
+    let has_abs = device.supported_absolute_axes().is_some_and(|axes| {
+        axes.contains(AbsoluteAxisCode::ABS_X) && axes.contains(AbsoluteAxisCode::ABS_Y)
+    });
+    match mode {
+        AxisMode::Rel => has_rel,
+        AxisMode::Abs => has_abs,
+        AxisMode::Auto => has_rel || has_abs,
+    }
+}
+
+/// Whether `code` is one of the wheels, in clicks or high-resolution
+/// steps.
+pub fn is_wheel(code: RelativeAxisCode) -> bool {
+    WheelMode::Off.dropped().contains(&code)
+}
+
+/// Counts per inch along X, if the device reports a resolution. Only
+/// absolute axes have one; mice don't say how many counts they make.
+pub fn reported_dpi(device: &Device) -> Option<f64> {
+    device
+        .get_absinfo()
+        .ok()?
+        .find(|(code, _)| *code == AbsoluteAxisCode::ABS_X)
+        .map(|(_, info)| info.resolution())
+        .filter(|&per_mm| per_mm > 0)
+        .map(|per_mm| per_mm as f64 * 25.4)
+}
+
+/// Whether `device` is a touchpad, whose wheel events are two fingers moving
+/// on the surface rather than a wheel of their own.
+pub fn is_touchpad(device: &Device) -> bool {
+    device.supported_keys().is_some_and(|keys| {
+        keys.contains(KeyCode::BTN_TOOL_FINGER) && !keys.contains(KeyCode::BTN_TOOL_PEN)
+    })
+}
+
+/// Prints a summary of every input device, marking the pointing devices.
+pub fn list() {
+    let devices = enumerate();
+    if devices.is_empty() {
+        println!("No input devices found, is /dev/input readable?");
+    }
+    for (path, device) in devices {
+        let marker = if is_pointer(&device) {
+            " [pointer]"
+        } else {
+            ""
+        };
+        println!(
+            "{}: {}{marker}",
+            path.display(),
+            device.name().unwrap_or("Unknown")
+        );
+        if let Some(phys) = device.physical_path() {
+            println!("  phys: {phys}");
+        }
+        println!(
+            "  relative axes: {}",
+            join_names(device.supported_relative_axes().into_iter().flatten())
+        );
+        println!(
+            "  absolute axes: {}",
+            join_names(device.supported_absolute_axes().into_iter().flatten())
+        );
+        let keys: Vec<KeyCode> = device.supported_keys().into_iter().flatten().collect();
+        match (keys.first(), keys.last()) {
+            (Some(first), Some(last)) => {
+                println!("  keys: {} ({first:?}..{last:?})", keys.len())
+            }
+            _ => println!("  keys: none"),
+        }
+    }
+}
+
+/// Prints everything `device` reports about itself, for bug reports: more
+/// than [`list`] shows, with every axis range and key, and the state of its
+/// switches and LEDs.
+pub fn inspect(path: &std::path::Path, device: &Device) {
+    println!("{}: {}", path.display(), device.name().unwrap_or("Unknown"));
+    println!("  phys: {}", device.physical_path().unwrap_or("none"));
+    println!("  uniq: {}", device.unique_name().unwrap_or("none"));
+    let id = device.input_id();
+    println!(
+        "  id: bus {} ({:?}), vendor {:04x}, product {:04x}, version {:04x}",
+        id.bus_type(),
+        id.bus_type(),
+        id.vendor(),
+        id.product(),
+        id.version()
+    );
+    let (major, minor, patch) = device.driver_version();
+    println!("  driver version: {major}.{minor}.{patch}");
+    println!(
+        "  event types: {}",
+        join_names(device.supported_events().iter())
+    );
+    println!("  properties: {}", join_names(device.properties().iter()));
+    println!(
+        "  relative axes: {}",
+        join_names(device.supported_relative_axes().into_iter().flatten())
+    );
+    match device.get_absinfo() {
+        Ok(axes) => {
+            let axes: Vec<_> = axes.collect();
+            if axes.is_empty() {
+                println!("  absolute axes: none");
+            } else {
+                println!("  absolute axes:");
+            }
+            for (code, info) in axes {
+                println!(
+                    "    {code:?}: value {}, min {}, max {}, fuzz {}, flat {}, resolution {}",
+                    info.value(),
+                    info.minimum(),
+                    info.maximum(),
+                    info.fuzz(),
+                    info.flat(),
+                    info.resolution()
+                );
+            }
+        }
+        Err(e) => println!("  absolute axes: unreadable ({e})"),
+    }
+    println!(
+        "  keys: {}",
+        join_names(device.supported_keys().into_iter().flatten())
+    );
+    if let Ok(pressed) = device.get_key_state() {
+        println!("  keys down: {}", join_names(pressed.iter()));
+    }
+    println!(
+        "  misc: {}",
+        join_names(device.misc_properties().into_iter().flatten())
+    );
+    if let Some(switches) = device.supported_switches() {
+        let on = device.get_switch_state().unwrap_or_default();
+        println!(
+            "  switches: {}",
+            join_states(switches.iter(), |code| on.contains(code))
+        );
+    }
+    if let Some(leds) = device.supported_leds() {
+        let lit = device.get_led_state().unwrap_or_default();
+        println!(
+            "  LEDs: {}",
+            join_states(leds.iter(), |code| lit.contains(code))
+        );
+    }
+    println!(
+        "  sounds: {}",
+        join_names(device.supported_sounds().into_iter().flatten())
+    );
+    println!(
+        "  force feedback: {}",
+        join_names(device.supported_ff().into_iter().flatten())
+    );
+    if let Some(repeat) = device.get_auto_repeat() {
+        println!(
+            "  autorepeat: after {} ms, every {} ms",
+            repeat.delay, repeat.period
+        );
+    }
+}
+
+/// Names with whether each is on, such as `SW_LID on`.
+fn join_states<T: fmt::Debug + Copy>(
+    codes: impl Iterator<Item = T>,
+    on: impl Fn(T) -> bool,
+) -> String {
+    let names: Vec<String> = codes
+        .map(|code| {
+            let state = if on(code) { "on" } else { "off" };
+            format!("{code:?} {state}")
+        })
+        .collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+fn join_names<T: fmt::Debug>(codes: impl Iterator<Item = T>) -> String {
+    let names: Vec<String> = codes.map(|code| format!("{code:?}")).collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
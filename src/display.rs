@@ -0,0 +1,144 @@
+use std::process::Command;
+
+use log::debug;
+
+/// The rotation of display `output` in degrees, counter-clockwise as
+/// `xrandr --rotate left` or a Wayland transform of 90 turns it, for
+/// --match-display.
+///
+/// The kernel doesn't say how the desktop has turned a screen, so this asks
+/// `wlr-randr` under a wlroots compositor and `xrandr` under X11, whichever
+/// the environment points at. `None` if neither works, the output isn't
+/// there, or it's flipped as well as rotated, which no angle can describe.
+pub fn rotation_deg(output: &str) -> Option<f64> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some()
+        && let Some(text) = run("wlr-randr", &[])
+        && let Some(rotation) = parse_wlr_randr(&text, output)
+    {
+        return Some(rotation);
+    }
+    if std::env::var_os("DISPLAY").is_some()
+        && let Some(text) = run("xrandr", &["--query"])
+    {
+        return parse_xrandr(&text, output);
+    }
+    None
+}
+
+/// The standard output of `program`, if it ran and succeeded.
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output() {
+        Ok(result) if result.status.success() => String::from_utf8(result.stdout).ok(),
+        Ok(result) => {
+            debug!("{program} failed: {}", result.status);
+            None
+        }
+        Err(e) => {
+            debug!("Running {program} failed: {e}");
+            None
+        }
+    }
+}
+
+/// Finds `output` in the output of `wlr-randr`, which gives each output an
+/// unindented line starting with its name, followed by indented properties
+/// such as `Transform: 90`.
+fn parse_wlr_randr(text: &str, output: &str) -> Option<f64> {
+    let mut lines = text.lines();
+    lines.find(|line| line.split_whitespace().next() == Some(output))?;
+    let transform = lines
+        .take_while(|line| line.starts_with(char::is_whitespace))
+        .find_map(|line| line.trim().strip_prefix("Transform:"))?;
+    match transform.trim() {
+        "normal" => Some(0.0),
+        "90" => Some(90.0),
+        "180" => Some(180.0),
+        "270" => Some(270.0),
+        _ => None,
+    }
+}
+
+/// Finds `output` in the output of `xrandr --query`, where a connected
+/// output's line reads like `HDMI-1 connected 1080x1920+0+0 left (normal
+/// left inverted right x axis y axis) ...`, the rotation coming before the
+/// parenthesis only if there is one. Reflections show up there as `X axis`
+/// or `Y axis`.
+fn parse_xrandr(text: &str, output: &str) -> Option<f64> {
+    let line = text
+        .lines()
+        .find(|line| line.split_whitespace().next() == Some(output))?;
+    let current = line.split('(').next()?;
+    if current.contains(" axis") {
+        return None;
+    }
+    let mut rotation = 0.0;
+    for word in current.split_whitespace() {
+        match word {
+            "left" => rotation = 90.0,
+            "inverted" => rotation = 180.0,
+            "right" => rotation = 270.0,
+            _ => {}
+        }
+    }
+    // A disconnected output, or one that's switched off, has no geometry.
+    current.contains('+').then_some(rotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XRANDR: &str = "\
+Screen 0: minimum 320 x 200, current 3000 x 1920, maximum 16384 x 16384
+eDP-1 connected primary 1920x1080+1080+0 (normal left inverted right x axis y axis) 344mm x 194mm
+   1920x1080     60.02*+
+HDMI-1 connected 1080x1920+0+0 left (normal left inverted right x axis y axis) 527mm x 296mm
+   1920x1080     60.00*+  50.00
+DP-1 connected 1920x1080+0+0 inverted X axis (normal left inverted right x axis y axis) 527mm x 296mm
+DP-2 disconnected (normal left inverted right x axis y axis)
+";
+
+    const WLR_RANDR: &str = "\
+eDP-1 \"Sharp Corporation 0x14F9 (eDP-1)\"
+  Physical size: 344x194 mm
+  Enabled: yes
+  Modes:
+    1920x1080 px, 60.020000 Hz (preferred, current)
+  Position: 1080,0
+  Transform: normal
+  Scale: 1.000000
+HDMI-A-1 \"Dell Inc. DELL U2415 (HDMI-A-1)\"
+  Enabled: yes
+  Position: 0,0
+  Transform: 270
+  Scale: 1.000000
+DP-1 \"Some Monitor (DP-1)\"
+  Enabled: yes
+  Transform: flipped-90
+";
+
+    #[test]
+    fn xrandr_gives_the_rotation_before_the_parenthesis() {
+        assert_eq!(parse_xrandr(XRANDR, "eDP-1"), Some(0.0));
+        assert_eq!(parse_xrandr(XRANDR, "HDMI-1"), Some(90.0));
+    }
+
+    #[test]
+    fn xrandr_has_no_angle_for_a_reflected_or_missing_output() {
+        assert_eq!(parse_xrandr(XRANDR, "DP-1"), None);
+        assert_eq!(parse_xrandr(XRANDR, "DP-2"), None);
+        assert_eq!(parse_xrandr(XRANDR, "HDMI-2"), None);
+    }
+
+    #[test]
+    fn wlr_randr_gives_the_transform_of_the_output() {
+        assert_eq!(parse_wlr_randr(WLR_RANDR, "eDP-1"), Some(0.0));
+        assert_eq!(parse_wlr_randr(WLR_RANDR, "HDMI-A-1"), Some(270.0));
+    }
+
+    #[test]
+    fn wlr_randr_has_no_angle_for_a_flipped_or_missing_output() {
+        assert_eq!(parse_wlr_randr(WLR_RANDR, "DP-1"), None);
+        assert_eq!(parse_wlr_randr(WLR_RANDR, "DP-2"), None);
+    }
+}
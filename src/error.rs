@@ -0,0 +1,246 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The uinput node the virtual device is created through.
+pub const UINPUT_PATH: &str = "/dev/uinput";
+
+/// What went wrong opening the devices or running the event loop, telling
+/// failures worth retrying apart from ones that won't go away by themselves.
+#[derive(Debug)]
+pub enum AlignError {
+    /// The input device couldn't be found or opened.
+    DeviceOpen {
+        device: String,
+        source: std::io::Error,
+    },
+    /// The input device is open, but grabbing it failed, usually because
+    /// something else already has.
+    Grab {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Building the virtual device through uinput failed.
+    VirtualDevice(std::io::Error),
+    /// A setting that can't be used as given.
+    Parse(String),
+    /// The input device has none of the axes that would be transformed,
+    /// such as a keyboard picked by mistake.
+    NotPointer { path: PathBuf, name: String },
+    /// Access to a device node was denied.
+    Permission {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Reading or writing events failed.
+    Io(std::io::Error),
+}
+
+/// Whether `e` is EACCES or EPERM, which std both calls `PermissionDenied`.
+fn is_permission_denied(e: &std::io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(nix::libc::EACCES | nix::libc::EPERM))
+        || e.kind() == std::io::ErrorKind::PermissionDenied
+}
+
+impl AlignError {
+    /// Sorts a failure to open `path` into a denied permission or the device
+    /// not being there (yet).
+    pub fn open(path: &Path, source: std::io::Error) -> AlignError {
+        if is_permission_denied(&source) {
+            AlignError::Permission {
+                path: path.to_path_buf(),
+                source,
+            }
+        } else {
+            AlignError::DeviceOpen {
+                device: path.display().to_string(),
+                source,
+            }
+        }
+    }
+
+    /// Sorts a failure building the virtual device the same way.
+    pub fn virtual_device(source: std::io::Error) -> AlignError {
+        if is_permission_denied(&source) {
+            AlignError::Permission {
+                path: UINPUT_PATH.into(),
+                source,
+            }
+        } else {
+            AlignError::VirtualDevice(source)
+        }
+    }
+
+    /// Whether trying again can't help.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            AlignError::Parse(_)
+            | AlignError::NotPointer { .. }
+            | AlignError::Permission { .. } => true,
+            // The path names something that isn't an input device, such as
+            // a typo pointing at the wrong node, which stays that way.
+            AlignError::DeviceOpen { source, .. } => matches!(
+                source.raw_os_error(),
+                Some(
+                    nix::libc::ENOTTY | nix::libc::EINVAL | nix::libc::ENOTDIR | nix::libc::EISDIR
+                )
+            ),
+            // Without the uinput module there's nothing to retry against, and
+            // capabilities uinput refused are refused again.
+            AlignError::VirtualDevice(source) => {
+                source.kind() == std::io::ErrorKind::NotFound
+                    || source.raw_os_error() == Some(nix::libc::EINVAL)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the input device just isn't there, e.g. while it's unplugged.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, AlignError::DeviceOpen { source, .. }
+            if source.kind() == std::io::ErrorKind::NotFound)
+    }
+
+    /// Whether reading the input device failed because its file descriptor
+    /// no longer refers to a working device, as when it's unplugged or after
+    /// a suspend and resume. Reading it again won't help, it has to be
+    /// opened anew.
+    pub fn is_stale_device(&self) -> bool {
+        matches!(self, AlignError::Io(source) if matches!(
+            source.raw_os_error(),
+            Some(nix::libc::ENODEV | nix::libc::EBADF | nix::libc::EIO | nix::libc::ENXIO)
+        ))
+    }
+
+    /// What the user could do about the error, if it's down to how the
+    /// system is set up.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            AlignError::Permission { path, .. } if path == Path::new(UINPUT_PATH) => Some(format!(
+                "Creating the virtual device needs write access to {UINPUT_PATH}. Give the \
+                 input group access with a udev rule such as KERNEL==\"uinput\", \
+                 GROUP=\"input\", MODE=\"0660\", or run as root."
+            )),
+            AlignError::Permission { path, .. } => Some(format!(
+                "Reading {} needs access to the input devices. Add yourself to the input \
+                 group with `sudo usermod -aG input $USER` and log in again, or run as root.",
+                path.display()
+            )),
+            AlignError::NotPointer { .. } => Some(
+                "Check the device with `sensor_alignment list`, or pass --force if it really is the one."
+                    .to_string(),
+            ),
+            AlignError::VirtualDevice(source) if source.kind() == std::io::ErrorKind::NotFound => {
+                Some(format!(
+                    "{UINPUT_PATH} is missing. Load the uinput module with `sudo modprobe uinput`."
+                ))
+            }
+            AlignError::DeviceOpen { .. } if self.is_fatal() => Some(
+                "That isn't an input device. Check the path with `sensor_alignment list`."
+                    .to_string(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The hint for `e`, if it came from an `AlignError`.
+    pub fn hint_for(e: &std::io::Error) -> Option<String> {
+        e.get_ref()?.downcast_ref::<AlignError>()?.hint()
+    }
+
+    fn io_kind(&self) -> std::io::ErrorKind {
+        match self {
+            AlignError::DeviceOpen { source, .. }
+            | AlignError::Grab { source, .. }
+            | AlignError::VirtualDevice(source)
+            | AlignError::Io(source) => source.kind(),
+            AlignError::Parse(_) | AlignError::NotPointer { .. } => {
+                std::io::ErrorKind::InvalidInput
+            }
+            AlignError::Permission { .. } => std::io::ErrorKind::PermissionDenied,
+        }
+    }
+}
+
+impl fmt::Display for AlignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlignError::DeviceOpen { device, source } => write!(f, "opening {device}: {source}"),
+            AlignError::Grab { path, source } => {
+                write!(f, "grabbing {}: {source}", path.display())
+            }
+            AlignError::VirtualDevice(source) => write!(f, "building the virtual device: {source}"),
+            AlignError::Parse(message) => f.write_str(message),
+            AlignError::NotPointer { path, name } => write!(
+                f,
+                "{} ({name}) has no motion axes to transform, refusing to grab it",
+                path.display()
+            ),
+            AlignError::Permission { path, source } => {
+                write!(f, "accessing {}: {source}", path.display())
+            }
+            AlignError::Io(source) => source.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AlignError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AlignError::DeviceOpen { source, .. }
+            | AlignError::Grab { source, .. }
+            | AlignError::VirtualDevice(source)
+            | AlignError::Permission { source, .. }
+            | AlignError::Io(source) => Some(source),
+            AlignError::Parse(_) | AlignError::NotPointer { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AlignError {
+    fn from(e: std::io::Error) -> Self {
+        AlignError::Io(e)
+    }
+}
+
+impl From<nix::errno::Errno> for AlignError {
+    fn from(e: nix::errno::Errno) -> Self {
+        AlignError::Io(e.into())
+    }
+}
+
+/// So that `main` and the subcommands can keep returning `io::Result`.
+impl From<AlignError> for std::io::Error {
+    fn from(e: AlignError) -> Self {
+        match e {
+            AlignError::Io(source) => source,
+            e => std::io::Error::new(e.io_kind(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_error(errno: i32) -> std::io::Error {
+        std::io::Error::from_raw_os_error(errno)
+    }
+
+    #[test]
+    fn only_errors_that_would_come_back_are_fatal() {
+        let path = Path::new("/dev/input/event3");
+        assert!(!AlignError::open(path, os_error(nix::libc::ENOENT)).is_fatal());
+        assert!(!AlignError::open(path, os_error(nix::libc::EBUSY)).is_fatal());
+        assert!(AlignError::open(path, os_error(nix::libc::ENOTTY)).is_fatal());
+        assert!(AlignError::open(path, os_error(nix::libc::EACCES)).is_fatal());
+        assert!(!AlignError::virtual_device(os_error(nix::libc::EBUSY)).is_fatal());
+        assert!(AlignError::virtual_device(os_error(nix::libc::EINVAL)).is_fatal());
+        assert!(AlignError::virtual_device(os_error(nix::libc::ENOENT)).is_fatal());
+        assert!(!AlignError::Io(os_error(nix::libc::ENODEV)).is_fatal());
+        assert!(
+            AlignError::open(path, os_error(nix::libc::ENOTTY))
+                .hint()
+                .is_some()
+        );
+    }
+}
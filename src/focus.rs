@@ -0,0 +1,171 @@
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, info};
+use sensor_alignment::state::LiveState;
+
+use crate::config::AppProfile;
+use crate::devices::glob_match;
+use crate::signals;
+
+/// How often the focused window is looked up. Each lookup runs a program,
+/// so this is slow enough not to cost anything noticeable and quick enough
+/// that the profile has switched by the time the window is used.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The window that has the focus, as far as the desktop says.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Window {
+    /// The X11 class, or the Wayland app ID.
+    pub class: String,
+    pub title: String,
+}
+
+/// The profile of the first rule matching `window`, if any does. A rule
+/// matches when each of the patterns it has matches.
+pub fn pick<'a>(rules: &'a [AppProfile], window: &Window) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|rule| {
+            rule.class
+                .as_deref()
+                .is_none_or(|pattern| glob_match(pattern, &window.class))
+                && rule
+                    .title
+                    .as_deref()
+                    .is_none_or(|pattern| glob_match(pattern, &window.title))
+        })
+        .map(|rule| rule.profile.as_str())
+}
+
+/// Looks up the focused window every [`POLL_INTERVAL`] on a thread of its
+/// own and switches to the profile of the first `[[app_profile]]` rule it
+/// matches, or back to `fallback` when none does, the way `switch-profile`
+/// on the control socket does. The event loop only notices once the config
+/// is reloaded, so lookups never hold up motion.
+///
+/// Only a change of the profile picked switches, so one chosen some other
+/// way in between stays until another window is focused.
+pub fn watch(
+    rules: Vec<AppProfile>,
+    command: Option<String>,
+    fallback: Option<String>,
+    state: Arc<LiveState>,
+) {
+    std::thread::spawn(move || {
+        signals::block_on_current_thread();
+        let mut picked = fallback.clone();
+        let mut focused = None;
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let Some(window) = focused_window(command.as_deref()) else {
+                continue;
+            };
+            if focused.as_ref() == Some(&window) {
+                continue;
+            }
+            let profile = pick(&rules, &window)
+                .map(str::to_string)
+                .or_else(|| fallback.clone());
+            if profile != picked {
+                info!(
+                    "{} ({}) has the focus, switching to profile {}",
+                    window.class,
+                    window.title,
+                    profile.as_deref().unwrap_or("default")
+                );
+                state.set_profile(profile.clone());
+                signals::request_reload();
+                picked = profile;
+            }
+            focused = Some(window);
+        }
+    });
+}
+
+/// The focused window, from `command` if one is given, which prints the
+/// class on its first line and the title on its second, or else from
+/// whichever of Hyprland, Sway and X11 the environment points at.
+fn focused_window(command: Option<&str>) -> Option<Window> {
+    if let Some(command) = command {
+        let text = run("sh", &["-c", command])?;
+        let mut lines = text.lines();
+        return Some(Window {
+            class: lines.next()?.to_string(),
+            title: lines.next().unwrap_or_default().to_string(),
+        });
+    }
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some()
+        && let Some(text) = run("hyprctl", &["activewindow", "-j"])
+    {
+        return parse_hyprctl(&text);
+    }
+    if std::env::var_os("SWAYSOCK").is_some()
+        && let Some(text) = run("swaymsg", &["-t", "get_tree"])
+    {
+        return parse_sway_tree(&text);
+    }
+    if std::env::var_os("DISPLAY").is_some()
+        && let Some(text) = run(
+            "xdotool",
+            &["getactivewindow", "getwindowclassname", "getwindowname"],
+        )
+    {
+        let mut lines = text.lines();
+        return Some(Window {
+            class: lines.next()?.to_string(),
+            title: lines.next().unwrap_or_default().to_string(),
+        });
+    }
+    None
+}
+
+/// The standard output of `program`, if it ran and succeeded.
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output() {
+        Ok(result) if result.status.success() => String::from_utf8(result.stdout).ok(),
+        Ok(result) => {
+            debug!("{program} failed: {}", result.status);
+            None
+        }
+        Err(e) => {
+            debug!("Running {program} failed: {e}");
+            None
+        }
+    }
+}
+
+/// Reads `hyprctl activewindow -j`, an object with `class` and `title`.
+fn parse_hyprctl(text: &str) -> Option<Window> {
+    let window: serde_json::Value = serde_json::from_str(text).ok()?;
+    Some(Window {
+        class: window["class"].as_str()?.to_string(),
+        title: window["title"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+/// Finds the focused node in the tree `swaymsg -t get_tree` prints. Wayland
+/// windows have an `app_id`, X11 ones running under Xwayland a class in
+/// `window_properties` instead.
+pub fn parse_sway_tree(text: &str) -> Option<Window> {
+    fn find(node: &serde_json::Value) -> Option<&serde_json::Value> {
+        if node["focused"].as_bool() == Some(true) {
+            return Some(node);
+        }
+        ["nodes", "floating_nodes"]
+            .iter()
+            .filter_map(|key| node[key].as_array())
+            .flatten()
+            .find_map(find)
+    }
+    let tree: serde_json::Value = serde_json::from_str(text).ok()?;
+    let node = find(&tree)?;
+    let class = node["app_id"]
+        .as_str()
+        .or_else(|| node["window_properties"]["class"].as_str())?;
+    Some(Window {
+        class: class.to_string(),
+        title: node["name"].as_str().unwrap_or_default().to_string(),
+    })
+}
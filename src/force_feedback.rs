@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use evdev::uinput::VirtualDevice;
+use evdev::{Device, EventSummary, FFEffect, FFEffectCode, FFEffectData, UInputCode};
+use log::{debug, warn};
+use nix::errno::Errno;
+
+/// The effects a game uploaded to the virtual device, each uploaded again to
+/// the real one, by the ID the virtual device gave it.
+///
+/// The kernel hands uploads, erasures and plays to whoever created the
+/// virtual device instead of carrying them out, so without this rumble
+/// would stop working as soon as the real device is grabbed.
+///
+/// What each effect was uploaded with is kept too, so that the effects can
+/// be uploaded again when the device is unplugged and comes back while the
+/// virtual device stays.
+#[derive(Default)]
+pub struct Relay {
+    effects: HashMap<i16, FFEffect>,
+    uploaded: HashMap<i16, FFEffectData>,
+}
+
+impl Relay {
+    /// Handles what the virtual device has ready: uploads and erasures are
+    /// repeated on `device` and answered with how that went, and plays,
+    /// stops, the gain and autocentering are passed on to it. Without a
+    /// device, as while it's unplugged, uploads fail with `ENODEV` so that
+    /// the game isn't left waiting for the answer.
+    pub fn handle(
+        &mut self,
+        virtual_device: &mut VirtualDevice,
+        mut device: Option<&mut Device>,
+    ) -> std::io::Result<()> {
+        let events: Vec<_> = match virtual_device.fetch_events() {
+            Ok(events) => events.collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for event in events {
+            match event.destructure() {
+                EventSummary::UInput(event, UInputCode::UI_FF_UPLOAD, _) => {
+                    let mut upload = virtual_device.process_ff_upload(event)?;
+                    let id = upload.effect_id();
+                    let data = upload.effect();
+                    let uploaded = match (device.as_deref_mut(), self.effects.get_mut(&id)) {
+                        (None, _) => Err(Errno::ENODEV.into()),
+                        (Some(_), Some(effect)) => effect.update(data),
+                        (Some(device), None) => device.upload_ff_effect(data).map(|effect| {
+                            self.effects.insert(id, effect);
+                        }),
+                    };
+                    match uploaded {
+                        Ok(()) => {
+                            self.uploaded.insert(id, data);
+                        }
+                        Err(e) => {
+                            warn!("Uploading force feedback effect {id} failed: {e}");
+                            upload.set_retval(-e.raw_os_error().unwrap_or(Errno::EIO as i32));
+                        }
+                    }
+                }
+                EventSummary::UInput(event, UInputCode::UI_FF_ERASE, _) => {
+                    let erase = virtual_device.process_ff_erase(event)?;
+                    // Dropping the effect erases it from the real device.
+                    let id = erase.effect_id() as i16;
+                    self.effects.remove(&id);
+                    self.uploaded.remove(&id);
+                }
+                EventSummary::ForceFeedback(_, code, value) => {
+                    if let Err(e) = self.pass_on(device.as_deref_mut(), code, value) {
+                        warn!("Passing on force feedback failed: {e}");
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Lets go of the effects on a device that was unplugged, along with the
+    /// handles to it they hold, but remembers them for [`Relay::attach`].
+    pub fn detach(&mut self) {
+        self.effects.clear();
+    }
+
+    /// Uploads the effects remembered to `device`, which came back after
+    /// being unplugged. An effect that fails to upload is forgotten, and
+    /// plays of it are ignored until the game uploads it again.
+    pub fn attach(&mut self, device: &mut Device) {
+        self.uploaded
+            .retain(|&id, data| match device.upload_ff_effect(*data) {
+                Ok(effect) => {
+                    self.effects.insert(id, effect);
+                    true
+                }
+                Err(e) => {
+                    warn!("Uploading force feedback effect {id} again failed: {e}");
+                    false
+                }
+            });
+    }
+
+    /// Plays or stops an effect on `device`, or sets its gain or
+    /// autocentering.
+    fn pass_on(
+        &mut self,
+        device: Option<&mut Device>,
+        code: FFEffectCode,
+        value: i32,
+    ) -> std::io::Result<()> {
+        let Some(device) = device else {
+            return Ok(());
+        };
+        let level = value.clamp(0, u16::MAX.into()) as u16;
+        match code {
+            FFEffectCode::FF_GAIN => device.set_ff_gain(level),
+            FFEffectCode::FF_AUTOCENTER => device.set_ff_autocenter(level),
+            FFEffectCode(id) => match self.effects.get_mut(&(id as i16)) {
+                Some(effect) if value > 0 => effect.play(value),
+                Some(effect) => effect.stop(),
+                None => {
+                    debug!("Force feedback effect {id} isn't uploaded");
+                    Ok(())
+                }
+            },
+        }
+    }
+}
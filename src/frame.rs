@@ -0,0 +1,3572 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use evdev::{
+    AbsInfo, AbsoluteAxisCode, Device, EventType, InputEvent, KeyCode, RelativeAxisCode,
+    SynchronizationCode,
+};
+use log::{debug, info, trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::state::LiveState;
+use crate::{
+    AngleCurve, Carry, Degrees, Matrix, Pipeline, PointerOptions, ResponseCurve, Transform,
+    rotation_weight,
+};
+
+/// Where a [`Frame`] sends the frames it assembled, and what it tells about
+/// them along the way. Only [`emit`](FrameSink::emit) has to do anything.
+pub trait FrameSink {
+    /// Writes one frame of events, which the sink ends with a `SYN_REPORT`
+    /// of its own.
+    fn emit(&mut self, events: &[InputEvent]) -> std::io::Result<()>;
+
+    /// Notes an event that was read and, for motion, what it was
+    /// transformed to.
+    fn trace(&mut self, _raw: &InputEvent, _transformed: Option<i32>) {}
+
+    /// Notes a frame of pointer motion as it went through. `frame` is only
+    /// called if the record is kept.
+    fn record(&mut self, _frame: impl FnOnce() -> FrameRecord) {}
+
+    /// Notes that a frame of pointer motion went through the transform.
+    fn rotated(&mut self) {}
+}
+
+/// One frame of pointer motion as it went through, a line of a dump: when
+/// its `SYN_REPORT` came in seconds since the Unix epoch, the motion read,
+/// what was emitted for it, `null` if a deadzone swallowed it or it was
+/// held back, and the angle it was rotated by. Motion smoothing still owed
+/// comes out in frames of its own, with none read.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameRecord {
+    pub time: f64,
+    pub raw: [i32; 2],
+    pub out: Option<[i32; 2]>,
+    pub angle_deg: f64,
+}
+
+impl FrameRecord {
+    pub fn new(at: SystemTime, raw: (i32, i32), out: Option<(i32, i32)>, angle_deg: f64) -> Self {
+        FrameRecord {
+            time: at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            raw: [raw.0, raw.1],
+            out: out.map(|(x, y)| [x, y]),
+            angle_deg,
+        }
+    }
+}
+
+/// Whether events of this type can be declared on a virtual device and so
+/// be forwarded to it.
+pub fn can_forward(event_type: EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::SYNCHRONIZATION
+            | EventType::KEY
+            | EventType::RELATIVE
+            | EventType::ABSOLUTE
+            | EventType::MISC
+            | EventType::SWITCH
+    )
+}
+
+/// Which motion of a device reporting both relative and absolute pointer
+/// axes is used, such as a touchpad that also emulates a mouse. The other
+/// kind is dropped and not declared on the virtual device, so that the
+/// compositor doesn't see a pointer that's two things at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AxisMode {
+    Rel,
+    Abs,
+    /// Absolute for touch surfaces and pens, relative for anything else.
+    #[default]
+    Auto,
+}
+
+impl AxisMode {
+    /// The mode `device` is used in, `None` if it reports only one kind of
+    /// motion, which is then kept whatever the mode: merging a mouse with a
+    /// tablet under `rel` leaves the tablet alone.
+    pub fn for_device(self, device: &Device) -> Option<AxisMode> {
+        let has_rel = device.supported_relative_axes().is_some_and(|axes| {
+            axes.contains(RelativeAxisCode::REL_X) && axes.contains(RelativeAxisCode::REL_Y)
+        });
+        let has_abs = device.supported_absolute_axes().is_some_and(|axes| {
+            axes.contains(AbsoluteAxisCode::ABS_X) && axes.contains(AbsoluteAxisCode::ABS_Y)
+        });
+        if !(has_rel && has_abs) {
+            return None;
+        }
+        match self {
+            AxisMode::Auto => {
+                let touch = device.supported_keys().is_some_and(|keys| {
+                    [
+                        KeyCode::BTN_TOUCH,
+                        KeyCode::BTN_TOOL_FINGER,
+                        KeyCode::BTN_TOOL_PEN,
+                    ]
+                    .into_iter()
+                    .any(|key| keys.contains(key))
+                });
+                Some(if touch { AxisMode::Abs } else { AxisMode::Rel })
+            }
+            mode => Some(mode),
+        }
+    }
+}
+
+impl std::str::FromStr for AxisMode {
+    type Err = String;
+
+    /// Parses `rel`, `abs` or `auto`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "rel" => Ok(AxisMode::Rel),
+            "abs" => Ok(AxisMode::Abs),
+            "auto" => Ok(AxisMode::Auto),
+            _ => Err(format!("expected rel, abs or auto, got {text:?}")),
+        }
+    }
+}
+
+/// Which representation of the wheels of a device reporting both clicks
+/// (`REL_WHEEL`) and high-resolution steps (`REL_WHEEL_HI_RES`) is passed
+/// on. They describe the same turn of the wheel, so a consumer that reads
+/// both scrolls twice as far. `Off` passes on neither, for --no-wheel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WheelMode {
+    LowRes,
+    HiRes,
+    #[default]
+    Both,
+    Off,
+}
+
+impl WheelMode {
+    /// The mode `device` is used in, `None` if it has only one kind of wheel
+    /// events, which are then kept whatever the mode but `Off`.
+    pub fn for_device(self, device: &Device) -> Option<WheelMode> {
+        if self == WheelMode::Off {
+            return Some(self);
+        }
+        let axes = device.supported_relative_axes()?;
+        let has_low = axes.contains(RelativeAxisCode::REL_WHEEL)
+            || axes.contains(RelativeAxisCode::REL_HWHEEL);
+        let has_high = axes.contains(RelativeAxisCode::REL_WHEEL_HI_RES)
+            || axes.contains(RelativeAxisCode::REL_HWHEEL_HI_RES);
+        (has_low && has_high && self != WheelMode::Both).then_some(self)
+    }
+
+    /// The wheel axes left out.
+    pub fn dropped(self) -> &'static [RelativeAxisCode] {
+        match self {
+            WheelMode::LowRes => &[
+                RelativeAxisCode::REL_WHEEL_HI_RES,
+                RelativeAxisCode::REL_HWHEEL_HI_RES,
+            ],
+            WheelMode::HiRes => &[RelativeAxisCode::REL_WHEEL, RelativeAxisCode::REL_HWHEEL],
+            WheelMode::Both => &[],
+            WheelMode::Off => &[
+                RelativeAxisCode::REL_WHEEL,
+                RelativeAxisCode::REL_HWHEEL,
+                RelativeAxisCode::REL_WHEEL_HI_RES,
+                RelativeAxisCode::REL_HWHEEL_HI_RES,
+            ],
+        }
+    }
+}
+
+impl std::str::FromStr for WheelMode {
+    type Err = String;
+
+    /// Parses `lowres`, `hires`, `both` or `off`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "lowres" => Ok(WheelMode::LowRes),
+            "hires" => Ok(WheelMode::HiRes),
+            "both" => Ok(WheelMode::Both),
+            "off" => Ok(WheelMode::Off),
+            _ => Err(format!("expected lowres, hires, both or off, got {text:?}")),
+        }
+    }
+}
+
+/// Motion accumulated on a pair of relative axes between two `SYN_REPORT`s,
+/// together with the sub-count remainder carried over from earlier frames.
+#[derive(Default)]
+pub struct AxisPair {
+    x: i32,
+    y: i32,
+    carry: Carry,
+}
+
+impl AxisPair {
+    fn is_empty(&self) -> bool {
+        self.x == 0 && self.y == 0
+    }
+
+    /// Rotates the accumulated motion, keeps the rounding remainder and
+    /// clears the accumulator for the next frame.
+    fn rotate(&mut self, matrix: &Matrix) -> (i32, i32) {
+        let motion = (self.x as f64, self.y as f64);
+        self.x = 0;
+        self.y = 0;
+        self.carry.round(matrix.apply(motion))
+    }
+}
+
+/// Pointer motion accumulated between two `SYN_REPORT`s.
+#[derive(Default, PartialEq)]
+struct Pointer {
+    dx: i32,
+    dy: i32,
+}
+
+/// Where in a frame's events each kind of transformed motion goes: where
+/// the first event it was collected from came, so that it keeps its place
+/// among the events forwarded as is.
+#[derive(Default)]
+struct Slots {
+    pointer: Option<usize>,
+    scroll: Option<usize>,
+    scroll_hi_res: Option<usize>,
+    position: Option<usize>,
+    tilt: Option<usize>,
+}
+
+impl Slots {
+    /// The slot of the absolute plane `code` belongs to.
+    fn abs(&mut self, code: AbsoluteAxisCode) -> &mut Option<usize> {
+        match code {
+            AbsoluteAxisCode::ABS_TILT_X | AbsoluteAxisCode::ABS_TILT_Y => &mut self.tilt,
+            _ => &mut self.position,
+        }
+    }
+}
+
+/// Range of one absolute axis, used to rotate positions around the centre of
+/// the surface rather than around its origin corner.
+struct AbsRange {
+    min: i32,
+    max: i32,
+    center: f64,
+    half: f64,
+}
+
+impl AbsRange {
+    fn new(info: &AbsInfo) -> Self {
+        let (min, max) = (info.minimum(), info.maximum());
+        AbsRange {
+            min,
+            max,
+            center: (min as f64 + max as f64) / 2.0,
+            half: (max as f64 - min as f64) / 2.0,
+        }
+    }
+
+    /// Maps a raw value into `[-1, 1]`, with the centre of the range at 0.
+    fn normalize(&self, value: i32) -> f64 {
+        if self.half == 0.0 {
+            0.0
+        } else {
+            (value as f64 - self.center) / self.half
+        }
+    }
+
+    /// Maps a normalized value back into the raw range, clamping positions
+    /// that were rotated past the edge of the surface.
+    fn denormalize(&self, value: f64) -> i32 {
+        ((self.center + value * self.half).round() as i32).clamp(self.min, self.max)
+    }
+}
+
+/// Last known values of a pair of absolute axes of the source device, the
+/// `ABS_X`/`ABS_Y` position or the `ABS_TILT_X`/`ABS_TILT_Y` of a pen.
+struct AbsPlane {
+    range_x: AbsRange,
+    range_y: AbsRange,
+    x: i32,
+    y: i32,
+    changed: bool,
+}
+
+impl AbsPlane {
+    fn from_device(
+        device: &Device,
+        code_x: AbsoluteAxisCode,
+        code_y: AbsoluteAxisCode,
+    ) -> Option<Self> {
+        AbsPlane::from_absinfo(device.get_absinfo().ok()?, code_x, code_y)
+    }
+
+    fn from_absinfo(
+        absinfo: impl IntoIterator<Item = (AbsoluteAxisCode, AbsInfo)>,
+        code_x: AbsoluteAxisCode,
+        code_y: AbsoluteAxisCode,
+    ) -> Option<Self> {
+        let mut info_x = None;
+        let mut info_y = None;
+        for (code, info) in absinfo {
+            if code == code_x {
+                info_x = Some(info);
+            } else if code == code_y {
+                info_y = Some(info);
+            }
+        }
+        let (info_x, info_y) = (info_x?, info_y?);
+        Some(AbsPlane {
+            range_x: AbsRange::new(&info_x),
+            range_y: AbsRange::new(&info_y),
+            x: info_x.value(),
+            y: info_y.value(),
+            changed: false,
+        })
+    }
+
+    /// Rotates the current position around the centre of the surface and
+    /// moves it by `offset`.
+    fn rotate(&mut self, matrix: &Matrix, offset: (f64, f64)) -> (i32, i32) {
+        self.changed = false;
+        rotate_position(
+            &self.range_x,
+            &self.range_y,
+            (self.x, self.y),
+            matrix,
+            offset,
+        )
+    }
+
+    /// Rotates the current values as a direction rather than a position:
+    /// around 0, in the axes' own units, which for tilt are degrees on both.
+    fn rotate_direction(&mut self, matrix: &Matrix) -> (i32, i32) {
+        self.changed = false;
+        let (x, y) = matrix.apply((self.x as f64, self.y as f64));
+        (
+            (x.round() as i32).clamp(self.range_x.min, self.range_x.max),
+            (y.round() as i32).clamp(self.range_y.min, self.range_y.max),
+        )
+    }
+}
+
+/// Rotates `position` around the centre of the surface, then moves it by
+/// `offset`, a share of the width and of the height. Both axes are
+/// normalized first so that corners map onto corners at right angles even
+/// when the surface isn't square.
+fn rotate_position(
+    range_x: &AbsRange,
+    range_y: &AbsRange,
+    (x, y): (i32, i32),
+    matrix: &Matrix,
+    (offset_x, offset_y): (f64, f64),
+) -> (i32, i32) {
+    let (x, y) = matrix.apply((range_x.normalize(x), range_y.normalize(y)));
+    // Normalized, the whole range is 2 wide.
+    (
+        range_x.denormalize(x + 2.0 * offset_x),
+        range_y.denormalize(y + 2.0 * offset_y),
+    )
+}
+
+/// `ABS_MT_TOOL_TYPE` of a contact the touchpad itself takes for a palm.
+const MT_TOOL_PALM: i32 = 2;
+
+/// Most counts a relative axis adds up to in one frame, either way. No
+/// mouse moves anywhere near this far between two reports; a driver gone
+/// wrong could, and would otherwise overflow the sum.
+const MAX_FRAME_DELTA: i32 = 1 << 20;
+
+/// Adds `value` to what the axis `code` moved by in this frame, stopping at
+/// `MAX_FRAME_DELTA` rather than overflowing. Reaching it is logged once
+/// per frame, as the sum stays there until the frame is flushed.
+fn accumulate(sum: &mut i32, value: i64, code: RelativeAxisCode) {
+    let total = i64::from(*sum) + value;
+    let capped = total.clamp(-i64::from(MAX_FRAME_DELTA), i64::from(MAX_FRAME_DELTA));
+    if capped != total && i64::from(*sum) != capped {
+        warn!("{code:?} moved by more than {MAX_FRAME_DELTA} counts in one frame, capping it");
+    }
+    *sum = capped as i32;
+}
+
+/// Last known position of one multitouch contact.
+#[derive(Clone, Default)]
+struct Contact {
+    x: i32,
+    y: i32,
+    changed: bool,
+    // The last size, pressure and tool reported in the slot, which the
+    // kernel only repeats when they change.
+    major: i32,
+    pressure: i32,
+    tool: i32,
+    // Whether the contact in the slot was taken for a palm and is ignored
+    // until it's lifted.
+    palm: bool,
+    // The slot's other events of the current frame, held back with palm
+    // rejection on until it's known whose they are.
+    held: Vec<InputEvent>,
+}
+
+/// Ignoring touchpad contacts that are a resting palm rather than a finger.
+/// Sizes and pressures are shares of the range the touchpad reports for
+/// them, so that they mean the same on any touchpad.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PalmOptions {
+    /// Share of the `ABS_MT_TOUCH_MAJOR` range above which a contact is a
+    /// palm, 1.0 for never.
+    pub size: f64,
+    /// Share of the `ABS_MT_PRESSURE` range above which a contact is a palm.
+    pub pressure: f64,
+    /// Share of the width on the left and on the right where a contact that
+    /// comes down there is a palm, 0.0 for nowhere.
+    pub edge: f64,
+}
+
+impl Default for PalmOptions {
+    fn default() -> Self {
+        PalmOptions {
+            size: 0.5,
+            pressure: 0.8,
+            edge: 0.05,
+        }
+    }
+}
+
+/// Contacts of a touchpad speaking the slotted multitouch protocol (type B).
+/// Each contact's position is rotated like the `ABS_X`/`ABS_Y` one, right
+/// before the events of the next slot or the end of the frame, so that it
+/// stays in the slot it was reported for.
+struct Touches {
+    range_x: AbsRange,
+    range_y: AbsRange,
+    range_major: Option<AbsRange>,
+    range_pressure: Option<AbsRange>,
+    palm: Option<PalmOptions>,
+    slot: usize,
+    contacts: Vec<Contact>,
+}
+
+impl Touches {
+    fn from_device(device: &Device) -> Option<Self> {
+        Touches::from_absinfo(device.get_absinfo().ok()?)
+    }
+
+    fn from_absinfo(
+        absinfo: impl IntoIterator<Item = (AbsoluteAxisCode, AbsInfo)>,
+    ) -> Option<Self> {
+        let mut info_slot = None;
+        let mut info_x = None;
+        let mut info_y = None;
+        let mut range_major = None;
+        let mut range_pressure = None;
+        for (code, info) in absinfo {
+            match code {
+                AbsoluteAxisCode::ABS_MT_SLOT => info_slot = Some(info),
+                AbsoluteAxisCode::ABS_MT_POSITION_X => info_x = Some(info),
+                AbsoluteAxisCode::ABS_MT_POSITION_Y => info_y = Some(info),
+                AbsoluteAxisCode::ABS_MT_TOUCH_MAJOR => range_major = Some(AbsRange::new(&info)),
+                AbsoluteAxisCode::ABS_MT_PRESSURE => range_pressure = Some(AbsRange::new(&info)),
+                _ => {}
+            }
+        }
+        let (info_slot, info_x, info_y) = (info_slot?, info_x?, info_y?);
+        Some(Touches {
+            range_x: AbsRange::new(&info_x),
+            range_y: AbsRange::new(&info_y),
+            range_major,
+            range_pressure,
+            palm: None,
+            slot: info_slot.value().max(0) as usize,
+            contacts: vec![Contact::default(); info_slot.maximum().max(0) as usize + 1],
+        })
+    }
+
+    fn select(&mut self, slot: i32) {
+        self.slot = slot.max(0) as usize;
+        if self.slot >= self.contacts.len() {
+            self.contacts.resize(self.slot + 1, Contact::default());
+        }
+    }
+
+    fn contact(&mut self) -> &mut Contact {
+        // `select` makes sure the slot exists, but the device's own
+        // starting slot may be out of range.
+        self.select(self.slot as i32);
+        &mut self.contacts[self.slot]
+    }
+
+    fn changed(&self) -> bool {
+        self.contacts
+            .get(self.slot)
+            .is_some_and(|contact| contact.changed || !contact.held.is_empty())
+    }
+
+    /// Whether the current contact is too big or pressed too hard to be a
+    /// finger, or the touchpad says it's a palm.
+    fn is_palm(&self, options: &PalmOptions) -> bool {
+        let contact = &self.contacts[self.slot];
+        let above = |range: &Option<AbsRange>, value: i32, share: f64| {
+            range.as_ref().is_some_and(|range| {
+                share < 1.0 && range.half > 0.0 && (range.normalize(value) + 1.0) / 2.0 > share
+            })
+        };
+        contact.tool == MT_TOOL_PALM
+            || above(&self.range_major, contact.major, options.size)
+            || above(&self.range_pressure, contact.pressure, options.pressure)
+    }
+
+    /// Holds back an event of the current contact for palm rejection, noting
+    /// what it says about the contact.
+    fn hold(&mut self, event: InputEvent) {
+        let contact = self.contact();
+        match AbsoluteAxisCode(event.code()) {
+            AbsoluteAxisCode::ABS_MT_TOUCH_MAJOR => contact.major = event.value(),
+            AbsoluteAxisCode::ABS_MT_PRESSURE => contact.pressure = event.value(),
+            AbsoluteAxisCode::ABS_MT_TOOL_TYPE => contact.tool = event.value(),
+            _ => {}
+        }
+        contact.held.push(event);
+    }
+
+    /// Decides whether the current contact is a palm, returning the events
+    /// held back for it that go through and whether its position does.
+    ///
+    /// A contact is judged when it comes down, the edge counting only then,
+    /// and stays a palm until it's lifted; the lift is dropped like the rest,
+    /// the virtual device never having seen it come down. A finger that
+    /// spreads out into a palm is lifted there and then.
+    fn settle_palm(&mut self, options: &PalmOptions, at: &InputEvent) -> (Vec<InputEvent>, bool) {
+        let palm = self.is_palm(options);
+        let at_edge = self.at_edge(options);
+        let contact = &mut self.contacts[self.slot];
+        let held = std::mem::take(&mut contact.held);
+        let tracking_id = held
+            .iter()
+            .rev()
+            .find(|event| event.code() == AbsoluteAxisCode::ABS_MT_TRACKING_ID.0)
+            .map(InputEvent::value);
+        match tracking_id {
+            Some(-1) if contact.palm => {
+                contact.palm = false;
+                (Vec::new(), false)
+            }
+            Some(-1) => (held, true),
+            Some(_) => {
+                contact.palm = palm || at_edge;
+                if contact.palm {
+                    trace!("Ignoring a palm in slot {}", self.slot);
+                    (Vec::new(), false)
+                } else {
+                    (held, true)
+                }
+            }
+            None if contact.palm => (Vec::new(), false),
+            None if palm => {
+                contact.palm = true;
+                trace!("The contact in slot {} turned into a palm", self.slot);
+                let lift = abs_event(at, AbsoluteAxisCode::ABS_MT_TRACKING_ID, -1);
+                (vec![lift], false)
+            }
+            None => (held, true),
+        }
+    }
+
+    /// Whether the current contact is in the strip along the left or right
+    /// edge where palms rest.
+    fn at_edge(&self, options: &PalmOptions) -> bool {
+        let contact = &self.contacts[self.slot];
+        options.edge > 0.0 && self.range_x.normalize(contact.x).abs() > 1.0 - 2.0 * options.edge
+    }
+
+    /// Forgets the held back events of a frame that won't be emitted.
+    fn discard(&mut self) {
+        for contact in &mut self.contacts {
+            contact.changed = false;
+            contact.held.clear();
+        }
+    }
+
+    /// The current contact's position and where it's rotated and moved to.
+    fn rotate(&mut self, matrix: &Matrix, offset: (f64, f64)) -> ((i32, i32), (i32, i32)) {
+        let (range_x, range_y) = (&self.range_x, &self.range_y);
+        let contact = &mut self.contacts[self.slot];
+        contact.changed = false;
+        let position = (contact.x, contact.y);
+        (
+            position,
+            rotate_position(range_x, range_y, position, matrix, offset),
+        )
+    }
+}
+
+/// An event stamped with the time of `at`, the event it was derived from, so
+/// that the transformed frame keeps the timing of the original. Note that the
+/// kernel restamps whatever is written to uinput, so only the JSON trace and
+/// the dry run show this.
+fn event_at(at: &InputEvent, event_type: EventType, code: u16, value: i32) -> InputEvent {
+    let mut raw: nix::libc::input_event = (*at).into();
+    raw.type_ = event_type.0;
+    raw.code = code;
+    raw.value = value;
+    raw.into()
+}
+
+fn abs_event(at: &InputEvent, code: AbsoluteAxisCode, value: i32) -> InputEvent {
+    event_at(at, EventType::ABSOLUTE, code.0, value)
+}
+
+fn rel_event(at: &InputEvent, code: RelativeAxisCode, value: i32) -> InputEvent {
+    event_at(at, EventType::RELATIVE, code.0, value)
+}
+
+/// Buttons and keys that control the daemon instead of being forwarded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hotkeys {
+    /// Pressing this switches to the next angle in `presets`.
+    pub cycle_button: Option<KeyCode>,
+    pub presets: Vec<f64>,
+    /// Pressing this switches between transformed and raw motion.
+    pub toggle_key: Option<KeyCode>,
+    /// Pressing this sets the angle back to 0°.
+    pub reset_key: Option<KeyCode>,
+    /// Raw pointer motion is passed through while this is held.
+    pub pause_key: Option<KeyCode>,
+    /// Whether the pause key still reaches the virtual device.
+    pub forward_pause_key: bool,
+    /// Pointer motion is only rotated while this is held, the other way
+    /// round from `pause_key`.
+    pub hold_button: Option<KeyCode>,
+    /// Whether the hold button still reaches the virtual device.
+    pub forward_hold_button: bool,
+    /// Keys pressed together to switch to an angle.
+    pub chords: Vec<Chord>,
+}
+
+/// Keys that switch to an angle when they're all held down, for
+/// `[[chord]]`. The chord happens when the last of them goes down, in any
+/// order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chord {
+    pub keys: Vec<KeyCode>,
+    pub angle_deg: f64,
+    /// Whether its keys reach the virtual device as they go down, for keys
+    /// that are used on their own too, such as Ctrl. Only the key that
+    /// completes the chord is kept back then. Otherwise each is held back
+    /// while it could still become part of the chord, and sent late if it
+    /// doesn't.
+    pub passthrough: bool,
+}
+
+/// Where the chords stand on one source.
+#[derive(Debug, Default)]
+struct Chording {
+    // Keys down right now, in the order they went down.
+    down: Vec<KeyCode>,
+    // Presses kept back while they could still become a chord.
+    held_back: Vec<InputEvent>,
+    // Keys of a chord that happened, whose repeats and releases are dropped
+    // since their presses never went out.
+    swallowed: Vec<KeyCode>,
+}
+
+impl Hotkeys {
+    /// Acts on `event` if it's one of the hotkeys and says whether it was.
+    /// Releases and repeats are swallowed too, so the virtual device never
+    /// sees half a click.
+    fn handle(&self, event: &InputEvent, state: &LiveState) -> bool {
+        let code = KeyCode(event.code());
+        let pressed = event.value() == 1;
+        if self.pause_key == Some(code) {
+            // Repeats leave it held.
+            match event.value() {
+                0 => state.set_paused(false),
+                1 => state.set_paused(true),
+                _ => {}
+            }
+            return !self.forward_pause_key;
+        }
+        if self.hold_button == Some(code) {
+            match event.value() {
+                0 => state.set_paused(true),
+                1 => state.set_paused(false),
+                _ => {}
+            }
+            return !self.forward_hold_button;
+        }
+        if self.reset_key == Some(code) {
+            if pressed {
+                state.set_angle_deg(0.0);
+                info!("Angle reset to {}", Degrees(0.0));
+            }
+            return true;
+        }
+        if self.toggle_key == Some(code) {
+            if pressed {
+                let enabled = state.toggle_enabled();
+                info!("Rotation {}", if enabled { "on" } else { "off" });
+            }
+            return true;
+        }
+        if self.cycle_button != Some(code) || self.presets.is_empty() {
+            return false;
+        }
+        if pressed {
+            // The one after the active preset, or the first if the angle isn't
+            // one of them.
+            let current = state.angle_deg();
+            let next = self
+                .presets
+                .iter()
+                .position(|&preset| preset == current)
+                .map_or(0, |index| (index + 1) % self.presets.len());
+            state.set_angle_deg(self.presets[next]);
+            info!("Angle changed to {}", Degrees(state.angle_deg()));
+        }
+        true
+    }
+}
+
+/// Wheel clicks coming closer together than this get accelerated, the more
+/// the closer they are. A steady turn of the wheel clicks every 100 ms or
+/// more, a flick every 10 to 30.
+pub const FAST_SCROLL_WINDOW: Duration = Duration::from_millis(100);
+
+/// What happens to scroll wheel motion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollOptions {
+    /// Rotate wheel motion together with pointer motion.
+    pub rotate: bool,
+    /// Rotate wheel motion by this angle instead, for an encoder that sits
+    /// differently from the sensor. It's not changed at runtime with the
+    /// pointer angle.
+    pub angle_deg: Option<f64>,
+    /// Negate wheel motion, after rotating it if that's on too.
+    pub invert: bool,
+    /// Largest gain for fast scrolling, given to wheel clicks that come right
+    /// after each other and falling to none at `FAST_SCROLL_WINDOW` apart.
+    /// 1.0 leaves scrolling alone.
+    pub accel: f64,
+    /// Which wheel `REL_DIAL` turns, if any.
+    pub dial: DialMode,
+}
+
+impl Default for ScrollOptions {
+    fn default() -> Self {
+        ScrollOptions {
+            rotate: false,
+            angle_deg: None,
+            invert: false,
+            accel: 1.0,
+            dial: DialMode::default(),
+        }
+    }
+}
+
+impl ScrollOptions {
+    /// Whether wheel events have to be collected rather than forwarded.
+    fn transforms(&self) -> bool {
+        self.rotate || self.invert || self.accel != 1.0 || self.dial != DialMode::Pass
+    }
+}
+
+/// What becomes of `REL_DIAL`, which jog dials and some mice report. Passed
+/// through, it's left alone like any other axis; taken as one of the wheels,
+/// it's added to that wheel's clicks and rotated, inverted and accelerated
+/// with them, coming out as that wheel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DialMode {
+    #[default]
+    Pass,
+    Wheel,
+    HWheel,
+}
+
+impl DialMode {
+    /// The wheel the dial is taken as.
+    pub fn wheel(self) -> Option<RelativeAxisCode> {
+        match self {
+            DialMode::Pass => None,
+            DialMode::Wheel => Some(RelativeAxisCode::REL_WHEEL),
+            DialMode::HWheel => Some(RelativeAxisCode::REL_HWHEEL),
+        }
+    }
+}
+
+impl std::str::FromStr for DialMode {
+    type Err = String;
+
+    /// Parses `pass`, `wheel` or `hwheel`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "pass" => Ok(DialMode::Pass),
+            "wheel" => Ok(DialMode::Wheel),
+            "hwheel" => Ok(DialMode::HWheel),
+            _ => Err(format!("expected pass, wheel or hwheel, got {text:?}")),
+        }
+    }
+}
+
+/// When the motion summed up from the device is emitted, for
+/// --frame-policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FramePolicy {
+    /// At every `SYN_REPORT`, a frame out for each frame in.
+    #[default]
+    PerSync,
+    /// At most once per interval. Frames with nothing but pointer motion
+    /// that end sooner after the last one emitted are added to the next, or
+    /// emitted once the interval is up if no other comes.
+    Coalesce(Duration),
+}
+
+impl std::str::FromStr for FramePolicy {
+    type Err = String;
+
+    /// Parses `per-sync` or `coalesce:` and a positive number of
+    /// milliseconds.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let expected = || format!("expected per-sync or coalesce:MS, got {text:?}");
+        match text.split_once(':') {
+            None if text == "per-sync" => Ok(FramePolicy::PerSync),
+            Some(("coalesce", ms)) => match ms.parse::<u64>() {
+                Ok(0) => Err("coalesce needs a positive number of milliseconds, not 0".to_string()),
+                Ok(ms) => Ok(FramePolicy::Coalesce(Duration::from_millis(ms))),
+                Err(_) => Err(expected()),
+            },
+            _ => Err(expected()),
+        }
+    }
+}
+
+/// Pointer motion of whole frames held back by [`FramePolicy::Coalesce`].
+#[derive(Default)]
+struct Coalesced {
+    motion: Pointer,
+    // When the last frame was emitted, and so when the next one may be.
+    emitted: Option<SystemTime>,
+}
+
+/// How fast the wheel is turning, for scroll acceleration.
+#[derive(Default)]
+struct ScrollSpeed {
+    // The time of the last frame with a low-resolution wheel click.
+    last_click: Option<SystemTime>,
+    gain: f64,
+}
+
+impl ScrollSpeed {
+    /// The gain for a frame at `at`, updated from the time since the last
+    /// click if the frame has one. High-resolution events between clicks get
+    /// the gain of the last click while it's recent.
+    fn gain(&mut self, at: SystemTime, click: bool, accel: f64) -> f64 {
+        let since = |last: Option<SystemTime>| {
+            last.and_then(|last| at.duration_since(last).ok())
+                .filter(|gap| *gap < FAST_SCROLL_WINDOW)
+        };
+        if click {
+            self.gain = match since(self.last_click) {
+                Some(gap) => {
+                    let fast = 1.0 - gap.as_secs_f64() / FAST_SCROLL_WINDOW.as_secs_f64();
+                    1.0 + (accel - 1.0) * fast
+                }
+                None => 1.0,
+            };
+            self.last_click = Some(at);
+        } else if since(self.last_click).is_none() {
+            self.gain = 1.0;
+        }
+        self.gain
+    }
+}
+
+/// Clicking by holding the pointer still, for those who can't press a
+/// button.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DwellOptions {
+    /// How long the pointer has to rest before it clicks.
+    pub time: Duration,
+    /// Counts the pointer may drift in total while resting.
+    pub tolerance: f64,
+    pub button: KeyCode,
+}
+
+/// Where a dwell click stands.
+struct Dwell {
+    options: DwellOptions,
+    // The time of the last frame that moved the pointer further than the
+    // tolerance. Cleared by the click and by pressing a real button, so that
+    // it takes moving again for the next click.
+    since: Option<SystemTime>,
+    drift: (f64, f64),
+}
+
+impl Dwell {
+    fn moved(&mut self, at: &InputEvent, (dx, dy): (i32, i32)) {
+        self.drift.0 += dx as f64;
+        self.drift.1 += dy as f64;
+        if self.drift.0.hypot(self.drift.1) > self.options.tolerance {
+            self.drift = (0.0, 0.0);
+            self.since = Some(at.timestamp());
+        }
+    }
+}
+
+/// Releases of mouse buttons held back for --debounce, so that a worn switch
+/// letting go and closing again within `window` doesn't click twice.
+struct Debounce {
+    window: Duration,
+    // Releases not let through yet, in the order they came.
+    held: Vec<InputEvent>,
+}
+
+impl Debounce {
+    /// Takes the held release of the button `press` presses again, if it
+    /// came within the window, which makes the two a bounce.
+    fn bounce(&mut self, press: &InputEvent) -> Option<InputEvent> {
+        let index = self.held.iter().position(|release| {
+            release.code() == press.code()
+                && press
+                    .timestamp()
+                    .duration_since(release.timestamp())
+                    .is_ok_and(|since| since <= self.window)
+        })?;
+        Some(self.held.remove(index))
+    }
+
+    /// Takes the held releases that are older than the window at `now`.
+    fn settled(&mut self, now: SystemTime) -> Vec<InputEvent> {
+        let window = self.window;
+        let (settled, held) = self.held.drain(..).partition(|release| {
+            now.duration_since(release.timestamp())
+                .is_ok_and(|since| since > window)
+        });
+        self.held = held;
+        settled
+    }
+}
+
+/// Whether `code` is a mouse button, the ones --debounce applies to.
+fn is_mouse_button(code: KeyCode) -> bool {
+    (KeyCode::BTN_LEFT.0..=KeyCode::BTN_TASK.0).contains(&code.0)
+}
+
+/// Tapping a key when the mouse is shaken, which a compositor can bind to
+/// enlarging the cursor so it's easy to find.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShakeOptions {
+    pub key: KeyCode,
+    /// Reversals of direction it takes within `window`.
+    pub reversals: usize,
+    pub window: Duration,
+    /// Counts per frame the motion on both sides of a reversal has to reach,
+    /// so that the back and forth of aiming at something doesn't count.
+    pub speed: f64,
+}
+
+/// Recent fast motion, for recognizing a shake.
+struct Shake {
+    options: ShakeOptions,
+    // The last frame that moved at least `speed` counts.
+    last: Option<(f64, f64)>,
+    // When the direction turned around, oldest first.
+    reversals: VecDeque<SystemTime>,
+}
+
+impl Shake {
+    /// Returns whether this motion completed a shake, which then has to be
+    /// repeated in full to count again.
+    fn moved(&mut self, at: &InputEvent, (dx, dy): (i32, i32)) -> bool {
+        let motion = (dx as f64, dy as f64);
+        if motion.0.hypot(motion.1) < self.options.speed {
+            return false;
+        }
+        let time = at.timestamp();
+        // Turned around by more than a right angle.
+        if let Some(last) = self.last
+            && last.0 * motion.0 + last.1 * motion.1 < 0.0
+        {
+            self.reversals.push_back(time);
+        }
+        self.last = Some(motion);
+        while let Some(&first) = self.reversals.front()
+            && time
+                .duration_since(first)
+                .is_ok_and(|since| since > self.options.window)
+        {
+            self.reversals.pop_front();
+        }
+        if self.reversals.len() < self.options.reversals {
+            return false;
+        }
+        self.reversals.clear();
+        self.last = None;
+        true
+    }
+}
+
+/// Per-source state of the frame being assembled between two `SYN_REPORT`s.
+pub struct Frame {
+    scroll_options: ScrollOptions,
+    hotkeys: Hotkeys,
+    chording: Chording,
+    // Keys and buttons sent under another code, as (from, to).
+    remap: Vec<(KeyCode, KeyCode)>,
+    // Pointer motion slower than this many counts per frame isn't rotated,
+    // 0 rotating all of it.
+    rotate_above: f64,
+    // Added to the angle of pointer motion by how fast the frame moved.
+    angle_curve: AngleCurve,
+    // The linear part of the fixed steps applied before the rotation, for
+    // positions and wheels; pointer motion goes through all of them in
+    // `transform`.
+    base: Matrix,
+    // Angle the cached matrices were computed from.
+    angle_deg: f64,
+    // This source's own angle, used instead of the live one.
+    own_angle_deg: Option<f64>,
+    // An angle given in radians, and the one in degrees it came to, for
+    // making the matrix from the radians while that's the angle.
+    angle_rad: Option<(f64, f64)>,
+    // Off to leave the angle out of pointer motion, which is still scaled,
+    // accelerated and the like.
+    rotate_pointer: bool,
+    rotation: Matrix,
+    matrix: Matrix,
+    // The angle and whether rotation is on for the frame being assembled.
+    frame_state: Option<(f64, bool)>,
+    pointer: Pointer,
+    transform: Transform,
+    // Events of the current frame that are ready to go, kept between frames
+    // so it doesn't have to be allocated again.
+    pending: Vec<InputEvent>,
+    slots: Slots,
+    // The transformed motion of the frame being flushed with where it goes
+    // in `pending`, kept for the same reason.
+    placed: Vec<(usize, InputEvent)>,
+    // Scroll is accumulated in pointer orientation: x is REL_HWHEEL and y is
+    // the negated REL_WHEEL, since wheel-up is positive but pointer-up is not.
+    scroll: AxisPair,
+    scroll_hi_res: AxisPair,
+    scroll_speed: ScrollSpeed,
+    abs_plane: Option<AbsPlane>,
+    // Pen tilt turns with the position, so it keeps pointing the same way
+    // relative to the screen.
+    tilt: Option<AbsPlane>,
+    touches: Option<Touches>,
+    // Added to absolute positions after rotating them, as a share of each
+    // axis's range.
+    abs_offset: (f64, f64),
+    dwell: Option<Dwell>,
+    shake: Option<Shake>,
+    debounce: Option<Debounce>,
+    frame_policy: FramePolicy,
+    coalesced: Coalesced,
+    // For devices with both relative and absolute motion, the one that's
+    // kept; the other kind's events are dropped.
+    axis_mode: Option<AxisMode>,
+    // For devices with both low- and high-resolution wheels, the one that's
+    // kept.
+    wheel_mode: Option<WheelMode>,
+    // Whether the last frame passed raw pointer motion through, so that the
+    // transform settles and starts afresh when that changes.
+    raw: bool,
+    // Set by SYN_DROPPED: the kernel lost events, so everything up to the
+    // next SYN_REPORT is thrown away rather than applied as one big jump.
+    dropped: bool,
+    // Types of events the virtual device can't have that were left out,
+    // each logged the first time.
+    unforwarded: Vec<EventType>,
+}
+
+impl Frame {
+    /// Without a `device` there are no axis ranges to rotate absolute
+    /// positions in, so they're passed through.
+    pub fn new(
+        device: Option<&Device>,
+        scroll_options: ScrollOptions,
+        pipeline: Pipeline,
+        pointer_options: PointerOptions,
+        hotkeys: Hotkeys,
+        remap: Vec<(KeyCode, KeyCode)>,
+        rotate_above: f64,
+    ) -> Self {
+        let base = pipeline.matrix();
+        Frame {
+            scroll_options,
+            hotkeys,
+            chording: Chording::default(),
+            remap,
+            rotate_above,
+            angle_curve: AngleCurve::default(),
+            base,
+            angle_deg: f64::NAN,
+            own_angle_deg: None,
+            angle_rad: None,
+            rotate_pointer: true,
+            rotation: Matrix::IDENTITY,
+            matrix: base,
+            frame_state: None,
+            pointer: Pointer::default(),
+            transform: Transform::new(pointer_options).with_pipeline(pipeline),
+            pending: Vec::new(),
+            slots: Slots::default(),
+            placed: Vec::new(),
+            scroll: AxisPair::default(),
+            scroll_hi_res: AxisPair::default(),
+            scroll_speed: ScrollSpeed::default(),
+            abs_plane: device.and_then(|device| {
+                AbsPlane::from_device(device, AbsoluteAxisCode::ABS_X, AbsoluteAxisCode::ABS_Y)
+            }),
+            tilt: device.and_then(|device| {
+                AbsPlane::from_device(
+                    device,
+                    AbsoluteAxisCode::ABS_TILT_X,
+                    AbsoluteAxisCode::ABS_TILT_Y,
+                )
+            }),
+            touches: device.and_then(Touches::from_device),
+            abs_offset: (0.0, 0.0),
+            dwell: None,
+            shake: None,
+            debounce: None,
+            frame_policy: FramePolicy::default(),
+            coalesced: Coalesced::default(),
+            axis_mode: None,
+            wheel_mode: None,
+            raw: false,
+            dropped: false,
+            unforwarded: Vec::new(),
+        }
+    }
+
+    /// Clicks when the pointer has been still for a while after moving.
+    pub fn with_dwell(mut self, options: Option<DwellOptions>) -> Self {
+        self.dwell = options.map(|options| Dwell {
+            options,
+            since: None,
+            drift: (0.0, 0.0),
+        });
+        self
+    }
+
+    /// Ignores a mouse button that's released and pressed again within
+    /// `window`, which is the switch bouncing rather than a double click.
+    /// Releases are held back for that long to tell.
+    pub fn with_debounce(mut self, window: Option<Duration>) -> Self {
+        self.debounce = window.map(|window| Debounce {
+            window,
+            held: Vec::new(),
+        });
+        self
+    }
+
+    /// Picks when motion is emitted, see [`FramePolicy`].
+    pub fn with_frame_policy(mut self, policy: FramePolicy) -> Self {
+        self.frame_policy = policy;
+        self
+    }
+
+    /// Rotates by `angle_deg` instead of the live angle, which adjusting at
+    /// runtime then leaves alone. Switching rotation off still applies.
+    pub fn with_angle_deg(mut self, angle_deg: Option<f64>) -> Self {
+        self.own_angle_deg = angle_deg;
+        self
+    }
+
+    /// The angle given with [`with_angle_deg`](Self::with_angle_deg).
+    /// Makes the rotation straight from `angle_rad` whenever the angle is
+    /// `angle_deg`, which it was converted to, so that an angle given in
+    /// radians doesn't go through degrees and back on its way to the matrix.
+    pub fn with_angle_rad(mut self, angle_deg: f64, angle_rad: f64) -> Self {
+        self.angle_rad = Some((angle_deg, angle_rad));
+        self
+    }
+
+    pub fn own_angle_deg(&self) -> Option<f64> {
+        self.own_angle_deg
+    }
+
+    /// Leaves the angle out of pointer motion when off, for rotating only
+    /// the wheels. The rest of what's done to pointer motion still is.
+    pub fn with_rotate_pointer(mut self, rotate: bool) -> Self {
+        self.rotate_pointer = rotate;
+        self
+    }
+
+    /// Taps a key when the mouse is shaken.
+    pub fn with_shake(mut self, options: Option<ShakeOptions>) -> Self {
+        self.shake = options.map(|options| Shake {
+            options,
+            last: None,
+            reversals: VecDeque::new(),
+        });
+        self
+    }
+
+    /// Offsets the angle of pointer motion by its speed.
+    pub fn with_angle_curve(mut self, curve: AngleCurve) -> Self {
+        self.angle_curve = curve;
+        self
+    }
+
+    /// Scales rotated pointer motion by the gain `curve` gives its speed.
+    pub fn with_response(mut self, curve: ResponseCurve) -> Self {
+        self.transform = self.transform.with_response(curve);
+        self
+    }
+
+    /// Keeps only the relative or the absolute motion of a device that has
+    /// both, as picked by [`AxisMode::for_device`].
+    pub fn with_axis_mode(mut self, mode: Option<AxisMode>) -> Self {
+        if mode == Some(AxisMode::Rel) {
+            self.abs_plane = None;
+            self.tilt = None;
+            self.touches = None;
+        }
+        self.axis_mode = mode;
+        self
+    }
+
+    /// Moves absolute positions, multitouch ones included, by a share of the
+    /// width and of the height after rotating them. Whatever ends up past an
+    /// edge is held at the edge.
+    pub fn with_abs_offset(mut self, offset: (f64, f64)) -> Self {
+        self.abs_offset = offset;
+        self
+    }
+
+    /// Ignores touchpad contacts that look like a palm. Only the multitouch
+    /// slots are filtered; devices without them are left alone.
+    pub fn with_palm(mut self, options: Option<PalmOptions>) -> Self {
+        if let Some(touches) = &mut self.touches {
+            touches.palm = options;
+        }
+        self
+    }
+
+    /// Keeps only the low- or the high-resolution wheel events of a device
+    /// that has both, as picked by [`WheelMode::for_device`].
+    pub fn with_wheel_mode(mut self, mode: Option<WheelMode>) -> Self {
+        self.wheel_mode = mode;
+        self
+    }
+
+    /// Accumulates motion events and emits the rotated frame on `SYN_REPORT`.
+    /// Everything else is forwarded as is, as part of the same frame and in
+    /// the order it came. The transformed motion takes the place of the
+    /// first event it was collected from, so that a click stays before or
+    /// after the motion it came with.
+    ///
+    /// Several `REL_X` or `REL_Y` events in one frame, which devices may
+    /// send, are summed and the sum is transformed as one motion. That's not
+    /// the same as transforming each event: acceleration, the deadzone and
+    /// `rotate_above` all go by how far the whole frame moved, and each
+    /// rounding would add its own error.
+    pub fn handle(
+        &mut self,
+        event: InputEvent,
+        state: &LiveState,
+        output: &mut impl FrameSink,
+    ) -> std::io::Result<()> {
+        if event.event_type() == EventType::SYNCHRONIZATION {
+            match SynchronizationCode(event.code()) {
+                SynchronizationCode::SYN_DROPPED => {
+                    trace!("SYN_DROPPED, discarding the current frame");
+                    self.discard();
+                    self.dropped = true;
+                    return Ok(());
+                }
+                SynchronizationCode::SYN_REPORT if self.dropped => {
+                    self.discard();
+                    self.dropped = false;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        if self.dropped {
+            return Ok(());
+        }
+
+        if event.event_type() == EventType::KEY
+            && event.value() == 1
+            && let Some(dwell) = &mut self.dwell
+        {
+            dwell.since = None;
+        }
+
+        // Releases that turned out not to be a bounce go first, they came
+        // before this.
+        if let Some(debounce) = &mut self.debounce
+            && !debounce.held.is_empty()
+        {
+            for release in debounce.settled(event.timestamp()) {
+                self.key(release, output);
+            }
+        }
+
+        let scroll = self.scroll_options.transforms();
+        match event.event_type() {
+            EventType::RELATIVE
+                if self.axis_mode == Some(AxisMode::Abs)
+                    && matches!(
+                        RelativeAxisCode(event.code()),
+                        RelativeAxisCode::REL_X | RelativeAxisCode::REL_Y
+                    ) =>
+            {
+                trace!("Dropping relative motion, the absolute position is used");
+            }
+            EventType::RELATIVE
+                if self.wheel_mode.is_some_and(|mode| {
+                    mode.dropped().contains(&RelativeAxisCode(event.code()))
+                }) =>
+            {
+                trace!("Dropping a wheel event the wheel mode leaves out");
+            }
+            EventType::ABSOLUTE if self.axis_mode == Some(AxisMode::Rel) => {
+                trace!("Dropping an absolute axis, relative motion is used");
+            }
+            EventType::RELATIVE => {
+                let relative_axis_code = RelativeAxisCode(event.code());
+                let at = self.pending.len();
+
+                match relative_axis_code {
+                    RelativeAxisCode::REL_X => {
+                        self.slots.pointer.get_or_insert(at);
+                        accumulate(
+                            &mut self.pointer.dx,
+                            i64::from(event.value()),
+                            relative_axis_code,
+                        );
+                    }
+                    RelativeAxisCode::REL_Y => {
+                        self.slots.pointer.get_or_insert(at);
+                        accumulate(
+                            &mut self.pointer.dy,
+                            i64::from(event.value()),
+                            relative_axis_code,
+                        );
+                    }
+                    RelativeAxisCode::REL_HWHEEL if scroll => {
+                        self.slots.scroll.get_or_insert(at);
+                        accumulate(
+                            &mut self.scroll.x,
+                            i64::from(event.value()),
+                            relative_axis_code,
+                        );
+                    }
+                    RelativeAxisCode::REL_WHEEL if scroll => {
+                        self.slots.scroll.get_or_insert(at);
+                        accumulate(
+                            &mut self.scroll.y,
+                            -i64::from(event.value()),
+                            relative_axis_code,
+                        );
+                    }
+                    RelativeAxisCode::REL_DIAL if self.scroll_options.dial == DialMode::Wheel => {
+                        self.slots.scroll.get_or_insert(at);
+                        accumulate(
+                            &mut self.scroll.y,
+                            -i64::from(event.value()),
+                            relative_axis_code,
+                        );
+                    }
+                    RelativeAxisCode::REL_DIAL if self.scroll_options.dial == DialMode::HWheel => {
+                        self.slots.scroll.get_or_insert(at);
+                        accumulate(
+                            &mut self.scroll.x,
+                            i64::from(event.value()),
+                            relative_axis_code,
+                        );
+                    }
+                    RelativeAxisCode::REL_HWHEEL_HI_RES if scroll => {
+                        self.slots.scroll_hi_res.get_or_insert(at);
+                        accumulate(
+                            &mut self.scroll_hi_res.x,
+                            i64::from(event.value()),
+                            relative_axis_code,
+                        );
+                    }
+                    RelativeAxisCode::REL_WHEEL_HI_RES if scroll => {
+                        self.slots.scroll_hi_res.get_or_insert(at);
+                        accumulate(
+                            &mut self.scroll_hi_res.y,
+                            -i64::from(event.value()),
+                            relative_axis_code,
+                        );
+                    }
+                    _ => self.forward(event, output),
+                }
+            }
+            EventType::ABSOLUTE => match (
+                &mut self.abs_plane,
+                &mut self.tilt,
+                &mut self.touches,
+                AbsoluteAxisCode(event.code()),
+            ) {
+                (Some(plane), _, _, code @ AbsoluteAxisCode::ABS_X)
+                | (_, Some(plane), _, code @ AbsoluteAxisCode::ABS_TILT_X) => {
+                    plane.x = event.value();
+                    plane.changed = true;
+                    self.slots.abs(code).get_or_insert(self.pending.len());
+                }
+                (Some(plane), _, _, code @ AbsoluteAxisCode::ABS_Y)
+                | (_, Some(plane), _, code @ AbsoluteAxisCode::ABS_TILT_Y) => {
+                    plane.y = event.value();
+                    plane.changed = true;
+                    self.slots.abs(code).get_or_insert(self.pending.len());
+                }
+                (_, _, Some(_), AbsoluteAxisCode::ABS_MT_SLOT) => {
+                    self.close_contact(&event, state, output);
+                    if let Some(touches) = &mut self.touches {
+                        touches.select(event.value());
+                    }
+                    self.forward(event, output);
+                }
+                (_, _, Some(touches), AbsoluteAxisCode::ABS_MT_POSITION_X) => {
+                    let contact = touches.contact();
+                    contact.x = event.value();
+                    contact.changed = true;
+                }
+                (_, _, Some(touches), AbsoluteAxisCode::ABS_MT_POSITION_Y) => {
+                    let contact = touches.contact();
+                    contact.y = event.value();
+                    contact.changed = true;
+                }
+                (_, _, Some(touches), code)
+                    if touches.palm.is_some()
+                        && (AbsoluteAxisCode::ABS_MT_TOUCH_MAJOR.0
+                            ..=AbsoluteAxisCode::ABS_MT_TOOL_Y.0)
+                            .contains(&code.0) =>
+                {
+                    touches.hold(event);
+                }
+                _ => self.forward(event, output),
+            },
+            EventType::KEY if self.chord(&event, state, output) => {}
+            EventType::KEY if self.hotkeys.handle(&event, state) => {
+                // Straightened out, with nothing owed from the old angle.
+                if self.hotkeys.reset_key == Some(KeyCode(event.code())) && event.value() == 1 {
+                    self.transform.reset();
+                }
+            }
+            EventType::KEY => {
+                let code = KeyCode(event.code());
+                let debounce = self.debounce.as_mut().filter(|_| is_mouse_button(code));
+                match (debounce, event.value()) {
+                    (Some(debounce), 0) => debounce.held.push(event),
+                    (Some(debounce), 1) => match debounce.bounce(&event) {
+                        Some(_) => trace!("Dropping a bounce of {code:?}"),
+                        None => self.key(event, output),
+                    },
+                    _ => self.key(event, output),
+                }
+            }
+            EventType::SYNCHRONIZATION
+                if SynchronizationCode(event.code()) == SynchronizationCode::SYN_REPORT =>
+            {
+                if !self.coalesce(&event) {
+                    self.flush(event, state, output)?
+                }
+            }
+            _ => self.forward(event, output),
+        }
+        Ok(())
+    }
+
+    /// Keeps track of the keys down for the chords and says whether `event`
+    /// was taken care of: held back, dropped, or the key completing a chord.
+    /// Presses held back go out ahead of the next key that can't be part of
+    /// a chord with them, or when one of them is let go of.
+    fn chord(
+        &mut self,
+        event: &InputEvent,
+        state: &LiveState,
+        output: &mut impl FrameSink,
+    ) -> bool {
+        if self.hotkeys.chords.is_empty() {
+            return false;
+        }
+        let code = KeyCode(event.code());
+        let chording = &mut self.chording;
+        match event.value() {
+            1 => {
+                if !chording.down.contains(&code) {
+                    chording.down.push(code);
+                }
+                // With chords sharing keys, the longest one that's complete.
+                let complete = self
+                    .hotkeys
+                    .chords
+                    .iter()
+                    .filter(|chord| {
+                        chord.keys.contains(&code)
+                            && chord.keys.iter().all(|key| chording.down.contains(key))
+                    })
+                    .max_by_key(|chord| chord.keys.len());
+                if let Some(chord) = complete {
+                    state.set_angle_deg(chord.angle_deg);
+                    info!("Angle changed to {}", Degrees(chord.angle_deg));
+                    chording.swallowed.push(code);
+                    let (taken, rest): (Vec<InputEvent>, Vec<InputEvent>) = chording
+                        .held_back
+                        .drain(..)
+                        .partition(|held| chord.keys.contains(&KeyCode(held.code())));
+                    chording
+                        .swallowed
+                        .extend(taken.iter().map(|held| KeyCode(held.code())));
+                    for held in rest {
+                        self.key(held, output);
+                    }
+                    return true;
+                }
+                let could_be_chord = self.hotkeys.chords.iter().any(|chord| {
+                    !chord.passthrough
+                        && chord.keys.contains(&code)
+                        && chording
+                            .held_back
+                            .iter()
+                            .all(|held| chord.keys.contains(&KeyCode(held.code())))
+                });
+                if could_be_chord {
+                    chording.held_back.push(*event);
+                    return true;
+                }
+                self.release_held_back(output);
+                false
+            }
+            0 => {
+                chording.down.retain(|key| *key != code);
+                if let Some(index) = chording.swallowed.iter().position(|key| *key == code) {
+                    chording.swallowed.remove(index);
+                    return true;
+                }
+                // Let go of before the chord was complete, so it was only a
+                // press of its own.
+                if chording
+                    .held_back
+                    .iter()
+                    .any(|held| KeyCode(held.code()) == code)
+                {
+                    self.release_held_back(output);
+                }
+                false
+            }
+            _ => {
+                chording.swallowed.contains(&code)
+                    || chording
+                        .held_back
+                        .iter()
+                        .any(|held| KeyCode(held.code()) == code)
+            }
+        }
+    }
+
+    /// Sends the presses held back for a chord that isn't going to happen.
+    fn release_held_back(&mut self, output: &mut impl FrameSink) {
+        for held in std::mem::take(&mut self.chording.held_back) {
+            self.key(held, output);
+        }
+    }
+
+    /// Queues a key or button event, remapped if it's one of those.
+    ///
+    /// Presses and releases are remapped alike, so every key that goes down
+    /// comes up again. Only when two keys share a target can releasing one
+    /// let go of the other.
+    fn key(&mut self, event: InputEvent, output: &mut impl FrameSink) {
+        let code = KeyCode(event.code());
+        match self.remap.iter().find(|(from, _)| *from == code) {
+            Some(&(_, to)) => {
+                output.trace(&event, None);
+                self.pending
+                    .push(event_at(&event, EventType::KEY, to.0, event.value()));
+            }
+            None => self.forward(event, output),
+        }
+    }
+
+    /// Holds back the pointer motion of the frame `syn` ends and says so, if
+    /// the frame has nothing else and the last one went out too recently
+    /// for [`FramePolicy::Coalesce`].
+    fn coalesce(&mut self, syn: &InputEvent) -> bool {
+        let FramePolicy::Coalesce(interval) = self.frame_policy else {
+            return false;
+        };
+        let only_pointer = self.pending.is_empty()
+            && self.touches.is_none()
+            && matches!(
+                self.slots,
+                Slots {
+                    pointer: _,
+                    scroll: None,
+                    scroll_hi_res: None,
+                    position: None,
+                    tilt: None,
+                }
+            );
+        let too_soon = self
+            .coalesced
+            .emitted
+            .is_some_and(|emitted| syn.timestamp() < emitted + interval);
+        if !(only_pointer && too_soon) {
+            return false;
+        }
+        let Pointer { dx, dy } = std::mem::take(&mut self.pointer);
+        let held = &mut self.coalesced.motion;
+        held.dx = held.dx.saturating_add(dx);
+        held.dy = held.dy.saturating_add(dy);
+        self.slots.pointer = None;
+        true
+    }
+
+    /// How long until motion held back by [`FramePolicy::Coalesce`] is due,
+    /// if there is any.
+    pub fn coalesce_due(&self) -> Option<Duration> {
+        let FramePolicy::Coalesce(interval) = self.frame_policy else {
+            return None;
+        };
+        if self.coalesced.motion == Pointer::default() {
+            return None;
+        }
+        let due = self.coalesced.emitted? + interval;
+        Some(
+            due.duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    /// Emits the motion held back by [`FramePolicy::Coalesce`] once it's
+    /// due, as a frame of its own, unless the device is in the middle of a
+    /// frame, which then takes it along.
+    pub fn release_coalesced(
+        &mut self,
+        state: &LiveState,
+        output: &mut impl FrameSink,
+    ) -> std::io::Result<()> {
+        if self.coalesce_due() != Some(Duration::ZERO) || self.mid_frame() {
+            return Ok(());
+        }
+        // There's no event from the device to take the time from.
+        let syn = InputEvent::new_now(
+            EventType::SYNCHRONIZATION.0,
+            SynchronizationCode::SYN_REPORT.0,
+            0,
+        );
+        self.flush(syn, state, output)
+    }
+
+    /// Whether the device sent part of a frame that its `SYN_REPORT` hasn't
+    /// ended yet.
+    fn mid_frame(&self) -> bool {
+        !self.pending.is_empty()
+            || self.pointer != Pointer::default()
+            || self.slots.pointer.is_some()
+    }
+
+    /// Catches rotation being toggled or paused while the device is quiet
+    /// and emits what the pointer pipeline still owed from before as a
+    /// frame of its own, so that motion resuming later doesn't start with a
+    /// jump. A change in the middle of a frame is left to its `SYN_REPORT`.
+    pub fn settle(
+        &mut self,
+        state: &LiveState,
+        output: &mut impl FrameSink,
+    ) -> std::io::Result<()> {
+        let raw = !state.enabled() || state.paused();
+        if raw == self.raw || self.mid_frame() {
+            return Ok(());
+        }
+        self.raw = raw;
+        let syn = InputEvent::new_now(
+            EventType::SYNCHRONIZATION.0,
+            SynchronizationCode::SYN_REPORT.0,
+            0,
+        );
+        self.emit_settled(&syn, output)
+    }
+
+    /// Emits everything still owed before the device is let go of: motion
+    /// held back by [`FramePolicy::Coalesce`], the rest the pointer pipeline
+    /// owes, then the releases held back for --debounce, however recent, so
+    /// that no button is left down on the virtual device.
+    pub fn release(
+        &mut self,
+        state: &LiveState,
+        output: &mut impl FrameSink,
+    ) -> std::io::Result<()> {
+        let syn = InputEvent::new_now(
+            EventType::SYNCHRONIZATION.0,
+            SynchronizationCode::SYN_REPORT.0,
+            0,
+        );
+        if self.coalesced.motion != Pointer::default() && !self.mid_frame() {
+            self.flush(syn, state, output)?;
+        }
+        self.emit_settled(&syn, output)?;
+        let held = match &mut self.debounce {
+            Some(debounce) => std::mem::take(&mut debounce.held),
+            None => Vec::new(),
+        };
+        self.let_through(held, output)
+    }
+
+    /// Emits the motion [`Transform::settle`] rounds up as a frame of its
+    /// own, if there's any, and the transform starts afresh, with no
+    /// remainder, smoothing or snapped axis left.
+    fn emit_settled(
+        &mut self,
+        syn: &InputEvent,
+        output: &mut impl FrameSink,
+    ) -> std::io::Result<()> {
+        let (dx, dy) = self.transform.settle();
+        let mut frame = Vec::with_capacity(2);
+        if dx != 0 {
+            frame.push(rel_event(syn, RelativeAxisCode::REL_X, dx));
+        }
+        if dy != 0 {
+            frame.push(rel_event(syn, RelativeAxisCode::REL_Y, dy));
+        }
+        if frame.is_empty() {
+            return Ok(());
+        }
+        trace!("settled: ({dx}, {dy}) owed");
+        output.record(|| FrameRecord::new(syn.timestamp(), (0, 0), Some((dx, dy)), self.angle_deg));
+        output.emit(&frame)
+    }
+
+    /// Whether smoothing still owes motion that should be emitted even if the
+    /// device stays quiet.
+    pub fn smoothing_pending(&self) -> bool {
+        self.transform.smoothing_pending()
+    }
+
+    /// Emits the next step of owed smoothed motion without new input from the
+    /// device.
+    pub fn tick(&mut self, output: &mut impl FrameSink) -> std::io::Result<()> {
+        let (new_dx, new_dy) = self.transform.tick();
+        // There's no event from the device to take the time from.
+        let syn = InputEvent::new_now(
+            EventType::SYNCHRONIZATION.0,
+            SynchronizationCode::SYN_REPORT.0,
+            0,
+        );
+        if (new_dx, new_dy) != (0, 0) {
+            output.record(|| {
+                FrameRecord::new(
+                    syn.timestamp(),
+                    (0, 0),
+                    Some((new_dx, new_dy)),
+                    self.angle_deg,
+                )
+            });
+        }
+        let mut frame = Vec::with_capacity(2);
+        if new_dx != 0 {
+            frame.push(rel_event(&syn, RelativeAxisCode::REL_X, new_dx));
+        }
+        if new_dy != 0 {
+            frame.push(rel_event(&syn, RelativeAxisCode::REL_Y, new_dy));
+        }
+        if frame.is_empty() {
+            return Ok(());
+        }
+        output.emit(&frame)
+    }
+
+    /// How long until the dwell click is due, if one is coming.
+    pub fn dwell_due(&self) -> Option<Duration> {
+        let dwell = self.dwell.as_ref()?;
+        let due = dwell.since? + dwell.options.time;
+        Some(
+            due.duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    /// Clicks the dwell button if the pointer has rested long enough.
+    pub fn dwell(&mut self, output: &mut impl FrameSink) -> std::io::Result<()> {
+        if self.dwell_due() != Some(Duration::ZERO) {
+            return Ok(());
+        }
+        let Some(dwell) = &mut self.dwell else {
+            return Ok(());
+        };
+        dwell.since = None;
+        let button = dwell.options.button;
+        debug!("Dwell click with {button:?}");
+        for value in [1, 0] {
+            let event = InputEvent::new_now(EventType::KEY.0, button.0, value);
+            output.emit(&[event])?;
+        }
+        Ok(())
+    }
+
+    /// How long until a release held back for --debounce is let through, if
+    /// one is.
+    pub fn debounce_due(&self) -> Option<Duration> {
+        let debounce = self.debounce.as_ref()?;
+        let now = SystemTime::now();
+        debounce
+            .held
+            .iter()
+            .map(|release| {
+                (release.timestamp() + debounce.window)
+                    .duration_since(now)
+                    .unwrap_or(Duration::ZERO)
+            })
+            .min()
+    }
+
+    /// Lets through the releases held back for --debounce that weren't
+    /// followed by a bounce in time, each as a frame of its own.
+    pub fn debounce(&mut self, output: &mut impl FrameSink) -> std::io::Result<()> {
+        let Some(debounce) = &mut self.debounce else {
+            return Ok(());
+        };
+        let settled = debounce.settled(SystemTime::now());
+        self.let_through(settled, output)
+    }
+
+    /// Emits each of the held back `releases` as a frame of its own.
+    fn let_through(
+        &mut self,
+        releases: Vec<InputEvent>,
+        output: &mut impl FrameSink,
+    ) -> std::io::Result<()> {
+        if releases.is_empty() {
+            return Ok(());
+        }
+        // Whatever the current frame has so far stays for its SYN_REPORT.
+        let frame = std::mem::take(&mut self.pending);
+        for release in releases {
+            self.key(release, output);
+            let emitted = output.emit(&self.pending);
+            self.pending.clear();
+            emitted?;
+        }
+        self.pending = frame;
+        Ok(())
+    }
+
+    /// Forgets the motion and events of a frame that won't be emitted. The
+    /// absolute position is kept, evdev reports the current one after a drop.
+    fn discard(&mut self) {
+        self.pointer = Pointer::default();
+        self.frame_state = None;
+        if let Some(touches) = &mut self.touches {
+            touches.discard();
+        }
+        for pair in [&mut self.scroll, &mut self.scroll_hi_res] {
+            pair.x = 0;
+            pair.y = 0;
+        }
+        self.pending.clear();
+        self.slots = Slots::default();
+    }
+
+    /// Queues an event that passes through untransformed, unless it's of a
+    /// type the virtual device can't have, such as LEDs, autorepeat, sounds
+    /// and force feedback. Those are left out, with a warning the first time
+    /// for each type.
+    fn forward(&mut self, event: InputEvent, output: &mut impl FrameSink) {
+        output.trace(&event, None);
+        let event_type = event.event_type();
+        if can_forward(event_type) {
+            self.pending.push(event);
+        } else if !self.unforwarded.contains(&event_type) {
+            warn!("{event_type:?} events can't be passed to the virtual device and are left out");
+            self.unforwarded.push(event_type);
+        }
+    }
+
+    /// The angle, whether rotation is on and the matrix that makes for.
+    ///
+    /// The angle may be changed from another thread, so it's picked up once
+    /// per frame for the whole frame to use the same one, multitouch
+    /// positions emitted before the `SYN_REPORT` included.
+    fn frame_matrix(&mut self, state: &LiveState) -> (f64, bool, Matrix) {
+        let (angle_deg, enabled) = *self.frame_state.get_or_insert_with(|| {
+            let angle_deg = self
+                .own_angle_deg
+                .unwrap_or_else(|| state.applied_angle_deg());
+            (angle_deg, state.enabled())
+        });
+        if angle_deg != self.angle_deg {
+            self.angle_deg = angle_deg;
+            self.rotation = match self.angle_rad {
+                Some((given_deg, angle_rad)) if given_deg == angle_deg => {
+                    Matrix::rotation_rad(angle_rad)
+                }
+                _ => Matrix::rotation(angle_deg),
+            };
+            self.matrix = self.base.then(self.rotation);
+        }
+        // Toggled off, everything passes through as the device sent it.
+        let matrix = if enabled {
+            self.matrix
+        } else {
+            Matrix::IDENTITY
+        };
+        (angle_deg, enabled, matrix)
+    }
+
+    /// How far absolute positions are moved, not at all while rotation is
+    /// toggled off.
+    fn abs_offset(&self, enabled: bool) -> (f64, f64) {
+        if enabled { self.abs_offset } else { (0.0, 0.0) }
+    }
+
+    /// Queues the rotated position of the current multitouch contact, if it
+    /// moved, before the events of another slot follow.
+    fn close_contact(&mut self, at: &InputEvent, state: &LiveState, output: &mut impl FrameSink) {
+        if !self.touches.as_ref().is_some_and(Touches::changed) {
+            return;
+        }
+        let (_, enabled, matrix) = self.frame_matrix(state);
+        let offset = self.abs_offset(enabled);
+        let Some(touches) = &mut self.touches else {
+            return;
+        };
+        if let Some(options) = touches.palm {
+            let (events, position) = touches.settle_palm(&options, at);
+            for event in events {
+                output.trace(&event, None);
+                self.pending.push(event);
+            }
+            let contact = &mut touches.contacts[touches.slot];
+            if !(position && contact.changed) {
+                contact.changed = false;
+                return;
+            }
+        }
+        let ((x, y), (new_x, new_y)) = touches.rotate(&matrix, offset);
+        for (code, value, new_value) in [
+            (AbsoluteAxisCode::ABS_MT_POSITION_X, x, new_x),
+            (AbsoluteAxisCode::ABS_MT_POSITION_Y, y, new_y),
+        ] {
+            output.trace(&abs_event(at, code, value), Some(new_value));
+            self.pending.push(abs_event(at, code, new_value));
+        }
+    }
+
+    fn flush(
+        &mut self,
+        syn: InputEvent,
+        state: &LiveState,
+        output: &mut impl FrameSink,
+    ) -> std::io::Result<()> {
+        self.close_contact(&syn, state, output);
+        let (angle_deg, enabled, matrix) = self.frame_matrix(state);
+        self.frame_state = None;
+        let rotation = self.rotation;
+        let abs_offset = self.abs_offset(enabled);
+
+        // Motion of a kind without a slot, such as that of a multitouch
+        // contact, was queued where it was closed; anything else left goes
+        // last.
+        let end = self.pending.len();
+        let slots = std::mem::take(&mut self.slots);
+
+        // The pause key only hands back pointer motion.
+        let raw = !enabled || state.paused();
+        if raw != self.raw {
+            self.raw = raw;
+            self.emit_settled(&syn, output)?;
+        }
+        let placed = &mut self.placed;
+        let Pointer { dx, dy } = std::mem::take(&mut self.pointer);
+        // Motion held back came before anything in this frame.
+        let held = std::mem::take(&mut self.coalesced.motion);
+        let slots = match held {
+            Pointer { dx: 0, dy: 0 } => slots,
+            _ => Slots {
+                pointer: Some(0),
+                ..slots
+            },
+        };
+        let (dx, dy) = (dx.saturating_add(held.dx), dy.saturating_add(held.dy));
+        self.coalesced.emitted = Some(syn.timestamp());
+        // Direction changes are the same whichever way the sensor sits, so
+        // the motion as read will do.
+        let shaken = self
+            .shake
+            .as_mut()
+            .is_some_and(|shake| shake.moved(&syn, (dx, dy)));
+        let transformed = match (dx, dy) {
+            (0, 0) => None,
+            _ if raw => Some((dx, dy)),
+            // Scaled, accelerated and the like, but at 0°.
+            _ if !self.rotate_pointer => {
+                let transformed = self.transform.apply(dx, dy, &Matrix::IDENTITY);
+                output.rotated();
+                transformed
+            }
+            _ => {
+                // Slow motion gets part of the angle or none of it. The
+                // remainder is carried all the same: it's less than a count,
+                // and which way it came from doesn't matter by then.
+                let speed = (dx as f64).hypot(dy as f64);
+                let weight = rotation_weight(speed, self.rotate_above);
+                let offset = self.angle_curve.offset(speed);
+                let rotation = if weight < 1.0 || offset != 0.0 {
+                    Matrix::rotation((angle_deg + offset) * weight)
+                } else {
+                    rotation
+                };
+                let transformed = self.transform.apply(dx, dy, &rotation);
+                output.rotated();
+                if transformed.is_none() {
+                    trace!("frame: ({dx}, {dy}) dropped by the deadzone or held back");
+                }
+                transformed
+            }
+        };
+        if (dx, dy) != (0, 0) {
+            let angle_deg = if raw || !self.rotate_pointer {
+                0.0
+            } else {
+                angle_deg
+            };
+            output.record(|| FrameRecord::new(syn.timestamp(), (dx, dy), transformed, angle_deg));
+        }
+        if let Some((new_dx, new_dy)) = transformed {
+            if let Some(dwell) = &mut self.dwell {
+                dwell.moved(&syn, (new_dx, new_dy));
+            }
+            trace!("frame: ({dx}, {dy}) -> ({new_dx}, {new_dy})");
+            output.trace(&rel_event(&syn, RelativeAxisCode::REL_X, dx), Some(new_dx));
+            output.trace(&rel_event(&syn, RelativeAxisCode::REL_Y, dy), Some(new_dy));
+            let at = slots.pointer.unwrap_or(end);
+            placed.push((at, rel_event(&syn, RelativeAxisCode::REL_X, new_dx)));
+            placed.push((at, rel_event(&syn, RelativeAxisCode::REL_Y, new_dy)));
+        }
+
+        // Low- and high-resolution wheels are rotated by the same matrix so
+        // both representations keep pointing the same way. Inverting isn't
+        // part of the rotation, so it stays on when that is toggled off.
+        let mut scroll_matrix = match self.scroll_options {
+            ScrollOptions {
+                rotate: true,
+                angle_deg: Some(angle_deg),
+                ..
+            } if enabled => Matrix::rotation(angle_deg),
+            ScrollOptions {
+                rotate: true,
+                angle_deg: None,
+                ..
+            } => matrix,
+            _ => Matrix::IDENTITY,
+        };
+        if self.scroll_options.invert {
+            scroll_matrix = scroll_matrix.then(Matrix {
+                a: -1.0,
+                b: 0.0,
+                c: 0.0,
+                d: -1.0,
+            });
+        }
+        // Accelerated clicks come out as fractions of one, which are carried
+        // like rotated ones are.
+        if self.scroll_options.accel != 1.0
+            && !(self.scroll.is_empty() && self.scroll_hi_res.is_empty())
+        {
+            let gain = self.scroll_speed.gain(
+                syn.timestamp(),
+                !self.scroll.is_empty(),
+                self.scroll_options.accel,
+            );
+            scroll_matrix = scroll_matrix.then(Matrix {
+                a: gain,
+                b: 0.0,
+                c: 0.0,
+                d: gain,
+            });
+        }
+        for (pair, slot, hwheel, wheel) in [
+            (
+                &mut self.scroll,
+                slots.scroll,
+                RelativeAxisCode::REL_HWHEEL,
+                RelativeAxisCode::REL_WHEEL,
+            ),
+            (
+                &mut self.scroll_hi_res,
+                slots.scroll_hi_res,
+                RelativeAxisCode::REL_HWHEEL_HI_RES,
+                RelativeAxisCode::REL_WHEEL_HI_RES,
+            ),
+        ] {
+            if pair.is_empty() {
+                continue;
+            }
+            let (h, v) = (pair.x, -pair.y);
+            let (new_h, new_v) = pair.rotate(&scroll_matrix);
+            output.trace(&rel_event(&syn, hwheel, h), Some(new_h));
+            output.trace(&rel_event(&syn, wheel, v), Some(-new_v));
+            let at = slot.unwrap_or(end);
+            if new_h != 0 {
+                placed.push((at, rel_event(&syn, hwheel, new_h)));
+            }
+            if new_v != 0 {
+                placed.push((at, rel_event(&syn, wheel, -new_v)));
+            }
+        }
+
+        if let Some(plane) = self.abs_plane.as_mut().filter(|plane| plane.changed) {
+            let (x, y) = (plane.x, plane.y);
+            let (new_x, new_y) = plane.rotate(&matrix, abs_offset);
+            output.trace(&abs_event(&syn, AbsoluteAxisCode::ABS_X, x), Some(new_x));
+            output.trace(&abs_event(&syn, AbsoluteAxisCode::ABS_Y, y), Some(new_y));
+            let at = slots.position.unwrap_or(end);
+            placed.push((at, abs_event(&syn, AbsoluteAxisCode::ABS_X, new_x)));
+            placed.push((at, abs_event(&syn, AbsoluteAxisCode::ABS_Y, new_y)));
+        }
+        if let Some(tilt) = self.tilt.as_mut().filter(|tilt| tilt.changed) {
+            let (x, y) = (tilt.x, tilt.y);
+            let (new_x, new_y) = tilt.rotate_direction(&matrix);
+            output.trace(
+                &abs_event(&syn, AbsoluteAxisCode::ABS_TILT_X, x),
+                Some(new_x),
+            );
+            output.trace(
+                &abs_event(&syn, AbsoluteAxisCode::ABS_TILT_Y, y),
+                Some(new_y),
+            );
+            let at = slots.tilt.unwrap_or(end);
+            placed.push((at, abs_event(&syn, AbsoluteAxisCode::ABS_TILT_X, new_x)));
+            placed.push((at, abs_event(&syn, AbsoluteAxisCode::ABS_TILT_Y, new_y)));
+        }
+
+        // Inserted from the back, so that the slots further ahead are still
+        // where they were. The sort is stable, keeping X before Y.
+        placed.sort_by_key(|&(at, _)| at);
+        for (at, event) in placed.drain(..).rev() {
+            self.pending.insert(at, event);
+        }
+
+        // The sink ends the frame with its own SYN_REPORT.
+        output.trace(&syn, None);
+        let emitted = output.emit(&self.pending);
+        self.pending.clear();
+        emitted?;
+
+        // A frame each, so that the press isn't lost on a consumer that only
+        // looks at where a key ends up at the end of a frame.
+        if let Some(shake) = self.shake.as_ref().filter(|_| shaken) {
+            let key = shake.options.key;
+            debug!("Shake, tapping {key:?}");
+            for value in [1, 0] {
+                output.emit(&[event_at(&syn, EventType::KEY, key.0, value)])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(pointer_options: PointerOptions) -> Frame {
+        Frame::new(
+            None,
+            ScrollOptions::default(),
+            Pipeline::default(),
+            pointer_options,
+            Hotkeys::default(),
+            Vec::new(),
+            0.0,
+        )
+    }
+
+    /// Keeps the frames emitted, as the virtual device would get them.
+    #[derive(Default)]
+    struct Frames(Vec<Vec<InputEvent>>);
+
+    impl Frames {
+        fn take_frames(&mut self) -> Vec<Vec<InputEvent>> {
+            std::mem::take(&mut self.0)
+        }
+    }
+
+    impl FrameSink for Frames {
+        fn emit(&mut self, events: &[InputEvent]) -> std::io::Result<()> {
+            self.0.push(events.to_vec());
+            Ok(())
+        }
+    }
+
+    fn output() -> Frames {
+        Frames::default()
+    }
+
+    fn event(event_type: EventType, code: u16, value: i32) -> InputEvent {
+        InputEvent::new(event_type.0, code, value)
+    }
+
+    fn syn(code: SynchronizationCode) -> InputEvent {
+        event(EventType::SYNCHRONIZATION, code.0, 0)
+    }
+
+    /// Feeds one frame of motion, closed by a `SYN_REPORT`.
+    fn move_by(frame: &mut Frame, state: &LiveState, output: &mut Frames, dx: i32, dy: i32) {
+        for (code, value) in [(RelativeAxisCode::REL_X, dx), (RelativeAxisCode::REL_Y, dy)] {
+            if value != 0 {
+                let motion = event(EventType::RELATIVE, code.0, value);
+                frame.handle(motion, state, output).unwrap();
+            }
+        }
+        frame
+            .handle(syn(SynchronizationCode::SYN_REPORT), state, output)
+            .unwrap();
+    }
+
+    /// The pointer motion of each emitted frame, `None` for frames without.
+    fn motion(frames: &[Vec<InputEvent>]) -> Vec<Option<(i32, i32)>> {
+        frames
+            .iter()
+            .map(|frame| {
+                let value = |code: RelativeAxisCode| {
+                    frame
+                        .iter()
+                        .find(|event| {
+                            event.event_type() == EventType::RELATIVE && event.code() == code.0
+                        })
+                        .map(InputEvent::value)
+                };
+                match (
+                    value(RelativeAxisCode::REL_X),
+                    value(RelativeAxisCode::REL_Y),
+                ) {
+                    (None, None) => None,
+                    (x, y) => Some((x.unwrap_or(0), y.unwrap_or(0))),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_flood_of_motion_stops_at_the_cap() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        // Far above the cap, which would otherwise hide it.
+        let mut frame = frame(PointerOptions {
+            max_delta: 1e9,
+            ..PointerOptions::default()
+        });
+        for _ in 0..10_000 {
+            for (code, value) in [
+                (RelativeAxisCode::REL_X, i32::MAX),
+                (RelativeAxisCode::REL_Y, i32::MIN),
+            ] {
+                frame
+                    .handle(
+                        event(EventType::RELATIVE, code.0, value),
+                        &state,
+                        &mut output,
+                    )
+                    .unwrap();
+            }
+        }
+        frame
+            .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+            .unwrap();
+        move_by(&mut frame, &state, &mut output, 3, -4);
+
+        assert_eq!(
+            motion(&output.take_frames()),
+            [Some((MAX_FRAME_DELTA, -MAX_FRAME_DELTA)), Some((3, -4))]
+        );
+    }
+
+    #[test]
+    fn flips_mirror_the_rotated_motion() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut flipped_x = frame(PointerOptions {
+            flip_x: true,
+            ..PointerOptions::default()
+        });
+        let mut flipped_both = frame(PointerOptions {
+            flip_x: true,
+            flip_y: true,
+            ..PointerOptions::default()
+        });
+        // (3, 1) at 90° is (-1, 3), mirrored after rotating.
+        move_by(&mut flipped_x, &state, &mut output, 3, 1);
+        move_by(&mut flipped_both, &state, &mut output, 3, 1);
+
+        assert_eq!(motion(&output.take_frames()), [Some((1, 3)), Some((1, -3))]);
+
+        // At 0° only X is negated, and the wheels aren't mirrored at all.
+        let state = LiveState::new(0.0);
+        move_by(&mut flipped_x, &state, &mut output, 3, 1);
+        for event in [
+            event(EventType::RELATIVE, RelativeAxisCode::REL_WHEEL.0, 1),
+            event(EventType::RELATIVE, RelativeAxisCode::REL_HWHEEL.0, -2),
+            syn(SynchronizationCode::SYN_REPORT),
+        ] {
+            flipped_x.handle(event, &state, &mut output).unwrap();
+        }
+
+        let frames = output.take_frames();
+        assert_eq!(motion(&frames), [Some((-3, 1)), None]);
+        let wheels: Vec<(u16, i32)> = frames[1]
+            .iter()
+            .map(|event| (event.code(), event.value()))
+            .collect();
+        assert_eq!(
+            wheels,
+            [
+                (RelativeAxisCode::REL_WHEEL.0, 1),
+                (RelativeAxisCode::REL_HWHEEL.0, -2)
+            ]
+        );
+    }
+
+    #[test]
+    fn an_angle_given_in_radians_makes_the_matrix_from_them() {
+        let angle_deg = 0.1f64.to_degrees();
+        let state = LiveState::new(angle_deg);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default()).with_angle_rad(angle_deg, 0.1);
+        move_by(&mut frame, &state, &mut output, 100, 0);
+        assert_eq!(frame.rotation, Matrix::rotation_rad(0.1));
+
+        // Once the angle is changed, it's in degrees like any other.
+        state.set_angle_deg(5.0);
+        move_by(&mut frame, &state, &mut output, 100, 0);
+        assert_eq!(frame.rotation, Matrix::rotation(5.0));
+    }
+
+    #[test]
+    fn acceleration_speeds_up_fast_motion_only() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            accel: 2.0,
+            ..PointerOptions::default()
+        });
+        // A frame moving m counts is scaled by m, so one count stays one.
+        move_by(&mut frame, &state, &mut output, 1, 0);
+        move_by(&mut frame, &state, &mut output, 3, -4);
+
+        assert_eq!(
+            motion(&output.take_frames()),
+            [Some((1, 0)), Some((15, -20))]
+        );
+    }
+
+    #[test]
+    fn the_deadzone_drops_small_motion_but_not_clicks() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            deadzone: 2.0,
+            ..PointerOptions::default()
+        });
+        // 1.4 counts is noise, 2.2 isn't.
+        move_by(&mut frame, &state, &mut output, 1, 1);
+        let press = event(EventType::KEY, KeyCode::BTN_LEFT.0, 1);
+        frame.handle(press, &state, &mut output).unwrap();
+        move_by(&mut frame, &state, &mut output, 1, -1);
+        move_by(&mut frame, &state, &mut output, 2, 1);
+
+        // Swallowed motion still leaves its SYN_REPORT.
+        let frames = output.take_frames();
+        assert_eq!(motion(&frames), [None, None, Some((2, 1))]);
+        assert_eq!(frames[1][0], press);
+    }
+
+    #[test]
+    fn motion_beyond_the_largest_delta_is_cut_off() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            max_delta: 10.0,
+            ..PointerOptions::default()
+        });
+        // (30, 5) at 90° is (-5, 30), cut off on the axis that's too far.
+        move_by(&mut frame, &state, &mut output, 30, 5);
+        move_by(&mut frame, &state, &mut output, 3, 4);
+
+        assert_eq!(
+            motion(&output.take_frames()),
+            [Some((-5, 10)), Some((-4, 3))]
+        );
+    }
+
+    #[test]
+    fn motion_near_the_ends_of_i32_is_clamped_not_wrapped() {
+        let mut output = output();
+        let max = i16::MAX as i32;
+        for (angle_deg, dx, dy, expected) in [
+            (0.0, i32::MAX, i32::MIN, (max, -max)),
+            (0.0, i32::MAX - 1, i32::MIN + 1, (max, -max)),
+            // (i32::MIN, i32::MAX) at 90°, both past the ends.
+            (90.0, i32::MAX, i32::MIN, (max, max)),
+            (180.0, i32::MIN, i32::MAX, (max, -max)),
+        ] {
+            let state = LiveState::new(angle_deg);
+            let mut frame = frame(PointerOptions::default());
+            move_by(&mut frame, &state, &mut output, dx, dy);
+            assert_eq!(
+                motion(&output.take_frames()),
+                [Some(expected)],
+                "({dx}, {dy}) at {angle_deg}°"
+            );
+        }
+    }
+
+    #[test]
+    fn inverted_scrolling_turns_both_wheels_around() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let scroll_options = ScrollOptions {
+            invert: true,
+            ..ScrollOptions::default()
+        };
+        let mut frame = Frame::new(
+            None,
+            scroll_options,
+            Pipeline::default(),
+            PointerOptions::default(),
+            Hotkeys::default(),
+            Vec::new(),
+            0.0,
+        );
+        for event in [
+            event(EventType::RELATIVE, RelativeAxisCode::REL_WHEEL.0, 1),
+            event(EventType::RELATIVE, RelativeAxisCode::REL_HWHEEL.0, -2),
+            syn(SynchronizationCode::SYN_REPORT),
+        ] {
+            frame.handle(event, &state, &mut output).unwrap();
+        }
+        // The pointer isn't part of it.
+        move_by(&mut frame, &state, &mut output, 3, 0);
+
+        let frames = output.take_frames();
+        let wheels: Vec<(u16, i32)> = frames[0]
+            .iter()
+            .map(|event| (event.code(), event.value()))
+            .collect();
+        assert!(
+            wheels.contains(&(RelativeAxisCode::REL_WHEEL.0, -1)),
+            "{wheels:?}"
+        );
+        assert!(
+            wheels.contains(&(RelativeAxisCode::REL_HWHEEL.0, 2)),
+            "{wheels:?}"
+        );
+        assert_eq!(motion(&frames), [None, Some((3, 0))]);
+    }
+
+    #[test]
+    fn swapped_buttons_come_out_as_each_other() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = Frame::new(
+            None,
+            ScrollOptions::default(),
+            Pipeline::default(),
+            PointerOptions::default(),
+            Hotkeys::default(),
+            vec![
+                (KeyCode::BTN_LEFT, KeyCode::BTN_RIGHT),
+                (KeyCode::BTN_RIGHT, KeyCode::BTN_LEFT),
+            ],
+            0.0,
+        );
+        for (button, value) in [
+            (KeyCode::BTN_LEFT, 1),
+            (KeyCode::BTN_LEFT, 0),
+            (KeyCode::BTN_RIGHT, 1),
+            (KeyCode::BTN_RIGHT, 0),
+            (KeyCode::BTN_MIDDLE, 1),
+        ] {
+            for event in [
+                event(EventType::KEY, button.0, value),
+                syn(SynchronizationCode::SYN_REPORT),
+            ] {
+                frame.handle(event, &state, &mut output).unwrap();
+            }
+        }
+
+        let sent: Vec<(u16, i32)> = output
+            .take_frames()
+            .iter()
+            .map(|frame| (frame[0].code(), frame[0].value()))
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                (KeyCode::BTN_RIGHT.0, 1),
+                (KeyCode::BTN_RIGHT.0, 0),
+                (KeyCode::BTN_LEFT.0, 1),
+                (KeyCode::BTN_LEFT.0, 0),
+                (KeyCode::BTN_MIDDLE.0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_without_motion_leaves_nothing_behind() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        let click = event(EventType::KEY, KeyCode::BTN_LEFT.0, 1);
+        frame.handle(click, &state, &mut output).unwrap();
+        frame
+            .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+            .unwrap();
+        move_by(&mut frame, &state, &mut output, 5, 0);
+
+        let frames = output.take_frames();
+        assert_eq!(frames[0].len(), 1);
+        assert_eq!(frames[0][0].code(), KeyCode::BTN_LEFT.0);
+        assert_eq!(motion(&frames), [None, Some((0, 5))]);
+    }
+
+    #[test]
+    fn a_scancode_stays_ahead_of_its_key() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        let scan = event(EventType::MISC, evdev::MiscCode::MSC_SCAN.0, 0x90001);
+        let click = event(EventType::KEY, KeyCode::BTN_LEFT.0, 1);
+        for event in [scan, click] {
+            frame.handle(event, &state, &mut output).unwrap();
+        }
+        move_by(&mut frame, &state, &mut output, 5, 0);
+
+        let frames = output.take_frames();
+        assert_eq!(frames[0][..2], [scan, click]);
+        assert_eq!(motion(&frames), [Some((0, 5))]);
+    }
+
+    #[test]
+    fn events_the_virtual_device_cant_have_are_left_out() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        let caps_lock = event(EventType::LED, evdev::LedCode::LED_CAPSL.0, 1);
+        let repeat = event(EventType::REPEAT, 0, 250);
+        let click = event(EventType::KEY, KeyCode::BTN_LEFT.0, 1);
+        for _ in 0..2 {
+            for event in [caps_lock, repeat, click] {
+                frame.handle(event, &state, &mut output).unwrap();
+            }
+            move_by(&mut frame, &state, &mut output, 5, 0);
+        }
+
+        let frames = output.take_frames();
+        assert_eq!(frames[0][0], click);
+        assert_eq!(frames[1][0], click);
+        assert_eq!(frame.unforwarded, [EventType::LED, EventType::REPEAT]);
+        assert_eq!(motion(&frames), [Some((0, 5)), Some((0, 5))]);
+    }
+
+    #[test]
+    fn events_of_one_frame_are_transformed_together() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        // Squaring the length, which tells 3 + 3 apart from 6.
+        let mut frame = frame(PointerOptions {
+            accel: 2.0,
+            ..PointerOptions::default()
+        });
+        let motion_x = event(EventType::RELATIVE, RelativeAxisCode::REL_X.0, 3);
+        frame.handle(motion_x, &state, &mut output).unwrap();
+        frame.handle(motion_x, &state, &mut output).unwrap();
+        frame
+            .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+            .unwrap();
+
+        let frames = output.take_frames();
+        assert_eq!(frames[0].len(), 2, "one REL_X and one REL_Y");
+        assert_eq!(motion(&frames), [Some((0, 36))]);
+    }
+
+    #[test]
+    fn back_to_back_frames_are_rotated_separately() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        move_by(&mut frame, &state, &mut output, 3, 0);
+        move_by(&mut frame, &state, &mut output, 0, 4);
+        move_by(&mut frame, &state, &mut output, 1, 1);
+
+        let frames = output.take_frames();
+        assert_eq!(
+            motion(&frames),
+            [Some((0, 3)), Some((-4, 0)), Some((-1, 1))]
+        );
+    }
+
+    #[test]
+    fn a_source_with_its_own_angle_ignores_the_live_one() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default()).with_angle_deg(Some(180.0));
+        move_by(&mut frame, &state, &mut output, 3, 0);
+        state.set_angle_deg(0.0);
+        move_by(&mut frame, &state, &mut output, 3, 0);
+
+        let frames = output.take_frames();
+        assert_eq!(motion(&frames), [Some((-3, 0)), Some((-3, 0))]);
+    }
+
+    #[test]
+    fn dropped_events_are_discarded_with_their_frame() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        let motion_x = event(EventType::RELATIVE, RelativeAxisCode::REL_X.0, 40);
+        frame.handle(motion_x, &state, &mut output).unwrap();
+        frame
+            .handle(syn(SynchronizationCode::SYN_DROPPED), &state, &mut output)
+            .unwrap();
+        frame.handle(motion_x, &state, &mut output).unwrap();
+        frame
+            .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+            .unwrap();
+        move_by(&mut frame, &state, &mut output, 2, 0);
+
+        assert_eq!(motion(&output.take_frames()), [Some((2, 0))]);
+    }
+
+    #[test]
+    fn passing_raw_motion_through_resets_the_remainder() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            sensitivity: 0.5,
+            ..PointerOptions::default()
+        });
+        // Half a count rounds up and leaves half a count owed.
+        move_by(&mut frame, &state, &mut output, 1, 0);
+        state.toggle_enabled();
+        move_by(&mut frame, &state, &mut output, 1, 0);
+        state.toggle_enabled();
+        // Without the reset, the owed half would cancel this one out.
+        move_by(&mut frame, &state, &mut output, 1, 0);
+
+        assert_eq!(
+            motion(&output.take_frames()),
+            [Some((1, 0)), Some((1, 0)), Some((1, 0))]
+        );
+    }
+
+    #[test]
+    fn toggling_off_settles_what_smoothing_still_owes() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            smooth: 0.5,
+            ..PointerOptions::default()
+        });
+        // Half of it comes out, the other half is owed.
+        move_by(&mut frame, &state, &mut output, 10, 0);
+        state.toggle_enabled();
+        // Toggled while the device is quiet, the rest comes out at once.
+        frame.settle(&state, &mut output).unwrap();
+        assert!(!frame.smoothing_pending());
+        frame.settle(&state, &mut output).unwrap();
+        move_by(&mut frame, &state, &mut output, 10, 0);
+        state.toggle_enabled();
+        // Toggled back on between frames, nothing's owed and rotation
+        // starts without a leftover.
+        move_by(&mut frame, &state, &mut output, 10, 0);
+        move_by(&mut frame, &state, &mut output, 1, 1);
+        frame.release(&state, &mut output).unwrap();
+
+        assert_eq!(
+            motion(&output.take_frames()),
+            [
+                Some((0, 5)),
+                Some((0, 5)),
+                Some((10, 0)),
+                Some((0, 5)),
+                Some((-1, 3)),
+                // What was owed when the device was let go of, which makes
+                // up the rest of (-1, 11).
+                Some((0, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_reset_key_straightens_out_and_clears_the_remainder() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let hotkeys = Hotkeys {
+            reset_key: Some(KeyCode::KEY_F13),
+            ..Hotkeys::default()
+        };
+        let pointer_options = PointerOptions {
+            sensitivity: 0.5,
+            ..PointerOptions::default()
+        };
+        let mut frame = Frame::new(
+            None,
+            ScrollOptions::default(),
+            Pipeline::default(),
+            pointer_options,
+            hotkeys,
+            Vec::new(),
+            0.0,
+        );
+        // Half a count rounds up and leaves half a count owed.
+        move_by(&mut frame, &state, &mut output, 1, 0);
+        state.set_angle_deg(10.0);
+        for value in [1, 0] {
+            let key = event(EventType::KEY, KeyCode::KEY_F13.0, value);
+            frame.handle(key, &state, &mut output).unwrap();
+        }
+        move_by(&mut frame, &state, &mut output, 1, 0);
+
+        assert_eq!(state.angle_deg(), 0.0);
+        assert_eq!(motion(&output.take_frames()), [Some((1, 0)), Some((1, 0))]);
+    }
+
+    #[test]
+    fn suppressed_jitter_comes_out_later_in_full() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            sensitivity: 0.4,
+            jitter_suppress: 1.0,
+            ..PointerOptions::default()
+        });
+        for _ in 0..5 {
+            move_by(&mut frame, &state, &mut output, 1, 0);
+        }
+
+        // 0.4 and 0.8 are held back rather than rounded up to a count early.
+        assert_eq!(
+            motion(&output.take_frames()),
+            [None, None, Some((1, 0)), None, Some((1, 0))]
+        );
+    }
+
+    #[test]
+    fn snapping_sticks_to_its_axis_near_the_diagonal() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            snap_axis: true,
+            snap_hysteresis: 0.25,
+            ..PointerOptions::default()
+        });
+        for (dx, dy) in [(10, 2), (10, 11), (11, 10), (10, 12), (10, 13), (12, 10)] {
+            move_by(&mut frame, &state, &mut output, dx, dy);
+        }
+
+        // Y has to get 25% larger than X to take over, and back again.
+        assert_eq!(
+            motion(&output.take_frames()),
+            [
+                Some((10, 0)),
+                Some((10, 0)),
+                Some((11, 0)),
+                Some((10, 0)),
+                Some((0, 13)),
+                Some((0, 10))
+            ]
+        );
+    }
+
+    #[test]
+    fn a_runaway_frame_is_slowed_down_without_turning() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            max_speed: Some(50.0),
+            ..PointerOptions::default()
+        });
+        move_by(&mut frame, &state, &mut output, 3000, 4000);
+        move_by(&mut frame, &state, &mut output, 3, 4);
+
+        // Rotated to (-4000, 3000), a speed of 5000 cut down to 50.
+        assert_eq!(
+            motion(&output.take_frames()),
+            [Some((-40, 30)), Some((-4, 3))]
+        );
+    }
+
+    #[test]
+    fn an_axis_cap_drops_the_rest_of_that_axis_only() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions {
+            max_speed_x: Some(20.0),
+            ..PointerOptions::default()
+        });
+        move_by(&mut frame, &state, &mut output, 30, -100);
+        move_by(&mut frame, &state, &mut output, 0, -3);
+
+        // Rotated to (100, 30), X is cut down to 20 and the 80 cut off
+        // doesn't come out in the next frame.
+        assert_eq!(
+            motion(&output.take_frames()),
+            [Some((20, 30)), Some((3, 0))]
+        );
+    }
+
+    #[test]
+    fn chords_switch_the_angle_and_keep_their_keys() {
+        let state = LiveState::new(3.0);
+        let mut output = output();
+        let (ctrl, alt, one, a) = (
+            KeyCode::KEY_LEFTCTRL,
+            KeyCode::KEY_LEFTALT,
+            KeyCode::KEY_1,
+            KeyCode::KEY_A,
+        );
+        let chord = |keys: &[KeyCode], angle_deg: f64, passthrough: bool| Chord {
+            keys: keys.to_vec(),
+            angle_deg,
+            passthrough,
+        };
+        let mut frame = Frame::new(
+            None,
+            ScrollOptions::default(),
+            Pipeline::default(),
+            PointerOptions::default(),
+            Hotkeys {
+                chords: vec![
+                    chord(&[ctrl, alt, one], 0.0, false),
+                    chord(&[KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_2], 45.0, true),
+                ],
+                ..Hotkeys::default()
+            },
+            Vec::new(),
+            0.0,
+        );
+        let mut keys = |strokes: &[(KeyCode, i32)]| {
+            for &(code, value) in strokes {
+                let key = event(EventType::KEY, code.0, value);
+                frame.handle(key, &state, &mut output).unwrap();
+                frame
+                    .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+                    .unwrap();
+            }
+            output
+                .take_frames()
+                .concat()
+                .iter()
+                .filter(|event| event.event_type() == EventType::KEY)
+                .map(|event| (KeyCode(event.code()), event.value()))
+                .collect::<Vec<_>>()
+        };
+
+        // None of the chord's keys come out, pressed or let go of.
+        let sent = keys(&[
+            (alt, 1),
+            (ctrl, 1),
+            (one, 1),
+            (one, 2),
+            (one, 0),
+            (ctrl, 0),
+            (alt, 0),
+        ]);
+        assert_eq!(sent, []);
+        assert_eq!(state.angle_deg(), 0.0);
+
+        // A key that can't be part of it lets the ones held back go first.
+        let sent = keys(&[(ctrl, 1), (a, 1), (a, 0), (ctrl, 0)]);
+        assert_eq!(sent, [(ctrl, 1), (a, 1), (a, 0), (ctrl, 0)]);
+        // And so does letting go of one.
+        assert_eq!(keys(&[(alt, 1), (alt, 0)]), [(alt, 1), (alt, 0)]);
+
+        // Passing through, only the key completing it is kept.
+        state.set_angle_deg(3.0);
+        let sent = keys(&[(KeyCode::KEY_2, 1), (KeyCode::KEY_2, 0)]);
+        assert_eq!(sent, [(KeyCode::KEY_2, 1), (KeyCode::KEY_2, 0)]);
+        assert_eq!(state.angle_deg(), 3.0);
+        let shift = KeyCode::KEY_LEFTSHIFT;
+        let sent = keys(&[
+            (shift, 1),
+            (KeyCode::KEY_2, 1),
+            (KeyCode::KEY_2, 0),
+            (shift, 0),
+        ]);
+        assert_eq!(sent, [(shift, 1), (shift, 0)]);
+        assert_eq!(state.angle_deg(), 45.0);
+    }
+
+    #[test]
+    fn a_click_keeps_its_place_around_the_motion() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        let motion_x = event(EventType::RELATIVE, RelativeAxisCode::REL_X.0, 3);
+        let motion_y = event(EventType::RELATIVE, RelativeAxisCode::REL_Y.0, 1);
+        let press = event(EventType::KEY, KeyCode::BTN_LEFT.0, 1);
+        let release = event(EventType::KEY, KeyCode::BTN_LEFT.0, 0);
+        for event in [
+            motion_x,
+            press,
+            motion_y,
+            syn(SynchronizationCode::SYN_REPORT),
+        ] {
+            frame.handle(event, &state, &mut output).unwrap();
+        }
+        for event in [release, motion_x, syn(SynchronizationCode::SYN_REPORT)] {
+            frame.handle(event, &state, &mut output).unwrap();
+        }
+
+        let frames = output.take_frames();
+        let codes = |frame: &[InputEvent]| {
+            frame
+                .iter()
+                .map(|event| (event.event_type(), event.code()))
+                .collect::<Vec<_>>()
+        };
+        let rel_x = (EventType::RELATIVE, RelativeAxisCode::REL_X.0);
+        let rel_y = (EventType::RELATIVE, RelativeAxisCode::REL_Y.0);
+        let button = (EventType::KEY, KeyCode::BTN_LEFT.0);
+        assert_eq!(codes(&frames[0]), [rel_x, rel_y, button]);
+        assert_eq!(codes(&frames[1]), [button, rel_x, rel_y]);
+        assert_eq!(motion(&frames), [Some((-1, 3)), Some((0, 3))]);
+    }
+
+    #[test]
+    fn the_dropped_kind_of_motion_is_not_emitted() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let position = event(EventType::ABSOLUTE, AbsoluteAxisCode::ABS_X.0, 100);
+
+        let mut relative = frame(PointerOptions::default()).with_axis_mode(Some(AxisMode::Rel));
+        relative.handle(position, &state, &mut output).unwrap();
+        move_by(&mut relative, &state, &mut output, 2, 0);
+        let mut absolute = frame(PointerOptions::default()).with_axis_mode(Some(AxisMode::Abs));
+        absolute.handle(position, &state, &mut output).unwrap();
+        move_by(&mut absolute, &state, &mut output, 2, 0);
+
+        let frames = output.take_frames();
+        assert_eq!(motion(&frames), [Some((2, 0)), None]);
+        assert!(
+            frames[0]
+                .iter()
+                .all(|e| e.event_type() != EventType::ABSOLUTE)
+        );
+        assert!(
+            frames[1]
+                .iter()
+                .any(|e| e.event_type() == EventType::ABSOLUTE)
+        );
+    }
+
+    #[test]
+    fn only_a_fast_shake_taps_the_key() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let options = ShakeOptions {
+            key: KeyCode::KEY_F24,
+            reversals: 4,
+            window: Duration::from_millis(600),
+            speed: 25.0,
+        };
+        let mut frame = frame(PointerOptions::default()).with_shake(Some(options));
+        let taps = |output: &mut Frames| {
+            output
+                .take_frames()
+                .iter()
+                .flatten()
+                .filter(|event| event.event_type() == EventType::KEY)
+                .count()
+        };
+
+        // Aiming back and forth, and going one way fast.
+        for dx in [10, -10, 12, -8, 10, -10] {
+            move_by(&mut frame, &state, &mut output, dx, 0);
+        }
+        for _ in 0..6 {
+            move_by(&mut frame, &state, &mut output, 40, 5);
+        }
+        assert_eq!(taps(&mut output), 0);
+
+        for dx in [40, -40, 40, -40, 40] {
+            move_by(&mut frame, &state, &mut output, dx, 0);
+        }
+        assert_eq!(taps(&mut output), 2);
+    }
+
+    #[test]
+    fn a_bouncing_button_clicks_once() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame =
+            frame(PointerOptions::default()).with_debounce(Some(Duration::from_millis(30)));
+        // Down, a bounce 5 ms after letting go, then a quick second click.
+        for (ms, value) in [(0, 1), (10, 0), (15, 1), (200, 0), (300, 1), (400, 0)] {
+            let mut click: nix::libc::input_event =
+                event(EventType::KEY, KeyCode::BTN_LEFT.0, value).into();
+            click.time.tv_usec = ms * 1000;
+            frame.handle(click.into(), &state, &mut output).unwrap();
+            frame
+                .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+                .unwrap();
+        }
+        // The last release is held back until it's clear it didn't bounce.
+        assert!(frame.debounce_due().is_some());
+        frame.debounce(&mut output).unwrap();
+
+        let clicks: Vec<(i32, i64)> = output
+            .take_frames()
+            .iter()
+            .flatten()
+            .filter(|event| event.event_type() == EventType::KEY)
+            .map(|event| {
+                let time: nix::libc::input_event = (*event).into();
+                (event.value(), time.time.tv_usec / 1000)
+            })
+            .collect();
+        assert_eq!(clicks, [(1, 0), (0, 200), (1, 300), (0, 400)]);
+    }
+
+    #[test]
+    fn letting_go_of_the_device_lets_go_of_a_debounced_button() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame =
+            frame(PointerOptions::default()).with_debounce(Some(Duration::from_secs(60)));
+        for value in [1, 0] {
+            let click = InputEvent::new_now(EventType::KEY.0, KeyCode::BTN_LEFT.0, value);
+            frame.handle(click, &state, &mut output).unwrap();
+            frame
+                .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+                .unwrap();
+        }
+        // Well within the window, but the device is going away.
+        frame.release(&state, &mut output).unwrap();
+
+        let clicks: Vec<i32> = output
+            .take_frames()
+            .iter()
+            .flatten()
+            .filter(|event| event.event_type() == EventType::KEY)
+            .map(InputEvent::value)
+            .collect();
+        assert_eq!(clicks, [1, 0]);
+        assert_eq!(frame.debounce_due(), None);
+    }
+
+    #[test]
+    fn coalesced_frames_add_up_to_the_same_motion() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default())
+            .with_frame_policy(FramePolicy::Coalesce(Duration::from_millis(20)));
+        let at = |ms: i64, event: InputEvent| {
+            let mut timed: nix::libc::input_event = event.into();
+            timed.time.tv_usec = ms * 1000;
+            InputEvent::from(timed)
+        };
+        let mut send = |frame: &mut Frame, ms: i64, events: &[InputEvent]| {
+            for &event in events {
+                frame.handle(at(ms, event), &state, &mut output).unwrap();
+            }
+            frame
+                .handle(
+                    at(ms, syn(SynchronizationCode::SYN_REPORT)),
+                    &state,
+                    &mut output,
+                )
+                .unwrap();
+        };
+        let rel = |dx: i32, dy: i32| {
+            [
+                event(EventType::RELATIVE, RelativeAxisCode::REL_X.0, dx),
+                event(EventType::RELATIVE, RelativeAxisCode::REL_Y.0, dy),
+            ]
+        };
+
+        // The first frame goes out, those within 20 ms of it are added to
+        // the first one after.
+        send(&mut frame, 0, &rel(3, 0));
+        send(&mut frame, 8, &rel(2, 1));
+        send(&mut frame, 16, &rel(1, 1));
+        send(&mut frame, 24, &rel(1, 0));
+        // A click isn't held back, and the motion before it goes first.
+        send(&mut frame, 30, &rel(5, 5));
+        send(
+            &mut frame,
+            32,
+            &[event(EventType::KEY, KeyCode::BTN_LEFT.0, 1)],
+        );
+        // Nothing comes after this one, so it goes out once it's due.
+        send(&mut frame, 40, &rel(2, 0));
+        assert_eq!(frame.coalesce_due(), Some(Duration::ZERO));
+        frame.release_coalesced(&state, &mut output).unwrap();
+        assert_eq!(frame.coalesce_due(), None);
+
+        let frames = output.take_frames();
+        assert_eq!(
+            motion(&frames),
+            [Some((3, 0)), Some((4, 2)), Some((5, 5)), Some((2, 0))]
+        );
+        assert_eq!(frames[2][0].event_type(), EventType::RELATIVE);
+        assert_eq!(frames[2].last().unwrap().event_type(), EventType::KEY);
+        assert_eq!(
+            "coalesce:0".parse::<FramePolicy>(),
+            Err("coalesce needs a positive number of milliseconds, not 0".to_string())
+        );
+        assert_eq!("per-sync".parse(), Ok(FramePolicy::PerSync));
+        assert!("coalesce".parse::<FramePolicy>().is_err());
+    }
+
+    #[test]
+    fn a_tablet_corner_turns_to_the_next_corner_at_90_degrees() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        frame.abs_plane = AbsPlane::from_absinfo(
+            [
+                (AbsoluteAxisCode::ABS_X, AbsInfo::new(0, 0, 1000, 0, 0, 0)),
+                (AbsoluteAxisCode::ABS_Y, AbsInfo::new(0, 0, 1000, 0, 0, 0)),
+            ],
+            AbsoluteAxisCode::ABS_X,
+            AbsoluteAxisCode::ABS_Y,
+        );
+        for (x, y) in [(0, 0), (1000, 0), (1000, 1000), (0, 1000)] {
+            for (code, value) in [(AbsoluteAxisCode::ABS_X, x), (AbsoluteAxisCode::ABS_Y, y)] {
+                let position = event(EventType::ABSOLUTE, code.0, value);
+                frame.handle(position, &state, &mut output).unwrap();
+            }
+            frame
+                .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+                .unwrap();
+        }
+
+        let positions: Vec<Vec<i32>> = output
+            .take_frames()
+            .iter()
+            .map(|frame| frame.iter().map(InputEvent::value).collect())
+            .collect();
+        // Clockwise round the surface, each corner one further on.
+        assert_eq!(
+            positions,
+            [vec![1000, 0], vec![1000, 1000], vec![0, 1000], vec![0, 0]]
+        );
+    }
+
+    #[test]
+    fn a_turned_screen_gets_its_corners_back() {
+        let mut output = output();
+        // A landscape panel, which turned by a right angle is a portrait one.
+        let absinfo = [
+            (AbsoluteAxisCode::ABS_X, AbsInfo::new(0, 0, 1920, 0, 0, 0)),
+            (AbsoluteAxisCode::ABS_Y, AbsInfo::new(0, 0, 1080, 0, 0, 0)),
+        ];
+        let touch = |frame: &mut Frame, state: &LiveState, output: &mut Frames, x, y| {
+            for (code, value) in [(AbsoluteAxisCode::ABS_X, x), (AbsoluteAxisCode::ABS_Y, y)] {
+                let position = event(EventType::ABSOLUTE, code.0, value);
+                frame.handle(position, state, output).unwrap();
+            }
+            frame
+                .handle(syn(SynchronizationCode::SYN_REPORT), state, output)
+                .unwrap();
+        };
+        // The top left corner, a quarter of the way along the top edge, and
+        // the centre.
+        for (angle_deg, expected) in [
+            (90.0, [(1920, 0), (1920, 270), (960, 540)]),
+            (180.0, [(1920, 1080), (1440, 1080), (960, 540)]),
+            (270.0, [(0, 1080), (0, 810), (960, 540)]),
+        ] {
+            let state = LiveState::new(angle_deg);
+            let mut frame = frame(PointerOptions::default());
+            frame.abs_plane =
+                AbsPlane::from_absinfo(absinfo, AbsoluteAxisCode::ABS_X, AbsoluteAxisCode::ABS_Y);
+            touch(&mut frame, &state, &mut output, 0, 0);
+            touch(&mut frame, &state, &mut output, 480, 0);
+            touch(&mut frame, &state, &mut output, 960, 540);
+
+            let positions: Vec<(i32, i32)> = output
+                .take_frames()
+                .iter()
+                .map(|frame| {
+                    let value = |code: AbsoluteAxisCode| {
+                        frame
+                            .iter()
+                            .find(|event| event.code() == code.0)
+                            .map_or(-1, |event| event.value())
+                    };
+                    (
+                        value(AbsoluteAxisCode::ABS_X),
+                        value(AbsoluteAxisCode::ABS_Y),
+                    )
+                })
+                .collect();
+            assert_eq!(positions, expected, "at {angle_deg}°");
+        }
+    }
+
+    #[test]
+    fn an_offset_moves_touches_and_stops_at_the_edge() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default()).with_abs_offset((0.25, -0.5));
+        frame.abs_plane = AbsPlane::from_absinfo(
+            [
+                (AbsoluteAxisCode::ABS_X, AbsInfo::new(0, 0, 1920, 0, 0, 0)),
+                (AbsoluteAxisCode::ABS_Y, AbsInfo::new(0, 0, 1080, 0, 0, 0)),
+            ],
+            AbsoluteAxisCode::ABS_X,
+            AbsoluteAxisCode::ABS_Y,
+        );
+        for (x, y) in [(960, 540), (0, 1080)] {
+            for (code, value) in [(AbsoluteAxisCode::ABS_X, x), (AbsoluteAxisCode::ABS_Y, y)] {
+                let position = event(EventType::ABSOLUTE, code.0, value);
+                frame.handle(position, &state, &mut output).unwrap();
+            }
+            frame
+                .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+                .unwrap();
+        }
+        state.toggle_enabled();
+        frame
+            .handle(
+                event(EventType::ABSOLUTE, AbsoluteAxisCode::ABS_X.0, 100),
+                &state,
+                &mut output,
+            )
+            .unwrap();
+        frame
+            .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+            .unwrap();
+
+        let positions: Vec<Vec<i32>> = output
+            .take_frames()
+            .iter()
+            .map(|frame| {
+                frame
+                    .iter()
+                    .filter(|event| event.event_type() == EventType::ABSOLUTE)
+                    .map(InputEvent::value)
+                    .collect()
+            })
+            .collect();
+        // The centre moves by a quarter of the width and half the height; the
+        // bottom left corner, turned to the top left, can't go further up.
+        // Switched off, nothing is moved either.
+        assert_eq!(positions, [vec![1440, 0], vec![480, 0], vec![100, 1080]]);
+    }
+
+    #[test]
+    fn palms_never_reach_the_virtual_device() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        let info = |max| AbsInfo::new(0, 0, max, 0, 0, 0);
+        frame.touches = Touches::from_absinfo([
+            (AbsoluteAxisCode::ABS_MT_SLOT, info(4)),
+            (AbsoluteAxisCode::ABS_MT_POSITION_X, info(1000)),
+            (AbsoluteAxisCode::ABS_MT_POSITION_Y, info(1000)),
+            (AbsoluteAxisCode::ABS_MT_TOUCH_MAJOR, info(100)),
+        ]);
+        let mut frame = frame.with_palm(Some(PalmOptions {
+            edge: 0.1,
+            ..PalmOptions::default()
+        }));
+        use AbsoluteAxisCode as Abs;
+        let frames: [&[(Abs, i32)]; 6] = [
+            // A finger in the middle.
+            &[
+                (Abs::ABS_MT_SLOT, 0),
+                (Abs::ABS_MT_TRACKING_ID, 10),
+                (Abs::ABS_MT_POSITION_X, 500),
+                (Abs::ABS_MT_POSITION_Y, 500),
+                (Abs::ABS_MT_TOUCH_MAJOR, 20),
+            ],
+            // Another one at the right edge.
+            &[
+                (Abs::ABS_MT_SLOT, 1),
+                (Abs::ABS_MT_TRACKING_ID, 11),
+                (Abs::ABS_MT_POSITION_X, 990),
+                (Abs::ABS_MT_POSITION_Y, 500),
+                (Abs::ABS_MT_TOUCH_MAJOR, 20),
+            ],
+            // Both move, the second one out of the edge.
+            &[
+                (Abs::ABS_MT_SLOT, 0),
+                (Abs::ABS_MT_POSITION_X, 510),
+                (Abs::ABS_MT_SLOT, 1),
+                (Abs::ABS_MT_POSITION_X, 800),
+            ],
+            &[(Abs::ABS_MT_TRACKING_ID, -1)],
+            // The finger flattens out.
+            &[(Abs::ABS_MT_SLOT, 0), (Abs::ABS_MT_TOUCH_MAJOR, 80)],
+            &[(Abs::ABS_MT_TRACKING_ID, -1)],
+        ];
+        for events in frames {
+            for &(code, value) in events {
+                let event = event(EventType::ABSOLUTE, code.0, value);
+                frame.handle(event, &state, &mut output).unwrap();
+            }
+            frame
+                .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+                .unwrap();
+        }
+
+        let contact_events: Vec<Vec<(Abs, i32)>> = output
+            .take_frames()
+            .iter()
+            .map(|frame| {
+                frame
+                    .iter()
+                    .filter(|event| event.event_type() == EventType::ABSOLUTE)
+                    .map(|event| (Abs(event.code()), event.value()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            contact_events,
+            [
+                vec![
+                    (Abs::ABS_MT_SLOT, 0),
+                    (Abs::ABS_MT_TRACKING_ID, 10),
+                    (Abs::ABS_MT_TOUCH_MAJOR, 20),
+                    (Abs::ABS_MT_POSITION_X, 500),
+                    (Abs::ABS_MT_POSITION_Y, 500),
+                ],
+                vec![(Abs::ABS_MT_SLOT, 1)],
+                vec![
+                    (Abs::ABS_MT_SLOT, 0),
+                    (Abs::ABS_MT_POSITION_X, 510),
+                    (Abs::ABS_MT_POSITION_Y, 500),
+                    (Abs::ABS_MT_SLOT, 1),
+                ],
+                vec![],
+                vec![(Abs::ABS_MT_SLOT, 0), (Abs::ABS_MT_TRACKING_ID, -1)],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn slightly_rotated_scrolling_adds_up() {
+        let state = LiveState::new(5.0);
+        let mut output = output();
+        let scroll_options = ScrollOptions {
+            rotate: true,
+            ..ScrollOptions::default()
+        };
+        let mut frame = Frame::new(
+            None,
+            scroll_options,
+            Pipeline::default(),
+            PointerOptions::default(),
+            Hotkeys::default(),
+            Vec::new(),
+            0.0,
+        );
+        let click = event(EventType::RELATIVE, RelativeAxisCode::REL_WHEEL.0, 1);
+        for _ in 0..1000 {
+            for event in [click, syn(SynchronizationCode::SYN_REPORT)] {
+                frame.handle(event, &state, &mut output).unwrap();
+            }
+        }
+
+        // Each click moves the horizontal wheel by less than half a click,
+        // which would all be lost without the remainder carried.
+        let frames = output.take_frames();
+        let total = |code: RelativeAxisCode| -> i32 {
+            frames
+                .iter()
+                .flatten()
+                .filter(|event| event.event_type() == EventType::RELATIVE && event.code() == code.0)
+                .map(InputEvent::value)
+                .sum()
+        };
+        let (horizontal, vertical) = (
+            total(RelativeAxisCode::REL_HWHEEL),
+            total(RelativeAxisCode::REL_WHEEL),
+        );
+        let angle = 5f64.to_radians();
+        assert!((horizontal.abs() as f64 - 1000.0 * angle.sin()).abs() <= 1.0);
+        assert!((vertical as f64 - 1000.0 * angle.cos()).abs() <= 1.0);
+    }
+
+    #[test]
+    fn the_wheels_can_be_rotated_without_the_pointer() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let scroll_options = ScrollOptions {
+            rotate: true,
+            ..ScrollOptions::default()
+        };
+        // Left unrotated, but not unscaled.
+        let pointer_options = PointerOptions {
+            sensitivity: 2.0,
+            ..PointerOptions::default()
+        };
+        let mut frame = Frame::new(
+            None,
+            scroll_options,
+            Pipeline::default(),
+            pointer_options,
+            Hotkeys::default(),
+            Vec::new(),
+            0.0,
+        )
+        .with_rotate_pointer(false);
+        move_by(&mut frame, &state, &mut output, 3, 0);
+        let click = event(EventType::RELATIVE, RelativeAxisCode::REL_WHEEL.0, 1);
+        for event in [click, syn(SynchronizationCode::SYN_REPORT)] {
+            frame.handle(event, &state, &mut output).unwrap();
+        }
+
+        let frames = output.take_frames();
+        assert_eq!(motion(&frames), [Some((6, 0)), None]);
+        assert_eq!(frames[1].len(), 1);
+        assert_eq!(frames[1][0].code(), RelativeAxisCode::REL_HWHEEL.0);
+    }
+
+    #[test]
+    fn motion_is_only_rotated_while_the_hold_button_is_down() {
+        let state = LiveState::new(90.0);
+        state.set_paused(true);
+        let mut output = output();
+        let hotkeys = Hotkeys {
+            hold_button: Some(KeyCode::BTN_EXTRA),
+            ..Hotkeys::default()
+        };
+        let mut frame = Frame::new(
+            None,
+            ScrollOptions::default(),
+            Pipeline::default(),
+            PointerOptions::default(),
+            hotkeys,
+            Vec::new(),
+            0.0,
+        );
+        let button = |value| event(EventType::KEY, KeyCode::BTN_EXTRA.0, value);
+        move_by(&mut frame, &state, &mut output, 3, 0);
+        frame.handle(button(1), &state, &mut output).unwrap();
+        move_by(&mut frame, &state, &mut output, 3, 0);
+        frame.handle(button(0), &state, &mut output).unwrap();
+        move_by(&mut frame, &state, &mut output, 3, 0);
+
+        let frames = output.take_frames();
+        assert_eq!(motion(&frames), [Some((3, 0)), Some((0, 3)), Some((3, 0))]);
+        // The button itself is swallowed.
+        assert!(
+            frames
+                .iter()
+                .flatten()
+                .all(|event| event.event_type() != EventType::KEY)
+        );
+    }
+
+    #[test]
+    fn a_dial_taken_as_a_wheel_turns_with_it() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let dial = event(EventType::RELATIVE, RelativeAxisCode::REL_DIAL.0, 1);
+        for mode in [DialMode::Pass, DialMode::Wheel] {
+            let scroll_options = ScrollOptions {
+                rotate: true,
+                dial: mode,
+                ..ScrollOptions::default()
+            };
+            let mut frame = Frame::new(
+                None,
+                scroll_options,
+                Pipeline::default(),
+                PointerOptions::default(),
+                Hotkeys::default(),
+                Vec::new(),
+                0.0,
+            );
+            for event in [dial, syn(SynchronizationCode::SYN_REPORT)] {
+                frame.handle(event, &state, &mut output).unwrap();
+            }
+        }
+
+        let codes: Vec<Vec<u16>> = output
+            .take_frames()
+            .iter()
+            .map(|frame| frame.iter().map(InputEvent::code).collect())
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                [RelativeAxisCode::REL_DIAL.0],
+                [RelativeAxisCode::REL_HWHEEL.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_kept_wheel_resolution_is_emitted() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let click = event(EventType::RELATIVE, RelativeAxisCode::REL_WHEEL.0, 1);
+        let steps = event(
+            EventType::RELATIVE,
+            RelativeAxisCode::REL_WHEEL_HI_RES.0,
+            120,
+        );
+
+        for mode in [WheelMode::LowRes, WheelMode::HiRes] {
+            let mut frame = frame(PointerOptions::default()).with_wheel_mode(Some(mode));
+            for event in [click, steps, syn(SynchronizationCode::SYN_REPORT)] {
+                frame.handle(event, &state, &mut output).unwrap();
+            }
+        }
+
+        let codes = output
+            .take_frames()
+            .iter()
+            .map(|frame| frame.iter().map(InputEvent::code).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            [
+                [RelativeAxisCode::REL_WHEEL.0],
+                [RelativeAxisCode::REL_WHEEL_HI_RES.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn with_the_wheels_off_only_the_pointer_moves() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let scroll_options = ScrollOptions {
+            rotate: true,
+            ..ScrollOptions::default()
+        };
+        let mut frame = Frame::new(
+            None,
+            scroll_options,
+            Pipeline::default(),
+            PointerOptions::default(),
+            Hotkeys::default(),
+            Vec::new(),
+            0.0,
+        )
+        .with_wheel_mode(Some(WheelMode::Off));
+        for code in [
+            RelativeAxisCode::REL_WHEEL,
+            RelativeAxisCode::REL_HWHEEL,
+            RelativeAxisCode::REL_WHEEL_HI_RES,
+            RelativeAxisCode::REL_HWHEEL_HI_RES,
+        ] {
+            let turn = event(EventType::RELATIVE, code.0, 1);
+            frame.handle(turn, &state, &mut output).unwrap();
+        }
+        move_by(&mut frame, &state, &mut output, 5, 0);
+
+        let frames = output.take_frames();
+        assert_eq!(frames[0].len(), 2);
+        assert_eq!(motion(&frames), [Some((0, 5))]);
+    }
+}
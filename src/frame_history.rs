@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+pub use sensor_alignment::frame::FrameRecord;
+
+// Set by `keep`, with --frame-history; without it recording costs a check.
+static HISTORY: OnceLock<Mutex<History>> = OnceLock::new();
+
+/// The last frames, the oldest dropped to make room for a new one. Room is
+/// made as frames come in rather than all at once, so a long history that
+/// never fills up costs nothing.
+pub struct History {
+    frames: VecDeque<FrameRecord>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            frames: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, frame: FrameRecord) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Writes the frames as lines of JSON, oldest first.
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        for frame in &self.frames {
+            serde_json::to_writer(&mut *writer, frame)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Starts keeping the last `capacity` frames for [`dump`].
+pub fn keep(capacity: usize) {
+    let _ = HISTORY.set(Mutex::new(History::new(capacity)));
+}
+
+/// Adds a frame to the history, if one is kept.
+pub fn record(frame: impl FnOnce() -> FrameRecord) {
+    if let Some(history) = HISTORY.get() {
+        history
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(frame());
+    }
+}
+
+/// Where a dump goes without --frame-history-file: the temporary directory,
+/// with the process ID in the name so that instances don't overwrite each
+/// other's.
+pub fn default_path() -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "sensor_alignment-frames-{}.jsonl",
+        std::process::id()
+    ))
+}
+
+/// Writes the frames kept so far to `path`, replacing what a previous dump
+/// left there, and returns how many there were. Nothing is written if no
+/// history is kept.
+pub fn dump(path: &Path) -> std::io::Result<Option<usize>> {
+    let Some(history) = HISTORY.get() else {
+        return Ok(None);
+    };
+    let history = history
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut writer = BufWriter::new(File::create(path)?);
+    history.write_to(&mut writer)?;
+    writer.flush()?;
+    Ok(Some(history.frames.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn only_the_last_frames_are_kept_and_dumped_as_json_lines() {
+        let mut history = History::new(2);
+        let at = UNIX_EPOCH + Duration::from_millis(1500);
+        history.push(FrameRecord::new(at, (1, 0), Some((1, 0)), 0.0));
+        history.push(FrameRecord::new(at, (2, 0), None, 0.0));
+        history.push(FrameRecord::new(at, (3, 4), Some((-4, 3)), 90.0));
+
+        let mut dumped = Vec::new();
+        history.write_to(&mut dumped).unwrap();
+        let text = String::from_utf8(dumped).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"time":1.5,"raw":[2,0],"out":null,"angle_deg":0.0}"#,
+                r#"{"time":1.5,"raw":[3,4],"out":[-4,3],"angle_deg":90.0}"#,
+            ]
+        );
+        let parsed: FrameRecord = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(parsed, FrameRecord::new(at, (3, 4), Some((-4, 3)), 90.0));
+    }
+}
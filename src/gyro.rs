@@ -0,0 +1,166 @@
+use std::os::fd::{AsFd, BorrowedFd};
+use std::path::Path;
+
+use evdev::{AbsoluteAxisCode, Device, EventSummary, InputEvent};
+
+/// How much of each new reading the smoothed gravity vector takes in. The
+/// rest is what it was, so a shaky hand doesn't make the angle jitter.
+pub const SMOOTHING: f64 = 0.1;
+
+/// Smallest change of the tilt passed on, in degrees, so that the angle
+/// isn't set again for every reading.
+pub const MIN_CHANGE_DEG: f64 = 0.25;
+
+/// An accelerometer whose X and Y axes tell how a handheld device is tilted,
+/// for --gyro-device. Gravity pulls along +Y while it's held upright, which
+/// is a tilt of 0°; turned the other way, gravity swings round and the tilt
+/// is the angle it turned by.
+///
+/// Accelerometers are only read, never grabbed, since others such as the
+/// desktop's screen rotation may be listening too.
+pub struct Gyro {
+    device: Device,
+    // The latest reading, updated axis by axis until `SYN_REPORT`.
+    raw: (i32, i32),
+    tilt: Tilt,
+}
+
+/// The tilt worked out from one reading after another, apart from the
+/// device they're read from.
+#[derive(Debug, Default)]
+struct Tilt {
+    smoothed: Option<(f64, f64)>,
+    reported: Option<f64>,
+}
+
+impl Gyro {
+    pub fn open(path: &Path) -> std::io::Result<Gyro> {
+        let device = Device::open(path)?;
+        let has_axes = device.supported_absolute_axes().is_some_and(|axes| {
+            axes.contains(AbsoluteAxisCode::ABS_X) && axes.contains(AbsoluteAxisCode::ABS_Y)
+        });
+        if !has_axes {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} has no X and Y axes to read gravity from",
+                    path.display()
+                ),
+            ));
+        }
+        device.set_nonblocking(true)?;
+        let state = device.get_abs_state()?;
+        let raw = (
+            state[AbsoluteAxisCode::ABS_X.0 as usize].value,
+            state[AbsoluteAxisCode::ABS_Y.0 as usize].value,
+        );
+        let mut tilt = Tilt::default();
+        tilt.settle(raw);
+        Ok(Gyro { device, raw, tilt })
+    }
+
+    /// The tilt in degrees as last reported, `None` while the device lies
+    /// flat.
+    pub fn tilt_deg(&self) -> Option<f64> {
+        self.tilt.reported
+    }
+
+    /// Takes in the readings the device has ready, returning the new tilt in
+    /// degrees if it moved by at least `MIN_CHANGE_DEG`.
+    pub fn read(&mut self) -> std::io::Result<Option<f64>> {
+        let events: Vec<InputEvent> = match self.device.fetch_events() {
+            Ok(events) => events.collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut changed = false;
+        for event in events {
+            match event.destructure() {
+                EventSummary::AbsoluteAxis(_, AbsoluteAxisCode::ABS_X, value) => self.raw.0 = value,
+                EventSummary::AbsoluteAxis(_, AbsoluteAxisCode::ABS_Y, value) => self.raw.1 = value,
+                EventSummary::Synchronization(..) => changed |= self.tilt.settle(self.raw),
+                _ => {}
+            }
+        }
+        Ok(changed.then_some(self.tilt.reported).flatten())
+    }
+}
+
+impl Tilt {
+    /// Folds a reading into the smoothed one and says whether the tilt
+    /// moved far enough to report.
+    fn settle(&mut self, raw: (i32, i32)) -> bool {
+        let (x, y) = (raw.0 as f64, raw.1 as f64);
+        let (sx, sy) = match self.smoothed {
+            Some((sx, sy)) => (sx + (x - sx) * SMOOTHING, sy + (y - sy) * SMOOTHING),
+            None => (x, y),
+        };
+        self.smoothed = Some((sx, sy));
+        // Lying flat, gravity is all on Z and there's no telling.
+        if sx == 0.0 && sy == 0.0 {
+            return false;
+        }
+        let tilt = sx.atan2(sy).to_degrees();
+        let moved = self.reported.is_none_or(|reported| {
+            sensor_alignment::normalize_angle_deg(tilt - reported).abs() >= MIN_CHANGE_DEG
+        });
+        if moved {
+            self.reported = Some(tilt);
+        }
+        moved
+    }
+}
+
+impl AsFd for Gyro {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.device.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_reading_is_taken_as_it_is() {
+        let mut upright = Tilt::default();
+        assert!(upright.settle((0, 1000)));
+        assert_eq!(upright.reported, Some(0.0));
+
+        let mut sideways = Tilt::default();
+        assert!(sideways.settle((1000, 0)));
+        assert_eq!(sideways.reported, Some(90.0));
+    }
+
+    #[test]
+    fn lying_flat_tells_nothing() {
+        let mut tilt = Tilt::default();
+        assert!(!tilt.settle((0, 0)));
+        assert_eq!(tilt.reported, None);
+    }
+
+    #[test]
+    fn a_turn_is_followed_gradually() {
+        let mut tilt = Tilt::default();
+        tilt.settle((0, 1000));
+        assert!(tilt.settle((1000, 0)));
+        let first = tilt.reported.unwrap();
+        assert!(first > 0.0 && first < 10.0, "{first}");
+
+        for _ in 0..100 {
+            tilt.settle((1000, 0));
+        }
+        let settled = tilt.reported.unwrap();
+        assert!((settled - 90.0).abs() < MIN_CHANGE_DEG, "{settled}");
+    }
+
+    #[test]
+    fn jitter_below_the_smallest_change_isnt_reported() {
+        let mut tilt = Tilt::default();
+        tilt.settle((0, 1000));
+        // About 0.6°, a tenth of which the smoothing lets through.
+        assert!(!tilt.settle((10, 1000)));
+        assert!(!tilt.settle((-10, 1000)));
+        assert_eq!(tilt.reported, Some(0.0));
+    }
+}
@@ -0,0 +1,75 @@
+use std::os::fd::{AsFd, BorrowedFd};
+use std::path::Path;
+
+use log::debug;
+use nix::errno::Errno;
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+use crate::signals;
+
+pub const INPUT_DIR: &str = "/dev/input";
+
+/// Watches a directory of device nodes so that a missing device can be waited
+/// for without polling.
+pub struct DeviceWatcher {
+    inotify: Inotify,
+}
+
+// udev sets up permissions after creating the node, hence IN_ATTRIB.
+const WATCHED: AddWatchFlags = AddWatchFlags::IN_CREATE
+    .union(AddWatchFlags::IN_DELETE)
+    .union(AddWatchFlags::IN_ATTRIB);
+
+impl DeviceWatcher {
+    pub fn new(dir: impl AsRef<Path>) -> std::io::Result<DeviceWatcher> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        inotify.add_watch(dir.as_ref(), WATCHED)?;
+        Ok(DeviceWatcher { inotify })
+    }
+
+    /// Watches the directory `path` is in as well, so that e.g. a link in
+    /// `/dev/input/by-id`, which udev adds after the node itself, wakes up
+    /// `wait` too. Watching a directory twice is harmless; one that doesn't
+    /// exist yet is left for the next call, its creation showing up in the
+    /// directory above.
+    pub fn watch_parent_of(&self, path: &Path) {
+        if let Some(dir) = path.parent()
+            && dir != Path::new(INPUT_DIR)
+            && let Err(e) = self.inotify.add_watch(dir, WATCHED)
+            && e != Errno::ENOENT
+        {
+            debug!("Watching {} failed: {e}", dir.display());
+        }
+    }
+
+    /// Blocks until a device node is added, removed or changed, a signal
+    /// arrives or the time given with --duration is up.
+    pub fn wait(&self) -> std::io::Result<()> {
+        let mut fds = [PollFd::new(self.inotify.as_fd(), PollFlags::POLLIN)];
+        let timeout = match signals::until_deadline() {
+            Some(left) => PollTimeout::try_from(left).unwrap_or(PollTimeout::MAX),
+            None => PollTimeout::NONE,
+        };
+        match poll(&mut fds, timeout) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.drain()
+    }
+
+    /// Discards pending notifications; only the wakeup matters, not which
+    /// node changed.
+    pub fn drain(&self) -> std::io::Result<()> {
+        match self.inotify.read_events() {
+            Ok(_) | Err(Errno::EAGAIN) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl AsFd for DeviceWatcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inotify.as_fd()
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use log::{info, warn};
+use sensor_alignment::Degrees;
+use sensor_alignment::state::LiveState;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::signals;
+
+/// Longer request bodies than this can't be an angle.
+const MAX_BODY: u64 = 64;
+
+/// The page at `/`, a slider for the angle and a button for `/toggle`.
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>sensor_alignment</title>
+</head>
+<body>
+<h1>sensor_alignment</h1>
+<p><input id="slider" type="range" min="-180" max="180" step="0.1" style="width: 100%">
+<p>Angle: <output id="angle"></output>°
+<p><button id="toggle">Toggle rotation</button> <span id="enabled"></span>
+<script>
+const slider = document.getElementById("slider");
+const angle = document.getElementById("angle");
+fetch("/angle").then(reply => reply.text()).then(text => {
+  slider.value = text;
+  angle.value = text;
+});
+slider.addEventListener("input", () => {
+  angle.value = slider.value;
+  fetch("/angle", { method: "POST", body: slider.value });
+});
+document.getElementById("toggle").addEventListener("click", () => {
+  fetch("/toggle", { method: "POST" }).then(reply => reply.text()).then(text => {
+    document.getElementById("enabled").textContent = text == "true" ? "on" : "off";
+  });
+});
+</script>
+</body>
+</html>
+"#;
+
+/// Listens on `addr` and serves the angle from a thread of its own for as
+/// long as the process runs, another way into the state the control socket
+/// and D-Bus change:
+///
+/// - `GET /` is a page with a slider for the angle
+/// - `GET /angle` returns the current angle
+/// - `POST /angle` with the angle in degrees as the body changes it
+/// - `POST /toggle` switches rotation off or back on and returns whether
+///   it's on now
+///
+/// There's no authentication, so `addr` should only be reachable by those
+/// who may change the angle. Returns the address listened on, which tells
+/// the port when `addr` left it to the system.
+pub fn serve(addr: SocketAddr, state: Arc<LiveState>) -> std::io::Result<SocketAddr> {
+    let server = Server::http(addr).map_err(|e| std::io::Error::other(format!("{addr}: {e}")))?;
+    let bound = server.server_addr().to_ip().unwrap_or(addr);
+    std::thread::spawn(move || {
+        signals::block_on_current_thread();
+        for mut request in server.incoming_requests() {
+            let response = respond(&mut request, &state);
+            if let Err(e) = request.respond(response) {
+                warn!("HTTP client failed: {e}");
+            }
+        }
+    });
+    Ok(bound)
+}
+
+fn respond(request: &mut Request, state: &LiveState) -> Response<std::io::Cursor<Vec<u8>>> {
+    match (request.method(), request.url()) {
+        (Method::Get, "/") => Response::from_string(PAGE).with_header(
+            Header::from_bytes("Content-Type", "text/html; charset=utf-8")
+                .expect("the header is valid"),
+        ),
+        (Method::Get, "/angle") => Response::from_string(state.angle_deg().to_string()),
+        (Method::Post, "/angle") => {
+            let mut body = String::new();
+            if let Err(e) = request.as_reader().take(MAX_BODY).read_to_string(&mut body) {
+                return Response::from_string(format!("error: {e}")).with_status_code(400);
+            }
+            match body.trim().parse::<f64>() {
+                Ok(angle) if angle.is_finite() => {
+                    state.set_angle_deg(angle);
+                    info!("Angle set to {} over HTTP", Degrees(angle));
+                    Response::from_string(angle.to_string())
+                }
+                _ => Response::from_string(format!("error: invalid angle '{}'", body.trim()))
+                    .with_status_code(400),
+            }
+        }
+        (Method::Post, "/toggle") => {
+            let enabled = state.toggle_enabled();
+            info!("Rotation {}", if enabled { "on" } else { "off" });
+            Response::from_string(enabled.to_string())
+        }
+        (_, "/" | "/angle" | "/toggle") => {
+            Response::from_string("error: method not allowed").with_status_code(405)
+        }
+        (_, url) => {
+            Response::from_string(format!("error: no such path '{url}'")).with_status_code(404)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    use super::*;
+
+    /// Sends one request and returns the status line and the body.
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        let (head, body) = reply.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[test]
+    fn the_api_changes_the_live_state() {
+        let state = Arc::new(LiveState::new(2.0));
+        let addr = serve("127.0.0.1:0".parse().unwrap(), Arc::clone(&state)).unwrap();
+
+        assert_eq!(request(addr, "GET", "/angle", "").1, "2");
+        let (status, body) = request(addr, "POST", "/angle", "-4.5\n");
+        assert!(status.contains("200"), "{status}");
+        assert_eq!(body, "-4.5");
+        assert_eq!(state.angle_deg(), -4.5);
+
+        assert!(request(addr, "POST", "/angle", "up").0.contains("400"));
+        assert!(request(addr, "POST", "/angle", "NaN").0.contains("400"));
+        assert_eq!(state.angle_deg(), -4.5);
+
+        assert_eq!(request(addr, "POST", "/toggle", "").1, "false");
+        assert!(!state.enabled());
+        assert!(request(addr, "GET", "/toggle", "").0.contains("405"));
+        assert!(request(addr, "GET", "/nothing", "").0.contains("404"));
+        assert!(
+            request(addr, "GET", "/", "")
+                .1
+                .contains("<input id=\"slider\"")
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::ffi::c_long;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::os::unix::fs::OpenOptionsExt;
+
+use evdev::{AttributeSetRef, Device, EventType, InputEvent, InputId, LedCode};
+use nix::fcntl::OFlag;
+
+/// What the kernel reads and writes for an `input_event`: the time as two
+/// longs, then the type, code and value.
+const EVENT_SIZE: usize = 2 * size_of::<c_long>() + 8;
+
+/// The few uinput ioctls the evdev crate's builder has no way to make.
+mod sys {
+    /// `struct uinput_setup` from `<linux/uinput.h>`.
+    #[repr(C)]
+    pub struct UinputSetup {
+        pub id: [u16; 4],
+        pub name: [u8; 80],
+        pub ff_effects_max: u32,
+    }
+
+    nix::ioctl_none!(ui_dev_create, b'U', 1);
+    nix::ioctl_write_ptr!(ui_dev_setup, b'U', 3, UinputSetup);
+    nix::ioctl_write_int!(ui_set_evbit, b'U', 100);
+    nix::ioctl_write_int!(ui_set_keybit, b'U', 101);
+    nix::ioctl_write_int!(ui_set_ledbit, b'U', 105);
+}
+
+/// A keyboard of its own next to the virtual device, with the LEDs of the
+/// source device, whose LEDs are set to whatever the desktop sets these to.
+///
+/// While the source device is grabbed, LED changes only reach it through
+/// the grab, and the desktop sends them to the keyboards it sees instead.
+/// The virtual device can't be one of those: the evdev crate's uinput
+/// builder has no way to declare LEDs, so this device is made by hand.
+pub struct LedMirror {
+    uinput: File,
+}
+
+impl LedMirror {
+    /// Creates the keyboard, named `name` and with the IDs `id`. It has the
+    /// keys of the main block so that udev and the desktop take it for a
+    /// keyboard, but never presses any.
+    pub fn create(
+        name: &str,
+        id: &InputId,
+        leds: &AttributeSetRef<LedCode>,
+    ) -> std::io::Result<LedMirror> {
+        let uinput = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(OFlag::O_NONBLOCK.bits())
+            .open("/dev/uinput")?;
+        let fd = uinput.as_raw_fd();
+        let mut setup = sys::UinputSetup {
+            id: [id.bus_type().0, id.vendor(), id.product(), id.version()],
+            name: [0; 80],
+            ff_effects_max: 0,
+        };
+        // Leaving room for the terminating nul.
+        let length = name.len().min(setup.name.len() - 1);
+        setup.name[..length].copy_from_slice(&name.as_bytes()[..length]);
+        // SAFETY: the ioctls are given a uinput descriptor that stays open
+        // throughout and a setup struct laid out as the kernel expects.
+        unsafe {
+            sys::ui_set_evbit(fd, EventType::KEY.0.into())?;
+            // KEY_ESC to KEY_S, which is what udev looks for in a keyboard.
+            for key in 1..32 {
+                sys::ui_set_keybit(fd, key)?;
+            }
+            sys::ui_set_evbit(fd, EventType::LED.0.into())?;
+            for led in leds.iter() {
+                sys::ui_set_ledbit(fd, led.0.into())?;
+            }
+            sys::ui_dev_setup(fd, &setup)?;
+            sys::ui_dev_create(fd)?;
+        }
+        Ok(LedMirror { uinput })
+    }
+
+    /// Sets the LEDs of `device` to what the desktop set since the last
+    /// call. Closing the uinput file removes the keyboard again.
+    pub fn relay(&mut self, device: &mut Device) -> std::io::Result<()> {
+        let mut buffer = [0; 64 * EVENT_SIZE];
+        let read = match self.uinput.read(&mut buffer) {
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let leds = led_events(&buffer[..read]);
+        if leds.is_empty() {
+            return Ok(());
+        }
+        device.send_events(&leds)
+    }
+}
+
+impl AsFd for LedMirror {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.uinput.as_fd()
+    }
+}
+
+/// The LED events among the raw `input_event`s in `bytes`.
+fn led_events(bytes: &[u8]) -> Vec<InputEvent> {
+    bytes
+        .chunks_exact(EVENT_SIZE)
+        .filter_map(|event| {
+            let (_, rest) = event.split_at(EVENT_SIZE - 8);
+            let event_type = u16::from_ne_bytes([rest[0], rest[1]]);
+            let code = u16::from_ne_bytes([rest[2], rest[3]]);
+            let value = i32::from_ne_bytes([rest[4], rest[5], rest[6], rest[7]]);
+            (event_type == EventType::LED.0).then(|| InputEvent::new(event_type, code, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(event_type: EventType, code: u16, value: i32) -> Vec<u8> {
+        let mut bytes = vec![0xaa; EVENT_SIZE - 8];
+        bytes.extend(event_type.0.to_ne_bytes());
+        bytes.extend(code.to_ne_bytes());
+        bytes.extend(value.to_ne_bytes());
+        bytes
+    }
+
+    #[test]
+    fn only_leds_are_picked_out() {
+        let bytes = [
+            raw(EventType::LED, LedCode::LED_CAPSL.0, 1),
+            raw(EventType::SYNCHRONIZATION, 0, 0),
+            raw(EventType::LED, LedCode::LED_NUML.0, 0),
+        ]
+        .concat();
+        let leds = led_events(&bytes);
+        assert_eq!(leds.len(), 2);
+        assert_eq!(
+            (leds[0].event_type(), leds[0].code(), leds[0].value()),
+            (EventType::LED, LedCode::LED_CAPSL.0, 1)
+        );
+        assert_eq!((leds[1].code(), leds[1].value()), (LedCode::LED_NUML.0, 0));
+        // A partly read event is left out.
+        assert!(led_events(&bytes[..EVENT_SIZE - 1]).is_empty());
+    }
+}
@@ -0,0 +1,1045 @@
+//! The coordinate math behind sensor_alignment, free of any device I/O.
+//!
+//! Motion comes in as the whole counts a sensor reports in one frame and goes
+//! out as whole counts again. Everything in between is done in floating point,
+//! with the rounding remainder carried from frame to frame so that long
+//! strokes don't drift.
+//!
+//! [`SensorAlignment`] applies it to whole event frames, for embedding in
+//! another input daemon. It wraps [`frame::Frame`], the frame handling the
+//! daemon runs for each device, which writes the frames it assembles to a
+//! [`frame::FrameSink`]. With the `async` feature, [`run`] drives the
+//! pointer pipeline on a tokio runtime.
+
+mod alignment;
+#[cfg(feature = "async")]
+mod async_run;
+pub mod frame;
+pub mod state;
+pub use alignment::SensorAlignment;
+#[cfg(feature = "async")]
+pub use async_run::run;
+
+/// Adjustments applied to pointer motion around the rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointerOptions {
+    /// Frames whose raw motion is shorter than this are dropped as sensor
+    /// noise. Buttons and scrolling are never affected.
+    pub deadzone: f64,
+    /// Output resolution over input resolution, scaling raw motion before
+    /// anything else changes it, so that a high-DPI sensor moves the pointer
+    /// as far as a lower one would.
+    pub dpi_scale: f64,
+    /// Gain applied to raw X motion before rotating, for sensors that count
+    /// differently along their two axes.
+    pub scale_x: f64,
+    /// Gain applied to raw Y motion before rotating.
+    pub scale_y: f64,
+    /// Acceleration exponent: a frame moving `m` counts is scaled by
+    /// `m^(accel - 1)`, keeping its direction. 1.0 leaves motion linear.
+    pub accel: f64,
+    /// Overall speed, applied to the rotated motion.
+    pub sensitivity: f64,
+    /// Weight of the newest frame in an exponential moving average of the
+    /// motion, 1.0 being no smoothing. Lower values steady a shaky hand but
+    /// make the pointer trail behind: at 0.2 it takes about ten frames to
+    /// catch up with a stroke.
+    pub smooth: f64,
+    /// Frames of rotated motion looked back over for direction reversals by
+    /// the tremor filter, 0 turning it off.
+    pub tremor_window: usize,
+    /// How much of the motion along an axis that keeps reversing is held
+    /// back, from 0.0 to 1.0. Held-back motion cancels against the opposite
+    /// shakes that follow and is let out once the axis moves steadily again,
+    /// so the net displacement is kept.
+    pub tremor_strength: f64,
+    /// Negate X, i.e. mirror left and right. Together with `flip_y` this is
+    /// the same as rotating another 180°.
+    pub flip_x: bool,
+    /// Negate Y, i.e. mirror up and down.
+    pub flip_y: bool,
+    /// Keep only the larger component of each frame, so that motion is either
+    /// horizontal or vertical.
+    pub snap_axis: bool,
+    /// How much larger the other component has to get before snapping
+    /// switches to it, as a fraction: at 0.25 it has to be 25% larger. Stops
+    /// the axis from flickering on diagonal strokes.
+    pub snap_hysteresis: f64,
+    /// Largest motion, in counts along either axis, that one frame may put
+    /// out. Anything beyond it is cut off rather than carried, so a sensor
+    /// spewing garbage can't fling the pointer across the screen.
+    pub max_delta: f64,
+    /// Fastest the pointer may move in one frame, in counts along the
+    /// direction it moves. Faster motion is slowed down to it without
+    /// turning, for comfort rather than to keep values in range. `None`
+    /// leaves speed alone.
+    pub max_speed: Option<f64>,
+    /// Most counts one frame may move along X once it's rotated and scaled,
+    /// for an axis that's noisier than the other. Motion beyond it is
+    /// dropped, not carried to the next frame like the rounding remainder
+    /// is, and Y is left as it is. `None` leaves X alone.
+    pub max_speed_x: Option<f64>,
+    /// The same along Y.
+    pub max_speed_y: Option<f64>,
+    /// How transformed X motion is split into whole counts and the
+    /// remainder carried to the next frame.
+    pub rounding_x: Rounding,
+    /// How transformed Y motion is, which may differ for alignment work
+    /// where a one-count bias along one axis shows.
+    pub rounding_y: Rounding,
+    /// Counts of motion along an axis, carried over frames, below which
+    /// nothing is emitted yet, so that a slow steady stroke at a shallow
+    /// angle doesn't wobble between two values from frame to frame. 0.0
+    /// emits every whole count as soon as it's there.
+    pub jitter_suppress: f64,
+}
+
+impl Default for PointerOptions {
+    /// Options that leave the rotated motion alone.
+    fn default() -> Self {
+        PointerOptions {
+            deadzone: 0.0,
+            dpi_scale: 1.0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            accel: 1.0,
+            sensitivity: 1.0,
+            smooth: 1.0,
+            tremor_window: 0,
+            tremor_strength: 0.0,
+            flip_x: false,
+            flip_y: false,
+            snap_axis: false,
+            snap_hysteresis: 0.25,
+            max_delta: i16::MAX as f64,
+            max_speed: None,
+            max_speed_x: None,
+            max_speed_y: None,
+            rounding_x: Rounding::Nearest,
+            rounding_y: Rounding::Nearest,
+            jitter_suppress: 0.0,
+        }
+    }
+}
+
+impl PointerOptions {
+    /// Whether these options leave whole counts as they are, so that only
+    /// the rotation changes motion. Rounding doesn't come into it then, and
+    /// neither does the largest delta unless a count goes beyond it, nor the
+    /// tremor filter's strength while it looks back over no frames.
+    pub fn is_neutral(&self) -> bool {
+        let neutral = PointerOptions {
+            rounding_x: self.rounding_x,
+            rounding_y: self.rounding_y,
+            snap_hysteresis: self.snap_hysteresis,
+            max_delta: self.max_delta,
+            tremor_strength: if self.tremor_window == 0 {
+                self.tremor_strength
+            } else {
+                PointerOptions::default().tremor_strength
+            },
+            ..PointerOptions::default()
+        };
+        *self == neutral
+    }
+}
+
+/// Sine and cosine below this far from 0 or ±1 are taken to be exactly that.
+/// Degrees turned into radians never land on a multiple of π, which leaves
+/// about 1e-16 where there should be nothing.
+const CARDINAL_EPSILON: f64 = 1e-12;
+
+/// Sine and cosine of `angle_deg`, exactly 0 or ±1 at multiples of 90° so
+/// that those move nothing onto the other axis. -0° is 0°.
+pub fn sin_cos_deg(angle_deg: f64) -> (f64, f64) {
+    let (sin_a, cos_a) = angle_deg.rem_euclid(360.0).to_radians().sin_cos();
+    (snap_cardinal(sin_a), snap_cardinal(cos_a))
+}
+
+/// Sine and cosine of `angle_rad`, snapped like [`sin_cos_deg`] does, for an
+/// angle given in radians that shouldn't go through degrees on the way.
+pub fn sin_cos_rad(angle_rad: f64) -> (f64, f64) {
+    let (sin_a, cos_a) = angle_rad.sin_cos();
+    (snap_cardinal(sin_a), snap_cardinal(cos_a))
+}
+
+fn snap_cardinal(value: f64) -> f64 {
+    if value.abs() < CARDINAL_EPSILON {
+        0.0
+    } else if (value.abs() - 1.0).abs() < CARDINAL_EPSILON {
+        value.signum()
+    } else {
+        value
+    }
+}
+
+/// Rotates one frame of motion by the angle whose sine and cosine are given,
+/// rounding to the nearest count. Both are snapped like `sin_cos_deg` does.
+pub fn rotate(dx: i32, dy: i32, sin_a: f64, cos_a: f64) -> (i32, i32) {
+    let (sin_a, cos_a) = (snap_cardinal(sin_a), snap_cardinal(cos_a));
+    let matrix = Matrix {
+        a: cos_a,
+        b: -sin_a,
+        c: sin_a,
+        d: cos_a,
+    };
+    Carry::default().round(matrix.apply((dx as f64, dy as f64)))
+}
+
+/// The same rotation as `angle_deg`, in `(-180, 180]`, so that 370° and
+/// -350° both come out as 10°.
+pub fn normalize_angle_deg(angle_deg: f64) -> f64 {
+    let angle_deg = angle_deg.rem_euclid(360.0);
+    if angle_deg > 180.0 {
+        angle_deg - 360.0
+    } else {
+        angle_deg
+    }
+}
+
+/// The angle `weight` of the way from `a` to `b` in degrees, going the short
+/// way round, so that blending 170° and -170° halfway gives 180° and not 0°.
+/// Normalized like [`normalize_angle_deg`].
+pub fn blend_angles_deg(a: f64, b: f64, weight: f64) -> f64 {
+    normalize_angle_deg(a + normalize_angle_deg(b - a) * weight)
+}
+
+/// The angle in degrees of the rotation that turns `from` into the direction
+/// of `to`, or `None` if either is zero.
+pub fn angle_between(from: (f64, f64), to: (f64, f64)) -> Option<f64> {
+    if from == (0.0, 0.0) || to == (0.0, 0.0) {
+        return None;
+    }
+    let cross = from.0 * to.1 - from.1 * to.0;
+    let dot = from.0 * to.0 + from.1 * to.1;
+    Some(cross.atan2(dot).to_degrees())
+}
+
+/// An angle in messages, with the degree sign when the locale can show it.
+/// Output is always UTF-8, which a terminal set to another encoding shows
+/// as `Â°`, so there it's written out as ` deg` instead.
+pub struct Degrees(pub f64);
+
+impl std::fmt::Display for Degrees {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        static SIGN: std::sync::OnceLock<&str> = std::sync::OnceLock::new();
+        let sign = SIGN.get_or_init(|| {
+            // The first of these that's set decides, as for setlocale(3).
+            let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+                .into_iter()
+                .filter_map(|name| std::env::var(name).ok())
+                .find(|value| !value.is_empty());
+            match locale {
+                Some(locale) => {
+                    let locale = locale.to_lowercase();
+                    if locale.contains("utf-8") || locale.contains("utf8") {
+                        "°"
+                    } else {
+                        " deg"
+                    }
+                }
+                // Nothing set is as good as UTF-8 nowadays, e.g. the journal.
+                None => "°",
+            }
+        });
+        self.0.fmt(f)?;
+        f.write_str(sign)
+    }
+}
+
+/// A linear map of the pointer plane, `[x', y'] = [[a, b], [c, d]] * [x, y]`.
+/// Rotation is the common case, but shear and uneven scale can be expressed
+/// too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+}
+
+impl Matrix {
+    pub const IDENTITY: Matrix = Matrix {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+    };
+
+    /// Counter-clockwise rotation in the usual maths sense, which on screen,
+    /// with Y pointing down, turns clockwise.
+    pub fn rotation(angle_deg: f64) -> Self {
+        Matrix::rotation_by(sin_cos_deg(angle_deg))
+    }
+
+    /// [`Matrix::rotation`] by an angle in radians.
+    pub fn rotation_rad(angle_rad: f64) -> Self {
+        Matrix::rotation_by(sin_cos_rad(angle_rad))
+    }
+
+    fn rotation_by((sin_a, cos_a): (f64, f64)) -> Self {
+        Matrix {
+            a: cos_a,
+            b: -sin_a,
+            c: sin_a,
+            d: cos_a,
+        }
+    }
+
+    /// Reflection across the line through the origin at `axis_deg` from the
+    /// X axis.
+    pub fn reflection(axis_deg: f64) -> Self {
+        let (sin_2a, cos_2a) = sin_cos_deg(2.0 * axis_deg);
+        Matrix {
+            a: cos_2a,
+            b: sin_2a,
+            c: sin_2a,
+            d: -cos_2a,
+        }
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// The map that applies `self` first and `next` after it.
+    pub fn then(self, next: Matrix) -> Matrix {
+        Matrix {
+            a: next.a * self.a + next.b * self.c,
+            b: next.a * self.b + next.b * self.d,
+            c: next.c * self.a + next.d * self.c,
+            d: next.c * self.b + next.d * self.d,
+        }
+    }
+
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (self.a * x + self.b * y, self.c * x + self.d * y)
+    }
+}
+
+impl std::str::FromStr for Matrix {
+    type Err = String;
+
+    /// Parses `a,b,c,d`, row by row.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let values = text
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("{text:?}: {e}"))?;
+        match values[..] {
+            [a, b, c, d] => Ok(Matrix { a, b, c, d }),
+            _ => Err(format!(
+                "expected four comma-separated numbers a,b,c,d, got {}",
+                values.len()
+            )),
+        }
+    }
+}
+
+/// One step of a [`Pipeline`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// Gain along each axis.
+    Scale { x: f64, y: f64 },
+    /// Rotation as by [`Matrix::rotation`].
+    Rotate { angle_deg: f64 },
+    /// Any linear map.
+    Matrix(Matrix),
+    /// Negate X, Y or both.
+    Flip { x: bool, y: bool },
+    /// Drop the whole frame if its motion is shorter than this by now.
+    Deadzone(f64),
+}
+
+impl Step {
+    /// Applies the step to one frame of motion, or returns `None` if it
+    /// dropped the frame.
+    pub fn apply(&self, motion: (f64, f64)) -> Option<(f64, f64)> {
+        match *self {
+            Step::Deadzone(counts) if motion.0.hypot(motion.1) < counts => None,
+            _ => Some(self.matrix().apply(motion)),
+        }
+    }
+
+    /// The step as a linear map, which for a deadzone is the identity.
+    pub fn matrix(&self) -> Matrix {
+        let diagonal = |a, d| Matrix {
+            a,
+            b: 0.0,
+            c: 0.0,
+            d,
+        };
+        let sign = |flip: bool| if flip { -1.0 } else { 1.0 };
+        match *self {
+            Step::Scale { x, y } => diagonal(x, y),
+            Step::Rotate { angle_deg } => Matrix::rotation(angle_deg),
+            Step::Matrix(matrix) => matrix,
+            Step::Flip { x, y } => diagonal(sign(x), sign(y)),
+            Step::Deadzone(_) => Matrix::IDENTITY,
+        }
+    }
+}
+
+/// Steps applied to pointer motion one after the other, ahead of the
+/// rotation by the (adjustable) angle. A plain `--matrix` is a pipeline of
+/// one step.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pipeline {
+    steps: Vec<Step>,
+}
+
+impl Pipeline {
+    pub fn new(steps: Vec<Step>) -> Self {
+        Pipeline { steps }
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Runs one frame of motion through every step, or returns `None` if a
+    /// deadzone dropped it.
+    pub fn apply(&self, motion: (f64, f64)) -> Option<(f64, f64)> {
+        self.steps
+            .iter()
+            .try_fold(motion, |motion, step| step.apply(motion))
+    }
+
+    /// All steps combined into one linear map, leaving out the deadzones,
+    /// for what can't be dropped: absolute positions and scroll wheels.
+    pub fn matrix(&self) -> Matrix {
+        self.steps
+            .iter()
+            .fold(Matrix::IDENTITY, |matrix, step| matrix.then(step.matrix()))
+    }
+}
+
+impl From<Matrix> for Pipeline {
+    fn from(matrix: Matrix) -> Self {
+        if matrix == Matrix::IDENTITY {
+            Pipeline::default()
+        } else {
+            Pipeline::new(vec![Step::Matrix(matrix)])
+        }
+    }
+}
+
+/// How a transformed value becomes whole counts. Whichever is chosen, the
+/// remainder is carried, so this only decides when a count is handed out, not
+/// how many are overall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// To the nearest count, halves away from zero.
+    #[default]
+    Nearest,
+    Floor,
+    Ceil,
+    /// To the nearest count, halves to the even one, which doesn't favour
+    /// either direction.
+    Bankers,
+    /// Up for motion one way and down for the other, so that a count is
+    /// handed out as soon as the axis starts moving in either direction.
+    AwayFromZero,
+    /// The other way round, holding motion back until it makes a whole count.
+    TowardZero,
+}
+
+impl Rounding {
+    pub fn round(self, value: f64) -> f64 {
+        match self {
+            Rounding::Nearest => value.round(),
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+            Rounding::Bankers => value.round_ties_even(),
+            Rounding::AwayFromZero => value.abs().ceil().copysign(value),
+            Rounding::TowardZero => value.trunc(),
+        }
+    }
+}
+
+impl std::str::FromStr for Rounding {
+    type Err = String;
+
+    /// Parses `nearest`, `floor`, `ceil`, `bankers`, `away_from_zero` or
+    /// `toward_zero`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "nearest" => Ok(Rounding::Nearest),
+            "floor" => Ok(Rounding::Floor),
+            "ceil" => Ok(Rounding::Ceil),
+            "bankers" => Ok(Rounding::Bankers),
+            "away_from_zero" => Ok(Rounding::AwayFromZero),
+            "toward_zero" => Ok(Rounding::TowardZero),
+            _ => Err(format!(
+                "expected nearest, floor, ceil, bankers, away_from_zero or toward_zero, got {text:?}"
+            )),
+        }
+    }
+}
+
+/// Sub-count remainder of transformed motion, carried into the next frame so
+/// that rounding error doesn't accumulate over long strokes.
+#[derive(Debug, Default)]
+pub struct Carry {
+    rem_x: f64,
+    rem_y: f64,
+    rounding_x: Rounding,
+    rounding_y: Rounding,
+    hold: f64,
+}
+
+impl Carry {
+    pub fn new(rounding_x: Rounding, rounding_y: Rounding) -> Self {
+        Carry {
+            rem_x: 0.0,
+            rem_y: 0.0,
+            rounding_x,
+            rounding_y,
+            hold: 0.0,
+        }
+    }
+
+    /// Holds motion along an axis back, carrying all of it, until together
+    /// with what's carried it reaches `threshold` counts. Unlike a deadzone
+    /// nothing is dropped, it just comes out later and in one piece.
+    pub fn with_hold(mut self, threshold: f64) -> Self {
+        self.hold = threshold;
+        self
+    }
+
+    /// Rounds transformed motion to whole counts, carrying the remainder over
+    /// from and into other frames. Motion beyond what an event can hold is
+    /// clamped.
+    pub fn round(&mut self, (x, y): (f64, f64)) -> (i32, i32) {
+        let (min, max) = (i32::MIN as f64, i32::MAX as f64);
+        let x = (x + self.rem_x).clamp(min, max);
+        let y = (y + self.rem_y).clamp(min, max);
+        let new_x = if x.abs() < self.hold {
+            0.0
+        } else {
+            self.rounding_x.round(x)
+        };
+        let new_y = if y.abs() < self.hold {
+            0.0
+        } else {
+            self.rounding_y.round(y)
+        };
+        self.rem_x = x - new_x;
+        self.rem_y = y - new_y;
+        (new_x as i32, new_y as i32)
+    }
+}
+
+/// The whole pointer pipeline for one source: deadzone, resolution and
+/// per-axis scale, the steps of a [`Pipeline`] if there is one, rotation or
+/// other linear map, acceleration, sensitivity, flips, the tremor filter,
+/// smoothing and snapping, in that order.
+#[derive(Debug)]
+pub struct Transform {
+    options: PointerOptions,
+    pipeline: Pipeline,
+    response: ResponseCurve,
+    carry: Carry,
+    // Smoothed motion not emitted yet. Handing out a fraction of it each frame
+    // is the moving average, and keeping the rest means none goes missing.
+    smooth_lag: (f64, f64),
+    tremor: Tremor,
+    snapped: Axis,
+}
+
+/// Recent motion for the tremor filter and what it's holding back.
+#[derive(Debug, Default)]
+struct Tremor {
+    window: std::collections::VecDeque<(f64, f64)>,
+    owed: (f64, f64),
+}
+
+impl Tremor {
+    fn is_pending(&self) -> bool {
+        self.owed.0.hypot(self.owed.1) >= 0.5
+    }
+
+    /// Lets out as much of the owed motion plus `motion` as the reversals in
+    /// the window allow, like smoothing does but separately per axis and
+    /// with a weight that drops the more the axis shakes.
+    fn filter(&mut self, motion: (f64, f64), size: usize, strength: f64) -> (f64, f64) {
+        if size == 0 {
+            return motion;
+        }
+        if self.window.len() == size {
+            self.window.pop_front();
+        }
+        self.window.push_back(motion);
+        let alpha_x = 1.0 - strength * reversal_rate(self.window.iter().map(|m| m.0));
+        let alpha_y = 1.0 - strength * reversal_rate(self.window.iter().map(|m| m.1));
+        let owed = (self.owed.0 + motion.0, self.owed.1 + motion.1);
+        let out = (owed.0 * alpha_x, owed.1 * alpha_y);
+        self.owed = (owed.0 - out.0, owed.1 - out.1);
+        out
+    }
+}
+
+/// The fraction of consecutive values that changed sign, by which a window
+/// of frames still or moving one way scores 0 and a shake scores close to 1.
+fn reversal_rate(values: impl Iterator<Item = f64>) -> f64 {
+    let mut pairs = 0;
+    let mut reversals = 0;
+    let mut last = None;
+    for value in values {
+        if let Some(last) = last {
+            pairs += 1;
+            if value * last < 0.0 {
+                reversals += 1;
+            }
+        }
+        // Frames without motion along the axis don't break a shake up.
+        if value != 0.0 || last.is_none() {
+            last = Some(value);
+        }
+    }
+    if pairs == 0 {
+        0.0
+    } else {
+        reversals as f64 / pairs as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    X,
+    Y,
+}
+
+impl Transform {
+    pub fn new(options: PointerOptions) -> Self {
+        Transform {
+            options,
+            pipeline: Pipeline::default(),
+            response: ResponseCurve::default(),
+            carry: Carry::new(options.rounding_x, options.rounding_y)
+                .with_hold(options.jitter_suppress),
+            smooth_lag: (0.0, 0.0),
+            tremor: Tremor::default(),
+            snapped: Axis::X,
+        }
+    }
+
+    /// Runs `pipeline` ahead of the matrix given to `apply`.
+    pub fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Scales rotated motion by `curve`'s gain for its speed.
+    pub fn with_response(mut self, curve: ResponseCurve) -> Self {
+        self.response = curve;
+        self
+    }
+
+    /// Transforms one frame of motion, or returns `None` if a deadzone
+    /// swallowed it or jitter suppression held all of it back.
+    pub fn apply(&mut self, dx: i32, dy: i32, matrix: &Matrix) -> Option<(i32, i32)> {
+        let options = self.options;
+        if self.passes_through(dx, dy, matrix) {
+            return Some((dx, dy));
+        }
+        let (x, y) = (dx as f64, dy as f64);
+        if x.hypot(y) < options.deadzone {
+            return None;
+        }
+        let scale = options.dpi_scale;
+        let (x, y) = self
+            .pipeline
+            .apply((x * scale * options.scale_x, y * scale * options.scale_y))?;
+        let (x, y) = matrix.apply((x, y));
+        let magnitude = x.hypot(y);
+        let gain = accel_gain(magnitude, options.accel)
+            * self.response.gain(magnitude)
+            * options.sensitivity;
+        let (mut x, mut y) = (x * gain, y * gain);
+        if options.flip_x {
+            x = -x;
+        }
+        if options.flip_y {
+            y = -y;
+        }
+        let motion = self.filter_tremor((x, y));
+        let motion = self.smooth(motion);
+        let motion = self.snap(motion);
+        let rounded = self.carry.round(self.limit(motion));
+        if rounded == (0, 0) && options.jitter_suppress > 0.0 {
+            return None;
+        }
+        Some(rounded)
+    }
+
+    /// Whether [`Transform::apply`] hands `(dx, dy)` back exactly as it came
+    /// in, without going through floats at all: at 0° with nothing else to
+    /// do, and as long as neither count goes beyond the largest delta.
+    pub fn passes_through(&self, dx: i32, dy: i32, matrix: &Matrix) -> bool {
+        let within = |delta: i32| (delta.unsigned_abs() as f64) <= self.options.max_delta;
+        *matrix == Matrix::IDENTITY && within(dx) && within(dy) && self.is_neutral()
+    }
+
+    /// Whether everything but the matrix leaves motion alone. Rounding
+    /// doesn't matter then, whole counts stay what they are.
+    fn is_neutral(&self) -> bool {
+        self.options.is_neutral()
+            && self.pipeline.steps().is_empty()
+            && self.response.points().is_empty()
+    }
+
+    /// Forgets the rounding remainder and any owed motion, e.g. when
+    /// raw motion was passed through in between.
+    pub fn reset(&mut self) {
+        let pipeline = std::mem::take(&mut self.pipeline);
+        let response = std::mem::take(&mut self.response);
+        *self = Transform::new(self.options)
+            .with_pipeline(pipeline)
+            .with_response(response);
+    }
+
+    /// Rounds everything still owed, smoothed motion and that held by the
+    /// tremor filter along with the rounding remainder, into one last move
+    /// and starts over like [`Transform::reset`], snapping included. Halves
+    /// go to the even count, so a remainder of half a count is dropped
+    /// rather than pushing the pointer a whole one.
+    pub fn settle(&mut self) -> (i32, i32) {
+        let lag = (
+            self.smooth_lag.0 + self.tremor.owed.0,
+            self.smooth_lag.1 + self.tremor.owed.1,
+        );
+        let snapped = self.snap(lag);
+        let (x, y) = self.limit(snapped);
+        let settled = (
+            (x + self.carry.rem_x).round_ties_even() as i32,
+            (y + self.carry.rem_y).round_ties_even() as i32,
+        );
+        self.reset();
+        settled
+    }
+
+    /// Whether smoothing or the tremor filter still owes motion that should
+    /// be emitted even if the device stays quiet.
+    pub fn smoothing_pending(&self) -> bool {
+        self.smooth_lag.0.hypot(self.smooth_lag.1) >= 0.5 || self.tremor.is_pending()
+    }
+
+    /// The next step of owed smoothed motion, without new input.
+    pub fn tick(&mut self) -> (i32, i32) {
+        let motion = self.filter_tremor((0.0, 0.0));
+        let motion = self.smooth(motion);
+        let motion = self.snap(motion);
+        self.carry.round(self.limit(motion))
+    }
+
+    fn limit(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let speed = x.hypot(y);
+        let (x, y) = match self.options.max_speed {
+            Some(max_speed) if speed > max_speed => {
+                let scale = max_speed / speed;
+                (x * scale, y * scale)
+            }
+            _ => (x, y),
+        };
+        let cap = |value: f64, max_speed: Option<f64>| match max_speed {
+            Some(max_speed) => value.clamp(-max_speed, max_speed),
+            None => value,
+        };
+        let (x, y) = (
+            cap(x, self.options.max_speed_x),
+            cap(y, self.options.max_speed_y),
+        );
+        let max = self.options.max_delta;
+        (x.clamp(-max, max), y.clamp(-max, max))
+    }
+
+    fn filter_tremor(&mut self, motion: (f64, f64)) -> (f64, f64) {
+        let options = self.options;
+        self.tremor
+            .filter(motion, options.tremor_window, options.tremor_strength)
+    }
+
+    fn smooth(&mut self, (x, y): (f64, f64)) -> (f64, f64) {
+        let alpha = self.options.smooth;
+        let owed = (self.smooth_lag.0 + x, self.smooth_lag.1 + y);
+        let out = (owed.0 * alpha, owed.1 * alpha);
+        self.smooth_lag = (owed.0 - out.0, owed.1 - out.1);
+        out
+    }
+
+    fn snap(&mut self, (x, y): (f64, f64)) -> (f64, f64) {
+        if !self.options.snap_axis {
+            return (x, y);
+        }
+        let switch_ratio = 1.0 + self.options.snap_hysteresis;
+        self.snapped = match self.snapped {
+            Axis::X if y.abs() > x.abs() * switch_ratio => Axis::Y,
+            Axis::Y if x.abs() > y.abs() * switch_ratio => Axis::X,
+            axis => axis,
+        };
+        match self.snapped {
+            Axis::X => (x, 0.0),
+            Axis::Y => (0.0, y),
+        }
+    }
+}
+
+/// How much of the rotation a frame moving `magnitude` counts gets when only
+/// motion faster than `threshold` is rotated: none below 80% of it, all of it
+/// above 120%, and linearly more in between so the pointer doesn't jump when
+/// crossing it. A threshold of 0 rotates everything.
+pub fn rotation_weight(magnitude: f64, threshold: f64) -> f64 {
+    if threshold <= 0.0 {
+        return 1.0;
+    }
+    let (low, high) = (threshold * 0.8, threshold * 1.2);
+    ((magnitude - low) / (high - low)).clamp(0.0, 1.0)
+}
+
+/// Angle offsets by speed, for sensors whose angle error changes with how
+/// fast they move. Each point is a speed in counts per frame and the offset
+/// in degrees added to the angle at that speed. Between points the offset is
+/// interpolated linearly, starting from none at speed 0, and beyond the last
+/// point it stays at the last offset. Without points there's no offset.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AngleCurve {
+    points: Vec<(f64, f64)>,
+}
+
+impl AngleCurve {
+    /// Fails unless every value is finite and the speeds are positive and
+    /// increasing.
+    pub fn new(points: Vec<(f64, f64)>) -> Result<Self, String> {
+        let mut last_speed = 0.0;
+        for &(speed, offset) in &points {
+            if !(speed.is_finite() && offset.is_finite()) {
+                return Err(format!("angle curve point {speed}:{offset} isn't finite"));
+            }
+            if speed <= last_speed {
+                return Err(format!(
+                    "angle curve speeds must be positive and increasing, {speed} isn't"
+                ));
+            }
+            last_speed = speed;
+        }
+        Ok(AngleCurve { points })
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// The offset in degrees for a frame moving `speed` counts.
+    pub fn offset(&self, speed: f64) -> f64 {
+        let mut last = (0.0, 0.0);
+        for &(point_speed, offset) in &self.points {
+            if speed <= point_speed {
+                let along = (speed - last.0) / (point_speed - last.0);
+                return last.1 + along * (offset - last.1);
+            }
+            last = (point_speed, offset);
+        }
+        last.1
+    }
+}
+
+/// Gain by speed, a custom acceleration for those who want more control
+/// than one exponent gives. Each point is a speed in counts per frame and
+/// the gain at that speed, applied to the rotated motion together with the
+/// acceleration exponent. Between points the gain is interpolated linearly,
+/// and below the first and beyond the last point it stays at theirs.
+/// Without points the gain is 1.0.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResponseCurve {
+    points: Vec<(f64, f64)>,
+}
+
+impl ResponseCurve {
+    /// Fails unless every value is finite, the speeds are increasing from 0
+    /// up and the gains aren't negative.
+    pub fn new(points: Vec<(f64, f64)>) -> Result<Self, String> {
+        let mut last_speed = None;
+        for &(speed, gain) in &points {
+            if !(speed.is_finite() && gain.is_finite()) {
+                return Err(format!("response point {speed}:{gain} isn't finite"));
+            }
+            if speed < 0.0 || last_speed.is_some_and(|last| speed <= last) {
+                return Err(format!(
+                    "response speeds must be sorted and increasing from 0, {speed} isn't"
+                ));
+            }
+            if gain < 0.0 {
+                return Err(format!("response gains can't be negative, {gain} is"));
+            }
+            last_speed = Some(speed);
+        }
+        Ok(ResponseCurve { points })
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// The gain for a frame moving `speed` counts.
+    pub fn gain(&self, speed: f64) -> f64 {
+        let Some(&(first_speed, first_gain)) = self.points.first() else {
+            return 1.0;
+        };
+        if speed <= first_speed {
+            return first_gain;
+        }
+        let mut last = (first_speed, first_gain);
+        for &(point_speed, gain) in &self.points[1..] {
+            if speed <= point_speed {
+                let along = (speed - last.0) / (point_speed - last.0);
+                return last.1 + along * (gain - last.1);
+            }
+            last = (point_speed, gain);
+        }
+        last.1
+    }
+}
+
+/// An ellipse fitted to a stroke that was meant to be a circle, for
+/// `calibrate --circle`. A sensor that counts differently along two
+/// directions turns a circle into an ellipse stretched along one of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ellipse {
+    /// Direction of the long axis, from +X toward +Y, in `(-90, 90]`.
+    pub angle_deg: f64,
+    /// Half the length of the long axis.
+    pub major: f64,
+    /// Half the length of the short axis.
+    pub minor: f64,
+}
+
+impl Ellipse {
+    /// Least-squares fit of the conic `Ax² + Bxy + Cy² + Dx + Ey = 1` to
+    /// `points`, taken relative to their mean so that the conic never has to
+    /// pass through the origin. `None` unless there are at least five points
+    /// and the conic is an ellipse.
+    pub fn fit(points: &[(f64, f64)]) -> Option<Ellipse> {
+        if points.len() < 5 {
+            return None;
+        }
+        let count = points.len() as f64;
+        let mean = points.iter().fold((0.0, 0.0), |sum, &(x, y)| {
+            (sum.0 + x / count, sum.1 + y / count)
+        });
+        // Normal equations of the least-squares problem.
+        let mut normal = [[0.0; 6]; 5];
+        for &(x, y) in points {
+            let (x, y) = (x - mean.0, y - mean.1);
+            let row = [x * x, x * y, y * y, x, y];
+            for (equation, &factor) in normal.iter_mut().zip(&row) {
+                for (sum, &value) in equation.iter_mut().zip(&row) {
+                    *sum += factor * value;
+                }
+                equation[5] += factor;
+            }
+        }
+        let [a, b, c, d, e] = solve(normal)?;
+        // The centre, where the gradient is zero, and the level the conic
+        // is at around it.
+        let determinant = 4.0 * a * c - b * b;
+        if determinant <= 0.0 {
+            return None;
+        }
+        let center = (
+            (b * e - 2.0 * c * d) / determinant,
+            (b * d - 2.0 * a * e) / determinant,
+        );
+        let level =
+            1.0 + a * center.0 * center.0 + b * center.0 * center.1 + c * center.1 * center.1;
+        // Eigenvalues of [[a, b/2], [b/2, c]] over the level.
+        let half_sum = (a + c) / 2.0;
+        let radius = ((a - c) / 2.0).hypot(b / 2.0);
+        let (small, large) = ((half_sum - radius) / level, (half_sum + radius) / level);
+        if small <= 0.0 || !large.is_finite() {
+            return None;
+        }
+        // The long axis goes along the smaller eigenvalue, at right angles
+        // to the larger one's direction.
+        let large_deg = 0.5 * b.atan2(a - c).to_degrees();
+        let mut angle_deg = large_deg + 90.0;
+        if angle_deg > 90.0 {
+            angle_deg -= 180.0;
+        }
+        Some(Ellipse {
+            angle_deg,
+            major: 1.0 / small.sqrt(),
+            minor: 1.0 / large.sqrt(),
+        })
+    }
+
+    /// How many times as long as it's wide the ellipse is, 1.0 for a circle.
+    pub fn aspect(&self) -> f64 {
+        self.major / self.minor
+    }
+
+    /// Gains along X and Y that make the ellipse as wide as it's tall,
+    /// keeping its area. They make it round if its axes lie along X and Y.
+    pub fn axis_scales(&self) -> (f64, f64) {
+        let (sin_a, cos_a) = sin_cos_deg(self.angle_deg);
+        let width = (self.major * cos_a).hypot(self.minor * sin_a);
+        let height = (self.major * sin_a).hypot(self.minor * cos_a);
+        let mean = (width * height).sqrt();
+        (mean / width, mean / height)
+    }
+
+    /// The map that turns the ellipse into a circle of the same area,
+    /// whichever way its axes lie: it squeezes the long axis and stretches
+    /// the short one without turning anything.
+    pub fn rounding_matrix(&self) -> Matrix {
+        let mean = (self.major * self.minor).sqrt();
+        Matrix::rotation(-self.angle_deg)
+            .then(
+                Step::Scale {
+                    x: mean / self.major,
+                    y: mean / self.minor,
+                }
+                .matrix(),
+            )
+            .then(Matrix::rotation(self.angle_deg))
+    }
+}
+
+/// Solves five linear equations given as an augmented matrix, by Gaussian
+/// elimination with partial pivoting. `None` if they have no single
+/// solution.
+fn solve(mut rows: [[f64; 6]; 5]) -> Option<[f64; 5]> {
+    for column in 0..5 {
+        let pivot =
+            (column..5).max_by(|&i, &j| rows[i][column].abs().total_cmp(&rows[j][column].abs()))?;
+        if rows[pivot][column].abs() < 1e-300 {
+            return None;
+        }
+        rows.swap(column, pivot);
+        let pivot_row = rows[column];
+        for row in &mut rows[column + 1..] {
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(column) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+    let mut solution = [0.0; 5];
+    for row in (0..5).rev() {
+        let known: f64 = (row + 1..5).map(|k| rows[row][k] * solution[k]).sum();
+        solution[row] = (rows[row][5] - known) / rows[row][row];
+    }
+    solution
+        .iter()
+        .all(|value| value.is_finite())
+        .then_some(solution)
+}
+
+fn accel_gain(magnitude: f64, exponent: f64) -> f64 {
+    if exponent == 1.0 || magnitude == 0.0 {
+        1.0
+    } else {
+        magnitude.powf(exponent - 1.0)
+    }
+}
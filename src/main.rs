@@ -1,194 +1,4515 @@
-#![feature(never_type)]
-use std::path::Path;
-use std::sync::OnceLock;
+mod calibrate;
+mod capabilities;
+mod config;
+mod config_watch;
+mod control;
+mod daemon;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod devices;
+mod display;
+mod error;
+mod focus;
+mod force_feedback;
+mod frame_history;
+mod gyro;
+mod hotplug;
+#[cfg(feature = "http")]
+mod http;
+mod leds;
+mod output;
+mod recording;
+mod retry;
+mod signals;
+mod stats;
+mod status;
+mod systemd;
+#[cfg(feature = "tui")]
+mod tune;
 
-use clap::Parser;
+use std::ffi::CString;
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use log::{debug, error, info, trace, warn};
 
 use evdev::uinput::VirtualDevice;
-use evdev::{
-    AttributeSet, AttributeSetRef, Device, EventType, InputEvent, KeyCode, RelativeAxisCode,
+use evdev::{BusType, Device, InputId, KeyCode};
+use nix::errno::Errno;
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use sensor_alignment::frame::{
+    Chord, DialMode, DwellOptions, Frame, FramePolicy, Hotkeys, PalmOptions, ScrollOptions,
+    ShakeOptions,
+};
+use sensor_alignment::state::LiveState;
+use sensor_alignment::{
+    AngleCurve, Degrees, Matrix, Pipeline, PointerOptions, ResponseCurve, Rounding, Step,
 };
 
-const VIRTUAL_DEVICE_NAME: &str = "sensor alignment virtual device";
+use crate::capabilities::Capabilities;
+use crate::config::{AppProfile, Config};
+use crate::config_watch::ConfigWatcher;
+use crate::control::{ControlSocket, DEFAULT_CONTROL_SOCKET};
+use crate::devices::{AxisMode, DeviceSelector, DeviceSpec, WheelMode};
+use crate::error::AlignError;
+use crate::gyro::Gyro;
+use crate::hotplug::DeviceWatcher;
+use crate::leds::LedMirror;
+use crate::output::{EmitErrors, Output, Sink};
+use crate::retry::{Backoff, with_retry};
+
+const DEFAULT_VIRTUAL_NAME: &str = "sensor alignment virtual device";
+// The placeholder IDs from the kernel's uinput documentation, which no real
+// device uses.
+const DEFAULT_VENDOR: u16 = 0x1234;
+const DEFAULT_PRODUCT: u16 = 0x5678;
+const DEFAULT_VERSION: u16 = 0x0001;
+/// How many times creating the virtual device is tried before giving up.
+const VIRTUAL_DEVICE_ATTEMPTS: u32 = 5;
+/// The largest sensitivity or scale used. Beyond it each count throws the
+/// pointer across the screen, which is a typo rather than a preference.
+const MAX_GAIN: f64 = 100.0;
+/// The most frames --frame-history keeps, a few tens of megabytes' worth.
+const MAX_FRAME_HISTORY: i64 = 1_000_000;
+/// How many times in a row opening a device that is there may fail before
+/// giving up. Waiting for one that isn't there doesn't count.
+const OPEN_ATTEMPTS: u32 = 10;
+/// How many times in a row the event loop may fail soon after reconnecting
+/// before giving up.
+const EVENT_LOOP_ATTEMPTS: u32 = 10;
+/// How often smoothed motion still owed is emitted while a device is quiet.
+const SMOOTHING_TICK: std::time::Duration = std::time::Duration::from_millis(8);
+/// The longest the event loop waits without looking at the signal flags. A
+/// signal interrupts the wait, but not one that came just before it started.
+const IDLE_WAKEUP: std::time::Duration = std::time::Duration::from_millis(250);
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Log more: -v for connection changes, -vv for every frame
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// List the available input devices and exit, like `list`
+    #[arg(long, hide = true)]
+    list_devices: bool,
+    /// TOML config file [default: ~/.config/sensor_alignment/config.toml]
+    #[arg(short, long, global = true, env = "SENSOR_ALIGNMENT_CONFIG")]
+    config: Option<PathBuf>,
+    /// Apply the [profiles.NAME] table of the config file over the rest of
+    /// it [default: default, if the file has one]
+    #[arg(
+        long,
+        value_name = "NAME",
+        global = true,
+        env = "SENSOR_ALIGNMENT_PROFILE"
+    )]
+    profile: Option<String>,
+    /// Source device, where event7 or just 7 stands for /dev/input/event7,
+    /// optionally with an angle of its own as in 7,angle=3.5; repeat to merge
+    /// several devices into one
+    #[arg(short, long, global = true, env = "SENSOR_ALIGNMENT_DEVICE_PATH")]
+    device_path: Vec<DeviceSpec>,
+    /// Use the device whose name contains this (case-insensitive) instead of
+    /// a fixed path; repeatable, ignored if --device-path or --device-glob
+    /// is given
+    #[arg(
+        short = 'n',
+        long,
+        value_name = "SUBSTRING",
+        global = true,
+        env = "SENSOR_ALIGNMENT_DEVICE_NAME"
+    )]
+    device_name: Vec<String>,
+    /// Use the device whose whole name or physical path matches this
+    /// shell-style pattern, such as "usb-0000:00:14.0-3*"; repeatable,
+    /// ignored if --device-path is given
+    #[arg(
+        short = 'g',
+        long,
+        value_name = "PATTERN",
+        global = true,
+        env = "SENSOR_ALIGNMENT_DEVICE_GLOB"
+    )]
+    device_glob: Vec<String>,
+    #[command(flatten, next_help_heading = "Run options")]
+    run: RunArgs,
+}
+
+// What `run` takes, which is also accepted before any subcommand so that
+// running without one keeps working. `replay` and `calibrate` go by these as
+// well.
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// Reload the config file whenever it's saved, like SIGHUP does
+    #[arg(long, env = "SENSOR_ALIGNMENT_WATCH_CONFIG")]
+    watch_config: bool,
+    /// Which motion of a device with both relative and absolute axes is
+    /// rotated, the other being dropped: rel, abs, or auto for abs on touch
+    /// surfaces and pens [default: auto]
+    #[arg(long, value_name = "MODE", env = "SENSOR_ALIGNMENT_AXIS_MODE")]
+    axis_mode: Option<AxisMode>,
+    /// Move absolute positions by this share of the width and of the height
+    /// after rotating them, for a touch surface that covers part of a screen
+    #[arg(
+        long,
+        value_name = "x,y",
+        value_parser = parse_vector,
+        env = "SENSOR_ALIGNMENT_ABS_OFFSET"
+    )]
+    abs_offset: Option<(f64, f64)>,
+    #[arg(short, long, env = "SENSOR_ALIGNMENT_ANGLE_DEG")]
+    angle_deg: Option<f64>,
+    /// The angle in radians instead of degrees
+    #[arg(
+        long,
+        value_name = "RADIANS",
+        conflicts_with = "angle_deg",
+        env = "SENSOR_ALIGNMENT_ANGLE_RAD"
+    )]
+    angle_rad: Option<f64>,
+    /// The angle in gradians, 400 to the turn, instead of degrees
+    #[arg(
+        long,
+        value_name = "GRADIANS",
+        conflicts_with_all = ["angle_deg", "angle_rad"],
+        env = "SENSOR_ALIGNMENT_ANGLE_GRAD"
+    )]
+    angle_grad: Option<f64>,
+    /// The angle in turns instead of degrees, e.g. 0.25 for 90°
+    #[arg(
+        long,
+        value_name = "TURNS",
+        conflicts_with_all = ["angle_deg", "angle_rad", "angle_grad"],
+        env = "SENSOR_ALIGNMENT_ANGLE_TURNS"
+    )]
+    angle_turns: Option<f64>,
+    /// Direction pushed on the sensor; the angle becomes the one that turns
+    /// it into the --to direction
+    #[arg(
+        long,
+        value_name = "x,y",
+        value_parser = parse_vector,
+        requires = "to",
+        conflicts_with_all = ["angle_deg", "angle_rad", "angle_grad", "angle_turns"],
+        env = "SENSOR_ALIGNMENT_FROM"
+    )]
+    from: Option<(f64, f64)>,
+    /// Direction the pointer should move in when pushing towards --from
+    #[arg(
+        long,
+        value_name = "x,y",
+        value_parser = parse_vector,
+        requires = "from",
+        env = "SENSOR_ALIGNMENT_TO"
+    )]
+    to: Option<(f64, f64)>,
+    /// Follow the tilt of this accelerometer, adding it to the angle so that
+    /// up stays up on a handheld device
+    #[arg(long, value_name = "PATH", env = "SENSOR_ALIGNMENT_GYRO_DEVICE")]
+    gyro_device: Option<PathBuf>,
+    /// Turn motion on by the rotation of this display output, e.g. HDMI-1,
+    /// as xrandr or wlr-randr report it
+    #[arg(long, value_name = "OUTPUT", env = "SENSOR_ALIGNMENT_MATCH_DISPLAY")]
+    match_display: Option<String>,
+    /// Apply the linear map [[a, b], [c, d]] instead of a rotation, for
+    /// sensors that need shear or uneven scale corrected
+    #[arg(
+        long,
+        value_name = "a,b,c,d",
+        conflicts_with_all = ["angle_deg", "angle_rad", "angle_grad", "angle_turns", "from"],
+        env = "SENSOR_ALIGNMENT_MATRIX"
+    )]
+    matrix: Option<Matrix>,
+    /// Reflect motion across the line at this angle from horizontal instead
+    /// of rotating it: 0 mirrors up and down, 90 left and right
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        conflicts_with_all = [
+            "angle_deg", "angle_rad", "angle_grad", "angle_turns", "from", "matrix"
+        ],
+        env = "SENSOR_ALIGNMENT_MIRROR_AXIS_DEG"
+    )]
+    mirror_axis_deg: Option<f64>,
+    /// One of two calibrated angles to blend by --blend, instead of
+    /// --angle-deg
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        requires = "angle_b",
+        conflicts_with_all = [
+            "angle_deg", "angle_rad", "angle_grad", "angle_turns", "from", "matrix",
+            "mirror_axis_deg"
+        ],
+        env = "SENSOR_ALIGNMENT_ANGLE_A"
+    )]
+    angle_a: Option<f64>,
+    /// The other angle to blend by --blend
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        requires = "angle_a",
+        env = "SENSOR_ALIGNMENT_ANGLE_B"
+    )]
+    angle_b: Option<f64>,
+    /// How much of --angle-b to take, the rest being --angle-a, going the
+    /// short way round [default: 0.5]
+    #[arg(
+        long,
+        value_name = "0..1",
+        requires = "angle_a",
+        env = "SENSOR_ALIGNMENT_BLEND"
+    )]
+    blend: Option<f64>,
+    /// Rotate scroll wheel motion together with pointer motion
+    #[arg(long, env = "SENSOR_ALIGNMENT_ROTATE_SCROLL")]
+    rotate_scroll: bool,
+    /// Which relative axes are rotated, the others keeping their direction:
+    /// x,y for the pointer and wheel,hwheel for scrolling, each pair
+    /// together [default: x,y]
+    #[arg(
+        long,
+        value_name = "AXES",
+        value_parser = parse_rotate_axes,
+        conflicts_with = "rotate_scroll",
+        env = "SENSOR_ALIGNMENT_ROTATE_AXES"
+    )]
+    rotate_axes: Option<RotatedAxes>,
+    /// Rotate scroll wheel motion by this angle rather than the pointer's
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        env = "SENSOR_ALIGNMENT_SCROLL_ANGLE_DEG"
+    )]
+    scroll_angle_deg: Option<f64>,
+    /// Reverse the scroll direction, like natural scrolling, whether or not
+    /// it is rotated
+    #[arg(long, env = "SENSOR_ALIGNMENT_INVERT_SCROLL")]
+    invert_scroll: bool,
+    /// Which wheel events of a device with both clicks and high-resolution
+    /// steps are passed on, for consumers that would scroll twice reading
+    /// both: lowres, hires or both [default: both]
+    #[arg(long, value_name = "MODE", env = "SENSOR_ALIGNMENT_WHEEL_MODE")]
+    wheel_mode: Option<WheelMode>,
+    /// Pass on no wheel events at all, and leave the wheels off the virtual
+    /// device, for when scrolling is handled elsewhere
+    #[arg(long, conflicts_with = "wheel_mode", env = "SENSOR_ALIGNMENT_NO_WHEEL")]
+    no_wheel: bool,
+    /// What becomes of REL_DIAL, as reported by jog dials and some mice:
+    /// pass leaves it alone, wheel or hwheel scroll with it, transformed
+    /// like that wheel [default: pass]
+    #[arg(long, value_name = "MODE", env = "SENSOR_ALIGNMENT_DIAL_MODE")]
+    dial_mode: Option<DialMode>,
+    /// Multiply scrolling by up to this much when the wheel is flicked, the
+    /// more the faster its clicks follow each other
+    #[arg(long, value_name = "FACTOR", env = "SENSOR_ALIGNMENT_SCROLL_ACCEL")]
+    scroll_accel: Option<f64>,
+    /// Only rotate pointer motion faster than this many counts per frame,
+    /// leaving slow, precise motion as it is
+    #[arg(long, value_name = "COUNTS", env = "SENSOR_ALIGNMENT_ROTATE_ABOVE")]
+    rotate_above: Option<f64>,
+    /// Add an angle offset that depends on the speed, for sensors whose
+    /// error changes with it, as counts per frame and degrees such as
+    /// 10:0.5,40:1.2, interpolated linearly from none at a standstill
+    #[arg(
+        long,
+        value_name = "POINTS",
+        value_parser = parse_angle_curve,
+        env = "SENSOR_ALIGNMENT_ANGLE_CURVE"
+    )]
+    angle_curve: Option<AngleCurve>,
+    /// Drop pointer motion shorter than this many counts per frame, to hide
+    /// sensor jitter
+    #[arg(long, value_name = "COUNTS", env = "SENSOR_ALIGNMENT_DEADZONE")]
+    deadzone: Option<f64>,
+    /// Counts per inch of the sensor [default: what the device reports, if
+    /// anything]
+    #[arg(long, value_name = "DPI", env = "SENSOR_ALIGNMENT_INPUT_DPI")]
+    input_dpi: Option<f64>,
+    /// Counts per inch the virtual device moves by, scaling motion by its
+    /// ratio to --input-dpi before anything else
+    #[arg(long, value_name = "DPI", env = "SENSOR_ALIGNMENT_OUTPUT_DPI")]
+    output_dpi: Option<f64>,
+    /// Gain applied to raw horizontal motion before rotating it
+    #[arg(long, value_name = "FACTOR", env = "SENSOR_ALIGNMENT_SCALE_X")]
+    scale_x: Option<f64>,
+    /// Gain applied to raw vertical motion before rotating it
+    #[arg(long, value_name = "FACTOR", env = "SENSOR_ALIGNMENT_SCALE_Y")]
+    scale_y: Option<f64>,
+    /// Pointer acceleration: a frame moving m counts is scaled by
+    /// m^(EXPONENT-1), so 1.0 is linear and larger values speed up fast motion
+    #[arg(long, value_name = "EXPONENT", env = "SENSOR_ALIGNMENT_ACCEL")]
+    accel: Option<f64>,
+    /// Speed multiplier applied to rotated pointer motion
+    #[arg(long, value_name = "FACTOR", env = "SENSOR_ALIGNMENT_SENSITIVITY")]
+    sensitivity: Option<f64>,
+    /// Smooth pointer motion with a moving average giving the newest frame
+    /// this weight (0.0-1.0); lower is steadier but lags further behind
+    #[arg(long, value_name = "ALPHA", env = "SENSOR_ALIGNMENT_SMOOTH")]
+    smooth: Option<f64>,
+    /// Hold back pointer motion that keeps reversing direction, such as from
+    /// a hand tremor, while letting steady motion through
+    #[arg(long, env = "SENSOR_ALIGNMENT_TREMOR_FILTER")]
+    tremor_filter: bool,
+    /// Frames --tremor-filter looks back over for reversals [default: 8]
+    #[arg(long, value_name = "FRAMES", env = "SENSOR_ALIGNMENT_TREMOR_WINDOW")]
+    tremor_window: Option<usize>,
+    /// How much of shaking motion --tremor-filter holds back (0.0-1.0)
+    /// [default: 0.8]
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        env = "SENSOR_ALIGNMENT_TREMOR_STRENGTH"
+    )]
+    tremor_strength: Option<f64>,
+    /// Mirror pointer motion horizontally after rotating it
+    #[arg(long, env = "SENSOR_ALIGNMENT_FLIP_X")]
+    flip_x: bool,
+    /// Mirror pointer motion vertically after rotating it; together with
+    /// --flip-x this is the same as rotating another 180°
+    #[arg(long, env = "SENSOR_ALIGNMENT_FLIP_Y")]
+    flip_y: bool,
+    /// Turn each frame of pointer motion purely horizontal or vertical,
+    /// whichever it's closer to
+    #[arg(long, env = "SENSOR_ALIGNMENT_SNAP_AXIS")]
+    snap_axis: bool,
+    /// How much larger the other direction has to get before --snap-axis
+    /// switches to it [default: 0.25]
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        env = "SENSOR_ALIGNMENT_SNAP_HYSTERESIS"
+    )]
+    snap_hysteresis: Option<f64>,
+    /// Hold slow motion back until this many counts have built up along an
+    /// axis, so a steady stroke at a shallow angle doesn't wobble; nothing
+    /// is lost [default: 0, or 1 without a value]
+    #[arg(
+        long,
+        value_name = "COUNTS",
+        num_args = 0..=1,
+        default_missing_value = "1.0",
+        env = "SENSOR_ALIGNMENT_JITTER_SUPPRESS"
+    )]
+    jitter_suppress: Option<f64>,
+    /// Most counts one frame may move the pointer along either axis, the rest
+    /// is cut off [default: 32767]
+    #[arg(long, value_name = "COUNTS", env = "SENSOR_ALIGNMENT_MAX_DELTA")]
+    max_delta: Option<f64>,
+    /// Fastest the pointer may move in one frame, in counts, slowing faster
+    /// motion down without changing its direction
+    #[arg(long, value_name = "COUNTS", env = "SENSOR_ALIGNMENT_MAX_SPEED")]
+    max_speed: Option<f64>,
+    /// Most counts one frame may move the rotated pointer along X, for a
+    /// noisy axis; the rest is dropped and Y is left alone
+    #[arg(long, value_name = "COUNTS", env = "SENSOR_ALIGNMENT_MAX_SPEED_X")]
+    max_speed_x: Option<f64>,
+    /// Most counts one frame may move the rotated pointer along Y; the rest
+    /// is dropped and X is left alone
+    #[arg(long, value_name = "COUNTS", env = "SENSOR_ALIGNMENT_MAX_SPEED_Y")]
+    max_speed_y: Option<f64>,
+    /// How pointer motion is rounded to whole counts: nearest, floor, ceil,
+    /// bankers, which rounds halves to even, away_from_zero or toward_zero;
+    /// the file can set each axis [default: nearest]
+    #[arg(long, value_name = "MODE", env = "SENSOR_ALIGNMENT_ROUNDING")]
+    rounding: Option<Rounding>,
+    /// Accept commands to adjust the angle at runtime on a Unix socket
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_CONTROL_SOCKET,
+        env = "SENSOR_ALIGNMENT_CONTROL_SOCKET"
+    )]
+    control_socket: Option<PathBuf>,
+    /// Serve org.sensor_alignment.Control1 on the session or system bus
+    #[cfg(feature = "dbus")]
+    #[arg(
+        long,
+        value_name = "BUS",
+        num_args = 0..=1,
+        default_missing_value = "session",
+        env = "SENSOR_ALIGNMENT_DBUS"
+    )]
+    dbus: Option<dbus::Bus>,
+    /// Serve a small HTTP API and a page with a slider for the angle on this
+    /// address, e.g. 127.0.0.1:8080. Anyone who can reach it can change the
+    /// angle
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "ADDR", env = "SENSOR_ALIGNMENT_HTTP_ADDR")]
+    http_addr: Option<std::net::SocketAddr>,
+    /// Degrees added to the angle by SIGUSR1 and subtracted by SIGUSR2
+    #[arg(long, value_name = "DEGREES", env = "SENSOR_ALIGNMENT_ANGLE_STEP")]
+    angle_step: Option<f64>,
+    /// Angle to switch to with --cycle-button; repeat for more
+    #[arg(long, value_name = "DEGREES", env = "SENSOR_ALIGNMENT_PRESET")]
+    preset: Vec<f64>,
+    /// Button that cycles through the --preset angles instead of clicking,
+    /// e.g. BTN_SIDE
+    #[arg(
+        long,
+        value_name = "BTN_NAME",
+        value_parser = parse_key_code,
+        env = "SENSOR_ALIGNMENT_CYCLE_BUTTON"
+    )]
+    cycle_button: Option<KeyCode>,
+    /// Key or button that switches rotation off and back on, e.g. KEY_PAUSE
+    #[arg(
+        long,
+        value_name = "KEY",
+        value_parser = parse_key_code,
+        env = "SENSOR_ALIGNMENT_TOGGLE_KEY"
+    )]
+    toggle_key: Option<KeyCode>,
+    /// Key or button that sets the angle back to 0°, e.g. KEY_F13
+    #[arg(
+        long,
+        value_name = "KEY",
+        value_parser = parse_key_code,
+        env = "SENSOR_ALIGNMENT_RESET_KEY"
+    )]
+    reset_key: Option<KeyCode>,
+    /// Key or button that passes pointer motion through unrotated while it's
+    /// held, e.g. KEY_LEFTCTRL
+    #[arg(
+        long,
+        value_name = "KEY",
+        value_parser = parse_key_code,
+        env = "SENSOR_ALIGNMENT_PAUSE_KEY"
+    )]
+    pause_key: Option<KeyCode>,
+    /// Let the pause key through to the virtual device too, e.g. for a
+    /// modifier that should keep working
+    #[arg(
+        long,
+        requires = "pause_key",
+        env = "SENSOR_ALIGNMENT_FORWARD_PAUSE_KEY"
+    )]
+    forward_pause_key: bool,
+    /// Key or button that pointer motion is only rotated while it's held,
+    /// e.g. BTN_EXTRA for a precision mode
+    #[arg(
+        long,
+        value_name = "BTN",
+        value_parser = parse_key_code,
+        conflicts_with = "pause_key",
+        env = "SENSOR_ALIGNMENT_ROTATE_WHILE"
+    )]
+    rotate_while: Option<KeyCode>,
+    /// Let the --rotate-while button through to the virtual device too
+    #[arg(
+        long,
+        requires = "rotate_while",
+        env = "SENSOR_ALIGNMENT_FORWARD_HOLD_BUTTON"
+    )]
+    forward_hold_button: bool,
+    /// Swap the left and right buttons, for left-handed use
+    #[arg(long, env = "SENSOR_ALIGNMENT_SWAP_BUTTONS")]
+    swap_buttons: bool,
+    /// Send key or button OLD as NEW, e.g. BTN_SIDE=BTN_MIDDLE; repeat for
+    /// more
+    #[arg(long, value_name = "OLD=NEW", value_parser = parse_remap, env = "SENSOR_ALIGNMENT_REMAP")]
+    remap: Vec<(KeyCode, KeyCode)>,
+    /// Click once the pointer has rested for this many seconds after moving,
+    /// for use without pressing buttons
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = parse_interval,
+        env = "SENSOR_ALIGNMENT_DWELL_CLICK"
+    )]
+    dwell_click: Option<std::time::Duration>,
+    /// Counts the pointer may drift while resting for --dwell-click
+    /// [default: 3]
+    #[arg(long, value_name = "COUNTS", env = "SENSOR_ALIGNMENT_DWELL_TOLERANCE")]
+    dwell_tolerance: Option<f64>,
+    /// Button --dwell-click presses [default: BTN_LEFT]
+    #[arg(
+        long,
+        value_name = "BTN_NAME",
+        value_parser = parse_key_code,
+        env = "SENSOR_ALIGNMENT_DWELL_BUTTON"
+    )]
+    dwell_button: Option<KeyCode>,
+    /// Tap this key when the mouse is shaken, which a compositor can bind to
+    /// enlarging the cursor to find it, e.g. KEY_F24
+    #[arg(
+        long,
+        value_name = "KEY",
+        value_parser = parse_key_code,
+        env = "SENSOR_ALIGNMENT_SHAKE_KEY"
+    )]
+    shake_key: Option<KeyCode>,
+    /// Reversals of direction within --shake-window that make a shake
+    /// [default: 4]
+    #[arg(long, value_name = "COUNT", env = "SENSOR_ALIGNMENT_SHAKE_REVERSALS")]
+    shake_reversals: Option<usize>,
+    /// Seconds the reversals of a shake have to fall within [default: 0.6]
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = parse_interval,
+        env = "SENSOR_ALIGNMENT_SHAKE_WINDOW"
+    )]
+    shake_window: Option<std::time::Duration>,
+    /// Counts per frame the mouse has to move at for a reversal to count
+    /// towards a shake [default: 25]
+    #[arg(long, value_name = "COUNTS", env = "SENSOR_ALIGNMENT_SHAKE_SPEED")]
+    shake_speed: Option<f64>,
+    /// Ignore a mouse button let go and pressed again within this many
+    /// milliseconds, which is a worn switch bouncing; releases are held back
+    /// for as long
+    #[arg(long, value_name = "MS", env = "SENSOR_ALIGNMENT_DEBOUNCE")]
+    debounce: Option<u64>,
+    /// Ignore touchpad contacts that are too big, pressed too hard or come
+    /// down at the left or right edge, which is a palm rather than a finger
+    #[arg(long, env = "SENSOR_ALIGNMENT_PALM_REJECT")]
+    palm_reject: bool,
+    /// Share of the touch size range above which a contact is a palm, 1 for
+    /// never [default: 0.5]
+    #[arg(long, value_name = "SHARE", env = "SENSOR_ALIGNMENT_PALM_SIZE")]
+    palm_size: Option<f64>,
+    /// Share of the pressure range above which a contact is a palm, 1 for
+    /// never [default: 0.8]
+    #[arg(long, value_name = "SHARE", env = "SENSOR_ALIGNMENT_PALM_PRESSURE")]
+    palm_pressure: Option<f64>,
+    /// Share of the width at the left and at the right where a contact coming
+    /// down is a palm, 0 for nowhere [default: 0.05]
+    #[arg(long, value_name = "SHARE", env = "SENSOR_ALIGNMENT_PALM_EDGE")]
+    palm_edge: Option<f64>,
+    /// Name of the virtual device, e.g. for udev rules or to tell several
+    /// instances apart [default: sensor alignment virtual device]
+    #[arg(long, value_name = "NAME", env = "SENSOR_ALIGNMENT_VIRTUAL_NAME")]
+    virtual_name: Option<String>,
+    /// Physical path of the virtual device, for udev rules and per-device
+    /// settings to match on [default: none, or with --clone-id the input
+    /// device's under sensor_alignment/]
+    #[arg(long, value_name = "STRING", env = "SENSOR_ALIGNMENT_PHYS")]
+    phys: Option<String>,
+    /// Vendor ID of the virtual device, in hex like 0x1234 or decimal
+    /// [default: 0x1234]
+    #[arg(long, value_name = "ID", value_parser = parse_id, env = "SENSOR_ALIGNMENT_VENDOR")]
+    vendor: Option<u16>,
+    /// Product ID of the virtual device [default: 0x5678]
+    #[arg(long, value_name = "ID", value_parser = parse_id, env = "SENSOR_ALIGNMENT_PRODUCT")]
+    product: Option<u16>,
+    /// Product version of the virtual device [default: 0x0001]
+    #[arg(
+        long,
+        value_name = "ID",
+        value_parser = parse_id,
+        env = "SENSOR_ALIGNMENT_PRODUCT_VERSION"
+    )]
+    product_version: Option<u16>,
+    /// Give the virtual device the bus type and IDs of the (first) input
+    /// device, and its physical path under sensor_alignment/, keeping only
+    /// its name apart
+    #[arg(
+        long,
+        conflicts_with_all = ["vendor", "product", "product_version"],
+        env = "SENSOR_ALIGNMENT_CLONE_ID"
+    )]
+    clone_id: bool,
+    /// Give each source device its own virtual device, named after it, rather
+    /// than merging them into one, e.g. to tell a tablet and a mouse apart
+    #[arg(long, env = "SENSOR_ALIGNMENT_SEPARATE_OUTPUTS")]
+    separate_outputs: bool,
+    /// Send wheel events from a second virtual device that has nothing but
+    /// the wheels, for compositors that handle scrolling better that way
+    #[arg(long, env = "SENSOR_ALIGNMENT_SPLIT_SCROLL")]
+    split_scroll: bool,
+    /// When summed-up motion is emitted: per-sync at every SYN_REPORT the
+    /// device sends, or coalesce:MS to hold frames with nothing but pointer
+    /// motion back until MS milliseconds after the last one emitted, adding
+    /// them up, for fewer events at the cost of that much latency [default:
+    /// per-sync]
+    #[arg(long, value_name = "POLICY", env = "SENSOR_ALIGNMENT_FRAME_POLICY")]
+    frame_policy: Option<FramePolicy>,
+    /// What to do when the virtual device refuses a frame, which is logged
+    /// event by event either way: retry opens the devices again, skip drops
+    /// the events refused and carries on [default: retry]
+    #[arg(long, value_name = "POLICY", env = "SENSOR_ALIGNMENT_EMIT_ERRORS")]
+    emit_errors: Option<EmitErrors>,
+    /// Print the events read and what would be emitted for them instead of
+    /// grabbing the device and creating the virtual one
+    #[arg(long, env = "SENSOR_ALIGNMENT_DRY_RUN")]
+    dry_run: bool,
+    /// Read the device without grabbing it, so other listeners still get its
+    /// events; the cursor then moves both as the device and as the virtual
+    /// one do
+    #[arg(long, env = "SENSOR_ALIGNMENT_NO_GRAB")]
+    no_grab: bool,
+    /// Use the device even if it has none of the axes that would be
+    /// transformed, such as a keyboard
+    #[arg(long, env = "SENSOR_ALIGNMENT_FORCE")]
+    force: bool,
+    /// Write every event handled as a line of JSON, to stderr or to PATH
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = "-",
+        env = "SENSOR_ALIGNMENT_TRACE_JSON"
+    )]
+    trace_json: Option<PathBuf>,
+    /// Append a row for every event passing through to PATH as CSV, with
+    /// its time, type, code, value and the rotated value, for spreadsheets
+    #[arg(long, value_name = "PATH", env = "SENSOR_ALIGNMENT_CSV")]
+    csv: Option<PathBuf>,
+    /// Append every event read to PATH as a line of JSON, for replaying it
+    /// later
+    #[arg(long, value_name = "PATH", env = "SENSOR_ALIGNMENT_RECORD")]
+    record: Option<PathBuf>,
+    /// Log how many events were read and emitted every this many seconds
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = parse_interval,
+        env = "SENSOR_ALIGNMENT_STATS_INTERVAL"
+    )]
+    stats_interval: Option<std::time::Duration>,
+    /// Keep PATH up to date with the current angle and whether rotation is
+    /// on, as key=value lines for status bars
+    #[arg(long, value_name = "PATH", env = "SENSOR_ALIGNMENT_STATUS_FILE")]
+    status_file: Option<PathBuf>,
+    /// Time every frame from reading it to emitting it, and log the spread
+    /// with a histogram on exit
+    #[arg(long, env = "SENSOR_ALIGNMENT_MEASURE_LATENCY")]
+    measure_latency: bool,
+    /// Keep the last N frames of pointer motion, raw and as emitted, and
+    /// write them to --frame-history-file on SIGQUIT or when giving up; N
+    /// is at most 1000000
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..=MAX_FRAME_HISTORY),
+        env = "SENSOR_ALIGNMENT_FRAME_HISTORY"
+    )]
+    frame_history: Option<u32>,
+    /// Where --frame-history is written, as lines of JSON [default: a file
+    /// named after the process ID in the temporary directory]
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "frame_history",
+        env = "SENSOR_ALIGNMENT_FRAME_HISTORY_FILE"
+    )]
+    frame_history_file: Option<PathBuf>,
+    /// Exit after this many seconds, removing the virtual device, e.g. for
+    /// tests
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = parse_interval,
+        env = "SENSOR_ALIGNMENT_DURATION"
+    )]
+    duration: Option<std::time::Duration>,
+    /// Detach from the terminal and run in the background
+    #[arg(long, env = "SENSOR_ALIGNMENT_DAEMON")]
+    daemon: bool,
+    /// PID file written in daemon mode
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = daemon::DEFAULT_PID_FILE,
+        env = "SENSOR_ALIGNMENT_PID_FILE"
+    )]
+    pid_file: PathBuf,
+    /// Where output goes in daemon mode
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = daemon::DEFAULT_LOG_FILE,
+        env = "SENSOR_ALIGNMENT_LOG_FILE"
+    )]
+    log_file: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Rotate the source devices' motion into the virtual device until
+    /// stopped, which is also what happens without a subcommand
+    Run(Box<RunArgs>),
+    /// List the available input devices
+    List,
+    /// Measure the angle by moving the mouse straight up
+    Calibrate(CalibrateArgs),
+    /// Nudge the angle with the arrow keys while watching the motion turn,
+    /// and save it with Enter
+    #[cfg(feature = "tui")]
+    Tune,
+    /// Feed events saved with --record through the transform into the
+    /// virtual device, with their original timing
+    Replay(ReplayArgs),
+    /// Print everything a device reports about itself, for bug reports,
+    /// without grabbing it
+    Inspect(InspectArgs),
+    /// Ask the instance listening on the control socket whether it's active,
+    /// what its angle is, which devices it reads and how many events it
+    /// handled
+    Status(StatusArgs),
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+    /// Print the version along with the commit, compiler and features it was
+    /// built with, for bug reports; --version prints just the version
+    Version,
+}
+
+#[derive(clap::Args, Debug)]
+struct CalibrateArgs {
+    /// Save the measured angle to the config file, or the scales with
+    /// --circle
+    #[arg(long)]
+    write: bool,
+    /// Trace circles instead of pushing straight up, to measure how much
+    /// more the sensor counts along one direction than across it
+    #[arg(long)]
+    circle: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReplayArgs {
+    file: PathBuf,
+    /// Play back this many times faster
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+    /// Start over at the end until interrupted
+    #[arg(long = "loop")]
+    repeat: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct InspectArgs {
+    /// Device node, where event7 or just 7 stands for /dev/input/event7
+    path: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct StatusArgs {
+    /// Control socket of the instance [default: the config file's, or
+    /// /run/sensor_alignment.sock]
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompletionsArgs {
+    #[arg(value_enum)]
+    shell: clap_complete::Shell,
+}
+
+/// Effective settings after merging the command line over the config file.
+#[derive(Debug)]
+struct Settings {
+    config_path: Option<PathBuf>,
+    // It and the files it includes, for --watch-config.
+    config_files: Vec<PathBuf>,
+    // The keys set in the config file, whether or not something overrode
+    // them.
+    file_keys: Vec<String>,
+    profile: Option<String>,
+    // The profiles the config file has, for switching between them.
+    profiles: Vec<String>,
+    devices: Vec<DeviceSelector>,
+    // Angles of devices that have their own, used instead of `angle_deg`
+    // and not changed at runtime.
+    device_angles: Vec<(DeviceSelector, f64)>,
+    axis_mode: AxisMode,
+    wheel_mode: WheelMode,
+    // Shares of each axis's range absolute positions are moved by.
+    abs_offset: (f64, f64),
+    angle_deg: f64,
+    // The angle as given, when that wasn't in degrees.
+    angle_as_given: Option<(f64, AngleUnit)>,
+    // The directions the angle was derived from, if it was.
+    angle_from_to: Option<((f64, f64), (f64, f64))>,
+    // The two angles blended into it and the weight of the second, if it
+    // was.
+    angle_blend: Option<(f64, f64, f64)>,
+    // The accelerometer whose tilt is added to the angle as it changes.
+    gyro_device: Option<PathBuf>,
+    // The display output whose rotation was added to the angle, and that
+    // rotation, 0° if it couldn't be found out.
+    display: Option<(String, f64)>,
+    // Applied before the rotation, which starts out at 0° when it's given
+    // and no angle is.
+    pipeline: Pipeline,
+    scroll: ScrollOptions,
+    // Whether pointer motion is rotated, rather than passed through.
+    rotate_pointer: bool,
+    // Whether a touchpad's scrolling turns with its pointer motion without
+    // --rotate-scroll, which it does unless --rotate-axes says otherwise.
+    touch_scroll: bool,
+    pointer: PointerOptions,
+    rotate_above: f64,
+    angle_curve: AngleCurve,
+    response: ResponseCurve,
+    // Resolutions whose ratio becomes the pointer's `dpi_scale`, once the
+    // input one is known for each device.
+    input_dpi: Option<f64>,
+    output_dpi: Option<f64>,
+    control_socket: Option<PathBuf>,
+    angle_step: f64,
+    virtual_name: String,
+    // Replaces the physical path of every virtual device, the cloned one
+    // included.
+    phys: Option<CString>,
+    input_id: InputId,
+    // Whether `input_id` is replaced by that of the input device.
+    clone_id: bool,
+    // One virtual device per source instead of one for all.
+    separate_outputs: bool,
+    // The wheels on a virtual device of their own.
+    split_scroll: bool,
+    emit_errors: EmitErrors,
+    hotkeys: Hotkeys,
+    // Keys sent under another code, as (from, to), --swap-buttons included.
+    remap: Vec<(KeyCode, KeyCode)>,
+    dwell: Option<DwellOptions>,
+    shake: Option<ShakeOptions>,
+    // How long a mouse button's release is held back to tell a bounce.
+    debounce: Option<std::time::Duration>,
+    frame_policy: FramePolicy,
+    palm: Option<PalmOptions>,
+    // Profiles picked by the focused window, read once at start.
+    app_profiles: Vec<AppProfile>,
+    focus_command: Option<String>,
+    dry_run: bool,
+    // Whether to open devices without the axes that would be transformed.
+    force: bool,
+    // Whether the input devices are grabbed exclusively.
+    grab: bool,
+}
+
+impl Args {
+    /// Parses `argv` as the command line, with the subcommand settled.
+    fn parse_settled<I, T>(argv: I) -> Result<(Args, clap::ArgMatches), clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let matches = Args::command().try_get_matches_from(argv)?;
+        let mut args = Args::from_arg_matches(&matches)?;
+        args.settle_command(&matches)?;
+        Ok((args, matches))
+    }
+
+    /// Merges the options given after `run` into the ones given before any
+    /// subcommand, which count when it's left out, and turns the old
+    /// --list-devices into `list`. `matches` are those `self` was parsed
+    /// from.
+    fn settle_command(&mut self, matches: &clap::ArgMatches) -> Result<(), clap::Error> {
+        match self.command.take() {
+            Some(Command::Run(_)) => {
+                let after = matches
+                    .subcommand_matches("run")
+                    .expect("run has matches of its own");
+                self.run = merge_run_args(matches, after)?;
+            }
+            _ if self.list_devices => self.command = Some(Command::List),
+            command => self.command = command,
+        }
+        Ok(())
+    }
+}
+
+/// The run options given before `run` and those given after it, as one
+/// set. An option given on both sides is refused rather than one of them
+/// being dropped.
+///
+/// The options given are put back together into a command line of their
+/// own, which is parsed again, so that the environment and the defaults
+/// fill in the rest as they would otherwise.
+fn merge_run_args(
+    before: &clap::ArgMatches,
+    after: &clap::ArgMatches,
+) -> Result<RunArgs, clap::Error> {
+    let mut command = <RunArgs as clap::Args>::augment_args(clap::Command::new("run"));
+    let mut argv = vec![std::ffi::OsString::from("run")];
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        let given =
+            |matches: &clap::ArgMatches| matches.value_source(id) == Some(ValueSource::CommandLine);
+        let long = arg.get_long().expect("run options are long options");
+        let matches = match (given(before), given(after)) {
+            (true, true) => {
+                let message = format!("--{long} was given both before and after run");
+                return Err(
+                    Args::command().error(clap::error::ErrorKind::ArgumentConflict, message)
+                );
+            }
+            (true, false) => before,
+            (false, true) => after,
+            (false, false) => continue,
+        };
+        for occurrence in matches.get_raw_occurrences(id).into_iter().flatten() {
+            let values: Vec<&std::ffi::OsStr> = occurrence.collect();
+            if !arg.get_action().takes_values() || values.is_empty() {
+                argv.push(format!("--{long}").into());
+            }
+            for value in values.iter().filter(|_| arg.get_action().takes_values()) {
+                let mut token = std::ffi::OsString::from(format!("--{long}="));
+                token.push(value);
+                argv.push(token);
+            }
+        }
+    }
+    RunArgs::from_arg_matches(&command.try_get_matches_from_mut(argv)?)
+}
+
+impl Settings {
+    /// Whether switching to `new` needs the devices to be opened again,
+    /// rather than just updating the running event loop.
+    fn needs_reopen(&self, new: &Settings) -> bool {
+        self.devices != new.devices
+            || self.device_angles != new.device_angles
+            || self.axis_mode != new.axis_mode
+            || self.abs_offset != new.abs_offset
+            || self.wheel_mode != new.wheel_mode
+            || self.pipeline != new.pipeline
+            || self.scroll != new.scroll
+            || self.rotate_pointer != new.rotate_pointer
+            || self.touch_scroll != new.touch_scroll
+            || self.pointer != new.pointer
+            || self.rotate_above != new.rotate_above
+            || self.angle_curve != new.angle_curve
+            || self.response != new.response
+            || self.input_dpi != new.input_dpi
+            || self.output_dpi != new.output_dpi
+            || self.hotkeys != new.hotkeys
+            || self.remap != new.remap
+            || self.dwell != new.dwell
+            || self.shake != new.shake
+            || self.debounce != new.debounce
+            || self.frame_policy != new.frame_policy
+            || self.palm != new.palm
+            || self.virtual_name != new.virtual_name
+            || self.phys != new.phys
+            || self.input_id != new.input_id
+            || self.clone_id != new.clone_id
+            || self.separate_outputs != new.separate_outputs
+            || self.split_scroll != new.split_scroll
+            || self.emit_errors != new.emit_errors
+    }
+
+    /// Whether the angle is 0°, or close enough that its rotation is the
+    /// identity, with nothing else set that changes motion or scrolling, or
+    /// could turn the angle on its own.
+    fn changes_nothing(&self) -> bool {
+        let is_zero = |angle_deg: f64| Matrix::rotation(angle_deg) == Matrix::IDENTITY;
+        is_zero(self.angle_deg)
+            && self
+                .device_angles
+                .iter()
+                .all(|&(_, angle_deg)| is_zero(angle_deg))
+            && self.gyro_device.is_none()
+            && self.pipeline == Pipeline::default()
+            && self.pointer.is_neutral()
+            && self.angle_curve.points().is_empty()
+            && self.response.points().is_empty()
+            && self.input_dpi.is_none()
+            && self.output_dpi.is_none()
+            && self.abs_offset == (0.0, 0.0)
+            && self.scroll.angle_deg.is_none_or(is_zero)
+            && !self.scroll.invert
+            && self.scroll.accel == 1.0
+    }
+
+    /// The angle of its own given to the device `selector` picks, if any.
+    fn device_angle_deg(&self, selector: &DeviceSelector) -> Option<f64> {
+        self.device_angles
+            .iter()
+            .find(|(angled, _)| angled == selector)
+            .map(|&(_, angle_deg)| angle_deg)
+    }
+
+    /// Whether wheel motion turns with the pointer, as it does on its own
+    /// for a touchpad's two-finger scrolling.
+    fn rotates_scroll(&self, touchpad: bool) -> bool {
+        self.scroll.rotate || self.touch_scroll && touchpad
+    }
+
+    /// Declares what the transform may turn a device's events into on top
+    /// of what it has: the wheel a dial becomes, both axes of rotated
+    /// wheels, and the keys others are remapped to.
+    fn declare_transformed(&self, capabilities: &mut Capabilities, touchpad: bool) {
+        capabilities.declare_dial_wheel(self.scroll.dial);
+        if self.rotates_scroll(touchpad) {
+            capabilities.declare_rotated_wheels();
+        }
+        capabilities.declare_remapped_keys(&self.remap);
+    }
+
+    /// What differs in `new`, as `name old -> new` with the names used in
+    /// the config file, for logging reloads.
+    fn changes(&self, new: &Settings) -> Vec<String> {
+        let mut changes = Vec::new();
+        macro_rules! compare {
+            ($($name:literal: $($field:ident).+),* $(,)?) => {$(
+                if self.$($field).+ != new.$($field).+ {
+                    changes.push(format!(
+                        "{} {:?} -> {:?}",
+                        $name,
+                        self.$($field).+,
+                        new.$($field).+
+                    ));
+                }
+            )*};
+        }
+        compare!(
+            "devices": devices,
+            "device angles": device_angles,
+            "axis_mode": axis_mode,
+            "abs_offset": abs_offset,
+            "wheel_mode": wheel_mode,
+            "angle_deg": angle_deg,
+            "angle blend": angle_blend,
+            "match_display": display,
+            "transform": pipeline,
+            "rotate_scroll": scroll.rotate,
+            "rotate pointer": rotate_pointer,
+            "touchpad scroll": touch_scroll,
+            "scroll_angle_deg": scroll.angle_deg,
+            "invert_scroll": scroll.invert,
+            "scroll_accel": scroll.accel,
+            "dial_mode": scroll.dial,
+            "rotate_above": rotate_above,
+            "angle_curve": angle_curve,
+            "response": response,
+            "deadzone": pointer.deadzone,
+            "input_dpi": input_dpi,
+            "output_dpi": output_dpi,
+            "scale_x": pointer.scale_x,
+            "scale_y": pointer.scale_y,
+            "accel": pointer.accel,
+            "sensitivity": pointer.sensitivity,
+            "smooth": pointer.smooth,
+            "tremor_window": pointer.tremor_window,
+            "tremor_strength": pointer.tremor_strength,
+            "flip_x": pointer.flip_x,
+            "flip_y": pointer.flip_y,
+            "snap_axis": pointer.snap_axis,
+            "snap_hysteresis": pointer.snap_hysteresis,
+            "jitter_suppress": pointer.jitter_suppress,
+            "max_delta": pointer.max_delta,
+            "max_speed": pointer.max_speed,
+            "max_speed_x": pointer.max_speed_x,
+            "max_speed_y": pointer.max_speed_y,
+            "rounding_x": pointer.rounding_x,
+            "rounding_y": pointer.rounding_y,
+            "control_socket": control_socket,
+            "angle_step": angle_step,
+            "virtual_name": virtual_name,
+            "phys": phys,
+            "ids": input_id,
+            "clone_id": clone_id,
+            "separate_outputs": separate_outputs,
+            "split_scroll": split_scroll,
+            "emit_errors": emit_errors,
+            "preset": hotkeys.presets,
+            "cycle_button": hotkeys.cycle_button,
+            "toggle_key": hotkeys.toggle_key,
+            "reset_key": hotkeys.reset_key,
+            "pause_key": hotkeys.pause_key,
+            "forward_pause_key": hotkeys.forward_pause_key,
+            "rotate_while": hotkeys.hold_button,
+            "forward_hold_button": hotkeys.forward_hold_button,
+            "chord": hotkeys.chords,
+            "remap": remap,
+            "dwell": dwell,
+            "shake": shake,
+            "debounce": debounce,
+            "frame_policy": frame_policy,
+            "palm_reject": palm,
+        );
+        changes
+    }
+
+    /// The config file given on the command line or the default one if it
+    /// exists, and what it says with `profile` applied.
+    fn load_config(
+        args: &Args,
+        profile: Option<&str>,
+    ) -> std::io::Result<(Option<PathBuf>, Config)> {
+        let config_path = args
+            .config
+            .clone()
+            .or_else(|| Config::default_path().filter(|path| path.exists()));
+        let config = match (&config_path, profile) {
+            (Some(path), _) => Config::load(path, profile)?,
+            (None, Some(name)) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("profile {name:?} given, but there is no config file"),
+                ));
+            }
+            (None, None) => Config::default(),
+        };
+        Ok((config_path, config))
+    }
+
+    /// Devices given on the command line in any form override the config
+    /// file, and within each paths win over patterns, which win over names.
+    fn select_devices(args: &Args, config: &Config) -> Result<Vec<DeviceSpec>, AlignError> {
+        let config_paths = config
+            .device_path
+            .iter()
+            .map(|path| {
+                path.parse()
+                    .map_err(|e| AlignError::Parse(format!("device_path: {e}")))
+            })
+            .collect::<Result<Vec<DeviceSpec>, _>>()?;
+        let select = |paths: &[DeviceSpec], globs: &[String], names: &[String]| {
+            if !paths.is_empty() {
+                paths.to_vec()
+            } else if !globs.is_empty() {
+                globs
+                    .iter()
+                    .map(|glob| DeviceSelector::Glob(glob.clone()).into())
+                    .collect()
+            } else {
+                names
+                    .iter()
+                    .map(|name| DeviceSelector::Name(name.clone()).into())
+                    .collect()
+            }
+        };
+        let mut devices = select(&args.device_path, &args.device_glob, &args.device_name);
+        if devices.is_empty() {
+            devices = select(&config_paths, &config.device_glob, &config.device_name);
+        }
+        if devices.is_empty() {
+            devices.push(DeviceSelector::Auto.into());
+        }
+        Ok(devices)
+    }
+
+    fn virtual_name(args: &Args, config: &Config) -> String {
+        args.run
+            .virtual_name
+            .clone()
+            .or_else(|| config.virtual_name.clone())
+            .unwrap_or_else(|| DEFAULT_VIRTUAL_NAME.to_string())
+    }
+
+    fn resolve(args: &Args, profile: Option<&str>) -> Result<Settings, AlignError> {
+        let (config_path, config) = Settings::load_config(args, profile)?;
+        let specs = Settings::select_devices(args, &config)?;
+        let devices = specs.iter().map(|spec| spec.selector.clone()).collect();
+        let device_angles: Vec<(DeviceSelector, f64)> = specs
+            .into_iter()
+            .filter_map(|spec| {
+                Some((
+                    spec.selector,
+                    sensor_alignment::normalize_angle_deg(spec.angle_deg?),
+                ))
+            })
+            .collect();
+
+        let missing = |name: &str| {
+            AlignError::Parse(format!(
+                "{name} must be given on the command line or in the config file"
+            ))
+        };
+
+        // Only one way of describing the correction may be used, but one
+        // given on the command line replaces any in the config file.
+        let cli_given = args.run.angle_deg.is_some()
+            || args.run.angle_rad.is_some()
+            || args.run.angle_grad.is_some()
+            || args.run.angle_turns.is_some()
+            || args.run.from.is_some()
+            || args.run.matrix.is_some()
+            || args.run.mirror_axis_deg.is_some()
+            || args.run.angle_a.is_some();
+        let (angle_deg, other_units, angle_from_to, matrix, mirror_axis_deg, steps) = if cli_given {
+            (
+                args.run.angle_deg,
+                [
+                    (args.run.angle_rad, AngleUnit::Radians),
+                    (args.run.angle_grad, AngleUnit::Gradians),
+                    (args.run.angle_turns, AngleUnit::Turns),
+                ],
+                args.run.from.zip(args.run.to),
+                args.run.matrix,
+                args.run.mirror_axis_deg,
+                Vec::new(),
+            )
+        } else {
+            let from_to = match (config.from, config.to) {
+                (Some([fx, fy]), Some([tx, ty])) => Some(((fx, fy), (tx, ty))),
+                (None, None) => None,
+                _ => {
+                    return Err(AlignError::Parse(
+                        "from and to have to be given together".into(),
+                    ));
+                }
+            };
+            (
+                config.angle_deg,
+                [
+                    (config.angle_rad, AngleUnit::Radians),
+                    (config.angle_grad, AngleUnit::Gradians),
+                    (config.angle_turns, AngleUnit::Turns),
+                ],
+                from_to,
+                config.matrix.map(|[a, b, c, d]| Matrix { a, b, c, d }),
+                config.mirror_axis_deg,
+                config.transform.iter().map(|step| step.to_step()).collect(),
+            )
+        };
+        let derived_deg = match angle_from_to {
+            Some((from, to)) => {
+                Some(sensor_alignment::angle_between(from, to).ok_or_else(|| {
+                    AlignError::Parse("from and to must not be zero vectors".into())
+                })?)
+            }
+            None => None,
+        };
+        let mut other_units = other_units
+            .into_iter()
+            .filter_map(|(angle, unit)| Some((angle?, unit)));
+        let angle_as_given = other_units.next();
+        let angle_deg = match (angle_deg, angle_as_given, derived_deg, other_units.next()) {
+            (angle_deg, None, None, None) => angle_deg,
+            (None, Some((angle, unit)), None, None) => Some(unit.to_degrees(angle)),
+            (None, None, derived_deg, None) => derived_deg,
+            _ => {
+                return Err(AlignError::Parse(
+                    "only one of angle_deg, angle_rad, angle_grad, angle_turns and from/to can \
+                     be used"
+                        .into(),
+                ));
+            }
+        };
+        let (angle_a, angle_b, blend) = if cli_given {
+            (args.run.angle_a, args.run.angle_b, args.run.blend)
+        } else {
+            (config.angle_a, config.angle_b, config.blend)
+        };
+        let angle_blend = match (angle_a, angle_b) {
+            (Some(a), Some(b)) => {
+                let weight = blend.unwrap_or(0.5);
+                if !(0.0..=1.0).contains(&weight) {
+                    return Err(AlignError::Parse(format!(
+                        "blend must be between 0.0 and 1.0, not {weight}"
+                    )));
+                }
+                Some((a, b, weight))
+            }
+            (None, None) if blend.is_none() => None,
+            _ => {
+                return Err(AlignError::Parse(
+                    "angle_a and angle_b have to be given together, blend only with them".into(),
+                ));
+            }
+        };
+        let angle_deg = match (angle_deg, angle_blend) {
+            (Some(_), Some(_)) => {
+                return Err(AlignError::Parse(
+                    "angle_a and angle_b can't be combined with another angle".into(),
+                ));
+            }
+            (angle_deg, angle_blend) => angle_deg
+                .or(angle_blend
+                    .map(|(a, b, weight)| sensor_alignment::blend_angles_deg(a, b, weight))),
+        };
+        // Looked up again on every reload, so SIGHUP catches a display that
+        // was turned since.
+        let display = args
+            .run
+            .match_display
+            .clone()
+            .or_else(|| config.match_display.clone())
+            .map(|output| {
+                let rotation = display::rotation_deg(&output).unwrap_or_else(|| {
+                    warn!("Couldn't find out how {output} is rotated, taking it as not rotated");
+                    0.0
+                });
+                (output, rotation)
+            });
+        // The display's rotation is a step of its own after the rest, so a
+        // display alone stands in for an angle of 0°.
+        let angle_deg = match &display {
+            Some(_) if matrix.is_none() && mirror_axis_deg.is_none() => {
+                Some(angle_deg.unwrap_or(0.0))
+            }
+            _ => angle_deg,
+        };
+        for (name, angle) in [
+            ("angle", angle_deg),
+            ("mirror_axis_deg", mirror_axis_deg),
+            (
+                "scroll_angle_deg",
+                args.run.scroll_angle_deg.or(config.scroll_angle_deg),
+            ),
+        ] {
+            if let Some(angle) = angle
+                && !angle.is_finite()
+            {
+                return Err(AlignError::Parse(format!(
+                    "{name} must be a finite number, not {angle}"
+                )));
+            }
+        }
+        let angle_deg = angle_deg.map(sensor_alignment::normalize_angle_deg);
+        // An angle on its own is a pipeline of nothing but the rotation.
+        let (angle_deg, pipeline) = match (angle_deg, matrix, mirror_axis_deg) {
+            (angle_deg, None, None) if !steps.is_empty() => {
+                (angle_deg.unwrap_or(0.0), Pipeline::new(steps))
+            }
+            (Some(angle_deg), None, None) => (angle_deg, Pipeline::default()),
+            (None, Some(matrix), None) if steps.is_empty() => (0.0, Pipeline::from(matrix)),
+            (None, None, Some(axis_deg)) if steps.is_empty() => {
+                (0.0, Pipeline::from(Matrix::reflection(axis_deg)))
+            }
+            (None, None, None) => {
+                return Err(missing(
+                    "angle_deg, angle_rad, angle_grad, angle_turns, from/to, matrix, \
+                     mirror_axis_deg or [[transform]]",
+                ));
+            }
+            _ if !steps.is_empty() => {
+                return Err(AlignError::Parse(
+                    "matrix and mirror_axis_deg can't be combined with [[transform]], add them as steps instead".into(),
+                ));
+            }
+            _ => {
+                return Err(AlignError::Parse(
+                    "only one of an angle, matrix and mirror_axis_deg can be used".into(),
+                ));
+            }
+        };
+        // After whatever the user's transform does, since the screen turns
+        // what comes out of it. Rotations commute, so coming ahead of the
+        // live angle's is the same as after it.
+        let pipeline = match &display {
+            Some((_, rotation)) if *rotation != 0.0 => Pipeline::new(
+                pipeline
+                    .steps()
+                    .iter()
+                    .copied()
+                    .chain([Step::Rotate {
+                        angle_deg: *rotation,
+                    }])
+                    .collect(),
+            ),
+            _ => pipeline,
+        };
+        if angle_deg == 0.0
+            && pipeline.steps().is_empty()
+            && device_angles.iter().all(|&(_, angle_deg)| angle_deg == 0.0)
+        {
+            warn!(
+                "The angle is {}, so motion is passed through unchanged",
+                Degrees(0.0)
+            );
+        }
+        let determinant = pipeline.matrix().determinant();
+        if determinant.abs() < 1e-3 {
+            warn!(
+                "The matrix is nearly degenerate (determinant {determinant}), motion will collapse onto a line"
+            );
+        }
+
+        let smooth = args.run.smooth.or(config.smooth).unwrap_or(1.0);
+        if !(smooth > 0.0 && smooth <= 1.0) {
+            return Err(AlignError::Parse(format!(
+                "smooth must be above 0.0 and at most 1.0, not {smooth}"
+            )));
+        }
+
+        let tremor_filter = args.run.tremor_filter || config.tremor_filter.unwrap_or(false);
+        let tremor_window = args.run.tremor_window.or(config.tremor_window).unwrap_or(8);
+        let tremor_strength = args
+            .run
+            .tremor_strength
+            .or(config.tremor_strength)
+            .unwrap_or(0.8);
+        if !(0.0..=1.0).contains(&tremor_strength) {
+            return Err(AlignError::Parse(format!(
+                "tremor_strength must be between 0.0 and 1.0, not {tremor_strength}"
+            )));
+        }
+        if tremor_filter && tremor_window < 2 {
+            return Err(AlignError::Parse(format!(
+                "tremor_window must be at least 2 frames, not {tremor_window}"
+            )));
+        }
+
+        let angle_curve = match (&args.run.angle_curve, &config.angle_curve) {
+            (Some(curve), _) => curve.clone(),
+            (None, Some(points)) => AngleCurve::new(
+                points
+                    .iter()
+                    .map(|&[speed, offset]| (speed, offset))
+                    .collect(),
+            )
+            .map_err(AlignError::Parse)?,
+            (None, None) => AngleCurve::default(),
+        };
+        let response = ResponseCurve::new(
+            config
+                .response
+                .iter()
+                .map(|point| (point.speed, point.gain))
+                .collect(),
+        )
+        .map_err(AlignError::Parse)?;
+
+        let jitter_suppress = args
+            .run
+            .jitter_suppress
+            .or(config.jitter_suppress)
+            .unwrap_or(0.0);
+        if !(jitter_suppress >= 0.0 && jitter_suppress.is_finite()) {
+            return Err(AlignError::Parse(format!(
+                "jitter_suppress must be 0 or more, not {jitter_suppress}"
+            )));
+        }
+
+        let max_delta = args
+            .run
+            .max_delta
+            .or(config.max_delta)
+            .unwrap_or(i16::MAX as f64);
+        if max_delta.is_nan() || max_delta < 1.0 {
+            return Err(AlignError::Parse(format!(
+                "max_delta must be at least 1, not {max_delta}"
+            )));
+        }
+
+        let max_speed = args.run.max_speed.or(config.max_speed);
+        let max_speed_x = args.run.max_speed_x.or(config.max_speed_x);
+        let max_speed_y = args.run.max_speed_y.or(config.max_speed_y);
+        for (name, value) in [
+            ("max_speed", max_speed),
+            ("max_speed_x", max_speed_x),
+            ("max_speed_y", max_speed_y),
+        ] {
+            if let Some(value) = value
+                && !(value > 0.0 && value.is_finite())
+            {
+                return Err(AlignError::Parse(format!(
+                    "{name} must be above 0, not {value}"
+                )));
+            }
+        }
+
+        // Gains that leave the pointer frozen or flung across the screen are
+        // caught here, rather than looking like the device stopped working.
+        let sensitivity = args.run.sensitivity.or(config.sensitivity).unwrap_or(1.0);
+        if !(sensitivity > 0.0 && sensitivity.is_finite()) {
+            return Err(AlignError::Parse(format!(
+                "sensitivity must be above 0, not {sensitivity}: the pointer wouldn't move"
+            )));
+        }
+        let scale_x = args.run.scale_x.or(config.scale_x).unwrap_or(1.0);
+        let scale_y = args.run.scale_y.or(config.scale_y).unwrap_or(1.0);
+        for (name, scale) in [("scale_x", scale_x), ("scale_y", scale_y)] {
+            if scale == 0.0 || !scale.is_finite() {
+                return Err(AlignError::Parse(format!(
+                    "{name} must be a number other than 0, not {scale}: the pointer wouldn't \
+                     move along that axis"
+                )));
+            }
+            if scale < 0.0 {
+                warn!(
+                    "{name} is {scale}, which mirrors motion along that axis before rotating it; \
+                     flip_x and flip_y mirror it afterwards"
+                );
+            }
+        }
+        let [sensitivity, scale_x, scale_y] = [
+            ("sensitivity", sensitivity),
+            ("scale_x", scale_x),
+            ("scale_y", scale_y),
+        ]
+        .map(|(name, gain)| {
+            if gain.abs() <= MAX_GAIN {
+                return gain;
+            }
+            warn!("{name} {gain} would throw the pointer across the screen, using {MAX_GAIN}");
+            MAX_GAIN.copysign(gain)
+        });
+
+        let input_dpi = args.run.input_dpi.or(config.input_dpi);
+        let output_dpi = args.run.output_dpi.or(config.output_dpi);
+        for (name, dpi) in [("input_dpi", input_dpi), ("output_dpi", output_dpi)] {
+            if let Some(dpi) = dpi
+                && !(dpi > 0.0 && dpi.is_finite())
+            {
+                return Err(AlignError::Parse(format!(
+                    "{name} must be a positive number, not {dpi}"
+                )));
+            }
+        }
+        if input_dpi.is_some() && output_dpi.is_none() {
+            warn!("An input DPI is set, but motion isn't scaled without output_dpi");
+        }
+
+        // Axes are only set apart in the file, a flag applies to both.
+        let rounding = |axis: &Option<String>| match (args.run.rounding, axis, &config.rounding) {
+            (Some(rounding), _, _) => Ok(rounding),
+            (None, Some(name), _) | (None, None, Some(name)) => {
+                name.parse().map_err(AlignError::Parse)
+            }
+            (None, None, None) => Ok(Rounding::default()),
+        };
+        let rounding_x = rounding(&config.rounding_x)?;
+        let rounding_y = rounding(&config.rounding_y)?;
+
+        let axis_mode = match (args.run.axis_mode, &config.axis_mode) {
+            (Some(mode), _) => mode,
+            (None, Some(name)) => name.parse().map_err(AlignError::Parse)?,
+            (None, None) => AxisMode::default(),
+        };
+        let wheel_mode = match (args.run.wheel_mode, &config.wheel_mode) {
+            _ if args.run.no_wheel || config.no_wheel.unwrap_or(false) => WheelMode::Off,
+            (Some(mode), _) => mode,
+            (None, Some(name)) => name.parse().map_err(AlignError::Parse)?,
+            (None, None) => WheelMode::default(),
+        };
+        let abs_offset = args
+            .run
+            .abs_offset
+            .or(config.abs_offset.map(|[x, y]| (x, y)))
+            .unwrap_or((0.0, 0.0));
+        // A whole range or more would leave nothing of the surface in range.
+        if ![abs_offset.0, abs_offset.1]
+            .iter()
+            .all(|share| share.abs() < 1.0)
+        {
+            return Err(AlignError::Parse(format!(
+                "abs_offset must be above -1.0 and below 1.0 on both axes, not {},{}",
+                abs_offset.0, abs_offset.1
+            )));
+        }
+
+        let key_code = |arg: Option<KeyCode>, name: &Option<String>| match (arg, name) {
+            (Some(code), _) => Ok(Some(code)),
+            (None, Some(name)) => parse_key_code(name).map(Some).map_err(AlignError::Parse),
+            (None, None) => Ok(None),
+        };
+        // A list of axes decides on its own, in the file over rotate_scroll.
+        let rotated_axes = match (args.run.rotate_axes, &config.rotate_axes) {
+            (Some(axes), _) => axes,
+            (None, Some(names)) => parse_rotate_axes(&names.join(","))
+                .map_err(|e| AlignError::Parse(format!("rotate_axes: {e}")))?,
+            (None, None) => RotatedAxes {
+                pointer: true,
+                wheels: args.run.rotate_scroll || config.rotate_scroll.unwrap_or(false),
+            },
+        };
+        let scroll = ScrollOptions {
+            rotate: rotated_axes.wheels,
+            angle_deg: args.run.scroll_angle_deg.or(config.scroll_angle_deg),
+            invert: args.run.invert_scroll || config.invert_scroll.unwrap_or(false),
+            accel: args.run.scroll_accel.or(config.scroll_accel).unwrap_or(1.0),
+            dial: match (args.run.dial_mode, &config.dial_mode) {
+                (Some(mode), _) => mode,
+                (None, Some(name)) => name.parse().map_err(AlignError::Parse)?,
+                (None, None) => DialMode::default(),
+            },
+        };
+        if wheel_mode == WheelMode::Off && scroll.dial != DialMode::Pass {
+            return Err(AlignError::Parse(format!(
+                "dial_mode can't scroll with {:?} while the wheels are off",
+                scroll.dial
+            )));
+        }
+        if !(scroll.accel >= 1.0 && scroll.accel.is_finite()) {
+            return Err(AlignError::Parse(format!(
+                "scroll_accel must be at least 1.0, not {}",
+                scroll.accel
+            )));
+        }
+        if scroll.angle_deg.is_some() && !scroll.rotate {
+            warn!(
+                "A scroll angle is set, but scroll isn't rotated without rotate_scroll or the wheels in rotate_axes"
+            );
+        }
+
+        // The command line beats the file, and both beat --swap-buttons.
+        let mut remap = Vec::<(KeyCode, KeyCode)>::new();
+        let mut set =
+            |from: KeyCode, to: KeyCode| match remap.iter_mut().find(|(known, _)| *known == from) {
+                Some(entry) => entry.1 = to,
+                None => remap.push((from, to)),
+            };
+        if args.run.swap_buttons || config.swap_buttons.unwrap_or(false) {
+            set(KeyCode::BTN_LEFT, KeyCode::BTN_RIGHT);
+            set(KeyCode::BTN_RIGHT, KeyCode::BTN_LEFT);
+        }
+        for (from, to) in &config.remap {
+            let parse = |name: &str| parse_key_code(name).map_err(AlignError::Parse);
+            set(parse(from)?, parse(to)?);
+        }
+        for &(from, to) in &args.run.remap {
+            set(from, to);
+        }
+
+        let dwell_time = match (args.run.dwell_click, config.dwell_click) {
+            (Some(time), _) => Some(time),
+            (None, Some(seconds)) => Some(
+                std::time::Duration::try_from_secs_f64(seconds)
+                    .ok()
+                    .filter(|time| !time.is_zero())
+                    .ok_or_else(|| {
+                        AlignError::Parse(format!(
+                            "dwell_click must be a positive number of seconds, not {seconds}"
+                        ))
+                    })?,
+            ),
+            (None, None) => None,
+        };
+        let dwell_tolerance = args
+            .run
+            .dwell_tolerance
+            .or(config.dwell_tolerance)
+            .unwrap_or(3.0);
+        if !(dwell_tolerance >= 0.0 && dwell_tolerance.is_finite()) {
+            return Err(AlignError::Parse(format!(
+                "dwell_tolerance must be a number of counts, not {dwell_tolerance}"
+            )));
+        }
+        let dwell = match dwell_time {
+            Some(time) => Some(DwellOptions {
+                time,
+                tolerance: dwell_tolerance,
+                button: key_code(args.run.dwell_button, &config.dwell_button)?
+                    .unwrap_or(KeyCode::BTN_LEFT),
+            }),
+            None => None,
+        };
+
+        let shake_reversals = args
+            .run
+            .shake_reversals
+            .or(config.shake_reversals)
+            .unwrap_or(4);
+        if shake_reversals < 2 {
+            return Err(AlignError::Parse(format!(
+                "shake_reversals must be at least 2, not {shake_reversals}"
+            )));
+        }
+        let shake_window = match (args.run.shake_window, config.shake_window) {
+            (Some(window), _) => window,
+            (None, Some(seconds)) => std::time::Duration::try_from_secs_f64(seconds)
+                .ok()
+                .filter(|window| !window.is_zero())
+                .ok_or_else(|| {
+                    AlignError::Parse(format!(
+                        "shake_window must be a positive number of seconds, not {seconds}"
+                    ))
+                })?,
+            (None, None) => std::time::Duration::from_millis(600),
+        };
+        let shake_speed = args.run.shake_speed.or(config.shake_speed).unwrap_or(25.0);
+        if !(shake_speed > 0.0 && shake_speed.is_finite()) {
+            return Err(AlignError::Parse(format!(
+                "shake_speed must be above 0, not {shake_speed}"
+            )));
+        }
+        let shake = key_code(args.run.shake_key, &config.shake_key)?.map(|key| ShakeOptions {
+            key,
+            reversals: shake_reversals,
+            window: shake_window,
+            speed: shake_speed,
+        });
+
+        let debounce = match args.run.debounce.or(config.debounce) {
+            Some(0) => {
+                return Err(AlignError::Parse(
+                    "debounce must be a positive number of milliseconds, not 0".to_string(),
+                ));
+            }
+            ms => ms.map(std::time::Duration::from_millis),
+        };
+
+        let palm = if args.run.palm_reject || config.palm_reject.unwrap_or(false) {
+            let defaults = PalmOptions::default();
+            let options = PalmOptions {
+                size: args
+                    .run
+                    .palm_size
+                    .or(config.palm_size)
+                    .unwrap_or(defaults.size),
+                pressure: args
+                    .run
+                    .palm_pressure
+                    .or(config.palm_pressure)
+                    .unwrap_or(defaults.pressure),
+                edge: args
+                    .run
+                    .palm_edge
+                    .or(config.palm_edge)
+                    .unwrap_or(defaults.edge),
+            };
+            for (key, share) in [
+                ("palm_size", options.size),
+                ("palm_pressure", options.pressure),
+            ] {
+                if !(0.0..=1.0).contains(&share) {
+                    return Err(AlignError::Parse(format!(
+                        "{key} must be between 0.0 and 1.0, not {share}"
+                    )));
+                }
+            }
+            if !(0.0..0.5).contains(&options.edge) {
+                return Err(AlignError::Parse(format!(
+                    "palm_edge must be at least 0.0 and below 0.5, not {}",
+                    options.edge
+                )));
+            }
+            Some(options)
+        } else {
+            None
+        };
+
+        let virtual_name = Settings::virtual_name(args, &config);
+        let phys = match args.run.phys.clone().or(config.phys) {
+            Some(phys) if phys.is_empty() => {
+                return Err(AlignError::Parse("phys can't be empty".to_string()));
+            }
+            Some(phys) => Some(CString::new(phys).map_err(|_| {
+                AlignError::Parse("phys can't contain a NUL character".to_string())
+            })?),
+            None => None,
+        };
+        let frame_policy = match (args.run.frame_policy, &config.frame_policy) {
+            (Some(policy), _) => policy,
+            (None, Some(text)) => text.parse().map_err(AlignError::Parse)?,
+            (None, None) => FramePolicy::default(),
+        };
+        let emit_errors = match (args.run.emit_errors, &config.emit_errors) {
+            (Some(policy), _) => policy,
+            (None, Some(name)) => name.parse().map_err(AlignError::Parse)?,
+            (None, None) => EmitErrors::default(),
+        };
+        let cycle_button = key_code(args.run.cycle_button, &config.cycle_button)?;
+        let toggle_key = key_code(args.run.toggle_key, &config.toggle_key)?;
+        let reset_key = key_code(args.run.reset_key, &config.reset_key)?;
+        // Either on the command line replaces both in the file.
+        let (pause_key, hold_button) =
+            if args.run.pause_key.is_some() || args.run.rotate_while.is_some() {
+                (args.run.pause_key, args.run.rotate_while)
+            } else {
+                (
+                    key_code(None, &config.pause_key)?,
+                    key_code(None, &config.rotate_while)?,
+                )
+            };
+        if pause_key.is_some() && hold_button.is_some() {
+            return Err(AlignError::Parse(
+                "pause_key and rotate_while can't both be set".to_string(),
+            ));
+        }
+        let presets = if args.run.preset.is_empty() {
+            config.preset
+        } else {
+            args.run.preset.clone()
+        };
+        if cycle_button.is_some() && presets.is_empty() {
+            warn!("A cycle button is set, but there are no presets to cycle through");
+        }
+        let chords = config
+            .chord
+            .iter()
+            .map(|chord| {
+                Ok(Chord {
+                    keys: chord
+                        .keys
+                        .iter()
+                        .map(|name| parse_key_code(name).map_err(AlignError::Parse))
+                        .collect::<Result<_, _>>()?,
+                    angle_deg: chord.angle_deg,
+                    passthrough: chord.passthrough,
+                })
+            })
+            .collect::<Result<_, AlignError>>()?;
+
+        Ok(Settings {
+            config_path,
+            config_files: config.files,
+            file_keys: config.keys,
+            profile: profile.map(str::to_string),
+            profiles: config.profiles,
+            devices,
+            device_angles,
+            axis_mode,
+            wheel_mode,
+            abs_offset,
+            angle_deg,
+            angle_as_given,
+            angle_from_to,
+            angle_blend,
+            gyro_device: args
+                .run
+                .gyro_device
+                .clone()
+                .or_else(|| config.gyro_device.clone()),
+            display,
+            pipeline,
+            scroll,
+            rotate_pointer: rotated_axes.pointer,
+            touch_scroll: args.run.rotate_axes.is_none() && config.rotate_axes.is_none(),
+            rotate_above: args.run.rotate_above.or(config.rotate_above).unwrap_or(0.0),
+            angle_curve,
+            response,
+            input_dpi,
+            output_dpi,
+            pointer: PointerOptions {
+                deadzone: args.run.deadzone.or(config.deadzone).unwrap_or(0.0),
+                dpi_scale: 1.0,
+                scale_x,
+                scale_y,
+                accel: args.run.accel.or(config.accel).unwrap_or(1.0),
+                sensitivity,
+                smooth,
+                tremor_window: if tremor_filter { tremor_window } else { 0 },
+                tremor_strength,
+                flip_x: args.run.flip_x || config.flip_x.unwrap_or(false),
+                flip_y: args.run.flip_y || config.flip_y.unwrap_or(false),
+                snap_axis: args.run.snap_axis || config.snap_axis.unwrap_or(false),
+                snap_hysteresis: args
+                    .run
+                    .snap_hysteresis
+                    .or(config.snap_hysteresis)
+                    .unwrap_or(0.25),
+                max_delta,
+                max_speed,
+                max_speed_x,
+                max_speed_y,
+                rounding_x,
+                rounding_y,
+                jitter_suppress,
+            },
+            control_socket: args.run.control_socket.clone().or(config.control_socket),
+            angle_step: args.run.angle_step.or(config.angle_step).unwrap_or(0.5),
+            virtual_name,
+            phys,
+            input_id: InputId::new(
+                BusType::BUS_VIRTUAL,
+                args.run.vendor.or(config.vendor).unwrap_or(DEFAULT_VENDOR),
+                args.run
+                    .product
+                    .or(config.product)
+                    .unwrap_or(DEFAULT_PRODUCT),
+                args.run
+                    .product_version
+                    .or(config.product_version)
+                    .unwrap_or(DEFAULT_VERSION),
+            ),
+            // IDs given on the command line still beat `clone_id` in the file.
+            clone_id: args.run.clone_id
+                || (config.clone_id.unwrap_or(false)
+                    && args.run.vendor.is_none()
+                    && args.run.product.is_none()
+                    && args.run.product_version.is_none()),
+            separate_outputs: args.run.separate_outputs || config.separate_outputs.unwrap_or(false),
+            split_scroll: args.run.split_scroll || config.split_scroll.unwrap_or(false),
+            emit_errors,
+            hotkeys: Hotkeys {
+                cycle_button,
+                presets,
+                toggle_key,
+                reset_key,
+                pause_key,
+                forward_pause_key: args.run.forward_pause_key
+                    || config.forward_pause_key.unwrap_or(false),
+                hold_button,
+                forward_hold_button: args.run.forward_hold_button
+                    || config.forward_hold_button.unwrap_or(false),
+                chords,
+            },
+            remap,
+            dwell,
+            shake,
+            debounce,
+            frame_policy,
+            palm,
+            app_profiles: config.app_profile.clone(),
+            focus_command: config.focus_command.clone(),
+            dry_run: args.run.dry_run,
+            force: args.run.force,
+            grab: !args.run.dry_run && !args.run.no_grab,
+        })
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let (args, matches) = Args::parse_settled(std::env::args_os()).unwrap_or_else(|e| e.exit());
+    let matches = &matches;
+    init_logging(args.verbose);
+    log_sources(matches);
+    match args.command {
+        Some(Command::List) => {
+            devices::list();
+            return Ok(());
+        }
+        Some(Command::Calibrate(CalibrateArgs { write, circle })) => {
+            if let Err(e) = run_calibration(&args, write, circle) {
+                error!("Calibration failed: {e}");
+                if let Some(hint) = AlignError::hint_for(&e) {
+                    error!("{hint}");
+                }
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        #[cfg(feature = "tui")]
+        Some(Command::Tune) => {
+            if let Err(e) = run_tune(&args) {
+                error!("Tuning failed: {e}");
+                if let Some(hint) = AlignError::hint_for(&e) {
+                    error!("{hint}");
+                }
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Replay(ReplayArgs {
+            ref file,
+            speed,
+            repeat,
+        })) => {
+            if let Err(e) = run_replay(&args, file, speed, repeat) {
+                error!("Replay failed: {e}");
+                if let Some(hint) = AlignError::hint_for(&e) {
+                    error!("{hint}");
+                }
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Inspect(InspectArgs { ref path })) => {
+            let DeviceSelector::Path(path) = DeviceSelector::path(path) else {
+                unreachable!("a path always selects by path");
+            };
+            match Device::open(&path) {
+                Ok(device) => devices::inspect(&path, &device),
+                Err(e) => {
+                    let e = AlignError::open(&path, e);
+                    error!("Inspecting failed: {e}");
+                    if let Some(hint) = e.hint() {
+                        error!("{hint}");
+                    }
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Status(StatusArgs { ref socket })) => {
+            let path = match socket {
+                Some(path) => path.clone(),
+                None => match Settings::load_config(&args, args.profile.as_deref()) {
+                    Ok((_, config)) => config
+                        .control_socket
+                        .unwrap_or_else(|| DEFAULT_CONTROL_SOCKET.into()),
+                    Err(e) => {
+                        error!("Reading the config failed: {e}");
+                        std::process::exit(1);
+                    }
+                },
+            };
+            if let Err(e) = control::print_status(&path) {
+                error!("Querying the status failed: {e}");
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+                ) {
+                    error!("Is an instance running with --control-socket?");
+                }
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Completions(CompletionsArgs { shell })) => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+            return Ok(());
+        }
+        Some(Command::Version) => {
+            println!("{}", version_details());
+            return Ok(());
+        }
+        Some(Command::Run(_)) | None => {}
+    }
+
+    let mut settings = match Settings::resolve(&args, args.profile.as_deref()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(2);
+        }
+    };
+    log_file_sources(matches, &settings);
+    if settings.changes_nothing() {
+        warn!(
+            "Rotation is off: the angle is {} and nothing else is set to change the motion, \
+             so events pass through as they are until the angle is changed",
+            Degrees(settings.angle_deg)
+        );
+    }
+
+    // Forking has to happen before the control socket starts its thread.
+    let _pid_file = if args.run.daemon {
+        match daemon::daemonize(&args.run.pid_file, &args.run.log_file) {
+            Ok(pid_file) => Some(pid_file),
+            Err(e) => {
+                error!("Starting the daemon failed: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let state = Arc::new(LiveState::new(settings.angle_deg));
+    state.set_profile(settings.profile.clone());
+    state.set_profiles(settings.profiles.clone());
+    // Until the hold button is first pressed.
+    state.set_paused(settings.hotkeys.hold_button.is_some());
+    signals::install()?;
+    if let Some(frames) = args.run.frame_history {
+        frame_history::keep(frames as usize);
+        signals::install_dump()?;
+    }
+    if let Some(duration) = args.run.duration {
+        signals::shut_down_after(duration);
+    }
+
+    // Failing from here on leaves the frame history behind like a run
+    // that gives up does, even if no frame has come in yet.
+    let fatal = |_: &std::io::Error| dump_frame_history(&args);
+    let control_socket = match &settings.control_socket {
+        Some(path) => Some(ControlSocket::bind(path, Arc::clone(&state)).inspect_err(fatal)?),
+        None => None,
+    };
+    if !settings.app_profiles.is_empty() {
+        focus::watch(
+            settings.app_profiles.clone(),
+            settings.focus_command.clone(),
+            settings.profile.clone(),
+            Arc::clone(&state),
+        );
+    }
+    #[cfg(feature = "dbus")]
+    if let Some(bus) = args.run.dbus {
+        dbus::serve(bus, Arc::clone(&state)).inspect_err(fatal)?;
+    }
+    #[cfg(feature = "http")]
+    let http_addr = match args.run.http_addr {
+        Some(addr) => Some(http::serve(addr, Arc::clone(&state)).inspect_err(fatal)?),
+        None => None,
+    };
+
+    // An ambiguous name won't get any better by retrying.
+    for selector in &settings.devices {
+        if let Err(e) = selector.resolve(&settings.virtual_name)
+            && e.kind() == std::io::ErrorKind::InvalidInput
+        {
+            error!("{e}");
+            dump_frame_history(&args);
+            std::process::exit(2);
+        }
+    }
+
+    let mut watchers = Watchers {
+        devices: match DeviceWatcher::new(hotplug::INPUT_DIR) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("Watching {} failed: {e}", hotplug::INPUT_DIR);
+                None
+            }
+        },
+        not_connected: Vec::new(),
+        config: None,
+        gyro: None,
+    };
+    if let Some(path) = &settings.gyro_device {
+        match Gyro::open(path) {
+            Ok(gyro) => {
+                if let Some(tilt) = gyro.tilt_deg() {
+                    state.set_tilt_deg(tilt);
+                }
+                watchers.gyro = Some(gyro);
+            }
+            Err(e) => warn!(
+                "Reading {} failed: {e}. The angle stays at {}.",
+                path.display(),
+                Degrees(settings.angle_deg)
+            ),
+        }
+    }
+    if args.run.watch_config {
+        match &settings.config_path {
+            Some(path) => match ConfigWatcher::new(&settings.config_files) {
+                Ok(watcher) => watchers.config = Some(watcher),
+                Err(e) => warn!("Watching {} failed: {e}", path.display()),
+            },
+            None => warn!("--watch-config given, but there is no config file"),
+        }
+    }
+
+    let mut reporters = Reporters {
+        stats: args.run.stats_interval.map(stats::Reporter::new),
+        status: args.run.status_file.as_ref().map(status::StatusFile::new),
+    };
+    let mut reconnect = Backoff::default().max_attempts(EVENT_LOOP_ATTEMPTS);
+    // With the watcher, an unplugged device is waited for rather than
+    // retried, so only other failures count towards giving up. Without it
+    // that's all retrying can do, however long it takes.
+    let open_backoff = match watchers.devices {
+        Some(_) => Backoff::default().max_attempts(OPEN_ATTEMPTS),
+        None => Backoff::default(),
+    };
+    stats::start();
+    // Set when giving up, for the exit status after the summary.
+    let mut failed = false;
+
+    'outer: while !signals::shutdown_requested() {
+        let mut sources = Vec::new();
+        // Devices that aren't connected yet. With others to read they're
+        // left for the event loop to pick up as they come, rather than
+        // waited for while the others are grabbed and nothing reads them.
+        let mut missing: Vec<DeviceSelector> = Vec::new();
+        for selector in &settings.devices {
+            if watchers.devices.is_some() {
+                match resolve_and_open(selector, &settings) {
+                    Ok((path, device)) => {
+                        sources.push(Source::new(selector.clone(), path, device, &settings));
+                        continue;
+                    }
+                    Err(e) if e.is_not_found() => {
+                        missing.push(selector.clone());
+                        continue;
+                    }
+                    // Retried below like any device that fails to open.
+                    Err(_) => {}
+                }
+            }
+            match with_retry(
+                || open_input_device(selector, watchers.devices.as_ref(), &settings),
+                "Creating input device",
+                open_backoff.clone(),
+            ) {
+                Ok((path, device)) => {
+                    sources.push(Source::new(selector.clone(), path, device, &settings))
+                }
+                Err(_) if signals::shutdown_requested() => continue 'outer,
+                Err(_) => {
+                    failed = true;
+                    break 'outer;
+                }
+            }
+        }
+        // With none connected there's nothing to read, so the first one is
+        // waited for.
+        if sources.is_empty() {
+            let selector = missing.remove(0);
+            match with_retry(
+                || open_input_device(&selector, watchers.devices.as_ref(), &settings),
+                "Creating input device",
+                open_backoff.clone(),
+            ) {
+                Ok((path, device)) => sources.push(Source::new(selector, path, device, &settings)),
+                Err(_) if signals::shutdown_requested() => continue 'outer,
+                Err(_) => {
+                    failed = true;
+                    break 'outer;
+                }
+            }
+        }
+        for selector in &missing {
+            info!("{selector} isn't connected yet, starting without it");
+        }
+
+        let devices: Vec<&Device> = sources.iter().map(|source| &source.device).collect();
+        let (mut output, virtual_devices) =
+            match build_output(&args, &settings, &devices, &missing, true) {
+                Ok(built) => built,
+                // Those created so far are removed as they're dropped.
+                Err(_) if signals::shutdown_requested() => continue 'outer,
+                Err(_) => {
+                    failed = true;
+                    break 'outer;
+                }
+            };
+        if settings.separate_outputs {
+            for (index, source) in sources.iter_mut().enumerate() {
+                source.sink = index;
+            }
+        }
+        // Each one not connected yet gets the sink after those of the
+        // sources, or the only one.
+        let missing: Vec<(DeviceSelector, usize)> = missing
+            .into_iter()
+            .enumerate()
+            .map(|(index, selector)| {
+                let sink = if settings.separate_outputs {
+                    sources.len() + index
+                } else {
+                    0
+                };
+                (selector, sink)
+            })
+            .collect();
+        if let Some(path) = &args.run.csv
+            && let Err(e) = output.write_csv(path)
+        {
+            error!("Opening the CSV file failed: {e}");
+            failed = true;
+            break 'outer;
+        }
+        if args.run.measure_latency {
+            output.measure_latency();
+        }
+        // Repeated on every reopen, which systemd doesn't mind.
+        systemd::notify("READY=1");
+
+        info!("config:");
+        if let Some(path) = &settings.config_path {
+            info!(
+                "  config file: {}{}",
+                path.display(),
+                if watchers.config.is_some() {
+                    ", watched"
+                } else {
+                    ""
+                }
+            );
+        }
+        if let Some(name) = &settings.profile {
+            info!("  profile: {name}");
+        }
+        if !settings.app_profiles.is_empty() {
+            info!(
+                "  app profiles: {} rules, by the focused window",
+                settings.app_profiles.len()
+            );
+        }
+        match (settings.angle_as_given, settings.angle_from_to) {
+            _ if let Some((a, b, weight)) = settings.angle_blend => info!(
+                "  angle: {} ({:.0}% of {} and {:.0}% of {})",
+                Degrees(settings.angle_deg),
+                (1.0 - weight) * 100.0,
+                Degrees(a),
+                weight * 100.0,
+                Degrees(b)
+            ),
+            (Some((angle, unit)), _) => {
+                info!(
+                    "  angle: {angle} {} ({})",
+                    unit.symbol(),
+                    Degrees(settings.angle_deg)
+                )
+            }
+            (None, Some((from, to))) => {
+                info!(
+                    "  angle: {} (turning {from:?} into {to:?})",
+                    Degrees(settings.angle_deg)
+                )
+            }
+            (None, None) => info!("  angle: {}", Degrees(settings.angle_deg)),
+        }
+        if let Some(path) = &settings.gyro_device {
+            info!("  gyro: {}, tilt added to the angle", path.display());
+        }
+        if let Some((output, rotation)) = &settings.display {
+            info!("  display: {output}, rotated {}", Degrees(*rotation));
+        }
+        match settings.pipeline.steps() {
+            [] => {}
+            [Step::Matrix(Matrix { a, b, c, d })] => {
+                info!("  matrix: [[{a}, {b}], [{c}, {d}]]")
+            }
+            steps => {
+                for step in steps {
+                    info!("  transform: {step:?}");
+                }
+            }
+        }
+        if settings.abs_offset != (0.0, 0.0) {
+            let (x, y) = settings.abs_offset;
+            info!("  absolute offset: {x} of the width, {y} of the height");
+        }
+        if !settings.rotate_pointer {
+            info!("  rotate pointer: false");
+        }
+        match settings.scroll.angle_deg {
+            Some(angle_deg) if settings.scroll.rotate => {
+                info!("  rotate scroll: true, by {}", Degrees(angle_deg))
+            }
+            _ if settings.touch_scroll && !settings.scroll.rotate => {
+                info!("  rotate scroll: on touchpads")
+            }
+            _ => info!("  rotate scroll: {}", settings.scroll.rotate),
+        }
+        if settings.scroll.invert {
+            info!("  invert scroll: true");
+        }
+        if settings.scroll.accel != 1.0 {
+            info!("  scroll accel: up to {}", settings.scroll.accel);
+        }
+        if let Some(wheel) = settings.scroll.dial.wheel() {
+            info!("  dial: scrolls as {wheel:?}");
+        }
+        if settings.rotate_above > 0.0 {
+            info!("  rotate above: {} counts", settings.rotate_above);
+        }
+        if !settings.angle_curve.points().is_empty() {
+            let points: Vec<String> = settings
+                .angle_curve
+                .points()
+                .iter()
+                .map(|&(speed, offset)| format!("{speed} -> {}", Degrees(offset)))
+                .collect();
+            info!("  angle curve: {}", points.join(", "));
+        }
+        if !settings.response.points().is_empty() {
+            let points: Vec<String> = settings
+                .response
+                .points()
+                .iter()
+                .map(|&(speed, gain)| format!("{speed} -> x{gain}"))
+                .collect();
+            info!("  response: {}", points.join(", "));
+        }
+        if settings.pointer.deadzone > 0.0 {
+            info!("  deadzone: {}", settings.pointer.deadzone);
+        }
+        if let Some(output_dpi) = settings.output_dpi {
+            match settings.input_dpi {
+                Some(input_dpi) => info!("  dpi: {input_dpi} -> {output_dpi}"),
+                None => info!("  dpi: as reported -> {output_dpi}"),
+            }
+        }
+        if settings.pointer.scale_x != 1.0 || settings.pointer.scale_y != 1.0 {
+            info!(
+                "  scale: x {}, y {}",
+                settings.pointer.scale_x, settings.pointer.scale_y
+            );
+        }
+        if settings.pointer.accel != 1.0 {
+            info!("  accel: {}", settings.pointer.accel);
+        }
+        if settings.pointer.sensitivity != 1.0 {
+            info!("  sensitivity: {}", settings.pointer.sensitivity);
+        }
+        if settings.pointer.smooth != 1.0 {
+            info!("  smooth: {}", settings.pointer.smooth);
+        }
+        if settings.pointer.tremor_window > 0 {
+            info!(
+                "  tremor filter: {} frames, strength {}",
+                settings.pointer.tremor_window, settings.pointer.tremor_strength
+            );
+        }
+        if settings.pointer.flip_x || settings.pointer.flip_y {
+            info!(
+                "  flip: x {}, y {}",
+                settings.pointer.flip_x, settings.pointer.flip_y
+            );
+        }
+        if settings.pointer.snap_axis {
+            info!(
+                "  snap axis: hysteresis {}",
+                settings.pointer.snap_hysteresis
+            );
+        }
+        if settings.pointer.jitter_suppress > 0.0 {
+            info!(
+                "  jitter suppress: below {} counts",
+                settings.pointer.jitter_suppress
+            );
+        }
+        if settings.pointer.max_delta != i16::MAX as f64 {
+            info!("  max delta: {}", settings.pointer.max_delta);
+        }
+        if let Some(max_speed) = settings.pointer.max_speed {
+            info!("  max speed: {max_speed} counts per frame");
+        }
+        for (axis, max_speed) in [
+            ("x", settings.pointer.max_speed_x),
+            ("y", settings.pointer.max_speed_y),
+        ] {
+            if let Some(max_speed) = max_speed {
+                info!("  max speed {axis}: {max_speed} counts per frame, the rest dropped");
+            }
+        }
+        let (rounding_x, rounding_y) = (settings.pointer.rounding_x, settings.pointer.rounding_y);
+        if rounding_x != rounding_y {
+            info!("  rounding: x {rounding_x:?}, y {rounding_y:?}");
+        } else if rounding_x != Rounding::default() {
+            info!("  rounding: {rounding_x:?}");
+        }
+        if let Some(code) = settings.hotkeys.cycle_button {
+            info!(
+                "  presets: {:?}, cycled with {code:?}",
+                settings.hotkeys.presets
+            );
+        }
+        for chord in &settings.hotkeys.chords {
+            info!(
+                "  chord: {:?} switches to {}{}",
+                chord.keys,
+                Degrees(chord.angle_deg),
+                if chord.passthrough {
+                    ", its keys passing through"
+                } else {
+                    ""
+                }
+            );
+        }
+        if let Some(code) = settings.hotkeys.toggle_key {
+            info!(
+                "  toggle key: {code:?}, rotation {}",
+                if state.enabled() { "on" } else { "off" }
+            );
+        }
+        if let Some(code) = settings.hotkeys.reset_key {
+            info!("  reset key: {code:?}");
+        }
+        if let Some(code) = settings.hotkeys.pause_key {
+            info!(
+                "  pause key: {code:?}{}",
+                if settings.hotkeys.forward_pause_key {
+                    ", forwarded"
+                } else {
+                    ""
+                }
+            );
+        }
+        if let Some(code) = settings.hotkeys.hold_button {
+            info!(
+                "  rotate while: {code:?}{}",
+                if settings.hotkeys.forward_hold_button {
+                    ", forwarded"
+                } else {
+                    ""
+                }
+            );
+        }
+        for (from, to) in &settings.remap {
+            info!("  remap: {from:?} -> {to:?}");
+        }
+        if let Some(dwell) = &settings.dwell {
+            info!(
+                "  dwell click: {:?} after {:.2} seconds within {} counts",
+                dwell.button,
+                dwell.time.as_secs_f64(),
+                dwell.tolerance
+            );
+        }
+        if let Some(shake) = &settings.shake {
+            info!(
+                "  shake: {:?} after {} reversals within {:.2} seconds at {} counts",
+                shake.key,
+                shake.reversals,
+                shake.window.as_secs_f64(),
+                shake.speed
+            );
+        }
+        if let Some(debounce) = settings.debounce {
+            info!("  debounce: {} ms", debounce.as_millis());
+        }
+        if let FramePolicy::Coalesce(interval) = settings.frame_policy {
+            info!(
+                "  frame policy: coalesce pointer motion over {} ms",
+                interval.as_millis()
+            );
+        }
+        if let Some(palm) = &settings.palm {
+            info!(
+                "  palm rejection: size above {}, pressure above {}, edges of {}",
+                palm.size, palm.pressure, palm.edge
+            );
+        }
+        if let Some(socket) = &control_socket {
+            info!("  control socket: {}", socket.path().display());
+        }
+        #[cfg(feature = "dbus")]
+        if let Some(bus) = args.run.dbus {
+            info!("  D-Bus: {} on the {bus:?} bus", dbus::BUS_NAME);
+        }
+        #[cfg(feature = "http")]
+        if let Some(addr) = http_addr {
+            info!("  HTTP: http://{addr}/");
+        }
+        if !settings.grab && !settings.dry_run {
+            info!("  grab: off, the device moves the cursor as well");
+        }
+        for source in &sources {
+            let name = source.device.name().unwrap_or("Unknown");
+            match source.frame.own_angle_deg() {
+                Some(angle_deg) => info!(
+                    "  device: {name} ({}), angle {}",
+                    source.path.display(),
+                    Degrees(angle_deg)
+                ),
+                None => info!("  device: {name} ({})", source.path.display()),
+            }
+        }
+        for (index, (name, identity)) in virtual_devices.iter().enumerate() {
+            info!("  virtual device: {}", output.describe(index, name));
+            if let Some(scroll) = output.describe_scroll(index, &scroll_name(name)) {
+                info!("  scroll device: {scroll}");
+            }
+            let id = &identity.input_id;
+            if settings.clone_id
+                || (id.vendor(), id.product(), id.version())
+                    != (DEFAULT_VENDOR, DEFAULT_PRODUCT, DEFAULT_VERSION)
+            {
+                info!(
+                    "  virtual device id: {:04x}:{:04x} version {:04x}",
+                    id.vendor(),
+                    id.product(),
+                    id.version()
+                );
+            }
+            if let Some(phys) = &identity.phys {
+                info!("  virtual device phys: {}", phys.to_string_lossy());
+            }
+        }
+        for source in sources.iter().filter(|source| source.leds.is_some()) {
+            info!(
+                "  LEDs of {}: mirrored from {} LEDs",
+                source.device.name().unwrap_or("Unknown"),
+                settings.virtual_name
+            );
+        }
+        if settings.emit_errors == EmitErrors::Skip {
+            info!("  emit errors: skip the events refused");
+        }
+
+        // Unless shutting down, the outer loop reconnects whether the event
+        // loop failed or asked for the devices to be reopened, which also
+        // starts again with a clean remainder.
+        let started = std::time::Instant::now();
+        state.set_devices(describe_sources(&sources));
+        watchers.not_connected = missing;
+        let exit = event_loop(
+            &mut sources,
+            &mut output,
+            &state,
+            &args,
+            &mut settings,
+            &mut watchers,
+            &mut reporters,
+        );
+        // What the sources still owe goes out before they're let go of, so
+        // that the next run starts from a clean slate. After an emit error
+        // that may well fail again, which changes nothing.
+        for source in &mut sources {
+            output.select(source.sink);
+            if let Err(e) = source.frame.release(&state, &mut output) {
+                debug!("Settling {} failed: {e}", source.path.display());
+            }
+        }
+        // Before exiting, or reopening with a new output.
+        if let Err(e) = output.flush() {
+            warn!("Writing out the CSV or the recording failed: {e}");
+        }
+        // Ungrabbed as they're dropped, before any wait to reconnect.
+        drop(sources);
+        state.set_devices(Vec::new());
+        if !matches!(exit, Ok(LoopExit::Shutdown | LoopExit::Reopen))
+            && !signals::shutdown_requested()
+        {
+            stats::reconnected();
+        }
+        match exit {
+            Ok(LoopExit::Shutdown) => break,
+            Ok(LoopExit::Disconnected) => debug!("All devices disconnected"),
+            Ok(LoopExit::Reopen) => {}
+            // A stale fd is reopened at once, unless it went stale right
+            // after the last reopen, which then waits like a failure.
+            Ok(LoopExit::Stale) if started.elapsed() > retry::MAX_DELAY => reconnect.reset(),
+            Ok(LoopExit::Stale) => match reconnect.next_delay() {
+                Some(delay) => {
+                    debug!("Reopening in {:.1} seconds", delay.as_secs_f64());
+                    signals::sleep(delay);
+                }
+                None => {
+                    error!(
+                        "The devices went stale right after reopening {} times in a row, \
+                         giving up",
+                        reconnect.failures()
+                    );
+                    failed = true;
+                    break;
+                }
+            },
+            Err(_) if signals::shutdown_requested() => {}
+            Err(e) if e.is_fatal() => {
+                error!("Event loop failed: {e}");
+                if let Some(hint) = e.hint() {
+                    error!("{hint}");
+                }
+                failed = true;
+                break;
+            }
+            Err(e) => {
+                // Failing again soon after reconnecting waits longer each
+                // time, a failure after a good run starts over.
+                if started.elapsed() > retry::MAX_DELAY {
+                    reconnect.reset();
+                }
+                match reconnect.next_delay() {
+                    Some(delay) => {
+                        warn!(
+                            "Event loop failed: {e}. Reconnecting in {:.1} seconds...",
+                            delay.as_secs_f64()
+                        );
+                        signals::sleep(delay);
+                    }
+                    None => {
+                        error!(
+                            "Event loop failed: {e}. Giving up after {} attempts",
+                            reconnect.failures()
+                        );
+                        failed = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // The virtual device and the control socket are removed as they're
+    // dropped on the way out.
+    info!("Shutting down");
+    systemd::notify("STOPPING=1");
+    stats::summary();
+    if args.run.measure_latency {
+        stats::latency_summary();
+    }
+    if failed {
+        dump_frame_history(&args);
+        drop(control_socket);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Parses a direction given as `x,y`.
+fn parse_vector(text: &str) -> Result<(f64, f64), String> {
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<f64>()
+            .map_err(|e| format!("{text:?}: {e}"))
+    };
+    match text.split_once(',') {
+        Some((x, y)) => Ok((parse(x)?, parse(y)?)),
+        None => Err(format!("expected x,y, got {text:?}")),
+    }
+}
+
+/// Which pairs of relative axes are rotated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RotatedAxes {
+    /// `REL_X` and `REL_Y`.
+    pointer: bool,
+    /// `REL_WHEEL` and `REL_HWHEEL`, with their high-resolution versions.
+    wheels: bool,
+}
+
+/// Parses a list of relative axes such as `x,y,wheel,hwheel`. Rotating
+/// turns motion from one axis of a pair into the other, so both of a pair
+/// have to be given.
+fn parse_rotate_axes(text: &str) -> Result<RotatedAxes, String> {
+    let mut given = [false; 4];
+    for name in text.split(',').map(str::trim) {
+        let index = match name.to_ascii_lowercase().as_str() {
+            "x" => 0,
+            "y" => 1,
+            "wheel" => 2,
+            "hwheel" => 3,
+            _ => return Err(format!("expected x, y, wheel or hwheel, got {name:?}")),
+        };
+        given[index] = true;
+    }
+    match given {
+        [x, y, _, _] if x != y => Err("x and y can only be rotated together".into()),
+        [_, _, wheel, hwheel] if wheel != hwheel => {
+            Err("wheel and hwheel can only be rotated together".into())
+        }
+        [pointer, _, wheels, _] => Ok(RotatedAxes { pointer, wheels }),
+    }
+}
+
+/// Parses the points of an angle curve given as `speed:degrees,...`.
+fn parse_angle_curve(text: &str) -> Result<AngleCurve, String> {
+    let points = text
+        .split(',')
+        .map(|point| {
+            let (speed, offset) = point
+                .split_once(':')
+                .ok_or_else(|| format!("expected speed:degrees, got {point:?}"))?;
+            let parse = |value: &str| {
+                value
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|e| format!("{point:?}: {e}"))
+            };
+            Ok((parse(speed)?, parse(offset)?))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    AngleCurve::new(points)
+}
+
+/// Parses a positive number of seconds.
+fn parse_interval(text: &str) -> Result<std::time::Duration, String> {
+    let seconds: f64 = text.parse().map_err(|e| format!("{text:?}: {e}"))?;
+    std::time::Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|interval| !interval.is_zero())
+        .ok_or_else(|| format!("expected a positive number of seconds, got {text:?}"))
+}
+
+/// Writes the frames --frame-history kept, if it's given, to where
+/// --frame-history-file says.
+fn dump_frame_history(args: &Args) {
+    let path = args
+        .run
+        .frame_history_file
+        .clone()
+        .unwrap_or_else(frame_history::default_path);
+    match frame_history::dump(&path) {
+        Ok(Some(frames)) => info!("Wrote the last {frames} frames to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => warn!(
+            "Writing the frame history to {} failed: {e}",
+            path.display()
+        ),
+    }
+}
+
+/// Parses a 16 bit ID, given in hex with a `0x` prefix or in decimal.
+fn parse_id(text: &str) -> Result<u16, String> {
+    let result = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    result.map_err(|e| format!("{text:?}: {e}"))
+}
+
+/// Parses `OLD=NEW`, two key or button names.
+fn parse_remap(text: &str) -> Result<(KeyCode, KeyCode), String> {
+    match text.split_once('=') {
+        Some((from, to)) => Ok((parse_key_code(from.trim())?, parse_key_code(to.trim())?)),
+        None => Err(format!("expected OLD=NEW, got {text:?}")),
+    }
+}
+
+/// Parses a key or button name like `BTN_SIDE`, ignoring case.
+fn parse_key_code(name: &str) -> Result<KeyCode, String> {
+    name.to_uppercase()
+        .parse()
+        .map_err(|_| format!("unknown key or button {name:?}"))
+}
+
+/// Where the option `id` was taken from, whether given before `run` or
+/// after it.
+fn value_source(matches: &clap::ArgMatches, id: &str) -> Option<ValueSource> {
+    let after = matches
+        .subcommand_matches("run")
+        .filter(|after| after.ids().any(|known| known == id))
+        .and_then(|after| after.value_source(id));
+    matches.value_source(id).max(after)
+}
+
+/// Logs which options were taken from the command line or the environment,
+/// the rest coming from the config file or their defaults. See
+/// [`log_file_sources`] for the config file.
+fn log_sources(matches: &clap::ArgMatches) {
+    for arg in Args::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        match (value_source(matches, id), arg.get_env()) {
+            (Some(ValueSource::CommandLine), _) => debug!("{id} given on the command line"),
+            (Some(ValueSource::EnvVariable), Some(name)) => {
+                debug!("{id} taken from {}", name.to_string_lossy())
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Logs which options were taken from the config file, and which of the
+/// file's were overridden by the command line or the environment.
+fn log_file_sources(matches: &clap::ArgMatches, settings: &Settings) {
+    let Some(path) = &settings.config_path else {
+        return;
+    };
+    let command = Args::command();
+    for key in &settings.file_keys {
+        let overridden = command
+            .get_arguments()
+            .any(|arg| arg.get_id() == key.as_str())
+            && matches!(
+                value_source(matches, key),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            );
+        if overridden {
+            debug!("{key} in {} overridden", path.display());
+        } else {
+            debug!("{key} taken from {}", path.display());
+        }
+    }
+}
+
+/// Info and up by default, which covers the config banner and errors. Info
+/// messages are printed bare, everything else with its level. `RUST_LOG`
+/// still overrides the level.
+fn init_logging(verbose: u8) {
+    use std::io::Write;
+
+    let level = match verbose {
+        0 => log::LevelFilter::Info,
+        1 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format(|buf, record| match record.level() {
+            log::Level::Info => writeln!(buf, "{}", record.args()),
+            level => writeln!(buf, "{}: {}", level.as_str().to_lowercase(), record.args()),
+        })
+        .init();
+}
+
+fn run_replay(args: &Args, file: &Path, speed: f64, repeat: bool) -> std::io::Result<()> {
+    if !(speed > 0.0 && speed.is_finite()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("speed must be a positive number, not {speed}"),
+        ));
+    }
+    let settings = Settings::resolve(args, args.profile.as_deref())?;
+    let events = recording::read(file)?;
+    let Some(first) = events.first() else {
+        info!("{} has no events", file.display());
+        return Ok(());
+    };
+
+    let mut capabilities = Capabilities::from_events(&events);
+    // Replayed without the device, which isn't known to be a touchpad.
+    settings.declare_transformed(&mut capabilities, false);
+    if settings.wheel_mode == WheelMode::Off {
+        capabilities.keep_wheels(WheelMode::Off);
+    }
+    let wheels = settings
+        .split_scroll
+        .then(|| capabilities.split_wheels())
+        .flatten();
+    let identity = Identity {
+        input_id: settings.input_id.clone(),
+        phys: settings.phys.clone(),
+    };
+    let create = |name: &str, identity: &Identity, capabilities: &Capabilities| {
+        if settings.dry_run {
+            return Ok(Sink::DryRun);
+        }
+        create_virtual_device(name, identity, capabilities).map(Sink::Device)
+    };
+    let sink = create(&settings.virtual_name, &identity, &capabilities)?;
+    let scroll = match wheels {
+        Some(wheels) => Some((
+            create(
+                &scroll_name(&settings.virtual_name),
+                &identity.scroll(),
+                &wheels,
+            )?,
+            wheels,
+        )),
+        None => None,
+    };
+    let mut output = Output::new(sink, args.run.trace_json.as_deref(), None)?;
+    output.declare(vec![capabilities]);
+    output.split_scroll(vec![scroll]);
+    output.on_emit_error(settings.emit_errors);
+    if let Some(path) = &args.run.csv {
+        output.write_csv(path)?;
+    }
+    if args.run.measure_latency {
+        output.measure_latency();
+    }
+    let state = LiveState::new(settings.angle_deg);
+    signals::install()?;
+    info!("Replaying {} events from {}", events.len(), file.display());
+
+    'replay: loop {
+        // Each pass starts with a clean remainder, like a reconnect would.
+        let mut frame = new_frame(None, &settings);
+        let mut last = first.timestamp();
+        for event in &events {
+            let gap = event
+                .timestamp()
+                .duration_since(last)
+                .unwrap_or_default()
+                .div_f64(speed);
+            last = event.timestamp();
+            let mut waited = std::time::Duration::ZERO;
+            // Owed smoothed motion goes out on the live loop's schedule.
+            while frame.smoothing_pending() && waited + SMOOTHING_TICK < gap {
+                std::thread::sleep(SMOOTHING_TICK);
+                waited += SMOOTHING_TICK;
+                frame.tick(&mut output)?;
+            }
+            std::thread::sleep(gap - waited);
+            if signals::shutdown_requested() {
+                break 'replay;
+            }
+            output.received(event);
+            frame.handle(*event, &state, &mut output)?;
+        }
+        if !repeat {
+            break;
+        }
+    }
+    output.flush()?;
+    if args.run.measure_latency {
+        stats::latency_summary();
+    }
+    Ok(())
+}
+
+fn run_calibration(args: &Args, write: bool, circle: bool) -> std::io::Result<()> {
+    let (config_path, config) = Settings::load_config(args, args.profile.as_deref())?;
+    let selector = &Settings::select_devices(args, &config)?[0].selector;
+    let path = selector.resolve(&Settings::virtual_name(args, &config))?;
+    let wanted = (!args.run.force).then_some((AxisMode::Auto, false));
+    let mut device = create_input_device(&path, true, wanted)?;
+    println!(
+        "Calibrating {} ({})",
+        device.name().unwrap_or("Unknown"),
+        path.display()
+    );
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    #[arg(short, long)]
-    device_path: String,
-    #[arg(short, long)]
-    angle_deg: f64,
+    let config_path = || {
+        config_path
+            .clone()
+            .or_else(Config::default_path)
+            .ok_or_else(|| std::io::Error::other("no config file given and no home directory"))
+    };
+    if circle {
+        let ellipse = calibrate::measure_circle(&mut device)?;
+        return calibrate::recommend_scales(
+            &ellipse,
+            write.then(config_path).transpose()?.as_deref(),
+        );
+    }
+
+    // One decimal is as precise as a hand can push straight.
+    let angle_deg = (calibrate::measure(&mut device)? * 10.0).round() / 10.0;
+    println!("Recommended: --angle-deg {angle_deg}");
+    if write {
+        let config_path = config_path()?;
+        Config::save_angle_deg(&config_path, angle_deg)?;
+        println!("Saved to {}", config_path.display());
+    }
+    Ok(())
 }
 
-fn main() -> std::io::Result<()> {
-    let args = Args::parse();
+/// Opens the first selected device for `tune`, with a virtual device to
+/// emit on like `run` has and the control socket serving the same angle as
+/// the arrow keys change.
+#[cfg(feature = "tui")]
+fn run_tune(args: &Args) -> std::io::Result<()> {
+    let mut settings = Settings::resolve(args, args.profile.as_deref())?;
+    // The screen is taken by the tuning UI, so there's no --dry-run.
+    settings.dry_run = false;
+    let wanted = (!args.run.force).then_some((AxisMode::Auto, false));
+    let mut sources = Vec::new();
+    for (index, selector) in settings.devices.iter().enumerate() {
+        let path = selector.resolve(&settings.virtual_name)?;
+        let device = create_input_device(&path, true, wanted)?;
+        sources.push(tune::Tuned {
+            frame: new_frame(Some(&device), &settings)
+                .with_angle_deg(settings.device_angle_deg(selector)),
+            device,
+            sink: if settings.separate_outputs { index } else { 0 },
+        });
+    }
+    let devices: Vec<&Device> = sources.iter().map(|source| &source.device).collect();
+    // Nothing relays effect uploads while tuning.
+    let (mut output, _) = build_output(args, &settings, &devices, &[], false)?;
 
-    let (cos_a, sin_a) = {
-        let angle_rad = args.angle_deg.to_radians();
-        (angle_rad.cos(), angle_rad.sin())
+    let state = Arc::new(LiveState::new(settings.angle_deg));
+    signals::install()?;
+    let _control_socket = settings
+        .control_socket
+        .as_ref()
+        .map(|path| ControlSocket::bind(path, Arc::clone(&state)))
+        .transpose()?;
+    let save = |angle_deg: f64| {
+        let path = settings
+            .config_path
+            .clone()
+            .or_else(Config::default_path)
+            .ok_or_else(|| std::io::Error::other("no config file given and no home directory"))?;
+        Config::save_angle_deg(&path, angle_deg)?;
+        Ok(path)
     };
+    tune::run(&mut sources, &state, &mut output, settings.angle_step, save)?;
+    output.flush()
+}
 
+/// Resolves and opens the selected device. While the device is missing, this
+/// waits for device nodes to change rather than retrying on a timer.
+fn open_input_device(
+    selector: &DeviceSelector,
+    watcher: Option<&DeviceWatcher>,
+    settings: &Settings,
+) -> Result<(PathBuf, Device), AlignError> {
+    let mut waiting = false;
     loop {
-        let mut input_device = match with_retry(
-            || create_input_device(&args.device_path),
-            "Creating input device",
-            10,
-        ) {
-            Ok(device) => device,
-            Err(_) => continue,
-        };
+        let result = resolve_and_open(selector, settings);
+        match (result, watcher) {
+            (Err(e), Some(watcher)) if e.is_not_found() && !signals::shutdown_requested() => {
+                if let DeviceSelector::Path(path) = selector {
+                    watcher.watch_parent_of(path);
+                }
+                if !waiting {
+                    debug!("Waiting for {selector} to be connected...");
+                    waiting = true;
+                }
+                watcher.wait()?;
+            }
+            (result, _) => {
+                if waiting && let Ok((path, _)) = &result {
+                    debug!("Device connected ({})", path.display());
+                }
+                return result;
+            }
+        }
+    }
+}
 
-        let relative_axes = input_device
-            .supported_relative_axes()
-            .unwrap_or(default_rel_axes());
+/// The device `selector` currently stands for, opened.
+fn resolve_and_open(
+    selector: &DeviceSelector,
+    settings: &Settings,
+) -> Result<(PathBuf, Device), AlignError> {
+    let path =
+        selector
+            .resolve(&settings.virtual_name)
+            .map_err(|source| AlignError::DeviceOpen {
+                device: selector.to_string(),
+                source,
+            })?;
+    // Only the wheels are looked for if only they're rotated.
+    let wanted = (!settings.force).then_some((
+        settings.axis_mode,
+        !settings.rotate_pointer && settings.scroll.rotate,
+    ));
+    let device = create_input_device(&path, settings.grab, wanted)?;
+    Ok((path, device))
+}
 
-        let keys = input_device.supported_keys().unwrap_or(default_keys());
+/// Opens the source device and, unless `grab` is off for a dry run or
+/// --no-grab, grabs it exclusively. A device without the axes `wanted` by
+/// [`devices::has_transformed_axes`] is refused before it's grabbed, unless
+/// that's `None` for --force.
+///
+/// This is the only place the device gets grabbed, so that a failing grab is
+/// retried together with the open. The grab is released by the caller once
+/// the event loop gives up on the device.
+fn create_input_device(
+    path: impl AsRef<Path>,
+    grab: bool,
+    wanted: Option<(AxisMode, bool)>,
+) -> Result<Device, AlignError> {
+    let path = path.as_ref();
+    let mut input_device = Device::open(path).map_err(|e| AlignError::open(path, e))?;
+    if let Some((mode, wheels_only)) = wanted
+        && !devices::has_transformed_axes(&input_device, mode, wheels_only)
+    {
+        return Err(AlignError::NotPointer {
+            path: path.to_path_buf(),
+            name: input_device.name().unwrap_or("Unknown").to_string(),
+        });
+    }
+    if grab {
+        input_device.grab().map_err(|source| AlignError::Grab {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    }
+    // Reads only happen once poll says there's something to read, but with
+    // several devices a spurious wakeup mustn't block the others.
+    input_device.set_nonblocking(true)?;
+    Ok(input_device)
+}
 
-        let mut virtual_device = match with_retry(
-            || create_virtual_device(VIRTUAL_DEVICE_NAME, relative_axes, keys),
-            "Creating virtual device",
-            10,
-        ) {
-            Ok(device) => device,
-            Err(_) => continue,
-        };
+/// A unit the angle may be given in other than degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AngleUnit {
+    Radians,
+    Gradians,
+    Turns,
+}
 
-        println!("\nconfig:");
-        println!("  angle: {}°", args.angle_deg);
-        if let Some(name) = input_device.name() {
-            println!("  device: {} ({})", name, args.device_path);
-        } else {
-            println!("  device: Unknown ({})", args.device_path);
-        };
-        println!(
-            "  virtual device: {} ({})\n",
-            VIRTUAL_DEVICE_NAME,
-            virtual_device
-                .get_syspath()
-                .map(|path| path.to_string_lossy().into_owned())
-                .unwrap_or("Unknown".to_string())
-        );
+impl AngleUnit {
+    fn to_degrees(self, angle: f64) -> f64 {
+        match self {
+            AngleUnit::Radians => angle.to_degrees(),
+            AngleUnit::Gradians => angle * 0.9,
+            AngleUnit::Turns => angle * 360.0,
+        }
+    }
 
-        input_device.grab()?;
+    /// What follows the angle in the banner.
+    fn symbol(self) -> &'static str {
+        match self {
+            AngleUnit::Radians => "rad",
+            AngleUnit::Gradians => "grad",
+            AngleUnit::Turns => "turns",
+        }
+    }
+}
 
-        match with_retry(
-            || {
-                let res = event_loop(&mut input_device, &mut virtual_device, sin_a, cos_a);
-                let _ = input_device.ungrab();
-                res
-            },
-            "Event loop",
-            10,
-        ) {
-            Ok(_) => unreachable!(),
-            Err(_) => {}
+/// The output for reading `devices`, and `missing` ones that aren't
+/// connected yet: one virtual device for all of them, or one each in that
+/// order with --separate-outputs, and a scroll device next to each with
+/// --split-scroll. Each declares what its sources have and what the
+/// transform may turn that into. Force feedback is only declared where
+/// something will relay the effects. Failures are logged as they happen.
+///
+/// Returned along with the name and identity of each virtual device.
+fn build_output(
+    args: &Args,
+    settings: &Settings,
+    devices: &[&Device],
+    missing: &[DeviceSelector],
+    force_feedback: bool,
+) -> Result<(Output, Vec<(String, Identity)>), AlignError> {
+    let mut capabilities: Vec<Capabilities> = devices
+        .iter()
+        .map(|device| {
+            let mut capabilities = Capabilities::from_device(device);
+            if !force_feedback {
+                capabilities.force_feedback = None;
+            }
+            if let Some(mode) = settings.axis_mode.for_device(device) {
+                info!(
+                    "{} reports relative and absolute motion, using the {}",
+                    device.name().unwrap_or("Unknown"),
+                    if mode == AxisMode::Rel {
+                        "relative"
+                    } else {
+                        "absolute"
+                    }
+                );
+                capabilities.keep_axes(mode);
+            }
+            if let Some(mode) = settings.wheel_mode.for_device(device) {
+                capabilities.keep_wheels(mode);
+            }
+            settings.declare_transformed(&mut capabilities, devices::is_touchpad(device));
+            capabilities
+        })
+        // Those not connected yet are declared as a plain mouse, as
+        // what else they have isn't known.
+        .chain(missing.iter().map(|_| {
+            let mut capabilities = Capabilities::from_events(&[]);
+            settings.declare_transformed(&mut capabilities, false);
+            capabilities
+        }))
+        .collect();
+    // One virtual device for all sources, or one each named after it.
+    let identity = |device: &Device| {
+        let identity = if settings.clone_id {
+            Identity::of(device)
+        } else {
+            Identity {
+                input_id: settings.input_id.clone(),
+                phys: None,
+            }
         };
+        Identity {
+            phys: settings.phys.clone().or(identity.phys),
+            ..identity
+        }
+    };
+    let virtual_devices: Vec<(String, Identity)> = if settings.separate_outputs {
+        devices
+            .iter()
+            .map(|device| {
+                let name = format!(
+                    "{}: {}",
+                    settings.virtual_name,
+                    device.name().unwrap_or("Unknown")
+                );
+                (name, identity(device))
+            })
+            .chain(missing.iter().map(|selector| {
+                let name = format!("{}: {selector}", settings.virtual_name);
+                let identity = Identity {
+                    input_id: settings.input_id.clone(),
+                    phys: settings.phys.clone(),
+                };
+                (name, identity)
+            }))
+            .collect()
+    } else {
+        let merged = capabilities
+            .drain(..)
+            .reduce(Capabilities::merge)
+            .expect("there is at least one device");
+        capabilities.push(merged);
+        vec![(settings.virtual_name.clone(), identity(devices[0]))]
+    };
+    for capabilities in &mut capabilities {
+        if settings.wheel_mode == WheelMode::Off {
+            capabilities.keep_wheels(WheelMode::Off);
+        }
+        if let Some(dwell) = &settings.dwell {
+            capabilities.declare_key(dwell.button);
+        }
+        if let Some(shake) = &settings.shake {
+            capabilities.declare_key(shake.key);
+        }
+        debug!(
+            "Declaring {} keys, {} relative and {} absolute axes",
+            capabilities.keys.iter().count(),
+            capabilities.relative_axes.iter().count(),
+            capabilities.absolute_axes.len()
+        );
     }
-}
 
-fn with_retry<T, F>(mut action: F, name: &str, seconds: u64) -> std::io::Result<T>
-where
-    F: FnMut() -> std::io::Result<T>,
-{
-    match action() {
-        Ok(v) => return Ok(v),
-        Err(e) => {
-            eprintln!("{name} failed: {e}. Retrying in {seconds} seconds...");
-            std::thread::sleep(std::time::Duration::from_secs(seconds));
-            Err(e)
+    let scroll_capabilities: Vec<Option<Capabilities>> = capabilities
+        .iter_mut()
+        .map(|capabilities| {
+            settings
+                .split_scroll
+                .then(|| capabilities.split_wheels())
+                .flatten()
+        })
+        .collect();
+    let create = |name: &str, identity: &Identity, capabilities: &Capabilities| {
+        if settings.dry_run {
+            return Ok(Sink::DryRun);
         }
+        with_retry(
+            || create_virtual_device(name, identity, capabilities),
+            "Creating virtual device",
+            // Without uinput access this won't fix itself.
+            Backoff::default().max_attempts(VIRTUAL_DEVICE_ATTEMPTS),
+        )
+        .map(Sink::Device)
+    };
+    let mut sinks = Vec::new();
+    let mut scroll_sinks = Vec::new();
+    for (((name, identity), capabilities), wheels) in virtual_devices
+        .iter()
+        .zip(&capabilities)
+        .zip(scroll_capabilities)
+    {
+        sinks.push(create(name, identity, capabilities)?);
+        let scroll = wheels
+            .map(|wheels| {
+                create(&scroll_name(name), &identity.scroll(), &wheels)
+                    .map(|scroll_sink| (scroll_sink, wheels))
+            })
+            .transpose()?;
+        scroll_sinks.push(scroll);
     }
+    let mut sinks = sinks.into_iter();
+    let first = sinks.next().expect("there is at least one device");
+    let mut output = Output::new(
+        first,
+        args.run.trace_json.as_deref(),
+        args.run.record.as_deref(),
+    )
+    .inspect_err(|e| error!("Opening the JSON trace or the recording failed: {e}"))?;
+    for sink in sinks {
+        output.add_sink(sink);
+    }
+    output.declare(capabilities);
+    output.split_scroll(scroll_sinks);
+    output.on_emit_error(settings.emit_errors);
+    Ok((output, virtual_devices))
 }
 
-fn default_rel_axes() -> &'static AttributeSet<RelativeAxisCode> {
-    static DEFAULT: OnceLock<AttributeSet<RelativeAxisCode>> = OnceLock::new();
-    DEFAULT.get_or_init(|| {
-        let mut set = AttributeSet::new();
-        set.insert(RelativeAxisCode::REL_X);
-        set.insert(RelativeAxisCode::REL_Y);
-        set.insert(RelativeAxisCode::REL_WHEEL);
-        set
-    })
+/// What the virtual device says it is, apart from its name.
+struct Identity {
+    input_id: InputId,
+    phys: Option<CString>,
 }
 
-fn default_keys() -> &'static AttributeSet<KeyCode> {
-    static DEFAULT: OnceLock<AttributeSet<KeyCode>> = OnceLock::new();
-    DEFAULT.get_or_init(|| {
-        let mut set = AttributeSet::new();
-        set.insert(KeyCode::BTN_LEFT);
-        set.insert(KeyCode::BTN_RIGHT);
-        set.insert(KeyCode::BTN_MIDDLE);
-        set
-    })
+impl Identity {
+    /// The identity of `device`. The name is deliberately not part of it,
+    /// and the physical path is put under `sensor_alignment/`, so that the
+    /// virtual device is never mistaken for the one it replaces, not even by
+    /// --device-glob.
+    fn of(device: &Device) -> Identity {
+        Identity {
+            input_id: device.input_id(),
+            phys: device
+                .physical_path()
+                .filter(|phys| !phys.is_empty())
+                .and_then(|phys| CString::new(format!("sensor_alignment/{phys}")).ok()),
+        }
+    }
+
+    /// The identity of the scroll device of --split-scroll: the same IDs,
+    /// and the physical path with `/scroll` added so that udev rules can
+    /// tell the two apart.
+    fn scroll(&self) -> Identity {
+        Identity {
+            input_id: self.input_id.clone(),
+            phys: self
+                .phys
+                .as_ref()
+                .and_then(|phys| CString::new(format!("{}/scroll", phys.to_string_lossy())).ok()),
+        }
+    }
 }
 
-fn create_input_device(path: impl AsRef<Path>) -> std::io::Result<Device> {
-    let mut input_device = Device::open(path)?;
-    input_device.grab()?;
-    Ok(input_device)
+/// The name of the scroll device that goes with the virtual device `name`.
+fn scroll_name(name: &str) -> String {
+    format!("{name} scroll")
 }
 
 fn create_virtual_device(
     name: &str,
-    relative_axes: &AttributeSetRef<RelativeAxisCode>,
-    keys: &AttributeSetRef<KeyCode>,
+    identity: &Identity,
+    capabilities: &Capabilities,
+) -> Result<VirtualDevice, AlignError> {
+    build_virtual_device(name, identity, capabilities).map_err(AlignError::virtual_device)
+}
+
+fn build_virtual_device(
+    name: &str,
+    identity: &Identity,
+    capabilities: &Capabilities,
 ) -> std::io::Result<VirtualDevice> {
-    let virtual_device = VirtualDevice::builder()?
+    let mut builder = VirtualDevice::builder()?
         .name(name)
-        .with_relative_axes(relative_axes)?
-        .with_keys(keys)?
-        .build()?;
+        .input_id(identity.input_id.clone())
+        .with_relative_axes(&capabilities.relative_axes)?
+        .with_keys(&capabilities.keys)?
+        .with_properties(&capabilities.properties)?;
+    for axis in &capabilities.absolute_axes {
+        builder = builder.with_absolute_axis(axis)?;
+    }
+    if let Some(misc) = &capabilities.misc {
+        builder = builder.with_msc(misc)?;
+    }
+    if let Some(switches) = &capabilities.switches {
+        builder = builder.with_switches(switches)?;
+    }
+    if let Some(phys) = &identity.phys {
+        builder = builder.with_phys(phys)?;
+    }
+    if let Some(ff) = &capabilities.force_feedback {
+        builder = builder
+            .with_ff(ff)?
+            .with_ff_effects_max(capabilities.ff_effects_max);
+    }
+    let virtual_device = builder.build()?;
     Ok(virtual_device)
 }
 
+/// Re-reads the config file, keeping the current settings if it is invalid.
+/// Returns whether the devices have to be reopened for the change to apply.
+fn reload_settings(args: &Args, settings: &mut Settings, state: &LiveState) -> bool {
+    let profile = state.profile();
+    let new = match Settings::resolve(args, profile.as_deref()) {
+        Ok(new) => new,
+        Err(e) => {
+            error!("Reloading config failed: {e}. Keeping the previous settings.");
+            state.set_profile(settings.profile.clone());
+            return false;
+        }
+    };
+    match &profile {
+        Some(name) if profile != settings.profile => info!("Switched to profile {name}"),
+        _ => info!("Config reloaded"),
+    }
+    let changes = settings.changes(&new);
+    if changes.is_empty() {
+        info!("  nothing changed");
+    }
+    for change in changes {
+        info!("  {change}");
+    }
+    if new.angle_deg != settings.angle_deg {
+        state.configure(new.angle_deg);
+    }
+    state.set_profiles(new.profiles.clone());
+    if new.hotkeys.hold_button != settings.hotkeys.hold_button
+        || new.hotkeys.pause_key != settings.hotkeys.pause_key
+    {
+        state.set_paused(new.hotkeys.hold_button.is_some());
+    }
+    let reopen = settings.needs_reopen(&new);
+    *settings = new;
+    reopen
+}
+
+/// Reason for the event loop to hand control back without an error.
+enum LoopExit {
+    /// The settings changed in a way that needs the devices to be reopened.
+    Reopen,
+    /// SIGINT or SIGTERM was received.
+    Shutdown,
+    /// All source devices were unplugged.
+    Disconnected,
+    /// A source device stopped working while its node stayed, as happens
+    /// after a suspend and resume, so everything is opened again.
+    Stale,
+}
+
+fn new_frame(device: Option<&Device>, settings: &Settings) -> Frame {
+    let mut pointer = settings.pointer;
+    if let Some(output_dpi) = settings.output_dpi {
+        match settings
+            .input_dpi
+            .or_else(|| device.and_then(devices::reported_dpi))
+        {
+            Some(input_dpi) => pointer.dpi_scale = output_dpi / input_dpi,
+            None => warn!(
+                "The device doesn't report its resolution, give input_dpi for output_dpi to apply"
+            ),
+        }
+    }
+    // Two fingers moving across a touchpad turn with it like one does.
+    let mut scroll = settings.scroll;
+    scroll.rotate = settings.rotates_scroll(device.is_some_and(devices::is_touchpad));
+    let frame = Frame::new(
+        device,
+        scroll,
+        settings.pipeline.clone(),
+        pointer,
+        settings.hotkeys.clone(),
+        settings.remap.clone(),
+        settings.rotate_above,
+    )
+    .with_dwell(settings.dwell)
+    .with_shake(settings.shake)
+    .with_debounce(settings.debounce)
+    .with_frame_policy(settings.frame_policy)
+    .with_palm(settings.palm)
+    .with_abs_offset(settings.abs_offset)
+    .with_rotate_pointer(settings.rotate_pointer)
+    .with_angle_curve(settings.angle_curve.clone())
+    .with_response(settings.response.clone())
+    .with_axis_mode(device.and_then(|device| settings.axis_mode.for_device(device)))
+    .with_wheel_mode(match device {
+        Some(device) => settings.wheel_mode.for_device(device),
+        None => (settings.wheel_mode == WheelMode::Off).then_some(WheelMode::Off),
+    });
+    match settings.angle_as_given {
+        Some((angle_rad, AngleUnit::Radians)) => {
+            frame.with_angle_rad(settings.angle_deg, angle_rad)
+        }
+        _ => frame,
+    }
+}
+
+/// An opened source device together with the frame it is assembling. Each
+/// source accumulates its own motion until its own `SYN_REPORT`.
+///
+/// The device is ungrabbed when this is dropped, including while a panic
+/// unwinds, so that the real mouse never stays frozen. Release builds abort
+/// on panic instead, and then the kernel releases the grab as the device is
+/// closed on exit.
+struct Source {
+    selector: DeviceSelector,
+    path: PathBuf,
+    device: Device,
+    frame: Frame,
+    // Which of the output's sinks its frames go to, see `Output::select`.
+    sink: usize,
+    // The force feedback effects of its sink, if they're passed on to it.
+    force_feedback: force_feedback::Relay,
+    // The keyboard whose LEDs it mirrors, if it has any.
+    leds: Option<LedMirror>,
+}
+
+impl Drop for Source {
+    fn drop(&mut self) {
+        // Fails harmlessly for a device that's gone or was never grabbed.
+        let _ = self.device.ungrab();
+    }
+}
+
+impl Source {
+    fn new(selector: DeviceSelector, path: PathBuf, device: Device, settings: &Settings) -> Self {
+        let frame =
+            new_frame(Some(&device), settings).with_angle_deg(settings.device_angle_deg(&selector));
+        let leds = device
+            .supported_leds()
+            .filter(|leds| leds.iter().next().is_some() && !settings.dry_run)
+            .and_then(|leds| {
+                let id = if settings.clone_id {
+                    device.input_id()
+                } else {
+                    settings.input_id.clone()
+                };
+                let name = format!("{} LEDs", settings.virtual_name);
+                LedMirror::create(&name, &id, leds)
+                    .inspect_err(|e| warn!("Creating {name} failed: {e}. The LEDs stay off."))
+                    .ok()
+            });
+        Source {
+            selector,
+            path,
+            device,
+            frame,
+            sink: 0,
+            force_feedback: force_feedback::Relay::default(),
+            leds,
+        }
+    }
+
+    /// Sets the LEDs of the device to what the desktop set on its mirror.
+    /// They stay as they are from then on if that fails.
+    fn relay_leds(&mut self) {
+        if let Some(leds) = &mut self.leds
+            && let Err(e) = leds.relay(&mut self.device)
+        {
+            warn!(
+                "Setting the LEDs of {} failed: {e}",
+                self.device.name().unwrap_or("Unknown")
+            );
+            self.leds = None;
+        }
+    }
+
+    /// Handles whatever events the device has ready.
+    fn read(&mut self, state: &LiveState, output: &mut Output) -> Result<(), AlignError> {
+        let events = match self.device.fetch_events() {
+            Ok(events) => events,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        output.select(self.sink);
+        for event in events {
+            output.received(&event);
+            self.frame.handle(event, state, output)?;
+        }
+        Ok(())
+    }
+}
+
+/// The sources as "name (path)", for the `status` command.
+fn describe_sources(sources: &[Source]) -> Vec<String> {
+    sources
+        .iter()
+        .map(|source| {
+            format!(
+                "{} ({})",
+                source.device.name().unwrap_or("Unknown"),
+                source.path.display()
+            )
+        })
+        .collect()
+}
+
+/// What the event loop keeps others informed with while it runs.
+struct Reporters {
+    stats: Option<stats::Reporter>,
+    status: Option<status::StatusFile>,
+}
+
+/// What the event loop watches besides the devices.
+struct Watchers {
+    /// Device nodes, for sources that went missing.
+    devices: Option<DeviceWatcher>,
+    /// Sources that weren't connected yet when the devices were opened, with
+    /// the sink each goes to, for the event loop to pick up as they come.
+    not_connected: Vec<(DeviceSelector, usize)>,
+    /// The config file and the files it includes, with --watch-config.
+    config: Option<ConfigWatcher>,
+    /// The accelerometer given with --gyro-device, while it works.
+    gyro: Option<Gyro>,
+}
+
+/// Waits on all source devices at once, plus the config file with
+/// --watch-config, the accelerometer with --gyro-device and the hotplug
+/// watcher while a source is missing, and only
+/// reads from the ones that are readable. Signals and the timers for the
+/// watchdog and smoothing wake the wait up too, so the whole loop runs on this
+/// one thread; only the control socket has its own.
+///
+/// Each source's motion is summed up until its `SYN_REPORT` and transformed
+/// as one frame, see [`Frame::handle`], and emitted on the source's own
+/// virtual device with --separate-outputs.
 fn event_loop(
-    input_device: &mut Device,
-    virtual_device: &mut VirtualDevice,
-    sin_a: f64,
-    cos_a: f64,
-) -> std::io::Result<!> {
-    let mut dx: i32 = 0;
-    let mut dy: i32 = 0;
+    sources: &mut Vec<Source>,
+    output: &mut Output,
+    state: &LiveState,
+    args: &Args,
+    settings: &mut Settings,
+    watchers: &mut Watchers,
+    reporters: &mut Reporters,
+) -> Result<LoopExit, AlignError> {
+    let watcher = watchers.devices.as_ref();
+    // Sources unplugged while the others kept running, picked up again when
+    // they come back, with the sink they had and, for one with force
+    // feedback, the effects uploaded to it.
+    // Those not connected at the start are picked up the same way.
+    let mut lost: Vec<(DeviceSelector, usize, Option<force_feedback::Relay>)> =
+        std::mem::take(&mut watchers.not_connected)
+            .into_iter()
+            .map(|(selector, sink)| {
+                if let (Some(watcher), DeviceSelector::Path(path)) = (watcher, &selector) {
+                    watcher.watch_parent_of(path);
+                }
+                (selector, sink, None)
+            })
+            .collect();
+    let mut watchdog = systemd::Watchdog::from_env();
 
     loop {
-        for event in input_device.fetch_events()? {
-            match event.event_type() {
-                EventType::RELATIVE => {
-                    let relative_axis_code = RelativeAxisCode(event.code());
-
-                    match relative_axis_code {
-                        RelativeAxisCode::REL_X => dx += event.value(),
-                        RelativeAxisCode::REL_Y => dy += event.value(),
-                        _ => virtual_device.emit(&[event])?,
-                    }
+        if signals::shutdown_requested() {
+            return Ok(LoopExit::Shutdown);
+        }
+        // Saving the file again before it settled waits for the last save.
+        let config_saved = watchers
+            .config
+            .as_mut()
+            .is_some_and(ConfigWatcher::take_settled);
+        if signals::take_dump() {
+            dump_frame_history(args);
+        }
+        if signals::take_reload() || config_saved {
+            let reopen = reload_settings(args, settings, state);
+            // The includes may have changed with it.
+            if let Some(config) = &mut watchers.config
+                && let Err(e) = config.watch(&settings.config_files)
+            {
+                warn!("Watching the included config files failed: {e}");
+            }
+            if reopen {
+                return Ok(LoopExit::Reopen);
+            }
+        }
+
+        let steps = signals::take_angle_steps();
+        if steps != 0 {
+            state.set_angle_deg(state.angle_deg() + steps as f64 * settings.angle_step);
+            info!("Angle changed to {}", Degrees(state.angle_deg()));
+        }
+
+        let mut fds: Vec<PollFd> = sources
+            .iter()
+            .map(|source| PollFd::new(source.device.as_fd(), PollFlags::POLLIN))
+            .collect();
+        if let Some(config) = &watchers.config {
+            fds.push(PollFd::new(config.as_fd(), PollFlags::POLLIN));
+        }
+        if let Some(gyro) = &watchers.gyro {
+            fds.push(PollFd::new(gyro.as_fd(), PollFlags::POLLIN));
+        }
+        let watch_lost = watcher.filter(|_| !lost.is_empty());
+        if let Some(watcher) = watch_lost {
+            fds.push(PollFd::new(watcher.as_fd(), PollFlags::POLLIN));
+        }
+        let force_feedback = output.force_feedback_fds();
+        let relays: Vec<usize> = force_feedback.iter().map(|&(sink, _)| sink).collect();
+        fds.extend(
+            force_feedback
+                .into_iter()
+                .map(|(_, fd)| PollFd::new(fd, PollFlags::POLLIN)),
+        );
+        let mirrored: Vec<usize> = (0..sources.len())
+            .filter(|&index| sources[index].leds.is_some())
+            .collect();
+        fds.extend(mirrored.iter().filter_map(|&index| {
+            let leds = sources[index].leds.as_ref()?;
+            Some(PollFd::new(leds.as_fd(), PollFlags::POLLIN))
+        }));
+        // Wake up in time to feed the watchdog, to report stats, to catch
+        // state changes for the status file, to keep smoothed motion going
+        // even when the devices are idle, to click when the pointer has dwelt
+        // long enough, to let go of a button once it's clear it didn't
+        // bounce, to emit coalesced motion once it's due, to reload the
+        // config file once it settled and to stop when --duration is up, and
+        // never so long that a signal is missed.
+        let watchdog_due = watchdog.as_mut().map(|watchdog| watchdog.ping_if_due());
+        let stats_due = reporters.stats.as_mut().map(|stats| stats.report_if_due());
+        let status_due = reporters.status.as_mut().map(|status| status.update(state));
+        let smoothing_due = sources
+            .iter()
+            .any(|source| source.frame.smoothing_pending())
+            .then_some(SMOOTHING_TICK);
+        let dwell_due = sources
+            .iter()
+            .filter_map(|source| source.frame.dwell_due())
+            .min();
+        let debounce_due = sources
+            .iter()
+            .filter_map(|source| source.frame.debounce_due())
+            .min();
+        let coalesce_due = sources
+            .iter()
+            .filter_map(|source| source.frame.coalesce_due())
+            .min();
+        let timeout = watchdog_due
+            .into_iter()
+            .chain(stats_due)
+            .chain(status_due)
+            .chain(smoothing_due)
+            .chain(dwell_due)
+            .chain(debounce_due)
+            .chain(coalesce_due)
+            .chain(watchers.config.as_ref().and_then(ConfigWatcher::due))
+            .chain(signals::until_deadline())
+            .fold(IDLE_WAKEUP, std::time::Duration::min);
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+        match poll(&mut fds, timeout) {
+            Ok(_) => {}
+            // A signal interrupted the wait, go round to handle it.
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+        let mut ready: Vec<bool> = fds
+            .iter()
+            .map(|fd| fd.revents().is_some_and(|events| !events.is_empty()))
+            .collect();
+        drop(fds);
+        let mirrored_ready = ready.split_off(ready.len() - mirrored.len());
+        let relays_ready = ready.split_off(ready.len() - relays.len());
+        let watcher_ready = watch_lost.is_some() && ready.pop() == Some(true);
+        let gyro_ready = watchers.gyro.is_some() && ready.pop() == Some(true);
+        let config_ready = watchers.config.is_some() && ready.pop() == Some(true);
+        if let Some(config) = watchers.config.as_mut()
+            && config_ready
+        {
+            config.read()?;
+        }
+        if let Some(gyro) = watchers.gyro.as_mut()
+            && gyro_ready
+        {
+            match gyro.read() {
+                Ok(Some(tilt)) => {
+                    state.set_tilt_deg(tilt);
+                    trace!("Tilted to {}", Degrees(tilt));
                 }
-                EventType::SYNCHRONIZATION => {
-                    if dx != 0 || dy != 0 {
-                        let new_dx = (dx as f64 * cos_a - dy as f64 * sin_a).round() as i32;
-                        let new_dy = (dx as f64 * sin_a + dy as f64 * cos_a).round() as i32;
-
-                        virtual_device.emit(&[
-                            InputEvent::new_now(
-                                EventType::RELATIVE.0,
-                                RelativeAxisCode::REL_X.0,
-                                new_dx,
-                            ),
-                            InputEvent::new_now(
-                                EventType::RELATIVE.0,
-                                RelativeAxisCode::REL_Y.0,
-                                new_dy,
-                            ),
-                            event,
-                        ])?;
-
-                        dx = 0;
-                        dy = 0;
-                    } else {
-                        virtual_device.emit(&[event])?;
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(
+                        "Reading the accelerometer failed: {e}. The angle stays at {}.",
+                        Degrees(state.applied_angle_deg())
+                    );
+                    watchers.gyro = None;
+                }
+            }
+        }
+
+        for (&index, _) in mirrored
+            .iter()
+            .zip(&mirrored_ready)
+            .filter(|(_, ready)| **ready)
+        {
+            sources[index].relay_leds();
+        }
+
+        let mut index = 0;
+        while index < sources.len() {
+            if ready[index] {
+                match sources[index].read(state, output) {
+                    Ok(()) => {}
+                    Err(e) if e.is_stale_device() && sources[index].path.exists() => {
+                        info!(
+                            "{} stopped working ({e}), reopening the devices",
+                            sources[index].path.display()
+                        );
+                        return Ok(LoopExit::Stale);
                     }
+                    Err(e) if e.is_stale_device() => {
+                        let mut source = sources.remove(index);
+                        ready.remove(index);
+                        debug!("Device disconnected ({})", source.path.display());
+                        output.select(source.sink);
+                        source.frame.release(state, output)?;
+                        if let (Some(watcher), DeviceSelector::Path(path)) =
+                            (watcher, &source.selector)
+                        {
+                            watcher.watch_parent_of(path);
+                        }
+                        let relay = source.device.supported_ff().map(|_| {
+                            let mut relay = std::mem::take(&mut source.force_feedback);
+                            relay.detach();
+                            relay
+                        });
+                        lost.push((source.selector.clone(), source.sink, relay));
+                        state.set_devices(describe_sources(sources));
+                        continue;
+                    }
+                    Err(e) => return Err(e),
                 }
-                _ => virtual_device.emit(&[event])?,
             }
+            index += 1;
+        }
+        if sources.is_empty() {
+            return Ok(LoopExit::Disconnected);
+        }
+        for (&sink, _) in relays
+            .iter()
+            .zip(&relays_ready)
+            .filter(|(_, ready)| **ready)
+        {
+            let Some(virtual_device) = output.virtual_device(sink) else {
+                continue;
+            };
+            // The first source of the sink with force feedback is the one
+            // its effects were declared from. While it's unplugged its relay
+            // still hears of erasures, so that it doesn't upload those again.
+            let source = sources
+                .iter_mut()
+                .find(|source| source.sink == sink && source.device.supported_ff().is_some());
+            let unplugged = lost
+                .iter_mut()
+                .find_map(|(_, lost_sink, relay)| relay.as_mut().filter(|_| *lost_sink == sink));
+            match (source, unplugged) {
+                (Some(source), _) => source
+                    .force_feedback
+                    .handle(virtual_device, Some(&mut source.device)),
+                (None, Some(relay)) => relay.handle(virtual_device, None),
+                (None, None) => force_feedback::Relay::default().handle(virtual_device, None),
+            }
+            .map_err(AlignError::virtual_device)?;
+        }
+        for (source, ready) in sources.iter_mut().zip(&ready) {
+            output.select(source.sink);
+            if !ready && source.frame.smoothing_pending() {
+                source.frame.tick(output)?;
+            }
+            source.frame.dwell(output)?;
+            source.frame.debounce(output)?;
+            source.frame.release_coalesced(state, output)?;
+            source.frame.settle(state, output)?;
+        }
+
+        if let Some(watcher) = watch_lost
+            && watcher_ready
+        {
+            watcher.drain()?;
+            lost.retain_mut(
+                |(selector, sink, relay)| match resolve_and_open(selector, settings) {
+                    Ok((path, device)) => {
+                        debug!("Device reconnected ({})", path.display());
+                        let mut source = Source::new(selector.clone(), path, device, settings);
+                        source.sink = *sink;
+                        if let Some(relay) = relay.take() {
+                            source.force_feedback = relay;
+                            source.force_feedback.attach(&mut source.device);
+                        }
+                        sources.push(source);
+                        false
+                    }
+                    Err(_) => true,
+                },
+            );
+            state.set_devices(describe_sources(sources));
+        }
+    }
+}
+
+/// What `version` prints, as recorded by the build script.
+fn version_details() -> String {
+    format!(
+        "{} {}\n\
+         commit: {}\n\
+         built: {}\n\
+         compiler: {}\n\
+         target: {} ({})\n\
+         features: {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        env!("BUILD_COMMIT"),
+        env!("BUILD_DATE"),
+        env!("BUILD_RUSTC"),
+        env!("BUILD_TARGET"),
+        env!("BUILD_PROFILE"),
+        env!("BUILD_FEATURES"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Resolves the settings for `flags` with a config file saying `toml`.
+    fn resolve(name: &str, toml: &str, flags: &[&str]) -> Settings {
+        let path = std::env::temp_dir().join(format!(
+            "sensor_alignment-{name}-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, toml).unwrap();
+        let config = path.to_str().unwrap();
+        let (args, _) =
+            Args::parse_settled(["sensor_alignment", "--config", config].iter().chain(flags))
+                .unwrap();
+        let settings = Settings::resolve(&args, None);
+        std::fs::remove_file(&path).unwrap();
+        settings.unwrap()
+    }
+
+    #[test]
+    fn the_command_line_beats_the_file() {
+        // The environment is left to tests/precedence.rs, which sets it on
+        // a child rather than for every test running alongside.
+        let settings = resolve(
+            "precedence",
+            "angle_deg = 0.0\nscale_x = 2.0\nscale_y = 3.0\naccel = 4.0\n",
+            &["--scale-x", "7"],
+        );
+
+        assert_eq!(settings.pointer.scale_x, 7.0);
+        assert_eq!(settings.pointer.scale_y, 3.0);
+        assert_eq!(settings.pointer.accel, 4.0);
+        assert_eq!(settings.pointer.sensitivity, 1.0);
+        assert_eq!(
+            settings.file_keys,
+            ["accel", "angle_deg", "scale_x", "scale_y"]
+        );
+    }
+
+    #[test]
+    fn run_options_count_on_either_side_of_run() {
+        let (args, matches) =
+            Args::parse_settled(["sensor_alignment", "run", "--angle-deg", "3"]).unwrap();
+        log_sources(&matches);
+        assert_eq!(args.run.angle_deg, Some(3.0));
+        assert_eq!(
+            value_source(&matches, "angle_deg"),
+            Some(ValueSource::CommandLine)
+        );
+
+        let (args, _) = Args::parse_settled([
+            "sensor_alignment",
+            "--angle-deg=-3",
+            "--no-grab",
+            "run",
+            "--sensitivity",
+            "2",
+        ])
+        .unwrap();
+        assert_eq!(args.run.angle_deg, Some(-3.0));
+        assert!(args.run.no_grab);
+        assert_eq!(args.run.sensitivity, Some(2.0));
+
+        let both = [
+            "sensor_alignment",
+            "--angle-deg",
+            "3",
+            "run",
+            "--angle-deg",
+            "4",
+        ];
+        assert!(Args::parse_settled(both).is_err());
+    }
+
+    #[test]
+    fn two_angles_are_blended_the_short_way_round() {
+        let settings = resolve(
+            "blend",
+            "angle_a = 170.0\nangle_b = -170.0\nblend = 0.25\n",
+            &[],
+        );
+
+        assert!((settings.angle_deg - 175.0).abs() < 1e-9);
+        assert_eq!(settings.angle_blend, Some((170.0, -170.0, 0.25)));
+    }
+
+    #[test]
+    fn an_angle_on_the_command_line_replaces_any_kind_in_the_file() {
+        let settings = resolve(
+            "angle",
+            "matrix = [1.0, 0.0, 0.0, -1.0]\n",
+            &["--angle-deg", "3.5"],
+        );
+
+        assert_eq!(settings.angle_deg, 3.5);
+        assert_eq!(
+            settings.pipeline,
+            resolve("identity", "", &["--angle-deg", "0"]).pipeline
+        );
+    }
+
+    #[test]
+    fn swapped_buttons_give_way_to_a_remap_of_either() {
+        let swapped = resolve("swap", "angle_deg = 0.0", &["--swap-buttons"]);
+        assert_eq!(
+            swapped.remap,
+            [
+                (KeyCode::BTN_LEFT, KeyCode::BTN_RIGHT),
+                (KeyCode::BTN_RIGHT, KeyCode::BTN_LEFT),
+            ]
+        );
+
+        let remapped = resolve(
+            "swap-remap",
+            "angle_deg = 0.0",
+            &["--swap-buttons", "--remap", "BTN_RIGHT=BTN_MIDDLE"],
+        );
+        assert_eq!(
+            remapped.remap,
+            [
+                (KeyCode::BTN_LEFT, KeyCode::BTN_RIGHT),
+                (KeyCode::BTN_RIGHT, KeyCode::BTN_MIDDLE),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_device_path_given_is_read() {
+        let settings = resolve(
+            "devices",
+            "angle_deg = 0.0\ndevice_path = [\"9\"]\n",
+            &["-d", "7", "-d", "8"],
+        );
+
+        assert_eq!(
+            settings.devices,
+            [DeviceSelector::path("7"), DeviceSelector::path("8")]
+        );
+    }
+
+    #[test]
+    fn run_takes_what_running_without_a_subcommand_does() {
+        let bare = resolve("bare", "", &["--angle-deg", "3.5", "--sensitivity", "2"]);
+        let run = resolve(
+            "run",
+            "",
+            &["--angle-deg", "3.5", "run", "--sensitivity", "2"],
+        );
+
+        assert_eq!(bare.changes(&run), Vec::<String>::new());
+        assert_eq!(run.angle_deg, 3.5);
+    }
+
+    #[test]
+    fn touchpad_scrolling_turns_unless_the_axes_are_picked() {
+        let default = resolve("touch-default", "", &["--angle-deg", "3"]);
+        let picked = resolve(
+            "touch-picked",
+            "",
+            &["--angle-deg", "3", "--rotate-axes", "x,y"],
+        );
+        let in_file = resolve(
+            "touch-file",
+            "rotate_axes = [\"x\", \"y\"]",
+            &["--angle-deg", "3"],
+        );
+
+        assert!(default.touch_scroll && !default.scroll.rotate);
+        assert!(!picked.touch_scroll);
+        assert!(!in_file.touch_scroll);
+    }
+
+    #[test]
+    fn a_display_turns_a_matrix_too() {
+        // There's no display here to look up, which is taken as unrotated,
+        // but the matrix isn't turned into a conflicting angle.
+        let settings = resolve(
+            "display-matrix",
+            "",
+            &["--matrix", "1,0.2,0,1", "--match-display", "HDMI-9"],
+        );
+        assert_eq!(settings.angle_deg, 0.0);
+        assert_eq!(
+            settings.pipeline.steps(),
+            [Step::Matrix("1,0.2,0,1".parse().unwrap())]
+        );
+    }
+
+    #[test]
+    fn a_touchpad_declares_the_wheel_its_scrolling_turns_into() {
+        let settings = resolve("touch-declared", "", &["--angle-deg", "3"]);
+        let wheel = evdev::InputEvent::new(
+            evdev::EventType::RELATIVE.0,
+            evdev::RelativeAxisCode::REL_WHEEL.0,
+            1,
+        );
+        let mut touchpad = Capabilities::from_events(&[wheel]);
+        settings.declare_transformed(&mut touchpad, true);
+        let mut mouse = Capabilities::from_events(&[wheel]);
+        settings.declare_transformed(&mut mouse, false);
+
+        assert!(
+            touchpad
+                .relative_axes
+                .contains(evdev::RelativeAxisCode::REL_HWHEEL)
+        );
+        assert!(
+            !mouse
+                .relative_axes
+                .contains(evdev::RelativeAxisCode::REL_HWHEEL)
+        );
+    }
+
+    #[test]
+    fn phys_on_the_command_line_beats_the_file() {
+        let toml = "angle_deg = 0.0\nphys = \"desk/trackball\"";
+        let file = resolve("phys-file", toml, &[]);
+        let flag = resolve("phys-flag", toml, &["--phys", "desk/mouse"]);
+
+        assert_eq!(file.phys.as_deref(), Some(c"desk/trackball"));
+        assert_eq!(flag.phys.as_deref(), Some(c"desk/mouse"));
+    }
+
+    #[test]
+    fn emit_errors_are_retried_unless_skipped() {
+        let toml = "angle_deg = 0.0\nemit_errors = \"skip\"";
+        let default = resolve("emit-default", "angle_deg = 0.0", &[]);
+        let file = resolve("emit-file", toml, &[]);
+        let flag = resolve("emit-flag", toml, &["--emit-errors", "retry"]);
+
+        assert_eq!(default.emit_errors, EmitErrors::Retry);
+        assert_eq!(file.emit_errors, EmitErrors::Skip);
+        assert_eq!(flag.emit_errors, EmitErrors::Retry);
+        assert!("ignore".parse::<EmitErrors>().is_err());
+    }
+
+    #[test]
+    fn the_angle_can_be_given_in_gradians_or_turns() {
+        let grad = resolve("grad", "", &["--angle-grad", "100"]);
+        let turns = resolve("turns", "angle_turns = -0.125", &[]);
+
+        assert_eq!(grad.angle_deg, 90.0);
+        assert_eq!(grad.angle_as_given, Some((100.0, AngleUnit::Gradians)));
+        assert_eq!(turns.angle_deg, -45.0);
+        assert_eq!(turns.angle_as_given, Some((-0.125, AngleUnit::Turns)));
+        // Only one unit at a time.
+        assert!(
+            Args::try_parse_from(["sensor_alignment", "--angle-turns", "1", "--angle-rad", "1"])
+                .is_err()
+        );
+        let path = std::env::temp_dir().join(format!(
+            "sensor_alignment-two-units-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, "angle_grad = 50.0\nangle_turns = 0.125").unwrap();
+        let args =
+            Args::try_parse_from(["sensor_alignment", "--config", path.to_str().unwrap()]).unwrap();
+        let two_units = Settings::resolve(&args, None);
+        std::fs::remove_file(&path).unwrap();
+        assert!(two_units.is_err());
+    }
+
+    #[test]
+    fn gains_that_would_stop_or_fling_the_pointer_are_caught() {
+        let mirrored = resolve("mirrored", "angle_deg = 0.0\nscale_y = -1.0", &[]);
+        let huge = resolve(
+            "huge",
+            "angle_deg = 0.0",
+            &["--sensitivity", "1e6", "--scale-x=-500"],
+        );
+
+        assert_eq!(mirrored.pointer.scale_y, -1.0);
+        assert_eq!(huge.pointer.sensitivity, MAX_GAIN);
+        assert_eq!(huge.pointer.scale_x, -MAX_GAIN);
+        let path = std::env::temp_dir().join(format!(
+            "sensor_alignment-frozen-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, "angle_deg = 0.0").unwrap();
+        for flag in [
+            "--sensitivity=0",
+            "--sensitivity=-2",
+            "--scale-x=0",
+            "--scale-y=NaN",
+        ] {
+            let args = Args::try_parse_from([
+                "sensor_alignment",
+                "--config",
+                path.to_str().unwrap(),
+                flag,
+            ])
+            .unwrap();
+            let e = Settings::resolve(&args, None).err().unwrap().to_string();
+            assert!(e.contains("wouldn't move"), "{flag}: {e}");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn zero_degrees_alone_changes_nothing() {
+        let zero = resolve("zero", "angle_deg = 0.0", &[]);
+        let tiny = resolve("zero-tiny", "angle_deg = 1e-13", &[]);
+        let turned = resolve("zero-turned", "angle_deg = 90.0", &[]);
+        let scaled = resolve("zero-scaled", "angle_deg = 0.0\nscale_x = 2.0", &[]);
+        let flipped = resolve("zero-flipped", "angle_deg = 0.0", &["--flip-x"]);
+
+        assert!(zero.changes_nothing());
+        assert!(
+            sensor_alignment::Transform::new(zero.pointer).passes_through(5, -7, &Matrix::IDENTITY)
+        );
+        assert!(tiny.changes_nothing());
+        assert!(!turned.changes_nothing());
+        assert!(!scaled.changes_nothing());
+        assert!(!flipped.changes_nothing());
+    }
+
+    #[test]
+    fn included_files_come_first() {
+        let dir =
+            std::env::temp_dir().join(format!("sensor_alignment-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        std::fs::write(
+            dir.join("shared/base.toml"),
+            "angle_deg = 10.0\nscale_x = 2.0\n[profiles.default]\nsensitivity = 2.0\naccel = 3.0\n",
+        )
+        .unwrap();
+        let main = dir.join("main.toml");
+        std::fs::write(
+            &main,
+            "include = [\"shared/base.toml\"]\nangle_deg = 5.0\n[profiles.default]\naccel = 4.0\n",
+        )
+        .unwrap();
+        let config = Config::load(&main, None).unwrap();
+        assert_eq!(config.angle_deg, Some(5.0));
+        assert_eq!(config.scale_x, Some(2.0));
+        assert_eq!(config.sensitivity, Some(2.0));
+        assert_eq!(config.accel, Some(4.0));
+        // Both are watched with --watch-config.
+        assert_eq!(
+            config.files,
+            [
+                main.canonicalize().unwrap(),
+                dir.join("shared/base.toml").canonicalize().unwrap()
+            ]
+        );
+
+        // Checked like the including file, even where nothing uses it.
+        std::fs::write(
+            dir.join("shared/base.toml"),
+            "angle_deg = 10.0\n[profiles.slow]\nsensitivity = -1.0\n",
+        )
+        .unwrap();
+        let e = Config::load(&main, None).unwrap_err().to_string();
+        assert!(e.contains("base.toml: line 3: sensitivity"), "{e}");
+
+        // Relative to the file naming it, back up to where it started.
+        std::fs::write(dir.join("shared/base.toml"), "include = \"../main.toml\"\n").unwrap();
+        let e = Config::load(&main, None).unwrap_err().to_string();
+        assert!(e.contains("is a cycle"), "{e}");
+        assert!(e.contains("main.toml -> "), "{e}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_focused_window_picks_the_profile() {
+        let settings = resolve(
+            "app-profile",
+            "angle_deg = 3.0\n\
+             [[app_profile]]\nclass = \"steam_app_*\"\nprofile = \"game\"\n\
+             [[app_profile]]\nclass = \"krita\"\ntitle = \"*.kra*\"\nprofile = \"draw\"\n\
+             [profiles.game]\nangle_deg = 0.0\n[profiles.draw]\nsensitivity = 0.5\n",
+            &[],
+        );
+        let window = |class: &str, title: &str| focus::Window {
+            class: class.to_string(),
+            title: title.to_string(),
+        };
+        let rules = &settings.app_profiles;
+        assert_eq!(
+            focus::pick(rules, &window("steam_app_570", "Dota 2")),
+            Some("game")
+        );
+        assert_eq!(
+            focus::pick(rules, &window("krita", "cat.kra - Krita")),
+            Some("draw")
+        );
+        // Both patterns have to match.
+        assert_eq!(focus::pick(rules, &window("krita", "Krita")), None);
+        assert_eq!(focus::pick(rules, &window("firefox", "Krita")), None);
+
+        let tree = r#"{"focused": false, "nodes": [{"focused": false, "nodes": [],
+            "floating_nodes": [{"focused": true, "app_id": null, "name": "Dota 2",
+            "window_properties": {"class": "steam_app_570"}}]}]}"#;
+        assert_eq!(
+            focus::parse_sway_tree(tree),
+            Some(window("steam_app_570", "Dota 2"))
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "sensor_alignment-app-profile-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "[[app_profile]]\nclass = \"gimp\"\nprofile = \"paint\"\n",
+        )
+        .unwrap();
+        let e = Config::load(&path, None).unwrap_err().to_string();
+        assert!(e.contains("\"paint\", which isn't there"), "{e}");
+        std::fs::write(
+            &path,
+            "[[app_profile]]\nprofile = \"paint\"\n[profiles.paint]\n",
+        )
+        .unwrap();
+        let e = Config::load(&path, None).unwrap_err().to_string();
+        assert!(e.contains("line 2: profile"), "{e}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_panic_lets_go_of_the_source() {
+        // Needs /dev/uinput to make a device to grab, as tests/uinput.rs.
+        let keys: evdev::AttributeSet<KeyCode> = [KeyCode::BTN_LEFT].into_iter().collect();
+        let Ok(mut mouse) = VirtualDevice::builder()
+            .and_then(|builder| builder.name("sensor alignment test panic").with_keys(&keys))
+            .and_then(|builder| builder.build())
+        else {
+            eprintln!("/dev/uinput isn't writable, skipping");
+            return;
+        };
+        let path = mouse
+            .enumerate_dev_nodes_blocking()
+            .unwrap()
+            .find_map(Result::ok)
+            .unwrap();
+        let settings = resolve("panic", "angle_deg = 0.0", &[]);
+        let mut device = Device::open(&path).unwrap();
+        device.grab().unwrap();
+
+        let unwound = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _source = Source::new(
+                DeviceSelector::Path(path.clone()),
+                path.clone(),
+                device,
+                &settings,
+            );
+            panic!("in the event loop");
+        }));
+
+        assert!(unwound.is_err());
+        // A grab that was still held would make this fail with EBUSY.
+        Device::open(&path).unwrap().grab().unwrap();
+    }
+
+    /// The X motion of each frame emitted.
+    #[derive(Default)]
+    struct MotionX(Vec<i32>);
+
+    impl sensor_alignment::frame::FrameSink for MotionX {
+        fn emit(&mut self, events: &[evdev::InputEvent]) -> std::io::Result<()> {
+            let x = events
+                .iter()
+                .filter(|event| event.code() == evdev::RelativeAxisCode::REL_X.0)
+                .map(evdev::InputEvent::value)
+                .sum();
+            self.0.push(x);
+            Ok(())
+        }
+    }
+
+    /// One frame of a single count to the right.
+    fn nudge(frame: &mut Frame, state: &LiveState, sink: &mut MotionX) {
+        for (event_type, code, value) in [
+            (
+                evdev::EventType::RELATIVE,
+                evdev::RelativeAxisCode::REL_X.0,
+                1,
+            ),
+            (
+                evdev::EventType::SYNCHRONIZATION,
+                evdev::SynchronizationCode::SYN_REPORT.0,
+                0,
+            ),
+        ] {
+            let event = evdev::InputEvent::new(event_type.0, code, value);
+            frame.handle(event, state, sink).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_reconnected_device_carries_no_remainder_over() {
+        // Half a count per frame: the first rounds up to a whole one and
+        // the second makes up for it with the half a count it's owed.
+        let settings = resolve("reconnect", "angle_deg = 0.0\nsensitivity = 0.5\n", &[]);
+        let state = LiveState::new(settings.angle_deg);
+        let moved = |sink: &MotionX| sink.0.iter().sum::<i32>();
+        let mut sink = MotionX::default();
+        let mut frame = new_frame(None, &settings);
+        nudge(&mut frame, &state, &mut sink);
+        nudge(&mut frame, &state, &mut sink);
+        assert_eq!(moved(&sink), 1);
+
+        // The event loop rebuilds the frame like this when the device comes
+        // back, which rounds up afresh rather than making up for the first.
+        let mut sink = MotionX::default();
+        let mut frame = new_frame(None, &settings);
+        nudge(&mut frame, &state, &mut sink);
+        let mut reconnected = new_frame(None, &settings);
+        nudge(&mut reconnected, &state, &mut sink);
+        assert_eq!(moved(&sink), 2);
+
+        // The same through Source::new, which needs /dev/uinput for a
+        // device to open, as tests/uinput.rs.
+        let axes: evdev::AttributeSet<evdev::RelativeAxisCode> = [
+            evdev::RelativeAxisCode::REL_X,
+            evdev::RelativeAxisCode::REL_Y,
+        ]
+        .into_iter()
+        .collect();
+        let Ok(mut mouse) = VirtualDevice::builder()
+            .and_then(|builder| {
+                builder
+                    .name("sensor alignment test reconnect")
+                    .with_relative_axes(&axes)
+            })
+            .and_then(|builder| builder.build())
+        else {
+            eprintln!("/dev/uinput isn't writable, skipping Source::new");
+            return;
+        };
+        let path = mouse
+            .enumerate_dev_nodes_blocking()
+            .unwrap()
+            .find_map(Result::ok)
+            .unwrap();
+        let selector = DeviceSelector::Path(path.clone());
+        let mut sink = MotionX::default();
+        let device = Device::open(&path).unwrap();
+        let mut source = Source::new(selector.clone(), path.clone(), device, &settings);
+        nudge(&mut source.frame, &state, &mut sink);
+        drop(source);
+        let device = Device::open(&path).unwrap();
+        let mut source = Source::new(selector, path, device, &settings);
+        nudge(&mut source.frame, &state, &mut sink);
+        assert_eq!(moved(&sink), 2);
+    }
+
+    #[test]
+    fn frame_history_is_bounded() {
+        let parse = |frames: &str| {
+            Args::try_parse_from(["sensor_alignment", "--frame-history", frames])
+                .map(|args| args.run.frame_history)
+        };
+        assert_eq!(parse("1000000").unwrap(), Some(1_000_000));
+        assert!(parse("1000001").is_err());
+        assert!(parse("0").is_err());
+    }
+
+    #[test]
+    fn version_tells_the_build_apart() {
+        let args = Args::try_parse_from(["sensor_alignment", "version"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Version)));
+
+        let details = version_details();
+        assert!(details.starts_with(&format!("sensor_alignment {}\n", env!("CARGO_PKG_VERSION"))));
+        for field in ["commit", "built", "compiler", "target", "features"] {
+            assert!(details.contains(&format!("\n{field}: ")), "{details}");
         }
     }
 }
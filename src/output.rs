@@ -0,0 +1,734 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::os::fd::{AsFd, BorrowedFd};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use evdev::uinput::VirtualDevice;
+use evdev::{EventSummary, EventType, InputEvent, RelativeAxisCode, SynchronizationCode};
+use log::warn;
+use sensor_alignment::frame::{FrameRecord, FrameSink};
+
+use crate::capabilities::Capabilities;
+use crate::devices;
+use crate::frame_history;
+use crate::recording::Recorder;
+use crate::stats;
+
+/// Where transformed events go: the virtual device, or stdout with
+/// `--dry-run`.
+pub enum Sink {
+    Device(VirtualDevice),
+    DryRun,
+    /// Frames kept in memory, for tests to look at.
+    #[cfg(test)]
+    Memory(Vec<Vec<InputEvent>>),
+}
+
+/// What becomes of a frame the virtual device refuses, for --emit-errors.
+/// Either way, the events of the frame are logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmitErrors {
+    /// The error ends the event loop, which opens the devices again.
+    #[default]
+    Retry,
+    /// The events are emitted one by one and those refused are dropped,
+    /// unless the device is gone, which leaves nothing to emit to.
+    Skip,
+}
+
+impl std::str::FromStr for EmitErrors {
+    type Err = String;
+
+    /// Parses `retry` or `skip`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "retry" => Ok(EmitErrors::Retry),
+            "skip" => Ok(EmitErrors::Skip),
+            _ => Err(format!("expected retry or skip, got {text:?}")),
+        }
+    }
+}
+
+/// How often rows buffered for `--csv` are written out at the latest, as
+/// long as events keep coming.
+const CSV_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The `--csv` table of everything passing through, buffered so that a row
+/// costs no write of its own.
+struct Csv {
+    writer: BufWriter<File>,
+    flushed: Instant,
+}
+
+/// The sink together with the optional `--trace-json` log of everything
+/// passing through. With `--separate-outputs` there is a sink for each
+/// source, frames going to the one picked with [`Output::select`]. With
+/// `--split-scroll` a sink may have a second one its wheel events go to.
+pub struct Output {
+    sinks: Vec<Sink>,
+    // The scroll sink of each sink that has one, and what it was declared
+    // with.
+    scroll_sinks: Vec<Option<(Sink, Capabilities)>>,
+    selected: usize,
+    trace: Option<Box<dyn Write>>,
+    csv: Option<Csv>,
+    record: Option<Recorder>,
+    measure_latency: bool,
+    // The pointer motion emitted since it was last taken, once asked for.
+    tally: Option<(i64, i64)>,
+    emit_errors: EmitErrors,
+    // What each sink was declared with, if known, and the codes already
+    // warned about for not being among it, as (type, code).
+    declared: Vec<Capabilities>,
+    undeclared: HashSet<(u16, u16)>,
+    // When the first event of the frame being assembled was read.
+    frame_start: Option<Instant>,
+}
+
+impl Output {
+    /// `trace` is a file to append the JSON trace to, `-` meaning stderr, and
+    /// `record` one to append the raw events to.
+    pub fn new(sink: Sink, trace: Option<&Path>, record: Option<&Path>) -> std::io::Result<Self> {
+        let trace: Option<Box<dyn Write>> = match trace {
+            None => None,
+            Some(path) if path == Path::new("-") => Some(Box::new(std::io::stderr())),
+            Some(path) => Some(Box::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+        };
+        let record = record.map(Recorder::create).transpose()?;
+        Ok(Output {
+            sinks: vec![sink],
+            scroll_sinks: Vec::new(),
+            selected: 0,
+            trace,
+            csv: None,
+            record,
+            measure_latency: false,
+            tally: None,
+            emit_errors: EmitErrors::default(),
+            declared: Vec::new(),
+            undeclared: HashSet::new(),
+            frame_start: None,
+        })
+    }
+
+    /// Appends a row for every event passing through to the CSV file at
+    /// `path`, starting it with a header if it's new. The time is in seconds
+    /// on the monotonic clock since the first row of this run, so it doesn't
+    /// jump when the wall clock is set. What's still buffered is written by
+    /// [`Output::flush`], or when the output is dropped.
+    pub fn write_csv(&mut self, path: &Path) -> std::io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = BufWriter::new(file);
+        if writer.get_ref().metadata()?.len() == 0 {
+            writeln!(writer, "time,type,code,value,rotated")?;
+        }
+        self.csv = Some(Csv {
+            writer,
+            flushed: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Writes out what's buffered for `--csv` and `--record`, before the
+    /// output is let go of, reporting failures that dropping it would
+    /// swallow.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(csv) = &mut self.csv {
+            csv.writer.flush()?;
+        }
+        if let Some(record) = &mut self.record {
+            record.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Times every frame from reading its first event to emitting it, for
+    /// `--measure-latency`.
+    pub fn measure_latency(&mut self) {
+        self.measure_latency = true;
+    }
+
+    /// Adds up the pointer motion emitted from now on, for
+    /// [`Output::take_motion`].
+    #[cfg(any(test, feature = "tui"))]
+    pub fn tally_motion(&mut self) {
+        self.tally = Some((0, 0));
+    }
+
+    /// The pointer motion emitted since the last call, or since
+    /// [`Output::tally_motion`] for the first one.
+    #[cfg(any(test, feature = "tui"))]
+    pub fn take_motion(&mut self) -> (i64, i64) {
+        self.tally.as_mut().map_or((0, 0), std::mem::take)
+    }
+
+    /// Sets what becomes of frames the virtual device refuses.
+    pub fn on_emit_error(&mut self, policy: EmitErrors) {
+        self.emit_errors = policy;
+    }
+
+    /// Takes what each sink was declared with, in the order they were
+    /// added, so that events they weren't are pointed out instead of
+    /// vanishing.
+    pub fn declare(&mut self, capabilities: Vec<Capabilities>) {
+        self.declared = capabilities;
+    }
+
+    /// Takes the sinks wheel events go to instead, for `--split-scroll`, in
+    /// the order of the sinks they belong to.
+    pub fn split_scroll(&mut self, scroll_sinks: Vec<Option<(Sink, Capabilities)>>) {
+        self.scroll_sinks = scroll_sinks;
+    }
+
+    /// Adds another sink, for `--separate-outputs`.
+    pub fn add_sink(&mut self, sink: Sink) {
+        self.sinks.push(sink);
+    }
+
+    /// Has frames emitted from now on go to the sink added `index`th, the
+    /// one given to `new` being 0.
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.sinks.len() - 1);
+    }
+
+    /// Emits one frame of events, followed by the `SYN_REPORT` that ends it.
+    /// With a scroll sink the wheel events go there, and each of the two
+    /// gets a `SYN_REPORT` of its own for its part of the frame.
+    pub fn emit(&mut self, events: &[InputEvent]) -> std::io::Result<()> {
+        let policy = self.emit_errors;
+        if let Some((x, y)) = &mut self.tally {
+            for event in events {
+                match event.destructure() {
+                    EventSummary::RelativeAxis(_, RelativeAxisCode::REL_X, value) => {
+                        *x += i64::from(value)
+                    }
+                    EventSummary::RelativeAxis(_, RelativeAxisCode::REL_Y, value) => {
+                        *y += i64::from(value)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let sink = &mut self.sinks[self.selected];
+        let declared = self.declared.get(self.selected);
+        let (result, emitted) = match self.scroll_sinks.get_mut(self.selected) {
+            Some(Some((scroll_sink, scroll_declared))) => {
+                let (wheels, rest): (Vec<InputEvent>, Vec<InputEvent>) =
+                    events.iter().partition(|event| is_wheel(event));
+                warn_undeclared(declared, &mut self.undeclared, &rest);
+                warn_undeclared(Some(scroll_declared), &mut self.undeclared, &wheels);
+                let mut result = Ok(());
+                let mut emitted = events.len();
+                // A frame of nothing but SYN_REPORT still goes out.
+                if !rest.is_empty() || wheels.is_empty() {
+                    result = sink.emit(&rest, policy);
+                    emitted += 1;
+                }
+                if !wheels.is_empty() {
+                    result = result.and(scroll_sink.emit(&wheels, policy));
+                    emitted += 1;
+                }
+                (result, emitted)
+            }
+            _ => {
+                warn_undeclared(declared, &mut self.undeclared, events);
+                (sink.emit(events, policy), events.len() + 1)
+            }
+        };
+        match result {
+            Ok(()) => stats::emitted(emitted),
+            Err(_) => stats::emit_failed(),
+        }
+        // Frames emitted by the smoothing timer weren't read at all.
+        if let Some(start) = self.frame_start.take() {
+            stats::latency(start.elapsed());
+        }
+        result
+    }
+
+    /// Takes note of an event read from a source device: it's counted,
+    /// recorded with `--record` and shown in dry-run mode.
+    pub fn received(&mut self, event: &InputEvent) {
+        stats::received(event.event_type());
+        if self.measure_latency && self.frame_start.is_none() {
+            self.frame_start = Some(Instant::now());
+        }
+        if let Some(record) = &mut self.record
+            && let Err(e) = record.record(event)
+        {
+            warn!("Recording events failed, stopping it: {e}");
+            self.record = None;
+        }
+        if let Sink::DryRun = self.sinks[0] {
+            println!("in:  {}", describe(event));
+        }
+    }
+
+    /// Adds a line to the JSON trace and a row to the CSV file, if there are
+    /// any. `transformed` is the value emitted for an axis that was rotated.
+    pub fn trace(&mut self, raw: &InputEvent, transformed: Option<i32>) {
+        if let Some(csv) = &mut self.csv
+            && let Err(e) = csv.row(raw, transformed)
+        {
+            warn!("Writing the CSV file failed, stopping it: {e}");
+            self.csv = None;
+        }
+        let Some(writer) = &mut self.trace else {
+            return;
+        };
+        let time = raw
+            .timestamp()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let mut line = serde_json::json!({
+            "time": time,
+            "type": format!("{:?}", raw.event_type()),
+            "code": code_name(raw),
+            "value": raw.value(),
+        });
+        if let Some(transformed) = transformed {
+            line["transformed"] = transformed.into();
+        }
+        if let Err(e) = writeln!(writer, "{line}") {
+            warn!("Writing the JSON trace failed, stopping it: {e}");
+            self.trace = None;
+        }
+    }
+
+    /// The virtual devices declared with force feedback, by index, whose
+    /// effect uploads have to be answered.
+    pub fn force_feedback_fds(&self) -> Vec<(usize, BorrowedFd<'_>)> {
+        self.sinks
+            .iter()
+            .zip(&self.declared)
+            .enumerate()
+            .filter_map(|(index, (sink, declared))| match sink {
+                Sink::Device(virtual_device) if declared.force_feedback.is_some() => {
+                    Some((index, virtual_device.as_fd()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The `index`th virtual device, for relaying its force feedback.
+    pub fn virtual_device(&mut self, index: usize) -> Option<&mut VirtualDevice> {
+        match self.sinks.get_mut(index) {
+            Some(Sink::Device(virtual_device)) => Some(virtual_device),
+            _ => None,
+        }
+    }
+
+    /// Name and sysfs path of the `index`th virtual device, for the startup
+    /// banner.
+    pub fn describe(&mut self, index: usize, name: &str) -> String {
+        self.sinks[index].describe(name)
+    }
+
+    /// Name and sysfs path of the scroll device of the `index`th virtual
+    /// device, if it has one.
+    pub fn describe_scroll(&mut self, index: usize, name: &str) -> Option<String> {
+        match self.scroll_sinks.get_mut(index) {
+            Some(Some((scroll_sink, _))) => Some(scroll_sink.describe(name)),
+            _ => None,
+        }
+    }
+
+    /// Takes the frames emitted into a `Sink::Memory` so far.
+    #[cfg(test)]
+    pub fn take_frames(&mut self) -> Vec<Vec<InputEvent>> {
+        match &mut self.sinks[self.selected] {
+            Sink::Memory(frames) => std::mem::take(frames),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Takes the frames emitted into the scroll sink, if it's a
+    /// `Sink::Memory`.
+    #[cfg(test)]
+    pub fn take_scroll_frames(&mut self) -> Vec<Vec<InputEvent>> {
+        match self.scroll_sinks.get_mut(self.selected) {
+            Some(Some((Sink::Memory(frames), _))) => std::mem::take(frames),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl FrameSink for Output {
+    fn emit(&mut self, events: &[InputEvent]) -> std::io::Result<()> {
+        Output::emit(self, events)
+    }
+
+    fn trace(&mut self, raw: &InputEvent, transformed: Option<i32>) {
+        Output::trace(self, raw, transformed);
+    }
+
+    fn record(&mut self, frame: impl FnOnce() -> FrameRecord) {
+        frame_history::record(frame);
+    }
+
+    fn rotated(&mut self) {
+        stats::rotated();
+    }
+}
+
+impl Sink {
+    /// Writes one frame and the `SYN_REPORT` that ends it. Whatever the
+    /// virtual device refuses is logged, then dropped or passed up
+    /// according to `policy`.
+    fn emit(&mut self, events: &[InputEvent], policy: EmitErrors) -> std::io::Result<()> {
+        match self {
+            Sink::Device(virtual_device) => match virtual_device.emit(events) {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    let frame: Vec<String> = events.iter().map(describe_typed).collect();
+                    warn!("Emitting [{}] failed: {e}", frame.join(", "));
+                    // Without a working device there's nothing to skip to.
+                    let gone = matches!(
+                        e.raw_os_error(),
+                        Some(
+                            nix::libc::ENODEV
+                                | nix::libc::EBADF
+                                | nix::libc::EIO
+                                | nix::libc::ENXIO
+                        )
+                    );
+                    if policy == EmitErrors::Skip && !gone {
+                        skip_refused(events, |event| write_event(virtual_device, event))
+                    } else {
+                        Err(e)
+                    }
+                }
+            },
+            Sink::DryRun => {
+                for event in events {
+                    println!("out: {}", describe(event));
+                }
+                println!("out: SYN_REPORT 0");
+                Ok(())
+            }
+            #[cfg(test)]
+            Sink::Memory(frames) => {
+                frames.push(events.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    fn describe(&mut self, name: &str) -> String {
+        match self {
+            Sink::Device(virtual_device) => format!(
+                "{name} ({})",
+                virtual_device
+                    .get_syspath()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or("Unknown".to_string())
+            ),
+            Sink::DryRun => "none (dry run)".to_string(),
+            #[cfg(test)]
+            Sink::Memory(_) => "none (memory)".to_string(),
+        }
+    }
+}
+
+/// Warns about the events among `events` that `declared` doesn't have, once
+/// for each code, as the kernel drops them without a word.
+fn warn_undeclared(
+    declared: Option<&Capabilities>,
+    undeclared: &mut HashSet<(u16, u16)>,
+    events: &[InputEvent],
+) {
+    let Some(declared) = declared else {
+        return;
+    };
+    for event in events {
+        if !declared.declares(event) && undeclared.insert((event.event_type().0, event.code())) {
+            warn!(
+                "{:?} {} isn't declared on the virtual device, which drops it",
+                event.event_type(),
+                code_name(event)
+            );
+        }
+    }
+}
+
+fn is_wheel(event: &InputEvent) -> bool {
+    event.event_type() == EventType::RELATIVE && devices::is_wheel(RelativeAxisCode(event.code()))
+}
+
+impl Csv {
+    fn row(&mut self, raw: &InputEvent, transformed: Option<i32>) -> std::io::Result<()> {
+        static START: OnceLock<Instant> = OnceLock::new();
+        let time = START.get_or_init(Instant::now).elapsed().as_secs_f64();
+        write!(
+            self.writer,
+            "{time:.6},{:?},{},{},",
+            raw.event_type(),
+            code_name(raw),
+            raw.value()
+        )?;
+        match transformed {
+            Some(transformed) => writeln!(self.writer, "{transformed}")?,
+            None => writeln!(self.writer)?,
+        }
+        if self.flushed.elapsed() >= CSV_FLUSH_INTERVAL {
+            self.writer.flush()?;
+            self.flushed = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+fn code_name(event: &InputEvent) -> String {
+    match event.destructure() {
+        EventSummary::Synchronization(_, code, _) => format!("{code:?}"),
+        EventSummary::Key(_, code, _) => format!("{code:?}"),
+        EventSummary::RelativeAxis(_, code, _) => format!("{code:?}"),
+        EventSummary::AbsoluteAxis(_, code, _) => format!("{code:?}"),
+        _ => event.code().to_string(),
+    }
+}
+
+fn describe(event: &InputEvent) -> String {
+    format!("{} {}", code_name(event), event.value())
+}
+
+/// `describe` with the event type in front, for codes that mean something
+/// else in another type.
+fn describe_typed(event: &InputEvent) -> String {
+    format!("{:?} {}", event.event_type(), describe(event))
+}
+
+/// Writes `events` one by one, dropping those the virtual device refuses,
+/// and ends what it took with a single `SYN_REPORT`, so that the rest of the
+/// frame still arrives as one. Fails only if it refuses all of them.
+fn skip_refused(
+    events: &[InputEvent],
+    mut write: impl FnMut(&InputEvent) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut last_error = None;
+    let mut emitted = 0;
+    for event in events {
+        match write(event) {
+            Ok(()) => emitted += 1,
+            Err(e) => {
+                warn!("Skipping {}: {e}", describe_typed(event));
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) if emitted == 0 && !events.is_empty() => Err(e),
+        _ => write(&InputEvent::new(
+            EventType::SYNCHRONIZATION.0,
+            SynchronizationCode::SYN_REPORT.0,
+            0,
+        )),
+    }
+}
+
+/// Writes one event to the virtual device as it is, without the
+/// `SYN_REPORT` that `VirtualDevice::emit` ends every write with.
+fn write_event(virtual_device: &VirtualDevice, event: &InputEvent) -> std::io::Result<()> {
+    let raw = event.as_ref();
+    // SAFETY: input_event is plain data, handed to uinput as the bytes it
+    // reads it from.
+    let bytes = unsafe {
+        std::slice::from_raw_parts(
+            (raw as *const nix::libc::input_event).cast::<u8>(),
+            size_of_val(raw),
+        )
+    };
+    let written = nix::unistd::write(virtual_device.as_fd(), bytes)?;
+    if written < bytes.len() {
+        return Err(std::io::ErrorKind::WriteZero.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev::KeyCode;
+    use sensor_alignment::frame::{Frame, Hotkeys, ScrollOptions};
+    use sensor_alignment::state::LiveState;
+    use sensor_alignment::{Pipeline, PointerOptions};
+
+    use super::*;
+
+    fn frame(pointer_options: PointerOptions) -> Frame {
+        Frame::new(
+            None,
+            ScrollOptions::default(),
+            Pipeline::default(),
+            pointer_options,
+            Hotkeys::default(),
+            Vec::new(),
+            0.0,
+        )
+    }
+
+    fn output() -> Output {
+        Output::new(Sink::Memory(Vec::new()), None, None).unwrap()
+    }
+
+    fn event(event_type: EventType, code: u16, value: i32) -> InputEvent {
+        InputEvent::new(event_type.0, code, value)
+    }
+
+    fn syn(code: SynchronizationCode) -> InputEvent {
+        event(EventType::SYNCHRONIZATION, code.0, 0)
+    }
+
+    /// Feeds one frame of motion, closed by a `SYN_REPORT`.
+    fn move_by(frame: &mut Frame, state: &LiveState, output: &mut Output, dx: i32, dy: i32) {
+        for (code, value) in [(RelativeAxisCode::REL_X, dx), (RelativeAxisCode::REL_Y, dy)] {
+            if value != 0 {
+                let motion = event(EventType::RELATIVE, code.0, value);
+                frame.handle(motion, state, output).unwrap();
+            }
+        }
+        frame
+            .handle(syn(SynchronizationCode::SYN_REPORT), state, output)
+            .unwrap();
+    }
+
+    /// The pointer motion of each emitted frame, `None` for frames without.
+    fn motion(frames: &[Vec<InputEvent>]) -> Vec<Option<(i32, i32)>> {
+        frames
+            .iter()
+            .map(|frame| {
+                let value = |code: RelativeAxisCode| {
+                    frame
+                        .iter()
+                        .find(|event| {
+                            event.event_type() == EventType::RELATIVE && event.code() == code.0
+                        })
+                        .map(InputEvent::value)
+                };
+                match (
+                    value(RelativeAxisCode::REL_X),
+                    value(RelativeAxisCode::REL_Y),
+                ) {
+                    (None, None) => None,
+                    (x, y) => Some((x.unwrap_or(0), y.unwrap_or(0))),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_scroll_sends_each_its_part_of_the_frame() {
+        let state = LiveState::new(0.0);
+        let mut output = output();
+        let wheels = Capabilities::from_events(&[]).split_wheels().unwrap();
+        output.split_scroll(vec![Some((Sink::Memory(Vec::new()), wheels))]);
+        let mut frame = frame(PointerOptions::default());
+        let click = event(EventType::KEY, KeyCode::BTN_LEFT.0, 1);
+        let wheel = event(EventType::RELATIVE, RelativeAxisCode::REL_WHEEL.0, -1);
+        frame.handle(click, &state, &mut output).unwrap();
+        frame.handle(wheel, &state, &mut output).unwrap();
+        move_by(&mut frame, &state, &mut output, 5, 0);
+        frame.handle(wheel, &state, &mut output).unwrap();
+        frame
+            .handle(syn(SynchronizationCode::SYN_REPORT), &state, &mut output)
+            .unwrap();
+
+        // One frame each for what the first report had, the second going to
+        // the scroll device alone.
+        let frames = output.take_frames();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0][0].code(), KeyCode::BTN_LEFT.0);
+        assert_eq!(motion(&frames), [Some((5, 0))]);
+        let scroll_frames = output.take_scroll_frames();
+        assert_eq!(scroll_frames, [vec![wheel], vec![wheel]]);
+    }
+
+    #[test]
+    fn the_tally_adds_up_the_motion_emitted() {
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        let mut frame = frame(PointerOptions::default());
+        move_by(&mut frame, &state, &mut output, 5, 0);
+        // Only what's emitted after it's asked for counts.
+        output.tally_motion();
+        move_by(&mut frame, &state, &mut output, 0, -7);
+        move_by(&mut frame, &state, &mut output, 3, -2);
+        assert_eq!(output.take_motion(), (9, 3));
+        assert_eq!(output.take_motion(), (0, 0));
+    }
+
+    #[test]
+    fn the_csv_has_a_row_for_each_event_with_what_it_was_rotated_to() {
+        let path =
+            std::env::temp_dir().join(format!("sensor_alignment-csv-{}.csv", std::process::id()));
+        let state = LiveState::new(90.0);
+        let mut output = output();
+        output.write_csv(&path).unwrap();
+        let mut frame = frame(PointerOptions::default());
+        let click = event(EventType::KEY, KeyCode::BTN_LEFT.0, 1);
+        frame.handle(click, &state, &mut output).unwrap();
+        move_by(&mut frame, &state, &mut output, 5, 0);
+        // All of it, well within the second rows are written out after.
+        output.flush().unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let rows: Vec<&str> = csv
+            .lines()
+            .map(|line| line.split_once(',').map_or(line, |(_, rest)| rest))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "type,code,value,rotated",
+                "KEY,BTN_LEFT,1,",
+                "RELATIVE,REL_X,5,0",
+                "RELATIVE,REL_Y,0,5",
+                "SYNCHRONIZATION,SYN_REPORT,0,"
+            ]
+        );
+    }
+
+    #[test]
+    fn refused_events_leave_the_rest_of_the_frame_together() {
+        let click = event(EventType::KEY, KeyCode::BTN_LEFT.0, 1);
+        let dial = event(EventType::RELATIVE, RelativeAxisCode::REL_DIAL.0, 1);
+        let motion = event(EventType::RELATIVE, RelativeAxisCode::REL_X.0, 4);
+        let mut written = Vec::new();
+        skip_refused(&[click, dial, motion], |event| {
+            if event.code() == RelativeAxisCode::REL_DIAL.0 {
+                return Err(std::io::ErrorKind::InvalidInput.into());
+            }
+            written.push(*event);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            written,
+            [click, motion, syn(SynchronizationCode::SYN_REPORT)]
+        );
+    }
+
+    #[test]
+    fn a_frame_refused_whole_is_an_error() {
+        let dial = event(EventType::RELATIVE, RelativeAxisCode::REL_DIAL.0, 1);
+        let mut written = Vec::new();
+        let result = skip_refused(&[dial], |event| {
+            if event.event_type() == EventType::RELATIVE {
+                return Err(std::io::ErrorKind::InvalidInput.into());
+            }
+            written.push(*event);
+            Ok(())
+        });
+
+        assert!(result.is_err());
+        assert!(written.is_empty());
+    }
+}
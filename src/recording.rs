@@ -0,0 +1,96 @@
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use evdev::InputEvent;
+use serde::{Deserialize, Serialize};
+
+/// One line of a `--record` file: an event as read from the source device,
+/// with its kernel timestamp in seconds since the Unix epoch.
+#[derive(Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub time: f64,
+    #[serde(rename = "type")]
+    pub event_type: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl From<&InputEvent> for RecordedEvent {
+    fn from(event: &InputEvent) -> Self {
+        RecordedEvent {
+            time: event
+                .timestamp()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            event_type: event.event_type().0,
+            code: event.code(),
+            value: event.value(),
+        }
+    }
+}
+
+impl From<&RecordedEvent> for InputEvent {
+    fn from(recorded: &RecordedEvent) -> Self {
+        let (seconds, fraction) = (recorded.time.trunc(), recorded.time.fract());
+        nix::libc::input_event {
+            time: nix::libc::timeval {
+                tv_sec: seconds as nix::libc::time_t,
+                tv_usec: (fraction * 1e6).round() as nix::libc::suseconds_t,
+            },
+            type_: recorded.event_type,
+            code: recorded.code,
+            value: recorded.value,
+        }
+        .into()
+    }
+}
+
+/// Reads a whole `--record` file.
+pub fn read(path: &Path) -> std::io::Result<Vec<InputEvent>> {
+    let file = std::fs::File::open(path)?;
+    let mut events = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recorded: RecordedEvent = serde_json::from_str(&line).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}:{}: {e}", path.display(), index + 1),
+            )
+        })?;
+        events.push(InputEvent::from(&recorded));
+    }
+    Ok(events)
+}
+
+/// Writes the raw events to a `--record` file. Lines are buffered, so writing
+/// them costs the event loop next to nothing, and whatever is still buffered
+/// is written by [`Recorder::flush`], or when the recorder is dropped.
+pub struct Recorder {
+    writer: BufWriter<std::fs::File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, event: &InputEvent) -> std::io::Result<()> {
+        serde_json::to_writer(&mut self.writer, &RecordedEvent::from(event))?;
+        self.writer.write_all(b"\n")
+    }
+
+    /// Writes out what's buffered, which unlike dropping it tells of a
+    /// failure.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
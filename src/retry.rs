@@ -0,0 +1,120 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use log::{error, warn};
+
+use crate::error::AlignError;
+use crate::signals;
+
+/// The first delay after a failure, doubled after every further one.
+pub const BASE_DELAY: Duration = Duration::from_millis(500);
+/// The longest delay between two attempts.
+pub const MAX_DELAY: Duration = Duration::from_secs(30);
+// Delays are stretched or shrunk by up to this fraction, so that several
+// instances that failed together don't keep retrying in step.
+const JITTER: f64 = 0.1;
+
+/// Exponentially growing delays between attempts, optionally giving up after
+/// a number of them.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    max_attempts: Option<u32>,
+    failures: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(BASE_DELAY, MAX_DELAY)
+    }
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Backoff {
+            base,
+            max,
+            max_attempts: None,
+            failures: 0,
+        }
+    }
+
+    /// Gives up once `attempts` attempts have failed in a row.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Records a failure and returns how long to wait before the next
+    /// attempt, or `None` if there shouldn't be one.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        self.failures += 1;
+        if self.max_attempts.is_some_and(|max| self.failures >= max) {
+            return None;
+        }
+        let delay = self
+            .base
+            .saturating_mul(2u32.saturating_pow(self.failures - 1))
+            .min(self.max);
+        Some(delay.mul_f64(1.0 + JITTER * (2.0 * random_fraction() - 1.0)))
+    }
+
+    /// Starts over at the base delay, e.g. after a success.
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
+
+/// A number in `[0, 1)` that's good enough for jitter, from the random keys
+/// std seeds its hash maps with.
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Runs `action` until it succeeds, waiting as long as `backoff` says between
+/// attempts. Fails with the last error once shutdown is requested, the
+/// backoff gives up or the error is one retrying can't fix.
+pub fn with_retry<T, F>(mut action: F, name: &str, mut backoff: Backoff) -> Result<T, AlignError>
+where
+    F: FnMut() -> Result<T, AlignError>,
+{
+    loop {
+        match action() {
+            Ok(v) => return Ok(v),
+            Err(e) if signals::shutdown_requested() => return Err(e),
+            Err(e) if e.is_fatal() => {
+                error!("{name} failed: {e}");
+                if let Some(hint) = e.hint() {
+                    error!("{hint}");
+                }
+                return Err(e);
+            }
+            Err(e) => match backoff.next_delay() {
+                Some(delay) => {
+                    warn!(
+                        "{name} failed: {e}. Retrying in {:.1} seconds...",
+                        delay.as_secs_f64()
+                    );
+                    signals::sleep(delay);
+                }
+                None => {
+                    error!(
+                        "{name} failed: {e}. Giving up after {} attempts",
+                        backoff.failures()
+                    );
+                    if let Some(hint) = e.hint() {
+                        error!("{hint}");
+                    }
+                    return Err(e);
+                }
+            },
+        }
+    }
+}
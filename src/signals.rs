@@ -0,0 +1,132 @@
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction};
+
+// Net number of angle steps requested by SIGUSR1 (+1) and SIGUSR2 (-1) that
+// the event loop hasn't applied yet.
+static ANGLE_STEPS: AtomicI32 = AtomicI32::new(0);
+// Set by SIGHUP to have the config file read again.
+static RELOAD: AtomicBool = AtomicBool::new(false);
+// Set by SIGINT and SIGTERM, never cleared.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+// Set by SIGQUIT, with --frame-history, to have the frames kept written out.
+static DUMP: AtomicBool = AtomicBool::new(false);
+// When --duration runs out, counting as a shutdown request from then on.
+static DEADLINE: OnceLock<Instant> = OnceLock::new();
+
+extern "C" fn handle_signal(signal: nix::libc::c_int) {
+    match Signal::try_from(signal) {
+        Ok(Signal::SIGUSR1) => {
+            ANGLE_STEPS.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(Signal::SIGUSR2) => {
+            ANGLE_STEPS.fetch_sub(1, Ordering::Relaxed);
+        }
+        Ok(Signal::SIGHUP) => RELOAD.store(true, Ordering::Relaxed),
+        Ok(Signal::SIGQUIT) => DUMP.store(true, Ordering::Relaxed),
+        Ok(Signal::SIGINT | Signal::SIGTERM) => SHUTDOWN.store(true, Ordering::Relaxed),
+        _ => {}
+    }
+}
+
+const HANDLED: [Signal; 5] = [
+    Signal::SIGUSR1,
+    Signal::SIGUSR2,
+    Signal::SIGHUP,
+    Signal::SIGINT,
+    Signal::SIGTERM,
+];
+
+/// Installs the signal handlers.
+///
+/// `SA_RESTART` is deliberately not set, so a signal interrupts the blocking
+/// read in the event loop, which then picks up the change right away.
+pub fn install() -> std::io::Result<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(handle_signal),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    for signal in HANDLED {
+        // SAFETY: the handler only touches atomics.
+        unsafe { sigaction(signal, &action) }?;
+    }
+    Ok(())
+}
+
+/// Has SIGQUIT write out the frame history rather than end the program
+/// with a core dump.
+pub fn install_dump() -> std::io::Result<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(handle_signal),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    // SAFETY: the handler only touches atomics.
+    unsafe { sigaction(Signal::SIGQUIT, &action) }?;
+    Ok(())
+}
+
+/// Blocks the handled signals on the calling thread, so that they're always
+/// delivered to (and interrupt) the thread running the event loop.
+pub fn block_on_current_thread() {
+    let mut set = SigSet::empty();
+    for signal in HANDLED {
+        set.add(signal);
+    }
+    set.add(Signal::SIGQUIT);
+    let _ = set.thread_block();
+}
+
+/// Returns the net number of angle steps requested since the last call.
+pub fn take_angle_steps() -> i32 {
+    ANGLE_STEPS.swap(0, Ordering::Relaxed)
+}
+
+/// Has the config read again, as SIGHUP does. The signal wakes the event loop
+/// up right away.
+pub fn request_reload() {
+    let _ = nix::sys::signal::kill(nix::unistd::Pid::this(), Signal::SIGHUP);
+}
+
+/// Returns whether a config reload was requested since the last call.
+pub fn take_reload() -> bool {
+    RELOAD.swap(false, Ordering::Relaxed)
+}
+
+/// Returns whether the frame history was asked for since the last call.
+pub fn take_dump() -> bool {
+    DUMP.swap(false, Ordering::Relaxed)
+}
+
+/// Returns whether the program was asked to exit, or its time is up.
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::Relaxed) || until_deadline().is_some_and(|left| left.is_zero())
+}
+
+/// Has the program shut down once `duration` has passed.
+pub fn shut_down_after(duration: Duration) {
+    let _ = DEADLINE.set(Instant::now() + duration);
+}
+
+/// How long until the time given to `shut_down_after` is up, for blocking
+/// waits to wake up in time.
+pub fn until_deadline() -> Option<Duration> {
+    DEADLINE
+        .get()
+        .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// Sleeps for `duration`, returning early once shutdown was requested.
+pub fn sleep(duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while !shutdown_requested() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        std::thread::sleep(left.min(Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Tuning state that can be changed while the event loop is running.
+///
+/// The event loop only reads it between frames, so a change never applies to
+/// half a frame.
+#[derive(Debug)]
+pub struct LiveState {
+    // f64 bits of the angle in degrees.
+    angle_deg: AtomicU64,
+    // f64 bits of the angle from the settings, restored by `reset`.
+    configured_angle_deg: AtomicU64,
+    // f64 bits of the accelerometer's tilt, added to the angle when it's
+    // applied but kept apart from what's set.
+    tilt_deg: AtomicU64,
+    // Off while the toggle key has handed the raw motion back.
+    enabled: AtomicBool,
+    // On while the pause key is held down.
+    paused: AtomicBool,
+    // Config profile for the next reload to apply.
+    profile: Mutex<Option<String>>,
+    // The profiles there are to switch to.
+    profiles: Mutex<Vec<String>>,
+    // The source devices being read, as "name (path)", for `status`.
+    devices: Mutex<Vec<String>>,
+    started: Instant,
+}
+
+impl LiveState {
+    pub fn new(angle_deg: f64) -> Self {
+        LiveState {
+            angle_deg: AtomicU64::new(angle_deg.to_bits()),
+            configured_angle_deg: AtomicU64::new(angle_deg.to_bits()),
+            tilt_deg: AtomicU64::new(0.0f64.to_bits()),
+            enabled: AtomicBool::new(true),
+            paused: AtomicBool::new(false),
+            profile: Mutex::new(None),
+            profiles: Mutex::new(Vec::new()),
+            devices: Mutex::new(Vec::new()),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn angle_deg(&self) -> f64 {
+        f64::from_bits(self.angle_deg.load(Ordering::Relaxed))
+    }
+
+    pub fn set_angle_deg(&self, angle_deg: f64) {
+        self.angle_deg.store(angle_deg.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The angle motion is rotated by: the one set with the tilt added.
+    pub fn applied_angle_deg(&self) -> f64 {
+        let tilt_deg = f64::from_bits(self.tilt_deg.load(Ordering::Relaxed));
+        if tilt_deg == 0.0 {
+            self.angle_deg()
+        } else {
+            crate::normalize_angle_deg(self.angle_deg() + tilt_deg)
+        }
+    }
+
+    /// Sets how far an accelerometer is tilted, which turns motion on top of
+    /// the angle without changing it, so that stepping or resetting the
+    /// angle keeps following the tilt.
+    pub fn set_tilt_deg(&self, tilt_deg: f64) {
+        self.tilt_deg.store(tilt_deg.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Whether motion is transformed at all.
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Switches transforming motion on or off and returns the new state.
+    pub fn toggle_enabled(&self) -> bool {
+        !self.enabled.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    /// Whether the pause key is held, or the hold button isn't, which hands
+    /// back raw pointer motion until that changes.
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Hands back raw pointer motion while `paused`, set as the pause key or
+    /// the hold button changes.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn profile(&self) -> Option<String> {
+        self.profile.lock().unwrap().clone()
+    }
+
+    /// Picks the profile that settings are resolved with from the next reload
+    /// on.
+    pub fn set_profile(&self, profile: Option<String>) {
+        *self.profile.lock().unwrap() = profile;
+    }
+
+    pub fn profiles(&self) -> Vec<String> {
+        self.profiles.lock().unwrap().clone()
+    }
+
+    /// Records which profiles the config file has, whenever it is read.
+    pub fn set_profiles(&self, profiles: Vec<String>) {
+        *self.profiles.lock().unwrap() = profiles;
+    }
+
+    pub fn devices(&self) -> Vec<String> {
+        self.devices.lock().unwrap().clone()
+    }
+
+    /// Records which devices are being read, whenever that changes.
+    pub fn set_devices(&self, devices: Vec<String>) {
+        *self.devices.lock().unwrap() = devices;
+    }
+
+    /// When the program started.
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// Replaces the configured angle, e.g. after the config was reloaded,
+    /// and makes it the current one.
+    pub fn configure(&self, angle_deg: f64) {
+        self.configured_angle_deg
+            .store(angle_deg.to_bits(), Ordering::Relaxed);
+        self.set_angle_deg(angle_deg);
+    }
+
+    /// Restores the configured angle.
+    pub fn reset(&self) {
+        self.set_angle_deg(f64::from_bits(
+            self.configured_angle_deg.load(Ordering::Relaxed),
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt::Write;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use evdev::EventType;
+use log::info;
+
+// Event types go up to EV_MAX, 0x1f.
+const EVENT_TYPES: usize = 0x20;
+
+// Counted since startup and never reset, so that a report is the difference
+// between two snapshots.
+static RECEIVED: [AtomicU64; EVENT_TYPES] = [const { AtomicU64::new(0) }; EVENT_TYPES];
+static EMITTED: AtomicU64 = AtomicU64::new(0);
+static EMIT_FAILURES: AtomicU64 = AtomicU64::new(0);
+static FRAMES_ROTATED: AtomicU64 = AtomicU64::new(0);
+static RECONNECTS: AtomicU64 = AtomicU64::new(0);
+// Taken by `start`, for the summary on exit.
+static START: OnceLock<Snapshot> = OnceLock::new();
+// When the last event was read, in nanoseconds since `EPOCH` plus one, 0
+// meaning never.
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+static LAST_RECEIVED_NS: AtomicU64 = AtomicU64::new(0);
+
+// With --measure-latency, how long frames took from being read to being
+// emitted. Bucket `i` counts frames that took less than 2^i µs but at least
+// 2^(i-1) µs, the last one everything slower.
+const LATENCY_BUCKETS: usize = 24;
+static LATENCY: [AtomicU64; LATENCY_BUCKETS] = [const { AtomicU64::new(0) }; LATENCY_BUCKETS];
+static LATENCY_SUM_NS: AtomicU64 = AtomicU64::new(0);
+static LATENCY_MIN_NS: AtomicU64 = AtomicU64::new(u64::MAX);
+static LATENCY_MAX_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts an event read from a source device.
+pub fn received(event_type: EventType) {
+    if let Some(counter) = RECEIVED.get(event_type.0 as usize) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    let since_epoch = EPOCH.get_or_init(Instant::now).elapsed();
+    LAST_RECEIVED_NS.store(
+        (since_epoch.as_nanos() as u64).saturating_add(1),
+        Ordering::Relaxed,
+    );
+}
+
+/// Counts the events of a frame written to the sink, its `SYN_REPORT`
+/// included.
+pub fn emitted(events: usize) {
+    EMITTED.fetch_add(events as u64, Ordering::Relaxed);
+}
+
+pub fn emit_failed() {
+    EMIT_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts a frame whose pointer motion was transformed rather than passed
+/// through raw.
+pub fn rotated() {
+    FRAMES_ROTATED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts the devices being opened again after they failed or went away.
+pub fn reconnected() {
+    RECONNECTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Marks the start of the session that `summary` describes.
+pub fn start() {
+    let _ = START.set(Snapshot::take());
+}
+
+/// Logs what happened over the whole session, on exit.
+pub fn summary() {
+    let Some(start) = START.get() else {
+        return;
+    };
+    let now = Snapshot::take();
+    let seconds = (now.at - start.at).as_secs();
+    let (total, by_type) = now.received_since(start);
+    let mut line = format!(
+        "Session: ran {}h {:02}m {:02}s, received {total} events",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+    );
+    if !by_type.is_empty() {
+        let _ = write!(line, " ({by_type})");
+    }
+    let _ = write!(
+        line,
+        ", rotated {} frames, emitted {} events, {} reconnects, {} emits failed",
+        now.frames_rotated - start.frames_rotated,
+        now.emitted - start.emitted,
+        now.reconnects - start.reconnects,
+        now.emit_failures - start.emit_failures
+    );
+    info!("{line}");
+}
+
+/// The counters since startup, for the `status` command.
+pub struct Totals {
+    pub received: u64,
+    pub emitted: u64,
+    pub emit_failures: u64,
+    /// How long ago the last event was read, if any was.
+    pub idle: Option<Duration>,
+}
+
+pub fn totals() -> Totals {
+    let last = LAST_RECEIVED_NS.load(Ordering::Relaxed);
+    Totals {
+        received: RECEIVED
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .sum(),
+        emitted: EMITTED.load(Ordering::Relaxed),
+        emit_failures: EMIT_FAILURES.load(Ordering::Relaxed),
+        idle: EPOCH.get().filter(|_| last > 0).map(|epoch| {
+            epoch
+                .elapsed()
+                .saturating_sub(Duration::from_nanos(last - 1))
+        }),
+    }
+}
+
+/// Counts the time from reading the first event of a frame to emitting it.
+pub fn latency(latency: Duration) {
+    let ns = latency.as_nanos().min(u64::MAX as u128) as u64;
+    let us = ns / 1000;
+    let bucket = ((u64::BITS - us.leading_zeros()) as usize).min(LATENCY_BUCKETS - 1);
+    LATENCY[bucket].fetch_add(1, Ordering::Relaxed);
+    LATENCY_SUM_NS.fetch_add(ns, Ordering::Relaxed);
+    LATENCY_MIN_NS.fetch_min(ns, Ordering::Relaxed);
+    LATENCY_MAX_NS.fetch_max(ns, Ordering::Relaxed);
+}
+
+/// Logs the latency distribution measured so far, with a histogram.
+pub fn latency_summary() {
+    let counts: [u64; LATENCY_BUCKETS] =
+        std::array::from_fn(|index| LATENCY[index].load(Ordering::Relaxed));
+    let frames: u64 = counts.iter().sum();
+    if frames == 0 {
+        info!("Latency: no frames measured");
+        return;
+    }
+    let us = |ns: u64| ns as f64 / 1000.0;
+    // The upper bound of the bucket the 99th percentile falls in.
+    let p99_rank = frames - frames / 100;
+    let mut seen = 0;
+    let p99_bucket = counts
+        .iter()
+        .position(|&count| {
+            seen += count;
+            seen >= p99_rank
+        })
+        .unwrap_or(LATENCY_BUCKETS - 1);
+    info!(
+        "Latency over {frames} frames: min {:.1} µs, avg {:.1} µs, p99 < {} µs, max {:.1} µs",
+        us(LATENCY_MIN_NS.load(Ordering::Relaxed)),
+        us(LATENCY_SUM_NS.load(Ordering::Relaxed)) / frames as f64,
+        1u64 << p99_bucket,
+        us(LATENCY_MAX_NS.load(Ordering::Relaxed)),
+    );
+    let widest = counts.iter().copied().max().unwrap_or(1);
+    for (bucket, &count) in counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+        let bar = "#".repeat((count * 40).div_ceil(widest) as usize);
+        if bucket == LATENCY_BUCKETS - 1 {
+            info!("  >= {:>7} µs {count:>8} {bar}", 1u64 << (bucket - 1));
+        } else {
+            info!("  <  {:>7} µs {count:>8} {bar}", 1u64 << bucket);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Snapshot {
+    at: Instant,
+    received: [u64; EVENT_TYPES],
+    emitted: u64,
+    emit_failures: u64,
+    frames_rotated: u64,
+    reconnects: u64,
+}
+
+impl Snapshot {
+    fn take() -> Self {
+        Snapshot {
+            at: Instant::now(),
+            received: std::array::from_fn(|index| RECEIVED[index].load(Ordering::Relaxed)),
+            emitted: EMITTED.load(Ordering::Relaxed),
+            emit_failures: EMIT_FAILURES.load(Ordering::Relaxed),
+            frames_rotated: FRAMES_ROTATED.load(Ordering::Relaxed),
+            reconnects: RECONNECTS.load(Ordering::Relaxed),
+        }
+    }
+
+    /// How many events were received since `earlier`, in total and as a
+    /// list by type.
+    fn received_since(&self, earlier: &Snapshot) -> (u64, String) {
+        let mut by_type = String::new();
+        let mut total = 0;
+        for (index, (now, then)) in self.received.iter().zip(&earlier.received).enumerate() {
+            let count = now - then;
+            if count > 0 {
+                total += count;
+                let separator = if by_type.is_empty() { "" } else { ", " };
+                let _ = write!(by_type, "{separator}{:?} {count}", EventType(index as u16));
+            }
+        }
+        (total, by_type)
+    }
+
+    /// One log line about what happened between `earlier` and this snapshot.
+    fn describe_since(&self, earlier: &Snapshot) -> String {
+        let seconds = (self.at - earlier.at).as_secs_f64();
+        let (total, by_type) = self.received_since(earlier);
+        let emitted = self.emitted - earlier.emitted;
+        let mut line = format!(
+            "{seconds:.1} s: received {total} events ({:.0}/s)",
+            total as f64 / seconds.max(f64::EPSILON)
+        );
+        if !by_type.is_empty() {
+            let _ = write!(line, " ({by_type})");
+        }
+        let _ = write!(
+            line,
+            ", emitted {emitted} ({:.0}/s), {} emits failed",
+            emitted as f64 / seconds.max(f64::EPSILON),
+            self.emit_failures - earlier.emit_failures
+        );
+        line
+    }
+}
+
+/// Logs the counters every `--stats-interval`.
+pub struct Reporter {
+    interval: Duration,
+    last: Snapshot,
+}
+
+impl Reporter {
+    pub fn new(interval: Duration) -> Self {
+        Reporter {
+            interval,
+            last: Snapshot::take(),
+        }
+    }
+
+    /// Reports if it's time to, and returns how long until the next report
+    /// is due.
+    pub fn report_if_due(&mut self) -> Duration {
+        let due = self.last.at + self.interval;
+        let now = Instant::now();
+        if now < due {
+            return due - now;
+        }
+        let snapshot = Snapshot::take();
+        info!("Stats, last {}", snapshot.describe_since(&self.last));
+        self.last = snapshot;
+        self.interval
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::warn;
+use sensor_alignment::state::LiveState;
+
+/// How often the state is checked for changes while the devices are quiet,
+/// for changes made through the control socket, D-Bus or signals.
+pub const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A file holding the live angle and whether rotation is on, as `key=value`
+/// lines for status bars:
+///
+/// ```text
+/// angle_deg=3.5
+/// enabled=true
+/// ```
+///
+/// It's only rewritten when either changes, through a temporary file renamed
+/// over it so that readers never see half of it, and removed when this is
+/// dropped.
+pub struct StatusFile {
+    path: PathBuf,
+    written: Option<(f64, bool)>,
+}
+
+impl StatusFile {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        StatusFile {
+            path: path.as_ref().to_path_buf(),
+            written: None,
+        }
+    }
+
+    /// Rewrites the file if the state changed since it was last written, and
+    /// returns how soon to check again.
+    pub fn update(&mut self, state: &LiveState) -> Duration {
+        let current = (state.angle_deg(), state.enabled());
+        if self.written != Some(current) {
+            // Taken as written even if that fails, so that a path that can't
+            // be written to doesn't fill the log until the state changes.
+            self.written = Some(current);
+            if let Err(e) = self.write(current) {
+                warn!("Writing {} failed: {e}", self.path.display());
+            }
+        }
+        CHECK_INTERVAL
+    }
+
+    fn write(&self, (angle_deg, enabled): (f64, bool)) -> std::io::Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        std::fs::write(
+            &temporary,
+            format!("angle_deg={angle_deg}\nenabled={enabled}\n"),
+        )?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+impl Drop for StatusFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
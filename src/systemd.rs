@@ -0,0 +1,61 @@
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+/// Sends a state update like `READY=1` to the service manager. Does nothing
+/// unless `NOTIFY_SOCKET` is set, i.e. unless running as a `Type=notify`
+/// systemd unit.
+pub fn notify(state: &str) {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    // A leading `@` stands for a socket in the abstract namespace.
+    let address = match socket_path.as_encoded_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name),
+        None => SocketAddr::from_pathname(&socket_path),
+    };
+    let sent = address.and_then(|address| {
+        let socket = UnixDatagram::unbound()?;
+        socket.send_to_addr(state.as_bytes(), &address)
+    });
+    if let Err(e) = sent {
+        debug!("Notifying systemd failed: {e}");
+    }
+}
+
+/// Keeps the systemd watchdog fed while `WATCHDOG_USEC` asks for it.
+pub struct Watchdog {
+    interval: Duration,
+    next_ping: Instant,
+}
+
+impl Watchdog {
+    /// `None` unless systemd enabled the watchdog for this process.
+    pub fn from_env() -> Option<Watchdog> {
+        let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+        if let Some(pid) = std::env::var("WATCHDOG_PID").ok()
+            && pid.parse() != Ok(std::process::id())
+        {
+            return None;
+        }
+        // Ping at twice the rate systemd expects, as sd_watchdog_enabled(3)
+        // recommends.
+        let interval = Duration::from_micros(usec) / 2;
+        Some(Watchdog {
+            interval,
+            next_ping: Instant::now(),
+        })
+    }
+
+    /// Pings if it's time to, and returns how long until the next ping is due.
+    pub fn ping_if_due(&mut self) -> Duration {
+        let now = Instant::now();
+        if now >= self.next_ping {
+            notify("WATCHDOG=1");
+            self.next_ping = now + self.interval;
+        }
+        self.next_ping - now
+    }
+}
@@ -0,0 +1,236 @@
+use std::os::fd::AsFd;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use evdev::{Device, EventSummary, RelativeAxisCode};
+use log::LevelFilter;
+use nix::errno::Errno;
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use sensor_alignment::frame::Frame;
+use sensor_alignment::state::LiveState;
+use sensor_alignment::{Degrees, angle_between, normalize_angle_deg};
+
+use crate::SMOOTHING_TICK;
+use crate::output::Output;
+use crate::signals;
+
+/// How often the screen is redrawn while nothing happens.
+const REFRESH: Duration = Duration::from_millis(100);
+/// A pause in the motion this long starts a new stroke, so that the readout
+/// shows the latest one alone.
+const STROKE_GAP: Duration = Duration::from_millis(500);
+
+/// The terminal in raw mode on the alternate screen. It's restored when this
+/// is dropped, however tuning ends, and log messages are held back meanwhile
+/// since they would scribble over the screen.
+struct Screen {
+    terminal: DefaultTerminal,
+    level: LevelFilter,
+}
+
+impl Screen {
+    fn open() -> std::io::Result<Screen> {
+        // Raw mode may already be on when entering the alternate screen fails.
+        let terminal = ratatui::try_init().inspect_err(|_| ratatui::restore())?;
+        let level = log::max_level();
+        log::set_max_level(LevelFilter::Off);
+        Ok(Screen { terminal, level })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        ratatui::restore();
+        log::set_max_level(self.level);
+    }
+}
+
+/// The motion of the current stroke, as read from the device and as emitted.
+#[derive(Debug, Default)]
+struct Stroke {
+    raw: (i64, i64),
+    rotated: (i64, i64),
+    moved: Option<Instant>,
+}
+
+impl Stroke {
+    fn add(&mut self, raw: (i64, i64), rotated: (i64, i64)) {
+        if raw == (0, 0) && rotated == (0, 0) {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .moved
+            .is_none_or(|moved| now.duration_since(moved) > STROKE_GAP)
+        {
+            *self = Stroke::default();
+        }
+        self.raw = (self.raw.0 + raw.0, self.raw.1 + raw.1);
+        self.rotated = (self.rotated.0 + rotated.0, self.rotated.1 + rotated.1);
+        self.moved = Some(now);
+    }
+}
+
+/// One line of the readout: the motion and its heading, clockwise from
+/// straight up.
+fn readout(label: &str, (dx, dy): (i64, i64)) -> Line<'static> {
+    let heading = match angle_between((0.0, -1.0), (dx as f64, dy as f64)) {
+        Some(heading) => Degrees((heading * 10.0).round() / 10.0).to_string(),
+        None => "-".to_string(),
+    };
+    Line::from(format!("{label:<9}{dx:>8} {dy:>8}   heading {heading}"))
+}
+
+/// A device being tuned, with its frame and which of the output's sinks its
+/// frames go to, see `Output::select`.
+pub struct Tuned {
+    pub device: Device,
+    pub frame: Frame,
+    pub sink: usize,
+}
+
+/// Runs the pipeline on `sources` while showing the angle and each stroke as
+/// read from all of them and as emitted. The arrow keys nudge the angle in `state`, the same
+/// one the control socket changes, `r` resets it and Enter hands it to
+/// `save`, which returns where it was saved. `q`, Esc and Ctrl-C quit.
+pub fn run(
+    sources: &mut [Tuned],
+    state: &LiveState,
+    output: &mut Output,
+    step: f64,
+    save: impl Fn(f64) -> std::io::Result<PathBuf>,
+) -> std::io::Result<()> {
+    let mut screen = Screen::open()?;
+    output.tally_motion();
+    let mut stroke = Stroke::default();
+    let mut message = String::new();
+
+    while !signals::shutdown_requested() {
+        let angle_deg = state.angle_deg();
+        screen.terminal.draw(|f| {
+            let block = Block::bordered().title(" sensor_alignment tune ");
+            let [angle, _, raw, rotated, _, keys, status] =
+                Layout::vertical([Constraint::Length(1); 7]).areas(block.inner(f.area()));
+            f.render_widget(block, f.area());
+            f.render_widget(
+                Paragraph::new(format!("Angle:   {}", Degrees(angle_deg))),
+                angle,
+            );
+            f.render_widget(Paragraph::new(readout("Raw:", stroke.raw)), raw);
+            f.render_widget(Paragraph::new(readout("Rotated:", stroke.rotated)), rotated);
+            f.render_widget(
+                Paragraph::new(format!(
+                    "←/→ {}   ↑/↓ {}   Enter save   r reset   q quit",
+                    Degrees(step),
+                    Degrees(step / 10.0)
+                )),
+                keys,
+            );
+            f.render_widget(Paragraph::new(message.as_str()), status);
+        })?;
+
+        let timeout = if sources
+            .iter()
+            .any(|source| source.frame.smoothing_pending())
+        {
+            SMOOTHING_TICK
+        } else {
+            REFRESH
+        };
+        let timeout = sources
+            .iter()
+            .filter_map(|source| source.frame.coalesce_due())
+            .fold(timeout, Duration::min);
+        let stdin = std::io::stdin();
+        let mut fds: Vec<PollFd> = sources
+            .iter()
+            .map(|source| PollFd::new(source.device.as_fd(), PollFlags::POLLIN))
+            .collect();
+        fds.push(PollFd::new(stdin.as_fd(), PollFlags::POLLIN));
+        match poll(
+            &mut fds,
+            PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX),
+        ) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut raw = (0, 0);
+        for Tuned {
+            device,
+            frame,
+            sink,
+        } in sources.iter_mut()
+        {
+            output.select(*sink);
+            match device.fetch_events() {
+                Ok(events) => {
+                    for event in events {
+                        match event.destructure() {
+                            EventSummary::RelativeAxis(_, RelativeAxisCode::REL_X, value) => {
+                                raw.0 += i64::from(value)
+                            }
+                            EventSummary::RelativeAxis(_, RelativeAxisCode::REL_Y, value) => {
+                                raw.1 += i64::from(value)
+                            }
+                            _ => {}
+                        }
+                        output.received(&event);
+                        frame.handle(event, state, output)?;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+            if frame.smoothing_pending() {
+                frame.tick(output)?;
+            }
+            frame.release_coalesced(state, output)?;
+            frame.settle(state, output)?;
+        }
+        stroke.add(raw, output.take_motion());
+
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let nudge = |by: f64| {
+                let angle_deg = normalize_angle_deg(state.angle_deg() + by);
+                // Steps of a tenth don't add up exactly in binary.
+                state.set_angle_deg((angle_deg * 1000.0).round() / 1000.0);
+            };
+            match key.code {
+                KeyCode::Right => nudge(step),
+                KeyCode::Left => nudge(-step),
+                KeyCode::Up => nudge(step / 10.0),
+                KeyCode::Down => nudge(-step / 10.0),
+                KeyCode::Char('r') => state.reset(),
+                KeyCode::Enter => {
+                    let angle_deg = state.angle_deg();
+                    message = match save(angle_deg) {
+                        Ok(path) => {
+                            // What `r` goes back to from now on.
+                            state.configure(angle_deg);
+                            format!("Saved {} to {}", Degrees(angle_deg), path.display())
+                        }
+                        Err(e) => format!("Saving failed: {e}"),
+                    };
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+//! The frame-level library API: what goes in as device events comes out as
+//! whole frames, in order, with the motion transformed.
+
+use evdev::{EventType, InputEvent, KeyCode, RelativeAxisCode, SynchronizationCode};
+use sensor_alignment::SensorAlignment;
+
+fn rel(code: RelativeAxisCode, value: i32) -> InputEvent {
+    InputEvent::new(EventType::RELATIVE.0, code.0, value)
+}
+
+fn syn(code: SynchronizationCode) -> InputEvent {
+    InputEvent::new(EventType::SYNCHRONIZATION.0, code.0, 0)
+}
+
+fn summary(events: &[InputEvent]) -> Vec<(EventType, u16, i32)> {
+    events
+        .iter()
+        .map(|event| (event.event_type(), event.code(), event.value()))
+        .collect()
+}
+
+#[test]
+fn a_frame_comes_out_rotated_and_in_order() {
+    let mut alignment = SensorAlignment::new(90.0);
+    let click = InputEvent::new(EventType::KEY.0, KeyCode::BTN_LEFT.0, 1);
+    let mut out = Vec::new();
+    alignment.process(
+        &[
+            rel(RelativeAxisCode::REL_X, 2),
+            click,
+            rel(RelativeAxisCode::REL_X, 1),
+        ],
+        &mut out,
+    );
+    assert!(out.is_empty(), "nothing before the SYN_REPORT");
+    alignment.process(&[syn(SynchronizationCode::SYN_REPORT)], &mut out);
+
+    assert_eq!(
+        summary(&out),
+        [
+            (EventType::RELATIVE, RelativeAxisCode::REL_X.0, 0),
+            (EventType::RELATIVE, RelativeAxisCode::REL_Y.0, 3),
+            (EventType::KEY, KeyCode::BTN_LEFT.0, 1),
+            (
+                EventType::SYNCHRONIZATION,
+                SynchronizationCode::SYN_REPORT.0,
+                0
+            ),
+        ]
+    );
+}
+
+#[test]
+fn a_dropped_frame_is_discarded() {
+    let mut alignment = SensorAlignment::new(0.0);
+    let mut out = Vec::new();
+    alignment.process(
+        &[
+            rel(RelativeAxisCode::REL_X, 40),
+            syn(SynchronizationCode::SYN_DROPPED),
+            rel(RelativeAxisCode::REL_X, 40),
+            syn(SynchronizationCode::SYN_REPORT),
+            rel(RelativeAxisCode::REL_X, 2),
+            syn(SynchronizationCode::SYN_REPORT),
+        ],
+        &mut out,
+    );
+
+    assert_eq!(
+        summary(&out),
+        [
+            (EventType::RELATIVE, RelativeAxisCode::REL_X.0, 2),
+            (EventType::RELATIVE, RelativeAxisCode::REL_Y.0, 0),
+            (
+                EventType::SYNCHRONIZATION,
+                SynchronizationCode::SYN_REPORT.0,
+                0
+            ),
+        ]
+    );
+}
+
+#[test]
+fn disabling_passes_motion_through() {
+    let mut alignment = SensorAlignment::new(90.0);
+    alignment.set_enabled(false);
+    let mut out = Vec::new();
+    alignment.process(
+        &[
+            rel(RelativeAxisCode::REL_X, 5),
+            syn(SynchronizationCode::SYN_REPORT),
+        ],
+        &mut out,
+    );
+
+    assert_eq!(out[0].value(), 5);
+}
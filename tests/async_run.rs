@@ -0,0 +1,92 @@
+//! The async `run`, driven on a source device made through uinput and
+//! cancelled once its motion came through.
+//!
+//! Like `tests/uinput.rs`, it needs write access to `/dev/uinput` and
+//! passes without checking anything where that's missing.
+
+#![cfg(feature = "async")]
+
+use std::os::fd::AsFd;
+use std::time::{Duration, Instant};
+
+use evdev::uinput::VirtualDevice;
+use evdev::{AttributeSet, Device, EventType, InputEvent, KeyCode, RelativeAxisCode};
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use sensor_alignment::{Matrix, PointerOptions, run};
+use tokio_util::sync::CancellationToken;
+
+/// How long to wait for motion to come through.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A mouse named `name` with X/Y motion and a button, and its event node.
+fn mouse(name: &str) -> Option<(VirtualDevice, Device)> {
+    let axes: AttributeSet<RelativeAxisCode> = [RelativeAxisCode::REL_X, RelativeAxisCode::REL_Y]
+        .into_iter()
+        .collect();
+    let keys: AttributeSet<KeyCode> = [KeyCode::BTN_LEFT].into_iter().collect();
+    let mut device = VirtualDevice::builder()
+        .and_then(|builder| builder.name(name).with_relative_axes(&axes))
+        .and_then(|builder| builder.with_keys(&keys))
+        .and_then(|builder| builder.build())
+        .ok()?;
+    let path = device
+        .enumerate_dev_nodes_blocking()
+        .expect("listing the device's nodes")
+        .find_map(Result::ok)
+        .expect("the device has a node");
+    let node = Device::open(path).expect("opening the device");
+    Some((device, node))
+}
+
+#[tokio::test]
+async fn run_rotates_motion_until_cancelled() {
+    let Some((mut source, source_node)) = mouse("sensor alignment async test source") else {
+        eprintln!("/dev/uinput isn't writable, skipping");
+        return;
+    };
+    let (sink, mut output) = mouse("sensor alignment async test output").unwrap();
+    let cancel = CancellationToken::new();
+
+    // Sums up what the sink emits on a thread of its own, then stops the run.
+    let reader = std::thread::spawn({
+        let cancel = cancel.clone();
+        move || {
+            let mut total = (0, 0);
+            let started = Instant::now();
+            while total != (0, 10) && started.elapsed() < TIMEOUT {
+                let mut fds = [PollFd::new(output.as_fd(), PollFlags::POLLIN)];
+                let timeout = PollTimeout::try_from(TIMEOUT).unwrap_or(PollTimeout::MAX);
+                if poll(&mut fds, timeout).expect("waiting for events") == 0 {
+                    break;
+                }
+                for event in output.fetch_events().expect("reading events") {
+                    match (event.event_type(), RelativeAxisCode(event.code())) {
+                        (EventType::RELATIVE, RelativeAxisCode::REL_X) => total.0 += event.value(),
+                        (EventType::RELATIVE, RelativeAxisCode::REL_Y) => total.1 += event.value(),
+                        _ => {}
+                    }
+                }
+            }
+            cancel.cancel();
+            total
+        }
+    });
+    source
+        .emit(&[InputEvent::new(
+            EventType::RELATIVE.0,
+            RelativeAxisCode::REL_X.0,
+            10,
+        )])
+        .unwrap();
+
+    run(
+        source_node,
+        sink,
+        Matrix::rotation(90.0),
+        PointerOptions::default(),
+        cancel,
+    )
+    .await
+    .unwrap();
+    assert_eq!(reader.join().unwrap(), (0, 10));
+}
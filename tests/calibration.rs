@@ -0,0 +1,81 @@
+//! Fitting the ellipse `calibrate --circle` derives scales from: a stroke
+//! traced around a known ellipse gives that ellipse back, wherever it's
+//! centred and however it's turned, and the corrections made from it turn it
+//! into a circle.
+
+use proptest::prelude::*;
+use sensor_alignment::{Ellipse, normalize_angle_deg, sin_cos_deg};
+
+/// `count` points around the ellipse, starting anywhere, rounded to whole
+/// counts like a sensor reports them.
+fn trace(ellipse: Ellipse, center: (f64, f64), count: usize) -> Vec<(f64, f64)> {
+    let (sin_a, cos_a) = sin_cos_deg(ellipse.angle_deg);
+    (0..count)
+        .map(|i| {
+            let t = i as f64 / count as f64 * std::f64::consts::TAU;
+            let (u, v) = (ellipse.major * t.cos(), ellipse.minor * t.sin());
+            (
+                (center.0 + u * cos_a - v * sin_a).round(),
+                (center.1 + u * sin_a + v * cos_a).round(),
+            )
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn a_traced_ellipse_is_found_again(
+        angle_deg in -89.0..=90.0f64,
+        major in 500.0..3000.0f64,
+        aspect in 1.1..2.0f64,
+        center in (-5000.0..5000.0f64, -5000.0..5000.0f64),
+    ) {
+        let ellipse = Ellipse { angle_deg, major, minor: major / aspect };
+        let fitted = Ellipse::fit(&trace(ellipse, center, 200)).unwrap();
+        let turned = normalize_angle_deg(2.0 * (fitted.angle_deg - angle_deg)) / 2.0;
+        prop_assert!(turned.abs() < 1.0, "{ellipse:?} came out as {fitted:?}");
+        prop_assert!((fitted.major / major - 1.0).abs() < 0.01, "{ellipse:?} came out as {fitted:?}");
+        prop_assert!((fitted.aspect() / aspect - 1.0).abs() < 0.01, "{ellipse:?} came out as {fitted:?}");
+        prop_assert!(fitted.angle_deg > -90.0 && fitted.angle_deg <= 90.0);
+    }
+}
+
+#[test]
+fn the_corrections_make_it_round() {
+    // Along X, the scales alone round it off.
+    let wide = Ellipse {
+        angle_deg: 0.0,
+        major: 1200.0,
+        minor: 1000.0,
+    };
+    let (scale_x, scale_y) = wide.axis_scales();
+    assert!((scale_x * 1200.0 - scale_y * 1000.0).abs() < 1e-9);
+    assert!((scale_x * scale_y - 1.0).abs() < 1e-9);
+
+    // At a slant, only the matrix does.
+    let slanted = Ellipse {
+        angle_deg: 30.0,
+        ..wide
+    };
+    let matrix = slanted.rounding_matrix();
+    let rounded: Vec<f64> = trace(slanted, (0.0, 0.0), 100)
+        .into_iter()
+        .map(|point| {
+            let (x, y) = matrix.apply(point);
+            x.hypot(y)
+        })
+        .collect();
+    let radius = (1200.0f64 * 1000.0).sqrt();
+    assert!(
+        rounded.iter().all(|r| (r / radius - 1.0).abs() < 0.002),
+        "{rounded:?}"
+    );
+    assert!((matrix.b - matrix.c).abs() < 1e-12, "it doesn't turn");
+}
+
+#[test]
+fn a_line_or_too_few_points_fit_nothing() {
+    let line: Vec<(f64, f64)> = (0..100).map(|i| (i as f64, 2.0 * i as f64)).collect();
+    assert_eq!(Ellipse::fit(&line), None);
+    assert_eq!(Ellipse::fit(&line[..4]), None);
+}
@@ -0,0 +1,91 @@
+//! Where each option is taken from: the command line over the environment
+//! over the config file. The environment is the child's own, set with
+//! `Command::env`, so nothing here races with tests in this process.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the binary to log where its options came from.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn the_command_line_beats_the_environment_which_beats_the_file() {
+    let config = std::env::temp_dir().join(format!(
+        "sensor_alignment-precedence-{}.toml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &config,
+        "angle_deg = 0.0\nscale_x = 2.0\nscale_y = 3.0\naccel = 4.0\n",
+    )
+    .unwrap();
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_sensor_alignment"));
+    command
+        .arg("-vv")
+        .arg("--config")
+        .arg(&config)
+        .args(["--device-path", "/nonexistent/event0", "--scale-x", "7"])
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    // Nothing from the environment the tests run in.
+    for (key, _) in std::env::vars() {
+        if key.starts_with("SENSOR_ALIGNMENT_") {
+            command.env_remove(key);
+        }
+    }
+    command
+        .env("SENSOR_ALIGNMENT_SCALE_X", "5")
+        .env("SENSOR_ALIGNMENT_SCALE_Y", "6");
+    let mut child = command.spawn().expect("starting sensor_alignment");
+
+    // It goes on to wait for the device, so the log is read until the
+    // config file's keys are done with, the last of them being scale_y.
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    let (lines_tx, lines_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in stderr.lines().map_while(Result::ok) {
+            if lines_tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let file = config.display().to_string();
+    let mut log = Vec::new();
+    let started = Instant::now();
+    while !log
+        .iter()
+        .any(|line: &String| line.starts_with(&format!("debug: scale_y in {file}")))
+    {
+        let left = TIMEOUT.saturating_sub(started.elapsed());
+        match lines_rx.recv_timeout(left) {
+            Ok(line) => log.push(line),
+            Err(_) => break,
+        }
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::fs::remove_file(&config).unwrap();
+
+    for expected in [
+        "debug: scale_x given on the command line".to_string(),
+        "debug: scale_y taken from SENSOR_ALIGNMENT_SCALE_Y".to_string(),
+        format!("debug: accel taken from {file}"),
+        format!("debug: scale_x in {file} overridden"),
+        format!("debug: scale_y in {file} overridden"),
+    ] {
+        assert!(log.contains(&expected), "{expected:?} not in {log:#?}");
+    }
+    assert!(
+        !log.iter()
+            .any(|line| line.contains("SENSOR_ALIGNMENT_SCALE_X")),
+        "the command line lost to the environment: {log:#?}"
+    );
+    assert!(
+        !log.iter().any(|line| line.contains("sensitivity")),
+        "sensitivity wasn't given anywhere: {log:#?}"
+    );
+}
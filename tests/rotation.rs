@@ -0,0 +1,285 @@
+//! Properties of the rotation math that don't depend on particular values:
+//! rotating back undoes a rotation up to rounding, with or without the
+//! remainder being carried, 0° changes nothing at all and the other right
+//! angles move nothing onto the wrong axis. Rounding away from or toward
+//! zero leans the same way whichever way the axis moves, and curves stay
+//! between their points. A few angles are also checked against outputs
+//! worked out by hand, and reflections across either axis against the
+//! other axis flipping.
+
+use proptest::prelude::*;
+use sensor_alignment::{
+    AngleCurve, Carry, Matrix, PointerOptions, ResponseCurve, Rounding, Transform,
+    blend_angles_deg, normalize_angle_deg, rotate, sin_cos_deg,
+};
+
+// Small enough that clamping to the largest delta never comes into it.
+const DELTA: std::ops::RangeInclusive<i32> = -1000..=1000;
+
+proptest! {
+    #[test]
+    fn rotating_back_is_within_a_count(
+        dx in DELTA,
+        dy in DELTA,
+        angle_deg in -360.0..360.0f64,
+    ) {
+        let (sin_a, cos_a) = angle_deg.to_radians().sin_cos();
+        let (x, y) = rotate(dx, dy, sin_a, cos_a);
+        let (back_x, back_y) = rotate(x, y, -sin_a, cos_a);
+        prop_assert!((back_x - dx).abs() <= 1, "x: {dx} -> {x} -> {back_x}");
+        prop_assert!((back_y - dy).abs() <= 1, "y: {dy} -> {y} -> {back_y}");
+    }
+
+    #[test]
+    fn carried_strokes_come_back_within_a_count(
+        stroke in prop::collection::vec((-50..=50i32, -50..=50i32), 1..200),
+        angle_deg in -360.0..360.0f64,
+    ) {
+        let mut forward = Transform::new(PointerOptions::default());
+        let mut backward = Transform::new(PointerOptions::default());
+        let (there, back) = (Matrix::rotation(angle_deg), Matrix::rotation(-angle_deg));
+        let mut sent = (0, 0);
+        let mut returned = (0, 0);
+        for (dx, dy) in stroke {
+            sent = (sent.0 + dx, sent.1 + dy);
+            let (x, y) = forward.apply(dx, dy, &there).unwrap();
+            let (x, y) = backward.apply(x, y, &back).unwrap();
+            returned = (returned.0 + x, returned.1 + y);
+        }
+        // The remainders left in each transform never add up to a whole
+        // count and a half, however long the stroke.
+        prop_assert!((returned.0 - sent.0).abs() <= 1, "{sent:?} came back as {returned:?}");
+        prop_assert!((returned.1 - sent.1).abs() <= 1, "{sent:?} came back as {returned:?}");
+    }
+
+    #[test]
+    fn zero_degrees_is_the_identity(dx in DELTA, dy in DELTA) {
+        prop_assert_eq!(rotate(dx, dy, 0.0, 1.0), (dx, dy));
+        let mut transform = Transform::new(PointerOptions::default());
+        prop_assert_eq!(transform.apply(dx, dy, &Matrix::rotation(0.0)), Some((dx, dy)));
+        prop_assert_eq!(transform.apply(dx, dy, &Matrix::rotation(360.0)), Some((dx, dy)));
+    }
+
+    #[test]
+    fn strokes_at_zero_degrees_come_out_verbatim(
+        stroke in prop::collection::vec((-32767..=32767i32, -32767..=32767i32), 1..200),
+        floor in any::<bool>(),
+    ) {
+        let rounding = if floor { Rounding::Floor } else { Rounding::Bankers };
+        let mut transform = Transform::new(PointerOptions {
+            rounding_x: rounding,
+            rounding_y: rounding,
+            ..PointerOptions::default()
+        });
+        let identity = Matrix::rotation(0.0);
+        let out: Vec<_> = stroke
+            .iter()
+            .map(|&(dx, dy)| transform.apply(dx, dy, &identity).unwrap())
+            .collect();
+        prop_assert_eq!(out, stroke);
+    }
+
+    #[test]
+    fn rounding_leans_the_same_way_in_both_directions(
+        x in -1000.0..1000.0f64,
+        y in -1000.0..1000.0f64,
+    ) {
+        let (away_x, away_y) = Carry::new(Rounding::AwayFromZero, Rounding::AwayFromZero).round((x, y));
+        let (toward_x, toward_y) = Carry::new(Rounding::TowardZero, Rounding::TowardZero).round((x, y));
+        prop_assert!(away_x.abs() as f64 >= x.abs() && toward_x.abs() as f64 <= x.abs());
+        prop_assert!(away_y.abs() as f64 >= y.abs() && toward_y.abs() as f64 <= y.abs());
+        // Each axis keeps its own mode.
+        let mixed = Carry::new(Rounding::AwayFromZero, Rounding::TowardZero).round((x, y));
+        prop_assert_eq!(mixed, (away_x, toward_y));
+    }
+
+    #[test]
+    fn normalizing_keeps_the_rotation(angle_deg in -10_000.0..10_000.0f64) {
+        let normalized = normalize_angle_deg(angle_deg);
+        prop_assert!(normalized > -180.0 && normalized <= 180.0, "{angle_deg} -> {normalized}");
+        let (a, b) = (Matrix::rotation(angle_deg), Matrix::rotation(normalized));
+        for (x, y) in [(a.a, b.a), (a.b, b.b), (a.c, b.c), (a.d, b.d)] {
+            prop_assert!((x - y).abs() < 1e-9, "{angle_deg} -> {normalized}");
+        }
+    }
+
+    #[test]
+    fn blending_stays_on_the_short_arc(
+        a in -360.0..360.0f64,
+        b in -360.0..360.0f64,
+        weight in 0.0..=1.0f64,
+    ) {
+        let arc = normalize_angle_deg(b - a).abs();
+        let blended = blend_angles_deg(a, b, weight);
+        let from_a = normalize_angle_deg(blended - a).abs();
+        let from_b = normalize_angle_deg(b - blended).abs();
+        prop_assert!((from_a + from_b - arc).abs() < 1e-6, "{a} {b} {weight} -> {blended}");
+        prop_assert!((from_a - arc * weight).abs() < 1e-6, "{a} {b} {weight} -> {blended}");
+    }
+
+    #[test]
+    fn angle_curve_stays_between_its_points(
+        speed in 0.0..100.0f64,
+        low in -5.0..5.0f64,
+        high in -5.0..5.0f64,
+    ) {
+        let curve = AngleCurve::new(vec![(10.0, low), (40.0, high)]).unwrap();
+        prop_assert_eq!(curve.offset(0.0), 0.0);
+        let offset = curve.offset(speed);
+        let (min, max) = (low.min(high).min(0.0), low.max(high).max(0.0));
+        prop_assert!(offset >= min - 1e-9 && offset <= max + 1e-9, "{speed} -> {offset}");
+        if speed >= 40.0 {
+            prop_assert_eq!(offset, high);
+        }
+    }
+
+    #[test]
+    fn response_gain_is_clamped_to_its_ends(
+        speed in 0.0..100.0f64,
+        low in 0.0..5.0f64,
+        high in 0.0..5.0f64,
+    ) {
+        let curve = ResponseCurve::new(vec![(10.0, low), (40.0, high)]).unwrap();
+        let gain = curve.gain(speed);
+        prop_assert!(gain >= low.min(high) - 1e-9 && gain <= low.max(high) + 1e-9, "{speed} -> {gain}");
+        if speed <= 10.0 {
+            prop_assert_eq!(gain, low);
+        }
+        if speed >= 40.0 {
+            prop_assert_eq!(gain, high);
+        }
+    }
+}
+
+#[test]
+fn cardinal_angles_are_exact() {
+    for (angle_deg, expected) in [
+        (90.0, (0.0f64, 1.0f64)),
+        (180.0, (-1.0, 0.0)),
+        (270.0, (0.0, -1.0)),
+        (-90.0, (0.0, -1.0)),
+        (450.0, (0.0, 1.0)),
+        (-0.0, (1.0, 0.0)),
+        (f64::MIN_POSITIVE / 2.0, (1.0, 0.0)),
+    ] {
+        let (x, y) = Matrix::rotation(angle_deg).apply((1.0, 0.0));
+        // Compared by bits, which tells -0.0 from 0.0 too.
+        assert_eq!(
+            (x.to_bits(), y.to_bits()),
+            (expected.0.to_bits(), expected.1.to_bits()),
+            "{angle_deg}° -> ({x}, {y})"
+        );
+    }
+    let (sin_a, cos_a) = 90f64.to_radians().sin_cos();
+    assert_eq!(rotate(1, 0, sin_a, cos_a), (0, 1));
+    assert_eq!(sin_cos_deg(-0.0), (0.0, 1.0));
+    assert_eq!(Matrix::rotation(-0.0), Matrix::IDENTITY);
+}
+
+#[test]
+fn reflecting_across_the_axes_flips_the_other_axis() {
+    // Across the X axis y changes sign, across the Y axis x does.
+    for (axis_deg, motion, expected) in [
+        (0.0, (7, -3), (7, 3)),
+        (0.0, (-2, 5), (-2, -5)),
+        (90.0, (7, -3), (-7, -3)),
+        (90.0, (-2, 5), (2, 5)),
+    ] {
+        let matrix = Matrix::reflection(axis_deg);
+        let reflected = matrix.apply((motion.0 as f64, motion.1 as f64));
+        assert_eq!(
+            reflected,
+            (expected.0 as f64, expected.1 as f64),
+            "{motion:?} across {axis_deg}°"
+        );
+        let mut transform = Transform::new(PointerOptions::default());
+        let transformed = transform.apply(motion.0, motion.1, &matrix);
+        assert_eq!(transformed, Some(expected), "{motion:?} across {axis_deg}°");
+    }
+    assert_eq!(Matrix::reflection(0.0).determinant(), -1.0);
+    assert_eq!(Matrix::reflection(90.0).determinant(), -1.0);
+}
+
+#[test]
+fn radians_go_straight_to_the_matrix() {
+    for angle_rad in [0.1, 1.0, 2.5, -0.7] {
+        let matrix = Matrix::rotation_rad(angle_rad);
+        assert_eq!((matrix.c, matrix.a), angle_rad.sin_cos(), "{angle_rad} rad");
+    }
+    // Through degrees, 0.1 rad comes back a bit off.
+    assert_ne!(Matrix::rotation(0.1f64.to_degrees()).c, 0.1f64.sin());
+    assert_eq!(
+        Matrix::rotation_rad(std::f64::consts::FRAC_PI_2),
+        Matrix::rotation(90.0)
+    );
+    assert_eq!(
+        Matrix::rotation_rad(-std::f64::consts::PI),
+        Matrix::rotation(180.0)
+    );
+}
+
+#[test]
+fn zero_degrees_skips_the_float_path_with_the_defaults() {
+    assert!(PointerOptions::default().is_neutral());
+    let transform = Transform::new(PointerOptions::default());
+    assert!(transform.passes_through(3, -4, &Matrix::IDENTITY));
+    assert!(!transform.passes_through(3, -4, &Matrix::rotation(3.0)));
+    assert!(!transform.passes_through(i16::MAX as i32 + 1, 0, &Matrix::IDENTITY));
+    // The strength the command line defaults to is left out while the
+    // tremor filter looks back over no frames.
+    let unfiltered = Transform::new(PointerOptions {
+        tremor_strength: 0.8,
+        ..PointerOptions::default()
+    });
+    assert!(unfiltered.passes_through(3, -4, &Matrix::IDENTITY));
+    // Anything else to do takes the float path, even at 0°.
+    let slower = Transform::new(PointerOptions {
+        sensitivity: 0.5,
+        ..PointerOptions::default()
+    });
+    assert!(!slower.passes_through(3, -4, &Matrix::IDENTITY));
+    let filtered = Transform::new(PointerOptions {
+        tremor_window: 4,
+        ..PointerOptions::default()
+    });
+    assert!(!filtered.passes_through(3, -4, &Matrix::IDENTITY));
+}
+
+#[test]
+fn transformed_motion_is_exact_at_known_angles() {
+    for (angle_deg, motion, expected) in [
+        (0.0, (7, -3), (7, -3)),
+        (90.0, (7, -3), (3, 7)),
+        (180.0, (7, -3), (-7, 3)),
+        (270.0, (7, -3), (-3, -7)),
+        // (8.66, 5.0)
+        (30.0, (10, 0), (9, 5)),
+        // (0.0, 14.14)
+        (45.0, (10, 10), (0, 14)),
+        // (8.66, 5.0)
+        (-60.0, (0, 10), (9, 5)),
+        // (99.86, 5.23)
+        (3.0, (100, 0), (100, 5)),
+        // (-25.0, -43.3)
+        (240.0, (50, 0), (-25, -43)),
+    ] {
+        let mut transform = Transform::new(PointerOptions::default());
+        let transformed = transform.apply(motion.0, motion.1, &Matrix::rotation(angle_deg));
+        assert_eq!(transformed, Some(expected), "{motion:?} at {angle_deg}°");
+    }
+}
+
+#[test]
+fn a_thousand_small_moves_add_up_at_three_degrees() {
+    let matrix = Matrix::rotation(3.0);
+    let mut transform = Transform::new(PointerOptions::default());
+    let mut total = (0, 0);
+    for _ in 0..1000 {
+        let (x, y) = transform.apply(1, 0, &matrix).unwrap_or((0, 0));
+        total = (total.0 + x, total.1 + y);
+    }
+    // Rounded frame by frame without the remainder, y would stay at 0.
+    let (ideal_x, ideal_y) = matrix.apply((1000.0, 0.0));
+    assert!((total.0 as f64 - ideal_x).abs() <= 1.0, "{total:?}");
+    assert!((total.1 as f64 - ideal_y).abs() <= 1.0, "{total:?}");
+}
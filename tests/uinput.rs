@@ -0,0 +1,409 @@
+//! End-to-end tests of the whole event flow: a synthetic source device is
+//! created through uinput, the binary is started on it, and what its virtual
+//! device emits is read back.
+//!
+//! They need write access to `/dev/uinput` and read access to the event
+//! nodes, so each test passes without checking anything where that's
+//! missing, as in most containers and CI runners.
+
+use std::os::fd::AsFd;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use evdev::uinput::VirtualDevice;
+use evdev::{
+    AttributeSet, Device, EventSummary, EventType, FFEffectCode, FFEffectData, FFEffectKind,
+    FFReplay, FFTrigger, InputEvent, KeyCode, RelativeAxisCode, UInputCode,
+};
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+
+/// How long to wait for devices to show up and for motion to come through.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn uinput_available() -> bool {
+    let writable = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/uinput")
+        .is_ok();
+    if !writable {
+        eprintln!("/dev/uinput isn't writable, skipping");
+    }
+    writable
+}
+
+/// The letter keys, KEY_A to KEY_Z, which aren't numbered in order.
+const LETTERS: [KeyCode; 26] = [
+    KeyCode::KEY_A,
+    KeyCode::KEY_B,
+    KeyCode::KEY_C,
+    KeyCode::KEY_D,
+    KeyCode::KEY_E,
+    KeyCode::KEY_F,
+    KeyCode::KEY_G,
+    KeyCode::KEY_H,
+    KeyCode::KEY_I,
+    KeyCode::KEY_J,
+    KeyCode::KEY_K,
+    KeyCode::KEY_L,
+    KeyCode::KEY_M,
+    KeyCode::KEY_N,
+    KeyCode::KEY_O,
+    KeyCode::KEY_P,
+    KeyCode::KEY_Q,
+    KeyCode::KEY_R,
+    KeyCode::KEY_S,
+    KeyCode::KEY_T,
+    KeyCode::KEY_U,
+    KeyCode::KEY_V,
+    KeyCode::KEY_W,
+    KeyCode::KEY_X,
+    KeyCode::KEY_Y,
+    KeyCode::KEY_Z,
+];
+
+/// A source device the tests move, as the binary sees it.
+struct Source {
+    device: VirtualDevice,
+    path: PathBuf,
+}
+
+impl Source {
+    /// A device named `name` with the given relative axes, keys and force
+    /// feedback effects.
+    fn new(name: &str, axes: &[RelativeAxisCode], keys: &[KeyCode], ff: &[FFEffectCode]) -> Source {
+        let axes: AttributeSet<RelativeAxisCode> = axes.iter().copied().collect();
+        let keys: AttributeSet<KeyCode> = keys.iter().copied().collect();
+        let ff: AttributeSet<FFEffectCode> = ff.iter().copied().collect();
+        let mut device = VirtualDevice::builder()
+            .and_then(|builder| builder.name(name).with_relative_axes(&axes))
+            .and_then(|builder| builder.with_keys(&keys))
+            .and_then(|builder| match ff.iter().next() {
+                Some(_) => Ok(builder.with_ff(&ff)?.with_ff_effects_max(4)),
+                None => Ok(builder),
+            })
+            .and_then(|builder| builder.build())
+            .expect("creating the source device");
+        let path = device
+            .enumerate_dev_nodes_blocking()
+            .expect("listing the source device's nodes")
+            .find_map(Result::ok)
+            .expect("the source device has a node");
+        Source { device, path }
+    }
+
+    /// A plain mouse: X/Y motion, a wheel and three buttons.
+    fn mouse(name: &str) -> Source {
+        Source::new(
+            name,
+            &[
+                RelativeAxisCode::REL_X,
+                RelativeAxisCode::REL_Y,
+                RelativeAxisCode::REL_WHEEL,
+            ],
+            &[KeyCode::BTN_LEFT, KeyCode::BTN_RIGHT, KeyCode::BTN_MIDDLE],
+            &[],
+        )
+    }
+
+    /// A mouse that rumbles.
+    fn rumbling_mouse(name: &str) -> Source {
+        Source::new(
+            name,
+            &[RelativeAxisCode::REL_X, RelativeAxisCode::REL_Y],
+            &[KeyCode::BTN_LEFT],
+            &[FFEffectCode::FF_RUMBLE],
+        )
+    }
+
+    /// Takes the effect uploads and erasures the virtual device is sent
+    /// until an effect was erased, and returns the force feedback events it
+    /// was sent meanwhile.
+    fn force_feedback(&mut self) -> Vec<InputEvent> {
+        let mut sent = Vec::new();
+        let started = Instant::now();
+        while started.elapsed() < TIMEOUT {
+            let mut fds = [PollFd::new(self.device.as_fd(), PollFlags::POLLIN)];
+            let timeout = PollTimeout::try_from(TIMEOUT).unwrap_or(PollTimeout::MAX);
+            if poll(&mut fds, timeout).expect("waiting for events") == 0 {
+                break;
+            }
+            let events: Vec<InputEvent> = self
+                .device
+                .fetch_events()
+                .expect("reading events")
+                .collect();
+            for event in events {
+                match event.destructure() {
+                    EventSummary::UInput(upload, UInputCode::UI_FF_UPLOAD, _) => {
+                        drop(self.device.process_ff_upload(upload).expect("uploading"));
+                    }
+                    EventSummary::UInput(erase, UInputCode::UI_FF_ERASE, _) => {
+                        drop(self.device.process_ff_erase(erase).expect("erasing"));
+                        return sent;
+                    }
+                    EventSummary::ForceFeedback(..) => sent.push(event),
+                    _ => {}
+                }
+            }
+        }
+        sent
+    }
+
+    /// Presses `key` with a value of 1 or lets go of it with 0, as a frame
+    /// of its own.
+    fn key(&mut self, key: KeyCode, value: i32) {
+        let event = InputEvent::new(EventType::KEY.0, key.0, value);
+        self.device.emit(&[event]).expect("emitting a key");
+    }
+
+    /// Sends one frame of motion, closed by the `SYN_REPORT` uinput adds.
+    fn move_by(&mut self, dx: i32, dy: i32) {
+        let frame = [
+            InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_X.0, dx),
+            InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_Y.0, dy),
+        ];
+        self.device.emit(&frame).expect("emitting motion");
+    }
+}
+
+/// The binary running on a source device, interrupted when dropped.
+struct Running {
+    child: Child,
+    output: Device,
+}
+
+impl Running {
+    /// Starts the binary on `source` with `args` and opens its virtual
+    /// device once it's there.
+    fn start(source: &Source, name: &str, args: &[&str]) -> Running {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_sensor_alignment"));
+        command
+            .arg("--device-path")
+            .arg(&source.path)
+            .arg("--virtual-name")
+            .arg(name)
+            .args(args)
+            .env("XDG_CONFIG_HOME", "/nonexistent")
+            .stdout(Stdio::null());
+        // Nothing from the environment the tests run in.
+        for (key, _) in std::env::vars() {
+            if key.starts_with("SENSOR_ALIGNMENT_") {
+                command.env_remove(key);
+            }
+        }
+        let child = command.spawn().expect("starting sensor_alignment");
+        let started = Instant::now();
+        let output = loop {
+            let found = evdev::enumerate().find(|(_, device)| device.name() == Some(name));
+            if let Some((_, device)) = found {
+                break device;
+            }
+            assert!(
+                started.elapsed() < TIMEOUT,
+                "the virtual device didn't show up"
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        Running { child, output }
+    }
+
+    /// Sums up the motion the virtual device emits until it adds up to
+    /// `expected` along both axes, or until nothing more comes.
+    fn motion(&mut self, expected: (i32, i32)) -> (i32, i32) {
+        let mut total = (0, 0);
+        let started = Instant::now();
+        while total != expected && started.elapsed() < TIMEOUT {
+            let mut fds = [PollFd::new(self.output.as_fd(), PollFlags::POLLIN)];
+            let timeout = PollTimeout::try_from(TIMEOUT).unwrap_or(PollTimeout::MAX);
+            if poll(&mut fds, timeout).expect("waiting for events") == 0 {
+                break;
+            }
+            for event in self.output.fetch_events().expect("reading events") {
+                match (event.event_type(), RelativeAxisCode(event.code())) {
+                    (EventType::RELATIVE, RelativeAxisCode::REL_X) => total.0 += event.value(),
+                    (EventType::RELATIVE, RelativeAxisCode::REL_Y) => total.1 += event.value(),
+                    _ => {}
+                }
+            }
+        }
+        total
+    }
+
+    /// The key events the virtual device emits, until `count` of them came
+    /// or nothing more comes.
+    fn keys(&mut self, count: usize) -> Vec<(KeyCode, i32)> {
+        let mut keys = Vec::new();
+        let started = Instant::now();
+        while keys.len() < count && started.elapsed() < TIMEOUT {
+            let mut fds = [PollFd::new(self.output.as_fd(), PollFlags::POLLIN)];
+            let timeout = PollTimeout::try_from(TIMEOUT).unwrap_or(PollTimeout::MAX);
+            if poll(&mut fds, timeout).expect("waiting for events") == 0 {
+                break;
+            }
+            for event in self.output.fetch_events().expect("reading events") {
+                if let EventSummary::Key(_, key, value) = event.destructure() {
+                    keys.push((key, value));
+                }
+            }
+        }
+        keys
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = kill(Pid::from_raw(self.child.id() as i32), Signal::SIGINT);
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn zero_angle_passes_motion_through() {
+    if !uinput_available() {
+        return;
+    }
+    let mut source = Source::mouse("sensor alignment test source zero");
+    let mut running = Running::start(
+        &source,
+        "sensor alignment test output zero",
+        &["--angle-deg", "0"],
+    );
+    source.move_by(7, -3);
+    assert_eq!(running.motion((7, -3)), (7, -3));
+}
+
+#[test]
+fn quarter_turn_rotates_motion() {
+    if !uinput_available() {
+        return;
+    }
+    let mut source = Source::mouse("sensor alignment test source quarter");
+    let mut running = Running::start(
+        &source,
+        "sensor alignment test output quarter",
+        &["--angle-deg", "90"],
+    );
+    source.move_by(10, 0);
+    assert_eq!(running.motion((0, 10)), (0, 10));
+}
+
+#[test]
+fn remainders_carry_across_frames() {
+    if !uinput_available() {
+        return;
+    }
+    let mut source = Source::mouse("sensor alignment test source carry");
+    let mut running = Running::start(
+        &source,
+        "sensor alignment test output carry",
+        &["--angle-deg", "0", "--sensitivity", "0.5"],
+    );
+    // Each frame moves half a count, which only adds up if it's carried.
+    for _ in 0..10 {
+        source.move_by(1, 1);
+    }
+    assert_eq!(running.motion((5, 5)), (5, 5));
+}
+
+#[test]
+fn side_buttons_are_declared() {
+    if !uinput_available() {
+        return;
+    }
+    let buttons = [
+        KeyCode::BTN_LEFT,
+        KeyCode::BTN_SIDE,
+        KeyCode::BTN_EXTRA,
+        KeyCode::BTN_FORWARD,
+        KeyCode::BTN_BACK,
+    ];
+    let source = Source::new(
+        "sensor alignment test source side buttons",
+        &[RelativeAxisCode::REL_X, RelativeAxisCode::REL_Y],
+        &buttons,
+        &[],
+    );
+    let running = Running::start(
+        &source,
+        "sensor alignment test output side buttons",
+        &["--angle-deg", "0"],
+    );
+    let declared = running
+        .output
+        .supported_keys()
+        .expect("the virtual device has buttons");
+    for button in buttons {
+        assert!(declared.contains(button), "{button:?} isn't declared");
+    }
+}
+
+#[test]
+fn letter_keys_pass_through_unchanged() {
+    if !uinput_available() {
+        return;
+    }
+    // A mouse with a keyboard on the same device, like many receivers.
+    let keys: Vec<KeyCode> = [KeyCode::BTN_LEFT].into_iter().chain(LETTERS).collect();
+    let mut source = Source::new(
+        "sensor alignment test source letters",
+        &[RelativeAxisCode::REL_X, RelativeAxisCode::REL_Y],
+        &keys,
+        &[],
+    );
+    let mut running = Running::start(
+        &source,
+        "sensor alignment test output letters",
+        &["--angle-deg", "90"],
+    );
+    let declared = running
+        .output
+        .supported_keys()
+        .expect("the virtual device has keys");
+    for letter in LETTERS {
+        assert!(declared.contains(letter), "{letter:?} isn't declared");
+    }
+    source.key(KeyCode::KEY_Q, 1);
+    source.key(KeyCode::KEY_Q, 0);
+    assert_eq!(running.keys(2), [(KeyCode::KEY_Q, 1), (KeyCode::KEY_Q, 0)]);
+}
+
+#[test]
+fn rumble_reaches_the_source_device() {
+    if !uinput_available() {
+        return;
+    }
+    let mut source = Source::rumbling_mouse("sensor alignment test source rumble");
+    let mut running = Running::start(
+        &source,
+        "sensor alignment test output rumble",
+        &["--angle-deg", "90"],
+    );
+    let sent = std::thread::scope(|scope| {
+        // Each step only returns once the source device took it.
+        scope.spawn(|| {
+            let mut effect = running
+                .output
+                .upload_ff_effect(FFEffectData {
+                    direction: 0,
+                    trigger: FFTrigger::default(),
+                    replay: FFReplay {
+                        length: 100,
+                        delay: 0,
+                    },
+                    kind: FFEffectKind::Rumble {
+                        strong_magnitude: 0x8000,
+                        weak_magnitude: 0,
+                    },
+                })
+                .expect("uploading to the virtual device");
+            effect.play(1).expect("playing on the virtual device");
+        });
+        source.force_feedback()
+    });
+    assert_eq!(sent.len(), 1, "{sent:?}");
+    assert_eq!(sent[0].event_type(), EventType::FORCEFEEDBACK);
+    assert_eq!(sent[0].value(), 1);
+}